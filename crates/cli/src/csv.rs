@@ -1,45 +1,83 @@
 use std::{
+  borrow::Cow,
   error::Error,
   fmt::Debug,
   fs::File,
   io::BufWriter,
-  io::{stdout, Write},
+  io::{stdin, stdout, Read, Write},
   path::PathBuf,
   thread::scope,
 };
 
 use clap::Args;
 use crossbeam::channel::{bounded, Receiver, Sender};
-use log::{error, info, warn};
+use log::{info, warn};
 #[cfg(not(windows))]
 use memmap2::Advice;
-use memmap2::MmapOptions;
+use tempfile::tempfile;
 
 use fitstable::{
+  common::keywords::tables::bintable::tdisp::TDispValue,
   hdu::{
     header::{builder::r#impl::bintable::Bintable, HDUHeader, Header},
-    xtension::bintable::{
-      read::{
-        deser::sliceheap::DeserializerWithHeap,
-        visitor::csv::{CSVRowVisitor, CSVVisitor},
+    xtension::{
+      asciitable::header::AsciiTableHeaderWithColInfo,
+      bintable::{
+        header::BinTableHeaderWithColInfo,
+        read::{
+          deser::sliceheap::DeserializerWithHeap,
+          expreval::{ColumnExpr, ExprEvalRow, TableSchema},
+          visitor::{
+            csv::{CSVRowVisitor, CSVVisitor},
+            FieldVisitorProvider,
+          },
+        },
+        schema::RowSchema,
+        ztable::ChunkedTable,
       },
-      schema::RowSchema,
     },
   },
-  read::slice::{FitsBytes, HDU},
+  read::{
+    decompress,
+    reader::{HDUReader, StreamHDU},
+    slice::{FitsBytes, HDU},
+    table::{HDUSelector, Table},
+  },
 };
 
 #[derive(Debug, Clone, Args)]
 pub struct Csv {
-  /// Path of the input file.
+  /// Path of the input file ('-' to read from stdin, in streaming mode, using a single thread;
+  /// tables having variable length columns are then spooled in a temporary file).
   #[clap(value_name = "FILE")]
   pub input: PathBuf,
+  /// Export only the given BINTABLE: HDU index (0 being the primary HDU) or EXTNAME (or HDUNAME),
+  /// possibly followed by the EXTVER (e.g. 'EVENTS,2') [default: export all tables, including
+  /// ASCII tables]
+  #[clap(long, value_name = "NAME|INDEX")]
+  hdu: Option<HDUSelector>,
   /// Path of the output file [default: write to stdout]
   #[clap(short = 'o', long = "out", value_name = "FILE")]
   output: Option<PathBuf>,
   /// Do not print the header line (useful when concatenating a set a FITS file of same structure).
   #[clap(short, long)]
   no_header: bool,
+  /// Export only the given columns, in the given order: comma separated list of column names,
+  /// indices (starting at 0) or ranges of indices (e.g. 'ra,dec,5,10..20,30..=32').
+  #[clap(short, long, value_name = "LIST")]
+  columns: Option<String>,
+  /// Export only the rows for which the given boolean expression is true
+  /// (e.g. "phot_g_mean_mag < 12 && parallax > 1"). Not supported for ASCII tables.
+  #[clap(short, long, value_name = "EXPR")]
+  filter: Option<String>,
+  /// Append a column computed from an expression on the table columns, given as 'NAME=EXPR'
+  /// (e.g. "dist=1000/parallax"); may be repeated. Not supported for ASCII tables.
+  #[clap(short, long = "add-column", value_name = "NAME=EXPR")]
+  add_columns: Vec<String>,
+  /// Format the values according to the columns display formats ('TDISPn' keywords), if any,
+  /// instead of using default (full precision) representations.
+  #[clap(long)]
+  tdisp: bool,
   /// Exec concurrently using N threads [default: all possible threads]
   #[arg(long, value_name = "N")]
   parallel: Option<usize>,
@@ -57,15 +95,51 @@ pub struct Csv {
 
 impl Csv {
   pub fn exec(self) -> Result<(), Box<dyn Error>> {
+    if self.input.as_os_str() == "-" {
+      return self.exec_stream(decompress::decompressed_reader(stdin().lock())?);
+    }
     let n_threads = self.parallel.unwrap_or_else(|| num_cpus::get()).max(1);
+    match &self.hdu {
+      Some(hdu_selector) => self.exec_table(hdu_selector.clone(), n_threads),
+      None => self.exec_all_tables(n_threads),
+    }
+  }
+
+  /// Export the single BINTABLE selected by `--hdu`.
+  fn exec_table(&self, hdu_selector: HDUSelector, n_threads: usize) -> Result<(), Box<dyn Error>> {
+    let table = Table::open(&self.input, hdu_selector)?;
+    if self.seq {
+      #[cfg(not(windows))]
+      if let Err(e) = table.mmap().advise(Advice::Sequential) {
+        warn!(
+          "Error advising for sequential read on file '{:?}': {}",
+          self.input, e
+        );
+      }
+    }
+    match &self.output {
+      Some(path) => {
+        let mut write = BufWriter::new(File::create(path)?);
+        write_table_csv(&table, &mut write, self, n_threads)
+      }
+      None => {
+        let stdout = stdout();
+        let mut handle = stdout.lock();
+        write_table_csv(&table, &mut handle, self, n_threads)
+      }
+    }
+  }
+
+  /// Export all tables (BINTABLEs and ASCII tables) of the input file.
+  fn exec_all_tables(&self, n_threads: usize) -> Result<(), Box<dyn Error>> {
     let file = File::open(&self.input)?;
-    let mmap = unsafe { MmapOptions::new().map(&file)? };
+    let mmap = decompress::mmap(&file)?;
     if self.seq {
       #[cfg(not(windows))]
       if let Err(e) = mmap.advise(Advice::Sequential) {
         warn!(
           "Error advising for sequential read on file '{:?}': {}",
-          file, e
+          self.input, e
         );
       }
     }
@@ -90,26 +164,12 @@ impl Csv {
             File::create(new_path)
           }?;
           let mut write = BufWriter::new(file);
-          convert_to_csv(
-            hdu,
-            &mut write,
-            self.no_header,
-            n_threads,
-            self.chunk_size_mb,
-            !self.ssd,
-          )
+          convert_to_csv(hdu, &mut write, self, n_threads)
         }
         None => {
           let stdout = stdout();
           let mut handle = stdout.lock();
-          convert_to_csv(
-            hdu,
-            &mut handle,
-            self.no_header,
-            n_threads,
-            self.chunk_size_mb,
-            !self.ssd,
-          )
+          convert_to_csv(hdu, &mut handle, self, n_threads)
         }
       }?;
       if is_a_table {
@@ -118,225 +178,796 @@ impl Csv {
     }
     Ok(())
   }
+
+  /// Same as `exec` but reading the input FITS file sequentially from the given reader.
+  fn exec_stream<R: Read>(self, reader: R) -> Result<(), Box<dyn Error>> {
+    let mut hdu_reader = HDUReader::<R, Bintable>::from_reader(reader);
+    let mut first_table = true;
+    let mut i = 0;
+    while let Some(hdu) = hdu_reader.next_hdu() {
+      // Only the first matching HDU is selected
+      if self.hdu.is_some() && !first_table {
+        break;
+      }
+      let hdu = hdu?;
+      if !self.is_selected(i, &hdu.parsed_header) {
+        i += 1;
+        continue;
+      }
+      let is_a_table = match &self.output {
+        Some(path) => {
+          // Add the hdu number to the extension from the second table
+          let file = if first_table {
+            File::create(path)
+          } else {
+            let mut new_path = path.clone();
+            match path.extension().and_then(|ext| ext.to_str()) {
+              Some(ext) => new_path.set_extension(&format!("{}.{}", i, ext)),
+              None => new_path.set_extension(&format!("{}.csv", i)),
+            };
+            File::create(new_path)
+          }?;
+          let mut write = BufWriter::new(file);
+          convert_stream_to_csv(hdu, &mut write, &self)
+        }
+        None => {
+          let stdout = stdout();
+          let mut handle = stdout.lock();
+          convert_stream_to_csv(hdu, &mut handle, &self)
+        }
+      }?;
+      if is_a_table {
+        first_table = false;
+      }
+      i += 1;
+    }
+    self.check_selected_table_found(first_table)
+  }
+
+  /// Tells whether the HDU of given index and header has to be exported.
+  fn is_selected(&self, i_hdu: usize, parsed_header: &HDUHeader<Bintable>) -> bool {
+    self
+      .hdu
+      .as_ref()
+      .map(|hdu_selector| hdu_selector.matches(i_hdu, parsed_header))
+      .unwrap_or(true)
+  }
+
+  /// Returns an error if a table has been selected but not exported.
+  fn check_selected_table_found(&self, no_table_exported: bool) -> Result<(), Box<dyn Error>> {
+    match &self.hdu {
+      Some(hdu_selector) if no_table_exported => {
+        Err(format!("{} not found, or not a table.", hdu_selector).into())
+      }
+      _ => Ok(()),
+    }
+  }
+}
+
+/// Resolve a comma separated list of column names, indices (starting at 0) or ranges of indices
+/// (`a..b`, `a..=b`, `a..` or `..b`) into column indices.
+fn resolve_columns(list: &str, col_names: &[String]) -> Result<Vec<usize>, String> {
+  let n_cols = col_names.len();
+  let mut icols = Vec::new();
+  for elem in list.split(',').map(str::trim).filter(|elem| !elem.is_empty()) {
+    if let Some(i) = col_names.iter().position(|name| name == elem) {
+      icols.push(i);
+    } else if let Some((from, to)) = elem.split_once("..") {
+      let parse = |s: &str| {
+        s.parse::<usize>()
+          .map_err(|e| format!("Wrong column range '{}': {}", elem, e))
+      };
+      let from = if from.is_empty() { 0 } else { parse(from)? };
+      let to = match to.strip_prefix('=') {
+        Some(to) => parse(to)? + 1,
+        None if to.is_empty() => n_cols,
+        None => parse(to)?,
+      };
+      if from >= to || to > n_cols {
+        return Err(format!(
+          "Wrong column range '{}': must be a non-empty range in [0, {}).",
+          elem, n_cols
+        ));
+      }
+      icols.extend(from..to);
+    } else {
+      match elem.parse::<usize>() {
+        Ok(i) if i < n_cols => icols.push(i),
+        _ => return Err(format!("Column '{}' not found.", elem)),
+      }
+    }
+  }
+  if icols.is_empty() {
+    Err(String::from("Empty list of columns."))
+  } else {
+    Ok(icols)
+  }
+}
+
+/// Returns the (possibly projected) column names, together with the indices of the selected
+/// columns (`None` if all columns are selected).
+fn select_columns(
+  col_names: Vec<String>,
+  columns: Option<&str>,
+) -> Result<(Vec<String>, Option<Vec<usize>>), String> {
+  match columns {
+    None => Ok((col_names, None)),
+    Some(list) => resolve_columns(list, &col_names).map(|icols| {
+      let names = icols.iter().map(|&i| col_names[i].clone()).collect();
+      (names, Some(icols))
+    }),
+  }
+}
+
+/// Returns the display formats (`TDISPn`) of the written columns if option `tdisp` is set (an
+/// empty list else), given the names and the display formats of all columns.
+fn select_tdisps(
+  args: &Csv,
+  col_names: Vec<String>,
+  tdisps: Vec<Option<TDispValue>>,
+) -> Result<Vec<Option<TDispValue>>, String> {
+  if !args.tdisp {
+    return Ok(Vec::new());
+  }
+  select_columns(col_names, args.columns.as_deref()).map(|(_, icols)| match icols {
+    Some(icols) => icols.iter().map(|&i| tdisps[i]).collect(),
+    None => tdisps,
+  })
+}
+
+fn write_csv_header<W: Write>(write: &mut W, col_names: &[String]) -> Result<(), std::io::Error> {
+  let mut first = true;
+  for name in col_names {
+    if first {
+      first = false;
+    } else {
+      write!(write, ",")?;
+    }
+    write!(write, "{}", name)?;
+  }
+  Ok(())
+}
+
+/// Expressions evaluated on all the columns of a BINTABLE: the boolean expression used to filter
+/// the rows (if any) and the expressions of the computed columns appended to each row.
+#[derive(Clone)]
+struct RowExprs {
+  filter: Option<String>,
+  added_cols: Vec<String>,
+  col_names: Vec<String>,
+  row_schema: RowSchema,
+}
+impl RowExprs {
+  /// Returns an error if one of the expressions does not compile.
+  fn new(
+    filter: Option<String>,
+    added_cols: Vec<String>,
+    col_names: Vec<String>,
+    row_schema: RowSchema,
+  ) -> Result<Self, String> {
+    let table_schema = TableSchema::new(&col_names, row_schema.fields_schemas());
+    if let Some(expr) = &filter {
+      table_schema.compile_bool_expr(expr.clone()).map(|_| ())?;
+    }
+    for expr in &added_cols {
+      table_schema.compile_column_expr(expr.clone()).map(|_| ())?;
+    }
+    Ok(Self {
+      filter,
+      added_cols,
+      col_names,
+      row_schema,
+    })
+  }
+}
+
+/// Compiled [RowExprs], evaluated on a copy of each row so that they can be compiled once and
+/// evaluated on successive chunks of rows (or tiles).
+struct CompiledRowExprs<'a> {
+  keep: Option<Box<dyn Fn(&ExprEvalRow<'a>) -> bool + Sync + Send + 'a>>,
+  added_cols: Vec<ColumnExpr<'a>>,
+  eval_row: ExprEvalRow<'a>,
+}
+impl<'a> CompiledRowExprs<'a> {
+  fn new(exprs: &'a RowExprs, heap: &'a [u8]) -> Result<Self, String> {
+    let table_schema = TableSchema::new(&exprs.col_names, exprs.row_schema.fields_schemas());
+    let keep = match &exprs.filter {
+      Some(expr) => match table_schema.compile_column_expr(expr.clone())? {
+        ColumnExpr::Bool(keep) => Some(keep),
+        _ => {
+          return Err(format!(
+            "Filter expression '{}' must return a boolean.",
+            expr
+          ))
+        }
+      },
+      None => None,
+    };
+    let added_cols = exprs
+      .added_cols
+      .iter()
+      .map(|expr| table_schema.compile_column_expr(expr.clone()))
+      .collect::<Result<Vec<_>, _>>()?;
+    Ok(Self {
+      keep,
+      added_cols,
+      eval_row: ExprEvalRow::new_owned(exprs.row_schema.fields_schemas(), heap),
+    })
+  }
+}
+
+/// Write, in CSV, the rows of the given chunk of rows passing the filter (if any), followed by
+/// the computed columns (if any).
+fn write_csv_rows<W: Write>(
+  write: &mut W,
+  rows: &[u8],
+  row_byte_size: usize,
+  heap: &[u8],
+  row_schema: &RowSchema,
+  exprs: Option<&mut CompiledRowExprs>,
+  tdisps: &[Option<TDispValue>],
+) -> Result<(), Box<dyn Error>> {
+  let mut visitor = CSVVisitor::new(write);
+  visitor.set_tdisps(tdisps.to_vec());
+  match exprs {
+    None => {
+      for raw_row in rows.chunks(row_byte_size) {
+        let mut de = DeserializerWithHeap::new(raw_row, heap);
+        visitor.starts_new_line();
+        row_schema.deserialize(&mut de, &mut visitor, CSVRowVisitor)?;
+      }
+    }
+    Some(exprs) => {
+      for raw_row in rows.chunks(row_byte_size) {
+        exprs.eval_row.set_row(raw_row);
+        if exprs.keep.as_ref().is_none_or(|keep| keep(&exprs.eval_row)) {
+          let mut de = DeserializerWithHeap::new(raw_row, heap);
+          visitor.starts_new_line();
+          row_schema.deserialize(&mut de, &mut visitor, CSVRowVisitor)?;
+          for added_col in &exprs.added_cols {
+            added_col.visit(&exprs.eval_row, visitor.field_visitor())?;
+          }
+        }
+      }
+    }
+  }
+  Ok(())
+}
+
+/// Returns the names (including the ones of the computed columns) and the row schema of the
+/// columns to be written, and the filter and computed columns expressions (if any).
+fn build_schema_and_exprs(
+  args: &Csv,
+  all_col_names: Vec<String>,
+  row_schema: RowSchema,
+) -> Result<(Vec<String>, RowSchema, Option<RowExprs>), Box<dyn Error>> {
+  let (mut col_names, icols) = select_columns(all_col_names.clone(), args.columns.as_deref())?;
+  let out_row_schema = match icols {
+    Some(icols) => row_schema.project(&icols)?,
+    None => row_schema.clone(),
+  };
+  let mut added_cols = Vec::with_capacity(args.add_columns.len());
+  for add_column in &args.add_columns {
+    match add_column.split_once('=') {
+      Some((name, expr)) if !name.trim().is_empty() => {
+        col_names.push(name.trim().to_string());
+        added_cols.push(expr.to_string());
+      }
+      _ => {
+        return Err(
+          format!(
+            "Wrong computed column '{}'. Expected: 'NAME=EXPR'.",
+            add_column
+          )
+          .into(),
+        )
+      }
+    }
+  }
+  let exprs = if args.filter.is_none() && added_cols.is_empty() {
+    None
+  } else {
+    Some(RowExprs::new(
+      args.filter.clone(),
+      added_cols,
+      all_col_names,
+      row_schema,
+    )?)
+  };
+  Ok((col_names, out_row_schema, exprs))
+}
+
+/// Write the rows of an ASCII table (single threaded: ASCII tables are usually small).
+fn write_asciitable_csv<W: Write>(
+  asciitable_header_full: &AsciiTableHeaderWithColInfo,
+  data: &[u8],
+  write: &mut W,
+  args: &Csv,
+) -> Result<(), Box<dyn Error>> {
+  if args.filter.is_some() {
+    return Err(String::from("Option 'filter' not supported for ASCII tables.").into());
+  }
+  if !args.add_columns.is_empty() {
+    return Err(String::from("Option 'add-column' not supported for ASCII tables.").into());
+  }
+  let row_byte_size = asciitable_header_full.row_byte_size();
+  let n_rows = asciitable_header_full.n_rows();
+  let mut row_schema = asciitable_header_full.build_row_schema()?;
+  let all_col_names = asciitable_header_full.build_col_names();
+  let tdisps = select_tdisps(
+    args,
+    all_col_names.clone(),
+    asciitable_header_full
+      .cols()
+      .iter()
+      .map(|col| col.disp().copied())
+      .collect(),
+  )?;
+  let (col_names, icols) = select_columns(all_col_names, args.columns.as_deref())?;
+  if let Some(icols) = icols {
+    row_schema = row_schema.project(&icols)?;
+  }
+  if !args.no_header {
+    write_csv_header(write, &col_names)?;
+  }
+  let mut visitor = CSVVisitor::new(write);
+  visitor.set_tdisps(tdisps);
+  if row_byte_size > 0 {
+    for raw_row in data[..n_rows * row_byte_size].chunks(row_byte_size) {
+      visitor.starts_new_line();
+      row_schema.deserialize(raw_row, &mut visitor, CSVRowVisitor)?;
+    }
+  }
+  write!(write, "\n").map_err(|e| e.into())
+}
+
+// Returns true if the HDU was a table written in output.
+fn convert_stream_to_csv<R: Read, W: Write>(
+  mut hdu: StreamHDU<R, Bintable>,
+  write: &mut W,
+  args: &Csv,
+) -> Result<bool, Box<dyn Error>> {
+  match &hdu.parsed_header {
+    HDUHeader::BinTable(bintable_header_full) if bintable_header_full.is_tile_compressed() => {
+      // Tile-compressed tables: read the whole (compressed) data part to decompress it tile by tile
+      let data = hdu.read_data()?;
+      let HDUHeader::BinTable(bintable_header_full) = hdu.parsed_header else {
+        unreachable!()
+      };
+      write_bintable_csv(bintable_header_full, &data, write, args, 1).map(|()| true)
+    }
+    HDUHeader::BinTable(bintable_header_full) => {
+      let row_byte_size = bintable_header_full.row_byte_size();
+      // Variable length array columns may be empty in all rows (i.e. PCOUNT = 0)
+      let has_heap = bintable_header_full.heap_byte_size() > 0
+        || bintable_header_full.build_row_schema().has_heap_array();
+      let all_col_names = bintable_header_full.build_col_names();
+      let tdisps = select_tdisps(
+        args,
+        all_col_names.clone(),
+        bintable_header_full
+          .cols()
+          .iter()
+          .map(|col| col.disp().copied())
+          .collect(),
+      )?;
+      // Rows are read and written by chunks
+      let chunk_size = 1 + ((args.chunk_size_mb * 1048576.0_f32) as usize / row_byte_size.max(1));
+      let chunk_byte_size = chunk_size * row_byte_size;
+      let mut chunk = Vec::<u8>::with_capacity(chunk_byte_size);
+      if has_heap {
+        // Variable length arrays: spool the main table in a temporary file to be able to read the heap
+        let mut rows = hdu.bintable_rows_with_heap(tempfile()?)?;
+        let (col_names, row_schema, exprs) =
+          build_schema_and_exprs(args, all_col_names, rows.row_schema().clone())?;
+        // The heap is borrowed by the compiled expressions while reading the rows
+        let heap = rows.take_heap();
+        let mut exprs = exprs
+          .as_ref()
+          .map(|exprs| CompiledRowExprs::new(exprs, &heap))
+          .transpose()?;
+        if !args.no_header {
+          write_csv_header(write, &col_names)?;
+        }
+        loop {
+          chunk.clear();
+          while chunk.len() < chunk_byte_size {
+            match rows.next_raw_row() {
+              Some(raw_row) => chunk.extend_from_slice(raw_row?),
+              None => break,
+            }
+          }
+          if chunk.is_empty() {
+            break;
+          }
+          write_csv_rows(
+            write,
+            &chunk,
+            row_byte_size,
+            &heap,
+            &row_schema,
+            exprs.as_mut(),
+            &tdisps,
+          )?;
+        }
+      } else {
+        let mut rows = hdu.bintable_rows()?;
+        let (col_names, row_schema, exprs) =
+          build_schema_and_exprs(args, all_col_names, rows.row_schema().clone())?;
+        let mut exprs = exprs
+          .as_ref()
+          .map(|exprs| CompiledRowExprs::new(exprs, &[]))
+          .transpose()?;
+        if !args.no_header {
+          write_csv_header(write, &col_names)?;
+        }
+        loop {
+          chunk.clear();
+          while chunk.len() < chunk_byte_size {
+            match rows.next_raw_row() {
+              Some(raw_row) => chunk.extend_from_slice(raw_row?),
+              None => break,
+            }
+          }
+          if chunk.is_empty() {
+            break;
+          }
+          write_csv_rows(
+            write,
+            &chunk,
+            row_byte_size,
+            &[],
+            &row_schema,
+            exprs.as_mut(),
+            &tdisps,
+          )?;
+        }
+      }
+      write!(write, "\n")?;
+      Ok(true)
+    }
+    HDUHeader::AsciiTable(_) => {
+      // Read the data first, since it requires to mutably borrow the HDU
+      let data = hdu.read_data()?;
+      let HDUHeader::AsciiTable(asciitable_header_full) = &hdu.parsed_header else {
+        unreachable!()
+      };
+      write_asciitable_csv(asciitable_header_full, &data, write, args).map(|()| true)
+    }
+    _ => Ok(false),
+  }
 }
 
 // Returns true if the HDU was a table written in output.
 fn convert_to_csv<W: Write>(
   hdu: HDU<Bintable>,
   write: &mut W,
-  no_header: bool,
+  args: &Csv,
   n_threads: usize,
-  chunk_size_mb: f32,
-  hdd: bool,
 ) -> Result<bool, Box<dyn Error>> {
   let HDU {
     starting_byte: _,
     raw_header: _,
     parsed_header,
     data,
+    padding: _,
   } = hdu;
   match parsed_header {
     HDUHeader::Primary(_) => Ok(false),
     HDUHeader::Image(_) => Ok(false),
-    HDUHeader::AsciiTable(_) => todo!(),
+    HDUHeader::AsciiTable(asciitable_header_full) => {
+      write_asciitable_csv(&asciitable_header_full, data, write, args).map(|()| true)
+    }
     HDUHeader::BinTable(bintable_header_full) => {
-      // Get all variable to know where is and how to interpret the dat
-      let table_header = bintable_header_full.table();
-      let row_byte_size = table_header.row_byte_size();
-      let data_byte_size = table_header.data_byte_size();
-      let table_byte_size = table_header.main_table_byte_size();
-      let heap_byte_size = table_header.heap_byte_size();
-      let gap_byte_size = bintable_header_full.gap_byte_size();
-      let n_cols = table_header.n_cols();
-      let n_rows = table_header.n_rows();
-      assert_eq!(data_byte_size as usize, data.len());
-      assert_eq!(data_byte_size as usize, table_byte_size + heap_byte_size);
-      let row_schema: RowSchema = bintable_header_full
-        .cols()
-        .iter()
-        .enumerate()
-        .map(|(i, col_header)| {
-          col_header.schema().expect(&format!(
-            "Unable to create schema for column {}: TFORM probably missing!",
-            i + 1
-          ))
-        })
-        .collect();
-      assert_eq!(row_schema.n_cols(), n_cols);
+      let table = ChunkedTable::new(bintable_header_full, data)?;
+      write_bintable_csv(
+        table.header(),
+        table.heap(),
+        |n_rows| table.row_chunks(n_rows),
+        write,
+        args,
+        n_threads,
+      )
+      .map(|()| true)
+    }
+    HDUHeader::Unknown(_) => Ok(false),
+  }
+}
 
-      // Separate main table data and heap data
-      let (main, rem) = data.split_at(table_byte_size);
-      let (_gap, heap) = rem.split_at(gap_byte_size);
-      assert_eq!(n_rows * row_byte_size, table_byte_size);
+/// Write the rows of the BINTABLE of a [Table].
+fn write_table_csv<W: Write>(
+  table: &Table,
+  write: &mut W,
+  args: &Csv,
+  n_threads: usize,
+) -> Result<(), Box<dyn Error>> {
+  let main_table = table.main_table();
+  let row_byte_size = table.header().row_byte_size();
+  write_bintable_csv(
+    table.header(),
+    table.heap(),
+    |n_rows| {
+      main_table
+        .chunks((n_rows * row_byte_size).max(1))
+        .map(|chunk| Ok::<_, fitstable::error::Error>(Cow::Borrowed(chunk)))
+    },
+    write,
+    args,
+    n_threads,
+  )
+}
 
-      // Print header
-      if !no_header {
-        let mut first = true;
-        for (i, field) in bintable_header_full.cols().iter().enumerate() {
-          if first {
-            first = false;
-          } else {
-            write!(write, ",")?;
-          }
-          match field.colname() {
-            Some(name) => write!(write, "{}", name),
-            None => write!(write, "col_{}", i),
-          }?;
-        }
-      }
+/// Write the rows of a BINTABLE.
+/// # Params
+/// * `row_chunks`: provides an iterator over chunks of (about) the given number of consecutive
+///   rows, e.g. tiles for a tile-compressed table (see `ChunkedTable::row_chunks`)
+fn write_bintable_csv<'a, W, F, I>(
+  bintable_header_full: &BinTableHeaderWithColInfo,
+  heap: &'a [u8],
+  row_chunks: F,
+  write: &mut W,
+  args: &Csv,
+  n_threads: usize,
+) -> Result<(), Box<dyn Error>>
+where
+  W: Write,
+  F: Fn(usize) -> I + Sync,
+  I: Iterator<Item = Result<Cow<'a, [u8]>, fitstable::error::Error>> + Send,
+{
+  // Get all variable to know where is and how to interpret the dat
+  let table_header = bintable_header_full.table();
+  let row_byte_size = table_header.row_byte_size();
+  let table_byte_size = table_header.main_table_byte_size();
+  let n_cols = table_header.n_cols();
+  let n_rows = table_header.n_rows();
+  let row_schema: RowSchema = bintable_header_full
+    .cols()
+    .iter()
+    .enumerate()
+    .map(|(i, col_header)| {
+      col_header.schema().expect(&format!(
+        "Unable to create schema for column {}: TFORM probably missing!",
+        i + 1
+      ))
+    })
+    .collect();
+  assert_eq!(row_schema.n_cols(), n_cols);
+  // Select columns (not selected fields are skipped while deserializing rows) and compile expressions
+  let all_col_names = bintable_header_full.build_col_names();
+  let tdisps = select_tdisps(
+    args,
+    all_col_names.clone(),
+    bintable_header_full
+      .cols()
+      .iter()
+      .map(|col| col.disp().copied())
+      .collect(),
+  )?;
+  let (col_names, row_schema, exprs) = build_schema_and_exprs(args, all_col_names, row_schema)?;
 
-      // Print data
-      if n_threads == 1 {
-        info!("Exec with a single thread");
+  // Main table data are read by chunks of rows (or by tiles)
+  assert_eq!(n_rows * row_byte_size, table_byte_size);
 
-        let mut visitor = CSVVisitor::new(write);
-        for raw_row in main.chunks(row_byte_size) {
-          let mut de = DeserializerWithHeap::new(raw_row, heap);
-          visitor.starts_new_line();
-          row_schema.deserialize(&mut de, &mut visitor, CSVRowVisitor)?;
-        }
-        write!(write, "\n",).map_err(|e| e.into())
-      } else {
-        info!("Exec with {} threads", n_threads);
+  // Print header
+  if !args.no_header {
+    write_csv_header(write, &col_names)?;
+  }
+
+  // Print data
+  if n_threads == 1 {
+    info!("Exec with a single thread");
+
+    let mut exprs = exprs
+      .as_ref()
+      .map(|exprs| CompiledRowExprs::new(exprs, heap))
+      .transpose()?;
+    for rows_chunk in row_chunks(n_rows) {
+      write_csv_rows(
+        write,
+        &rows_chunk?,
+        row_byte_size,
+        heap,
+        &row_schema,
+        exprs.as_mut(),
+        &tdisps,
+      )?;
+    }
+    write!(write, "\n",).map_err(|e| e.into())
+  } else {
+    info!("Exec with {} threads", n_threads);
 
-        // Convert chunk size in MB in a number of rows.
-        let chunk_size = 1 + ((chunk_size_mb * 1048576.0_f32) as usize / row_byte_size);
-        info!("Number of rows per chunk: {}", chunk_size);
+    // Convert chunk size in MB in a number of rows.
+    let chunk_size = 1 + ((args.chunk_size_mb * 1048576.0_f32) as usize / row_byte_size);
+    info!("Number of rows per chunk: {}", chunk_size);
 
-        // Multithreaded code
-        // Here, we create one (sender, receiver) pairs per thread and iterate on the
-        // ordered sender/receiver to preserve the original row order.
-        // One thread, the producer (sender1), read the data and send it to multithreaded processors.
-        // One thread, the consumer (receivers2), retrieve the data from producers and write them in the output.
-        //
-        // The difference (an extra copy) with and without the 'hdd' option seems very small.
-        // we let the option for extra tests, but we could remove it for simplicity.
-        if hdd {
-          // HDD mode: make a copy to be sure to read in sequencial mode.
-          // The only difference with the SSD mode is the '.map(Vec::from)' in the chunk reader.
-          let (mut senders1, receivers1): (Vec<Sender<_>>, Vec<Receiver<_>>) =
-            (0..n_threads).map(|_| bounded(1)).unzip();
-          let (mut senders2, receivers2): (Vec<Sender<_>>, Vec<Receiver<_>>) =
-            (0..n_threads).map(|_| bounded(1)).unzip();
-          scope(|s| {
-            // Producer thread
-            s.spawn(|| {
-              let mut senders_it = senders1.iter().cycle();
-              for rows_chunk in main.chunks(row_byte_size * chunk_size).map(Vec::from) {
-                senders_it
-                  .next()
-                  .unwrap()
-                  .send(rows_chunk)
-                  .expect("Unexpected error sending raw rows");
-              }
-              // Close the channels, otherwise sink will never exit the for-loop
-              senders1.drain(..).for_each(drop);
-            });
-            // Parallel processing by n_threads
-            for (sendr2, recvr1) in senders2.iter().cloned().zip(receivers1.iter().cloned()) {
-              // Send to sink, receive from producer
-              let row_schema = row_schema.clone();
-              // Spawn workers in separate threads
-              s.spawn(move || {
-                // Receive until channel closes
-                for raw_rows_chunk in recvr1.iter() {
-                  // We estimate CSV size = 3x binary size
-                  let mut buff = Vec::<u8>::with_capacity(3 * row_byte_size * chunk_size);
-                  let mut visitor = CSVVisitor::new(&mut buff);
-                  for raw_row in raw_rows_chunk.chunks(row_byte_size) {
-                    let mut de = DeserializerWithHeap::new(raw_row, heap);
-                    visitor.starts_new_line();
-                    if let Err(e) = row_schema.deserialize(&mut de, &mut visitor, CSVRowVisitor) {
-                      error!("Error deserializing row: {:?}", e);
-                    }
-                  }
-                  sendr2
-                    .send(buff)
-                    .expect("Unexpected error sending converted rows");
-                }
+    // Multithreaded code
+    // Here, we create one (sender, receiver) pairs per thread and iterate on the
+    // ordered sender/receiver to preserve the original row order.
+    // One thread, the producer (sender1), read the data and send it to multithreaded processors.
+    // One thread, the consumer (receivers2), retrieve the data from producers and write them in the output.
+    //
+    // The difference (an extra copy) with and without the 'hdd' option seems very small.
+    // we let the option for extra tests, but we could remove it for simplicity.
+    if !args.ssd {
+      // HDD mode: make a copy to be sure to read in sequencial mode.
+      // The only difference with the SSD mode is the '.into_owned()' in the chunk reader.
+      let (mut senders1, receivers1): (Vec<Sender<_>>, Vec<Receiver<_>>) =
+        (0..n_threads).map(|_| bounded(1)).unzip();
+      let (mut senders2, receivers2): (Vec<Sender<_>>, Vec<Receiver<_>>) =
+        (0..n_threads).map(|_| bounded(1)).unzip();
+      scope(|s| -> Result<(), Box<dyn Error>> {
+        // Producer thread
+        let producer = s.spawn(|| {
+          let mut senders_it = senders1.iter().cycle();
+          let res = row_chunks(chunk_size).try_for_each(|rows_chunk| {
+            rows_chunk.map(|rows_chunk| {
+              senders_it
+                .next()
+                .unwrap()
+                .send(rows_chunk.into_owned())
+                .expect("Unexpected error sending raw rows")
+            })
+          });
+          // Close the channels, otherwise sink will never exit the for-loop
+          senders1.drain(..).for_each(drop);
+          res
+        });
+        // Parallel processing by n_threads
+        for (sendr2, recvr1) in senders2.iter().cloned().zip(receivers1.iter().cloned()) {
+          // Send to sink, receive from producer
+          let row_schema = row_schema.clone();
+          let exprs = exprs.clone();
+          let tdisps = tdisps.clone();
+          // Spawn workers in separate threads
+          s.spawn(move || {
+            // Expressions are compiled once per worker
+            let mut exprs = exprs
+              .as_ref()
+              .map(|exprs| CompiledRowExprs::new(exprs, heap))
+              .transpose();
+            // Receive until channel closes
+            for raw_rows_chunk in recvr1.iter() {
+              let res = exprs.as_mut().map_err(|e| e.clone()).and_then(|exprs| {
+                // We estimate CSV size = 3x binary size
+                let mut buff = Vec::<u8>::with_capacity(3 * row_byte_size * chunk_size);
+                write_csv_rows(
+                  &mut buff,
+                  &raw_rows_chunk,
+                  row_byte_size,
+                  heap,
+                  &row_schema,
+                  exprs.as_mut(),
+                  &tdisps,
+                )
+                .map(|()| buff)
+                .map_err(|e| format!("Error deserializing rows: {}", e))
               });
+              sendr2
+                .send(res)
+                .expect("Unexpected error sending converted rows");
             }
-            // Close the channel, otherwise sink will never exit the for-loop
-            senders2.drain(..).for_each(drop);
-            // Sink in the current thread
-            for recvr2 in receivers2.iter().cycle() {
-              match recvr2.recv() {
-                Ok(raw_rows) => match write.write_all(&raw_rows) {
-                  Ok(()) => (),
-                  Err(e) => panic!("Error writing in parallel: {:?}", e),
-                },
-                Err(_) => {
-                  // No more data to be written
-                  break;
-                }
-              }
+          });
+        }
+        // Close the channel, otherwise sink will never exit the for-loop
+        senders2.drain(..).for_each(drop);
+        // Sink in the current thread
+        // After an error, the remaining converted rows are received (to let the threads end) but
+        // not written
+        let mut error: Option<String> = None;
+        for recvr2 in receivers2.iter().cycle() {
+          match recvr2.recv() {
+            Ok(Ok(raw_rows)) if error.is_none() => match write.write_all(&raw_rows) {
+              Ok(()) => (),
+              Err(e) => panic!("Error writing in parallel: {:?}", e),
+            },
+            Ok(Ok(_)) => (),
+            Ok(Err(e)) => {
+              error.get_or_insert(e);
             }
+            Err(_) => {
+              // No more data to be written
+              break;
+            }
+          }
+        }
+        producer
+          .join()
+          .expect("Unexpected error joining the producer thread")?;
+        match error {
+          Some(e) => Err(e.into()),
+          None => Ok(()),
+        }
+      })?;
+    } else {
+      // Directly pass mmap bytes (read when accessed).
+      let (mut senders1, receivers1): (Vec<Sender<_>>, Vec<Receiver<_>>) =
+        (0..n_threads).map(|_| bounded(1)).unzip();
+      let (mut senders2, receivers2): (Vec<Sender<_>>, Vec<Receiver<_>>) =
+        (0..n_threads).map(|_| bounded(1)).unzip();
+      scope(|s| -> Result<(), Box<dyn Error>> {
+        // Producer thread (tiles of a tile-compressed table are necessarily copies)
+        let producer = s.spawn(|| {
+          let mut senders_it = senders1.iter().cycle();
+          let res = row_chunks(chunk_size).try_for_each(|rows_chunk| {
+            rows_chunk.map(|rows_chunk| {
+              senders_it
+                .next()
+                .unwrap()
+                .send(rows_chunk)
+                .expect("Unexpected error sending raw rows")
+            })
           });
-        } else {
-          // Directly pass mmap bytes (read when accessed).
-          let (mut senders1, receivers1): (Vec<Sender<_>>, Vec<Receiver<_>>) =
-            (0..n_threads).map(|_| bounded(1)).unzip();
-          let (mut senders2, receivers2): (Vec<Sender<_>>, Vec<Receiver<_>>) =
-            (0..n_threads).map(|_| bounded(1)).unzip();
-          scope(|s| {
-            // Producer thread
-            s.spawn(|| {
-              let mut senders_it = senders1.iter().cycle();
-              for rows_chunk in main.chunks(row_byte_size * chunk_size) {
-                senders_it
-                  .next()
-                  .unwrap()
-                  .send(rows_chunk)
-                  .expect("Unexpected error sending raw rows");
-              }
-              // Close the channels, otherwise sink will never exit the for-loop
-              senders1.drain(..).for_each(drop);
-            });
-            // Parallel processing by n_threads
-            for (sendr2, recvr1) in senders2.iter().cloned().zip(receivers1.iter().cloned()) {
-              // Send to sink, receive from producer
-              let row_schema = row_schema.clone();
-              // Spawn workers in separate threads
-              s.spawn(move || {
-                // Receive until channel closes
-                for raw_rows_chunk in recvr1.iter() {
-                  // We estimate CSV size = 3x binary size
-                  let mut buff = Vec::<u8>::with_capacity(3 * row_byte_size * chunk_size);
-                  let mut visitor = CSVVisitor::new(&mut buff);
-                  for raw_row in raw_rows_chunk.chunks(row_byte_size) {
-                    let mut de = DeserializerWithHeap::new(raw_row, heap);
-                    visitor.starts_new_line();
-                    if let Err(e) = row_schema.deserialize(&mut de, &mut visitor, CSVRowVisitor) {
-                      error!("Error deserializing row: {:?}", e);
-                    }
-                  }
-                  sendr2
-                    .send(buff)
-                    .expect("Unexpected error sending converted rows");
-                }
+          // Close the channels, otherwise sink will never exit the for-loop
+          senders1.drain(..).for_each(drop);
+          res
+        });
+        // Parallel processing by n_threads
+        for (sendr2, recvr1) in senders2.iter().cloned().zip(receivers1.iter().cloned()) {
+          // Send to sink, receive from producer
+          let row_schema = row_schema.clone();
+          let exprs = exprs.clone();
+          let tdisps = tdisps.clone();
+          // Spawn workers in separate threads
+          s.spawn(move || {
+            // Expressions are compiled once per worker
+            let mut exprs = exprs
+              .as_ref()
+              .map(|exprs| CompiledRowExprs::new(exprs, heap))
+              .transpose();
+            // Receive until channel closes
+            for raw_rows_chunk in recvr1.iter() {
+              let res = exprs.as_mut().map_err(|e| e.clone()).and_then(|exprs| {
+                // We estimate CSV size = 3x binary size
+                let mut buff = Vec::<u8>::with_capacity(3 * row_byte_size * chunk_size);
+                write_csv_rows(
+                  &mut buff,
+                  &raw_rows_chunk,
+                  row_byte_size,
+                  heap,
+                  &row_schema,
+                  exprs.as_mut(),
+                  &tdisps,
+                )
+                .map(|()| buff)
+                .map_err(|e| format!("Error deserializing rows: {}", e))
               });
-            }
-            // Close the channel, otherwise sink will never exit the for-loop
-            senders2.drain(..).for_each(drop);
-            // Sink in the current thread
-            for recvr2 in receivers2.iter().cycle() {
-              match recvr2.recv() {
-                Ok(raw_rows) => match write.write_all(&raw_rows) {
-                  Ok(()) => (),
-                  Err(e) => panic!("Error writing in parallel: {:?}", e),
-                },
-                Err(_) => {
-                  // No more data to be written
-                  break;
-                }
-              }
+              sendr2
+                .send(res)
+                .expect("Unexpected error sending converted rows");
             }
           });
         }
-        write!(write, "\n",).map_err(|e| e.into())
-      }
-      .map(|()| true)
+        // Close the channel, otherwise sink will never exit the for-loop
+        senders2.drain(..).for_each(drop);
+        // Sink in the current thread
+        // After an error, the remaining converted rows are received (to let the threads end) but
+        // not written
+        let mut error: Option<String> = None;
+        for recvr2 in receivers2.iter().cycle() {
+          match recvr2.recv() {
+            Ok(Ok(raw_rows)) if error.is_none() => match write.write_all(&raw_rows) {
+              Ok(()) => (),
+              Err(e) => panic!("Error writing in parallel: {:?}", e),
+            },
+            Ok(Ok(_)) => (),
+            Ok(Err(e)) => {
+              error.get_or_insert(e);
+            }
+            Err(_) => {
+              // No more data to be written
+              break;
+            }
+          }
+        }
+        producer
+          .join()
+          .expect("Unexpected error joining the producer thread")?;
+        match error {
+          Some(e) => Err(e.into()),
+          None => Ok(()),
+        }
+      })?;
     }
-    HDUHeader::Unknown(_) => Ok(false),
+    write!(write, "\n",).map_err(|e| e.into())
   }
 }
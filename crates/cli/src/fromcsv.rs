@@ -0,0 +1,81 @@
+use std::{collections::HashMap, error::Error, fs, path::PathBuf};
+
+use clap::Args;
+use log::info;
+use serde::Deserialize;
+
+use fitstable::read::{
+  checksum::write_checksums,
+  fromcsv::{ColumnSpec, csv_to_fits},
+};
+
+/// Convert a CSV file into a FITS file containing a single BINTABLE, the type of each column
+/// being inferred from its values.
+#[derive(Debug, Clone, Args)]
+pub struct FromCsv {
+  /// Path of the input CSV file, the first line containing the column names
+  #[clap(value_name = "FILE")]
+  input: PathBuf,
+  /// Path of the output FITS file
+  #[clap(value_name = "FILE")]
+  output: PathBuf,
+  /// Field separator
+  #[clap(short, long, default_value_t = ',')]
+  separator: char,
+  /// Number of rows scanned to infer the column types [default: scan all rows]
+  #[clap(long, value_name = "N")]
+  sample: Option<usize>,
+  /// TOML file overriding the metadata of columns, one table per CSV column name, e.g.:
+  /// '[RAJ2000]' followed by 'name = "ra"', 'unit = "deg"', 'ucd = "pos.eq.ra;meta.main"'
+  /// and/or 'description = "Right ascension"'
+  #[clap(long, value_name = "FILE")]
+  spec: Option<PathBuf>,
+  /// Compute and write the `CHECKSUM` and `DATASUM` keywords in all HDUs of the output file.
+  #[clap(long)]
+  checksum: bool,
+}
+
+/// Metadata of a column, as read in the TOML columns specifications file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ColumnSpecToml {
+  name: Option<String>,
+  unit: Option<String>,
+  ucd: Option<String>,
+  description: Option<String>,
+}
+
+impl From<ColumnSpecToml> for ColumnSpec {
+  fn from(spec: ColumnSpecToml) -> Self {
+    Self {
+      name: spec.name,
+      unit: spec.unit,
+      ucd: spec.ucd,
+      description: spec.description,
+    }
+  }
+}
+
+impl FromCsv {
+  pub fn exec(self) -> Result<(), Box<dyn Error>> {
+    let specs: HashMap<String, ColumnSpec> = match &self.spec {
+      Some(path) => toml::from_str::<HashMap<String, ColumnSpecToml>>(&fs::read_to_string(path)?)?
+        .into_iter()
+        .map(|(name, spec)| (name, spec.into()))
+        .collect(),
+      None => HashMap::new(),
+    };
+    let n_rows = csv_to_fits(
+      self.input,
+      self.separator,
+      self.sample,
+      &specs,
+      &self.output,
+    )?;
+    info!("Number of rows written: {}", n_rows);
+    if self.checksum {
+      write_checksums(self.output)?;
+    }
+    Ok(())
+  }
+}
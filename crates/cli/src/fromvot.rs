@@ -0,0 +1,32 @@
+use std::{error::Error, path::PathBuf};
+
+use clap::Args;
+use log::info;
+
+use fitstable::read::{checksum::write_checksums, fromvot::vot_to_fits_plus};
+
+/// Convert a VOTable (TABLEDATA, BINARY or BINARY2) into a FITS-plus file, i.e. a primary HDU
+/// containing the VOTable metadata followed by one BINTABLE per VOTable TABLE.
+#[derive(Debug, Clone, Args)]
+pub struct FromVot {
+  /// Path of the input VOTable file
+  #[clap(value_name = "FILE")]
+  input: PathBuf,
+  /// Path of the output FITS file
+  #[clap(value_name = "FILE")]
+  output: PathBuf,
+  /// Compute and write the `CHECKSUM` and `DATASUM` keywords in all HDUs of the output file.
+  #[clap(long)]
+  checksum: bool,
+}
+
+impl FromVot {
+  pub fn exec(self) -> Result<(), Box<dyn Error>> {
+    let n_tables = vot_to_fits_plus(self.input, &self.output)?;
+    info!("Number of BINTABLEs written: {}", n_tables);
+    if self.checksum {
+      write_checksums(self.output)?;
+    }
+    Ok(())
+  }
+}
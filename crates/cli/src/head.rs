@@ -1,11 +1,13 @@
 use std::{error::Error, fmt::Debug, fs::File, path::PathBuf};
 
 use clap::Args;
-use memmap2::MmapOptions;
 
 use fitstable::{
   hdu::header::builder::r#impl::minimal::Minimalist,
-  read::slice::{FitsBytes, HDU},
+  read::{
+    decompress,
+    slice::{FitsBytes, HDU},
+  },
 };
 
 #[derive(Debug, Clone, Args)]
@@ -18,7 +20,7 @@ pub struct Head {
 impl Head {
   pub fn exec(self) -> Result<(), Box<dyn Error>> {
     let file = File::open(&self.input)?;
-    let mmap = unsafe { MmapOptions::new().map(&file)? };
+    let mmap = decompress::mmap(&file)?;
     for (i, hdu) in FitsBytes::from_slice(mmap.as_ref())
       .new_iterator::<Minimalist>()
       .enumerate()
@@ -8,7 +8,6 @@ use std::{
 
 use clap::Args;
 use log::{error, info, warn};
-use memmap2::MmapOptions;
 
 use votable::{TableElem, VOTable, VoidTableDataContent};
 
@@ -20,13 +19,17 @@ use fitstable::{
     },
     primary::header::{PrimaryHeaderWithVOTable},
     xtension::{
-      asciitable::header::AsciiTableHeader,
+      asciitable::header::AsciiTableHeaderWithColInfo,
       bintable::header::{BinTableHeaderWithColInfo},
       image::header::ImageHeader,
       unknown::UnknownXtensionHeader,
     },
   },
-  read::slice::{FitsBytes, HDU},
+  read::{
+    decompress,
+    slice::{FitsBytes, HDU},
+    table::HDUSelector,
+  },
 };
 
 #[derive(Debug, Clone, Args)]
@@ -34,6 +37,10 @@ pub struct Info {
   /// Path of the input file.
   #[clap(value_name = "FILE")]
   pub input: PathBuf,
+  /// Print only the given HDU: HDU index (0 being the primary HDU) or BINTABLE EXTNAME (or
+  /// HDUNAME), possibly followed by the EXTVER (e.g. 'EVENTS,2') [default: print all HDUs]
+  #[clap(long, value_name = "NAME|INDEX")]
+  pub hdu: Option<HDUSelector>,
   /// Do not print the VOTable of a FITS Plus file
   #[clap(short = 'n', long)]
   pub no_vot: bool,
@@ -52,7 +59,7 @@ impl Info {
   pub fn exec(self) -> Result<(), Box<dyn Error>> {
     info!("Open file {:?}", &self.input);
     let file = File::open(&self.input)?;
-    let mmap = unsafe { MmapOptions::new().map(&file)? };
+    let mmap = decompress::mmap(&file)?;
     let bytes = mmap.as_ref();
     let fits = FitsBytes::from_slice(bytes);
     if self.only_vot {
@@ -84,10 +91,26 @@ impl Info {
             None => None
           }
         };
-        for (i, hdu) in  hdu_it {
-          hdu
-            .map_err(|e| e.into())
-            .and_then(|hdu| self.print_hdu_struct(i, hdu, vot.as_ref()))?;
+        match &self.hdu {
+          None => {
+            for (i, hdu) in  hdu_it {
+              hdu
+                .map_err(|e| e.into())
+                .and_then(|hdu| self.print_hdu_struct(i, hdu, vot.as_ref()))?;
+            }
+          }
+          Some(hdu_selector) => {
+            let mut hdu_it = std::iter::once((0, Ok(phd))).chain(hdu_it);
+            let (i, hdu) = loop {
+              match hdu_it.next() {
+                Some((i, Ok(hdu))) if hdu_selector.matches(i, &hdu.parsed_header) => break (i, hdu),
+                Some((_, Ok(_))) => {},
+                Some((_, Err(e))) => return Err(e.into()),
+                None => return Err(format!("{} not found.", hdu_selector).into()),
+              }
+            };
+            self.print_hdu_struct(i, hdu, vot.as_ref())?;
+          }
         }
       }
     }
@@ -124,11 +147,17 @@ fn print_imghdu_struct(_header: ImageHeader) -> Result<(), Box<dyn Error>> {
 }
 
 fn print_bintablehdu_struct(mut header: BinTableHeaderWithColInfo, i_hdu: usize, vot: Option<&VOTable<VoidTableDataContent>>, vot_overwrite: bool) -> Result<(), Box<dyn Error>> {
+  let extid = match (header.extid().extname(), header.extid().extver()) {
+    (Some(name), Some(ver)) => format!("; EXTNAME: {}; EXTVER: {}", name, ver),
+    (Some(name), None) => format!("; EXTNAME: {}", name),
+    (None, _) => String::new(),
+  };
   print_hdu_type(
     format!(
-      "BINTABLE  n_cols: {}; n_rows : {}",
+      "BINTABLE  n_cols: {}; n_rows : {}{}",
       header.table().n_cols(),
-      header.table().n_rows()
+      header.table().n_rows(),
+      extid
     )
     .as_str(),
   );
@@ -228,8 +257,55 @@ fn print_bintablehdu_struct(mut header: BinTableHeaderWithColInfo, i_hdu: usize,
   Ok(())
 }
 
-fn print_ascisstablehdu_struct(_header: AsciiTableHeader) -> Result<(), Box<dyn Error>> {
-  print_hdu_type("ASCIITABLE");
+fn print_ascisstablehdu_struct(header: AsciiTableHeaderWithColInfo) -> Result<(), Box<dyn Error>> {
+  print_hdu_type(
+    format!(
+      "ASCIITABLE  n_cols: {}; n_rows : {}",
+      header.table().n_cols(),
+      header.table().n_rows()
+    )
+    .as_str(),
+  );
+
+  // num / name / dt / unit / desc
+  let mut width_num = 4_usize;
+  let mut width_name = 4_usize;
+  let mut width_dt = 4_usize;
+  let mut width_unit = 4_usize;
+  let mut width_ucd = 3_usize;
+  let mut width_desc = 4_usize;
+
+  // Compute sizes
+  for (i, field) in header.cols().iter().enumerate() {
+    width_num = width_num.max(i.to_string().len());
+    width_name = width_name.max(field.colname().map(|s| s.len()).unwrap_or(0));
+    width_dt = width_dt.max(field.tform().map(|dt| dt.to_string().len()).unwrap_or(0));
+    width_unit = width_unit.max(field.unit().map(|s| s.len()).unwrap_or(0));
+    width_ucd = width_ucd.max(field.ucd().map(|s| s.len()).unwrap_or(0));
+    width_desc = width_desc.max(field.description().map(|s| s.len()).unwrap_or(0));
+  }
+
+  // Print
+  println!(
+    "{:>width_num$} {:>width_name$} {:>width_dt$} {:>width_unit$} {:>width_ucd$} {:<width_desc$}",
+    "#", "name", "type", "unit", "ucd", "desc"
+  );
+  for (i, field) in header.cols().iter().enumerate() {
+    let name = field.colname().unwrap_or("");
+    let dt = field
+      .tform()
+      .map(|dt| dt.to_string())
+      .unwrap_or(String::from(""));
+    let unit = field.unit().unwrap_or("---");
+    let ucd = field.ucd().unwrap_or("---");
+    let desc = field.description().unwrap_or("");
+
+    println!(
+      "{:>width_num$} {:>width_name$} {:>width_dt$} {:>width_unit$} {:>width_ucd$} {:<width_desc$}",
+      i, name, dt, unit, ucd, desc
+    );
+  }
+
   Ok(())
 }
 
@@ -3,12 +3,18 @@ extern crate cdshealpix;
 extern crate log;
 
 pub mod csv;
+pub mod fromcsv;
+pub mod fromvot;
 pub mod head;
 pub mod info;
 pub mod mkidx;
+pub mod parquet;
 pub mod qhips;
 pub mod qidx;
+pub mod select;
 pub mod sort;
 pub mod r#struct;
+pub mod verify;
+pub mod vot;
 
 pub mod mkhips;
@@ -13,8 +13,9 @@ use serde_qs as qs;
 #[cfg(feature = "cgi")]
 use fitstable_cli::qhips::Action;
 use fitstable_cli::{
-  csv::Csv, head::Head, info::Info, mkhips::MkHiPS, mkidx::MkIndex, qhips::QHips, qidx::QIndex,
-  sort::Sort, r#struct::Struct,
+  csv::Csv, fromcsv::FromCsv, fromvot::FromVot, head::Head, info::Info, mkhips::MkHiPS,
+  mkidx::MkIndex, parquet::Parquet, qhips::QHips, qidx::QIndex, select::Select, sort::Sort,
+  r#struct::Struct, verify::Verify, vot::Vot,
 };
 
 // Avoid musl's default allocator due to lackluster performance
@@ -36,9 +37,27 @@ enum Args {
   /// Print tables information (such as column names, units, ...)
   #[clap(name = "info")]
   Info(Info),
+  /// Verify the conformance of a FITS file to the FITS standard
+  #[clap(name = "verify")]
+  Verify(Verify),
   /// Print tables in CSV format.
   #[clap(name = "csv")]
   Csv(Csv),
+  /// Convert the first BINTABLE of a FITS file into an Apache Parquet file
+  #[clap(name = "parquet")]
+  Parquet(Parquet),
+  /// Write the rows of a BINTABLE satisfying a boolean expression in a new FITS file
+  #[clap(name = "select")]
+  Select(Select),
+  /// Convert a CSV file into a FITS BINTABLE, inferring the type of each column
+  #[clap(name = "fromcsv")]
+  FromCsv(FromCsv),
+  /// Convert a VOTable into a FITS-plus file (one BINTABLE per VOTable TABLE)
+  #[clap(name = "fromvot")]
+  FromVot(FromVot),
+  /// Write the BINTABLEs of a FITS file in a VOTable (TABLEDATA, BINARY or BINARY2)
+  #[clap(name = "vot")]
+  Vot(Vot),
   /// Sort a file, or sort and concatenate a set of files, according to HEALPix
   #[clap(name = "sort")]
   Sort(Sort),
@@ -63,7 +82,13 @@ impl Args {
       Self::Struct(args) => args.exec(),
       Self::Head(args) => args.exec(),
       Self::Info(args) => args.exec(),
+      Self::Verify(args) => args.exec(),
       Self::Csv(args) => args.exec(),
+      Self::Parquet(args) => args.exec(),
+      Self::Select(args) => args.exec(),
+      Self::FromCsv(args) => args.exec(),
+      Self::FromVot(args) => args.exec(),
+      Self::Vot(args) => args.exec(),
       Self::Sort(args) => args.exec(),
       Self::MkIndex(args) => args.exec(),
       Self::QIndex(args) => args.exec(),
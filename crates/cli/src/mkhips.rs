@@ -25,7 +25,6 @@ use std::{
 use clap::Args;
 use jiff::Timestamp;
 use log::{debug, info, trace};
-use memmap2::MmapOptions;
 use serde::{Deserialize, Serialize};
 
 use bstree_file_readonly::{
@@ -45,13 +44,19 @@ use cdshealpix::{
 use fitstable::{
   common::{keywords::naxis::NAxis2, ValueKwr},
   hdu::{
-    header::{builder::r#impl::bintable::Bintable, HDUHeader},
+    header::builder::r#impl::bintable::Bintable,
     xtension::bintable::{
       read::expreval::{ExprEvalRow, TableSchema},
-      schema::{FieldSchema, RowSchema, Schema},
+      schema::{FieldSchema, Schema},
     },
   },
-  read::{hidx::check_file_exists_and_check_file_len, slice::FitsBytes},
+  read::{
+    checksum::{copy_header_without_checksums, reserve_checksum_kw_records, write_checksums},
+    decompress,
+    hidx::check_file_exists_and_check_file_len,
+    slice::FitsBytes,
+    table::{HDUSelector, Table},
+  },
 };
 use moc::{
   moc::{
@@ -74,6 +79,10 @@ pub struct MkHiPS {
   /// Output directory containing the HiPS.
   #[clap(value_name = "DIR")]
   output: PathBuf,
+  /// Indexed BINTABLE: HDU index (0 being the primary HDU) or EXTNAME (or HDUNAME), possibly
+  /// followed by the EXTVER (e.g. 'EVENTS,2') [default: the BINTABLE the index points to]
+  #[clap(long, value_name = "NAME|INDEX")]
+  hdu: Option<HDUSelector>,
   /// Number of sources at level 1 (if allsky).
   #[clap(short = 'n', long, value_name = "N", default_value_t = 3000)]
   n1: u16, // l1, 48 cells, 62.5 source per cell (if homogeneous and allsky)
@@ -86,6 +95,9 @@ pub struct MkHiPS {
   /// Score, if any: sources with the lower score appear first in the hierarchy.
   #[clap(short = 's', long, allow_hyphen_values = true)]
   score: Option<String>,
+  /// Compute and write the `CHECKSUM` and `DATASUM` keywords in the HiPS layer FITS files.
+  #[clap(long)]
+  checksum: bool,
   #[command(flatten)]
   /// Set properties
   properties: Properties,
@@ -157,207 +169,211 @@ impl MkHiPS {
     let file =
       File::open(data_file).map_err(|e| format!("Error opening file '{}': {:?}", file_name, e))?;
     // Prepare reading, creating a memory map
-    let mmap =
-      unsafe { MmapOptions::new().map(&file) }.map_err(|e| format!("Mmap error: {:?}", e))?;
-    // Read as a FITS file, prepare iteration on HDUs
-    let bytes = mmap.as_ref();
-    let fits = FitsBytes::from_slice(bytes);
-    let mut hdu_it = fits.new_iterator::<Bintable>();
-
-    info!(" * read primary HDU...");
-    let prim_hdu_bytes = match hdu_it.next() {
-      Some(Ok(hdu)) => {
-        // Copy PrimaryHDU
-        let mut prim_hdu_bytes = Vec::<u8>::new();
-        hdu
-          .copy_hdu(&mut prim_hdu_bytes)
-          .map_err(|e| e.to_string())
-          .map(|()| prim_hdu_bytes)
-      }
-      Some(Err(e)) => Err(e.to_string()),
-      None => Err(String::from("No HDU found")),
-    }?;
-
-    info!(" * iterate on HDUs, looking for the first BINTABLE...");
-    let hdu = loop {
-      if let Some(hdu) = hdu_it.next() {
-        let hdu = hdu?;
-        if hdu.is_bintable_hdu() && first_byte == hdu.data_starting_byte() as u64 {
-          break Some(hdu);
+    let mmap = decompress::mmap(&file).map_err(|e| format!("Mmap error: {:?}", e))?;
+    let hdu_selector = match &self.hdu {
+      Some(hdu_selector) => hdu_selector.clone(),
+      None => {
+        info!(" * iterate on HDUs, looking for the indexed BINTABLE...");
+        let mut i_hdu = None;
+        for (i, hdu) in FitsBytes::from_slice(mmap.as_ref())
+          .new_iterator::<Bintable>()
+          .enumerate()
+        {
+          let hdu = hdu?;
+          if hdu.is_bintable_hdu() && first_byte == hdu.data_starting_byte() as u64 {
+            i_hdu = Some(i);
+            break;
+          }
         }
-      } else {
-        break None;
+        i_hdu.map(HDUSelector::Index).ok_or_else(|| {
+          format!(
+            "No BINTABLE with data starting at byte offset {}",
+            first_byte
+          )
+        })?
       }
     };
+    let table = Table::from_mmap(mmap, hdu_selector.clone())?;
+    if table.is_tile_compressed() {
+      return Err(String::from("Tile-compressed BINTABLE: decompress it first.").into());
+    }
+    if table.data_starting_byte() as u64 != first_byte {
+      return Err(format!("{} is not the indexed BINTABLE.", hdu_selector).into());
+    }
 
-    match hdu {
-      Some(hdu) => {
-        let mut bintable_header_bytes = Vec::<u8>::new();
-        hdu.copy_header(&mut bintable_header_bytes)?;
+    info!(" * read primary HDU and BINTABLE header...");
+    let prim_hdu_bytes = table.raw_primary_hdu().to_vec();
+    // (CHECKSUM and DATASUM are removed since each layer file contains a subset of the rows)
+    let mut bintable_header_bytes = Vec::<u8>::new();
+    copy_header_without_checksums(&table.raw_header(), &mut bintable_header_bytes)?;
+    // Reserve the checksum keyword records so that headers size do not change when stamping
+    let (prim_hdu_bytes, bintable_header_bytes) = if self.checksum {
+      (
+        reserve_checksum_kw_records(&prim_hdu_bytes)?,
+        reserve_checksum_kw_records(&bintable_header_bytes)?,
+      )
+    } else {
+      (prim_hdu_bytes, bintable_header_bytes)
+    };
 
-        info!("Parse header and get lon/lat column indices...");
-        info!(" * read BINTABLE metadata...");
-        let bintable_header = match &hdu.parsed_header {
-          HDUHeader::BinTable(h) => h,
-          _ => unreachable!(), // since we already tested with 'is_bintable_hdu'
-        };
+    info!("Parse header and get lon/lat column indices...");
+    info!(" * read BINTABLE metadata...");
+    let bintable_header = table.header();
 
-        info!(" * build table schema...");
-        let row_schema: RowSchema = bintable_header.build_row_schema();
-        let col_names: Vec<String> = bintable_header.build_col_names();
+    info!(" * build table schema...");
+    let row_schema = table.row_schema();
+    let col_names = table.col_names();
 
-        info!(
-          " * get RA and Dec columns info, and ensure they are of type Double (no scale/offset allowed here so far)..."
-        );
-        let lon_meta = &row_schema.fields_schemas()[lon];
-        let lat_meta = &row_schema.fields_schemas()[lat];
-        if !matches!(lon_meta.schema, Schema::Double) {
-          return Err(
-            format!(
-              "RA column is not a double. Header: {:?}",
-              &bintable_header.cols()[lon]
-            )
-            .into(),
-          );
-        }
-        if !matches!(lat_meta.schema, Schema::Double) {
-          return Err(
-            format!(
-              "Dec column is not a double. Header: {:?}",
-              &bintable_header.cols()[lat]
-            )
-            .into(),
-          );
-        }
+    info!(
+      " * get RA and Dec columns info, and ensure they are of type Double (no scale/offset allowed here so far)..."
+    );
+    let lon_meta = &row_schema.fields_schemas()[lon];
+    let lat_meta = &row_schema.fields_schemas()[lat];
+    if !matches!(lon_meta.schema, Schema::Double) {
+      return Err(
+        format!(
+          "RA column is not a double. Header: {:?}",
+          &bintable_header.cols()[lon]
+        )
+        .into(),
+      );
+    }
+    if !matches!(lat_meta.schema, Schema::Double) {
+      return Err(
+        format!(
+          "Dec column is not a double. Header: {:?}",
+          &bintable_header.cols()[lat]
+        )
+        .into(),
+      );
+    }
 
-        info!(" * define hpx29 method...");
-        let layer29 = get(29);
-        let hpx29 = move |row_bytes: &[u8]| {
-          let lon = f64::from_be_bytes(
-            row_bytes[lon_meta.starting_byte..lon_meta.starting_byte + 8]
-              .try_into()
-              .unwrap(),
-          );
-          let lat = f64::from_be_bytes(
-            row_bytes[lat_meta.starting_byte..lat_meta.starting_byte + 8]
-              .try_into()
-              .unwrap(),
-          );
-          if lon.is_nan() || lat.is_nan() {
-            0
-          } else {
-            layer29.hash(lon.to_radians(), lat.to_radians())
-          }
-        };
+    info!(" * define hpx29 method...");
+    let layer29 = get(29);
+    let hpx29 = move |row_bytes: &[u8]| {
+      let lon = f64::from_be_bytes(
+        row_bytes[lon_meta.starting_byte..lon_meta.starting_byte + 8]
+          .try_into()
+          .unwrap(),
+      );
+      let lat = f64::from_be_bytes(
+        row_bytes[lat_meta.starting_byte..lat_meta.starting_byte + 8]
+          .try_into()
+          .unwrap(),
+      );
+      if lon.is_nan() || lat.is_nan() {
+        0
+      } else {
+        layer29.hash(lon.to_radians(), lat.to_radians())
+      }
+    };
 
-        info!("Compute coverage of order 1 and 2...");
-        info!(" * count number of non-empty cell at level 2...");
-        let mut nc2 = 0_u64; // number of non-empty cells at depth 2, in [0..192]
-        for h in 0..192 {
-          if hci.get_cell_noncumulative(2, h) > 0 {
-            nc2 += 1;
-          }
-        }
+    info!("Compute coverage of order 1 and 2...");
+    info!(" * count number of non-empty cell at level 2...");
+    let mut nc2 = 0_u64; // number of non-empty cells at depth 2, in [0..192]
+    for h in 0..192 {
+      if hci.get_cell_noncumulative(2, h) > 0 {
+        nc2 += 1;
+      }
+    }
 
-        info!(" * deduce n1 and n2...");
-        let bintable_data_starting_byte = hdu.data_starting_byte();
-        let row_byte_size = bintable_header.row_byte_size();
-        let nrows = bintable_header.n_rows() as u64;
-
-        // n2 / n1 = r21
-        // n12 = n1 + n2 = n1 * (1 + r21)
-        // => n1 = n12 / (1 + r21) AND n2 = n12 - n1
-        let one_plus_r21 = 1_u64 + self.r21 as u64;
-        let n12_allsky = (self.n1 as u64) * one_plus_r21;
-        let n12 = (n12_allsky * nc2) / 192;
-        let n1 = n12 / one_plus_r21;
-        let n2 = n12 - n1;
-        info!("   + n1: {}; n2: {}; n1+2: {}.", n1, n2, n12);
-
-        // Create the destination directory if it does not exists.
-        if !fs::exists(&self.output).unwrap_or(false) {
-          fs::create_dir(&self.output)?;
-        } // else ensure the directory is empty?
-
-        info!("Prepare inputs (parameters, I/Os, MOC builder...");
-        let algo_params = AlgoParams::new(
-          self.output.clone(),
-          n12,
-          n1,
-          n2,
-          one_plus_r21,
-          self.n_tot as u64,
-        );
+    info!(" * deduce n1 and n2...");
+    let bintable_data_starting_byte = table.data_starting_byte();
+    let row_byte_size = bintable_header.row_byte_size();
+    let nrows = bintable_header.n_rows() as u64;
+
+    // n2 / n1 = r21
+    // n12 = n1 + n2 = n1 * (1 + r21)
+    // => n1 = n12 / (1 + r21) AND n2 = n12 - n1
+    let one_plus_r21 = 1_u64 + self.r21 as u64;
+    let n12_allsky = (self.n1 as u64) * one_plus_r21;
+    let n12 = (n12_allsky * nc2) / 192;
+    let n1 = n12 / one_plus_r21;
+    let n2 = n12 - n1;
+    info!("   + n1: {}; n2: {}; n1+2: {}.", n1, n2, n12);
+
+    // Create the destination directory if it does not exists.
+    if !fs::exists(&self.output).unwrap_or(false) {
+      fs::create_dir(&self.output)?;
+    } // else ensure the directory is empty?
+
+    info!("Prepare inputs (parameters, I/Os, MOC builder...");
+    let algo_params = AlgoParams::new(
+      self.output.clone(),
+      n12,
+      n1,
+      n2,
+      one_plus_r21,
+      self.n_tot as u64,
+      self.checksum,
+    );
 
-        // Get score! compile_f64_expr
-        let expr_table_schema = TableSchema::new(&col_names, row_schema.fields_schemas());
-        let score = self
-          .score
-          .clone()
-          .map(|expr| {
-            expr_table_schema.compile_f64_expr(expr) // .map(|f| {
-                                                     // Box::new(f) as Box<dyn for<'b> Fn(&ExprEvalRow<'b>) -> f64 + Sync + Send + 'b>
-                                                     //})
-          })
-          .transpose()?;
-
-        let input_data = InputData::new(
-          bytes,
-          bintable_data_starting_byte,
-          row_byte_size,
-          prim_hdu_bytes,
-          bintable_header_bytes,
-          nrows,
-          hci,
-          lon,
-          lat,
-          hpx29,
-          row_schema.fields_schemas(),
-          score,
-        );
+    // Get score! compile_f64_expr
+    let expr_table_schema = TableSchema::new(col_names, row_schema.fields_schemas());
+    let score = self
+      .score
+      .clone()
+      .map(|expr| {
+        expr_table_schema.compile_f64_expr(expr) // .map(|f| {
+                                                 // Box::new(f) as Box<dyn for<'b> Fn(&ExprEvalRow<'b>) -> f64 + Sync + Send + 'b>
+                                                 //})
+      })
+      .transpose()?;
+
+    let input_data = InputData::new(
+      table.mmap().as_ref(),
+      bintable_data_starting_byte,
+      row_byte_size,
+      prim_hdu_bytes,
+      bintable_header_bytes,
+      nrows,
+      hci,
+      lon,
+      lat,
+      hpx29,
+      row_schema.fields_schemas(),
+      score,
+    );
 
-        let mut moc_builder = RangeMocBuilder::<u64, Hpx<u64>>::new(29, None);
-        let mut stat_writer = TilesStatWriter::new(self.tmp_bstree_path(), self.bstree_path())?;
-
-        info!("Start processing...");
-        let depth_max = Layer1and2.exec(
-          &algo_params,
-          &input_data,
-          &mut moc_builder,
-          &mut stat_writer,
-        )?;
-
-        info!("Set proper MOC depth...");
-        let moc = RangeMOC::new(depth_max, moc_builder.into_moc().into_moc_ranges());
-
-        info!("Compute properties values...");
-        let sky_fraction = moc.coverage_percentage();
-        let (lon_rad, lat_rad) = (&moc).into_range_moc_iter().cells().mean_center();
-        let r_max_rad = (&moc)
-          .into_range_moc_iter()
-          .cells()
-          .max_distance_from(lon_rad, lat_rad);
-        self.properties.set_fixed_values();
-        self.properties.set_computed_values(
-          depth_max,
-          nrows,
-          (nrows * (row_byte_size as u64)) / 1024,
-          sky_fraction,
-          lon_rad.to_degrees(),
-          lat_rad.to_degrees(),
-          r_max_rad.to_degrees(),
-        );
+    let mut moc_builder = RangeMocBuilder::<u64, Hpx<u64>>::new(29, None);
+    let mut stat_writer = TilesStatWriter::new(self.tmp_bstree_path(), self.bstree_path())?;
 
-        info!("Write moc and properties files...");
-        self
-          .write_moc(moc)
-          .and_then(|()| self.write_properties(&self.properties))?;
+    info!("Start processing...");
+    let depth_max = Layer1and2.exec(
+      &algo_params,
+      &input_data,
+      &mut moc_builder,
+      &mut stat_writer,
+    )?;
 
-        info!("Write tiles stats in BSTree file...");
-        stat_writer.build_bstree().map_err(|e| e.into())
-      }
-      None => Err(format!("No HDU with data starting at byte offset {}", first_byte).into()),
-    }
+    info!("Set proper MOC depth...");
+    let moc = RangeMOC::new(depth_max, moc_builder.into_moc().into_moc_ranges());
+
+    info!("Compute properties values...");
+    let sky_fraction = moc.coverage_percentage();
+    let (lon_rad, lat_rad) = (&moc).into_range_moc_iter().cells().mean_center();
+    let r_max_rad = (&moc)
+      .into_range_moc_iter()
+      .cells()
+      .max_distance_from(lon_rad, lat_rad);
+    self.properties.set_fixed_values();
+    self.properties.set_computed_values(
+      depth_max,
+      nrows,
+      (nrows * (row_byte_size as u64)) / 1024,
+      sky_fraction,
+      lon_rad.to_degrees(),
+      lat_rad.to_degrees(),
+      r_max_rad.to_degrees(),
+    );
+
+    info!("Write moc and properties files...");
+    self
+      .write_moc(moc)
+      .and_then(|()| self.write_properties(&self.properties))?;
+
+    info!("Write tiles stats in BSTree file...");
+    stat_writer.build_bstree().map_err(|e| e.into())
   }
 
   fn write_moc(&self, moc: RangeMOC<u64, Hpx<u64>>) -> Result<(), Box<dyn Error>> {
@@ -729,9 +745,19 @@ struct AlgoParams {
   one_plus_r21: u64,
   /// Number of sources in each tile (of depth >= 3)
   nt: u64,
+  /// Write the `CHECKSUM` and `DATASUM` keywords in the layer files
+  checksum: bool,
 }
 impl AlgoParams {
-  fn new(dir: PathBuf, n12: u64, n1: u64, n2: u64, one_plus_r21: u64, nt: u64) -> Self {
+  fn new(
+    dir: PathBuf,
+    n12: u64,
+    n1: u64,
+    n2: u64,
+    one_plus_r21: u64,
+    nt: u64,
+    checksum: bool,
+  ) -> Self {
     Self {
       dir,
       n12,
@@ -739,6 +765,7 @@ impl AlgoParams {
       n2,
       one_plus_r21,
       nt,
+      checksum,
     }
   }
 }
@@ -1423,6 +1450,7 @@ impl<const N: usize> Layer1or2<N> {
       input.primary_hdu(),
       input.bintable_header(),
       input.row_byte_size,
+      algo.checksum,
     )
     .map(|fitsw| Self {
       depth,
@@ -1516,6 +1544,7 @@ impl LayerExpl {
       input.primary_hdu(),
       input.bintable_header(),
       input.row_byte_size,
+      algo.checksum,
     )
     .map(|fitsw| Self {
       depth,
@@ -1773,6 +1802,7 @@ struct FitsHiPSLayerWriter {
   bintable_header_starting_byte: u64,
   bintable_data_starting_byte: u64,
   n_written_rows: u64,
+  checksum: bool,
 }
 
 impl FitsHiPSLayerWriter {
@@ -1782,6 +1812,7 @@ impl FitsHiPSLayerWriter {
     prim_hdu_bytes: &[u8],
     bintable_header: &[u8],
     row_byte_size: u64,
+    checksum: bool,
   ) -> Result<Self, IoErr> {
     let prim_hdu_len = prim_hdu_bytes.len() as u64;
     let bintable_header_len = bintable_header.len() as u64;
@@ -1812,6 +1843,7 @@ impl FitsHiPSLayerWriter {
                 bintable_header_starting_byte,
                 bintable_data_starting_byte,
                 n_written_rows: 0,
+                checksum,
               }
             })
           })
@@ -1872,8 +1904,16 @@ impl FitsHiPSLayerWriter {
     self
       .writer
       .write_all(naxis2.as_slice())
-      .map_err(|e| e.into())
-      .map(|()| (self.depth, self.dir, byte_len))
+      .and_then(|()| self.writer.flush())?;
+
+    // Stamp checksums (the header size does not change since the keywords records are reserved)
+    if self.checksum {
+      debug!("Write checksums for layer {}...", self.depth);
+      let mut path = self.dir.clone();
+      path.push(Self::filename(self.depth));
+      write_checksums(path)?;
+    }
+    Ok((self.depth, self.dir, byte_len))
   }
 
   fn finalize<H: HCIndex>(
@@ -2,7 +2,7 @@ use std::{error::Error, path::PathBuf};
 
 use clap::Args;
 
-use fitstable::read::hidx::hcidx;
+use fitstable::read::{checksum::write_checksums, hidx::hcidx, table::HDUSelector};
 
 /// Make an index on an HEALPix NESTED sorted BINTABLE FITS file,
 /// to then quickly retrieve rows in a given HEALPix cell.
@@ -11,6 +11,10 @@ pub struct MkIndex {
   /// Path of the FITS file to be indexed
   #[clap(value_name = "FILE")]
   input: PathBuf,
+  /// BINTABLE to be indexed: HDU index (0 being the primary HDU) or EXTNAME (or HDUNAME),
+  /// possibly followed by the EXTVER (e.g. 'EVENTS,2') [default: all BINTABLEs]
+  #[clap(long, value_name = "NAME|INDEX")]
+  hdu: Option<HDUSelector>,
   /// Path of the output FITS file containing the HEALPix Cumulative Index.
   #[clap(value_name = "FILE")]
   output: PathBuf,
@@ -32,19 +36,28 @@ pub struct MkIndex {
   /// Below the limit, the implicit representation is chosen.
   /// If unset, use the in-memory representation.
   implicit_over_explicit_ratio: Option<f64>,
+  /// Compute and write the `CHECKSUM` and `DATASUM` keywords in all HDUs of the output file(s).
+  #[clap(long)]
+  checksum: bool,
 }
 
 impl MkIndex {
   pub fn exec(self) -> Result<(), Box<dyn Error>> {
-    hcidx(
+    let index_paths = hcidx(
       self.input,
+      self.hdu,
       self.output,
       self.lon - 1,
       self.lat - 1,
       self.depth,
       self.explicit,
       self.implicit_over_explicit_ratio,
-    )
-    .map_err(|e| e.into())
+    )?;
+    if self.checksum {
+      for index_path in index_paths {
+        write_checksums(index_path)?;
+      }
+    }
+    Ok(())
   }
 }
@@ -0,0 +1,54 @@
+use std::{error::Error, fs::File, path::PathBuf};
+
+use clap::Args;
+use log::{info, warn};
+#[cfg(not(windows))]
+use memmap2::Advice;
+use parquet::{arrow::ArrowWriter, basic::Compression, file::properties::WriterProperties};
+
+use fitstable::read::table::{HDUSelector, Table};
+
+/// Convert the first BINTABLE of a FITS file into an Apache Parquet file.
+/// A tile-compressed BINTABLE is decompressed in memory.
+#[derive(Debug, Clone, Args)]
+pub struct Parquet {
+  /// Path of the input file
+  #[clap(value_name = "FILE")]
+  input: PathBuf,
+  /// Path of the output Parquet file
+  #[clap(value_name = "FILE")]
+  output: PathBuf,
+  /// Number of rows per record batch (i.e. per Parquet row group)
+  #[clap(short, long, value_name = "N", default_value_t = 65_536)]
+  batch_size: usize,
+}
+
+impl Parquet {
+  pub fn exec(self) -> Result<(), Box<dyn Error>> {
+    let table = Table::open(&self.input, HDUSelector::First)?;
+    #[cfg(not(windows))]
+    if let Err(e) = table.mmap().advise(Advice::Sequential) {
+      warn!(
+        "Error advising for sequential read on file '{:?}': {}",
+        self.input, e
+      );
+    }
+    let batch_size = self.batch_size.max(1);
+    let batches = table.arrow_record_batches(batch_size);
+    let props = WriterProperties::builder()
+      .set_compression(Compression::SNAPPY)
+      .set_max_row_group_row_count(Some(batch_size))
+      .build();
+    let mut writer =
+      ArrowWriter::try_new(File::create(&self.output)?, batches.schema(), Some(props))?;
+    for batch in batches {
+      writer.write(&batch?)?;
+    }
+    let metadata = writer.close()?;
+    info!(
+      "Number of rows written: {}",
+      metadata.file_metadata().num_rows()
+    );
+    Ok(())
+  }
+}
@@ -12,7 +12,6 @@ use http::status::StatusCode as Status;
 use log::{error, warn};
 #[cfg(not(windows))]
 use memmap2::Advice;
-use memmap2::MmapOptions;
 use serde;
 
 use bstree_file_readonly::{
@@ -29,15 +28,13 @@ use cdshealpix::nested::{
 use fitstable::{
   hdu::{
     header::{HDUHeader, Header, builder::r#impl::bintable::Bintable},
-    xtension::bintable::{
-      read::{
-        deser::sliceheap::DeserializerWithHeap,
-        visitor::csv::{CSVRowVisitor, CSVVisitor},
-      },
-      schema::RowSchema,
-    },
+    xtension::bintable::read::visitor::csv::{CSVRowVisitor, CSVVisitor},
+  },
+  read::{
+    decompress,
+    slice::FitsBytes,
+    table::{HDUSelector, Table as FitsTable},
   },
-  read::slice::{FitsBytes, HDU},
 };
 use votable::{Resource, Table, VOTable, VoidTableDataContent, votable::Version};
 
@@ -161,7 +158,7 @@ fn print_metadata(mut input: PathBuf, is_cgi: bool) -> Result<(), Box<dyn Error>
   input.push("hips.cat.layer1.fits");
   check_file_exists(&input, is_cgi)?;
   let file = File::open(&input)?;
-  let mmap = unsafe { MmapOptions::new().map(&file)? };
+  let mmap = decompress::mmap(&file)?;
   let bytes = mmap.as_ref();
   let fits = FitsBytes::from_slice(bytes);
   let mut hdu_it = fits.new_iterator::<Bintable>().enumerate();
@@ -253,86 +250,26 @@ fn print_allsky(mut input: PathBuf, depth: u8, is_cgi: bool) -> Result<(), Box<d
     }
     println!("Content-Type: text/plain\n");
   }
-  let file = File::open(&input)?;
-  let mmap = unsafe { MmapOptions::new().map(&file)? };
+  let table = FitsTable::open(&input, HDUSelector::First)?;
   #[cfg(not(windows))]
-  if let Err(e) = mmap.advise(Advice::Sequential) {
+  if let Err(e) = table.mmap().advise(Advice::Sequential) {
     warn!(
       "Error advising for sequential read on file '{:?}': {}",
-      file, e
+      input, e
     );
   }
 
   // Set output
   let mut write = stdout().lock();
-
-  // Iterate over HDUs
-  for hdu in FitsBytes::from_slice(mmap.as_ref()).new_iterator::<Bintable>() {
-    let HDU {
-      starting_byte: _,
-      raw_header: _,
-      parsed_header,
-      data,
-    } = hdu?;
-    match parsed_header {
-      HDUHeader::BinTable(bintable_header_full) => {
-        // Get all variable to know where is and how to interpret the dat
-        let table_header = bintable_header_full.table();
-        let row_byte_size = table_header.row_byte_size();
-        let data_byte_size = table_header.data_byte_size();
-        let table_byte_size = table_header.main_table_byte_size();
-        let heap_byte_size = table_header.heap_byte_size();
-        let gap_byte_size = bintable_header_full.gap_byte_size();
-        let n_cols = table_header.n_cols();
-        let n_rows = table_header.n_rows();
-        assert_eq!(data_byte_size as usize, data.len());
-        assert_eq!(data_byte_size as usize, table_byte_size + heap_byte_size);
-
-        // Get table schema
-        let row_schema: RowSchema = bintable_header_full
-          .cols()
-          .iter()
-          .enumerate()
-          .map(|(i, col_header)| {
-            col_header.schema().expect(&format!(
-              "Unable to create schema for column {}: TFORM probably missing!",
-              i + 1
-            ))
-          })
-          .collect();
-        assert_eq!(row_schema.n_cols(), n_cols);
-
-        // Separate main table data and heap data
-        let (main, rem) = data.split_at(table_byte_size);
-        let (_gap, heap) = rem.split_at(gap_byte_size);
-        assert_eq!(n_rows * row_byte_size, table_byte_size);
-
-        // Print header
-        let mut first = true;
-        for (i, field) in bintable_header_full.cols().iter().enumerate() {
-          if first {
-            first = false;
-          } else {
-            write!(write, "\t")?;
-          }
-          match field.colname() {
-            Some(name) => write!(write, "{}", name),
-            None => write!(write, "col_{}", i),
-          }?;
-        }
-
-        // Print data
-        let mut visitor = CSVVisitor::new_custom(&mut write, b'\t');
-        for raw_row in main.chunks(row_byte_size) {
-          let mut de = DeserializerWithHeap::new(raw_row, heap);
-          visitor.starts_new_line();
-          row_schema.deserialize(&mut de, &mut visitor, CSVRowVisitor)?;
-        }
-        write!(write, "\n",)?;
-      }
-      _ => {}
-    }
+  // Print header
+  write!(write, "{}", table.col_names().join("\t"))?;
+  // Print data
+  let mut visitor = CSVVisitor::new_custom(&mut write, b'\t');
+  for irow in 0..table.n_rows() {
+    visitor.starts_new_line();
+    table.visit_row(irow, &mut visitor, CSVRowVisitor)?;
   }
+  write!(write, "\n",)?;
   Ok(())
 }
 
@@ -342,7 +279,7 @@ fn print_tile(input: PathBuf, depth: u8, hash: u64, is_cgi: bool) -> Result<(),
   check_file_exists(&bstree_path, is_cgi)?;
 
   let bstree_file = File::open(&bstree_path)?;
-  let mmap = unsafe { MmapOptions::new().map(&bstree_file)? };
+  let mmap = decompress::mmap(&bstree_file)?;
   let (_version, data_starting_byte, bstree_meta) = read_meta(&mmap)?;
   let visitor = bstree_meta.get_root().visit(
     VisitorExact::new(to_zuniq(depth, hash)),
@@ -387,7 +324,7 @@ fn print_tiles_stats(mut input: PathBuf, is_cgi: bool) -> Result<(), Box<dyn Err
   }
   // Open file and read metadata
   let file = File::open(&input)?;
-  let mmap = unsafe { MmapOptions::new().map(&file)? };
+  let mmap = decompress::mmap(&file)?;
   let (_version, data_starting_byte, _) = read_meta(&mmap)?;
   let mut write = stdout().lock();
   writeln!(&mut write, "depth,cell,cumul_count,tot_count")?;
@@ -459,66 +396,20 @@ where
 
   // Load FITS file data
   dir.push(format!("hips.cat.layer{}.fits", depth));
-  let file = File::open(dir)?;
-  // Prepare reading, creating a memory map
-  let mmap = unsafe { MmapOptions::new().map(&file) }?;
-  #[cfg(not(windows))]
-  if let Err(e) = mmap.advise(Advice::Sequential) {
-    warn!(
-      "Error advising for sequential read on file '{:?}': {}",
-      file, e
-    );
-  }
-  // Read as a FITS file, prepare iteration on HDUs
-  let bytes = mmap.as_ref();
-  let fits = FitsBytes::from_slice(bytes);
-  let mut hdu_it = fits.new_iterator::<Bintable>();
-  let _prim_hdu = hdu_it
-    .next()
-    .ok_or_else(|| String::from("No primary HDU found"))?;
-  let bint_hdu = hdu_it
-    .next()
-    .ok_or_else(|| String::from("No secondary HDU found"))??;
-  assert!(bint_hdu.is_bintable_hdu());
-  match bint_hdu.parsed_header {
-    HDUHeader::BinTable(bintable_header_full) => {
-      let row_byte_size = bintable_header_full.row_byte_size();
-      // Get schema
-      let row_schema: RowSchema = bintable_header_full
-        .cols()
-        .iter()
-        .enumerate()
-        .map(|(i, col_header)| {
-          col_header.schema().expect(&format!(
-            "Unable to create schema for column {}: TFORM probably missing!",
-            i + 1
-          ))
-        })
-        .collect();
-      // Print header
-      let mut first = true;
-      for (i, field) in bintable_header_full.cols().iter().enumerate() {
-        if first {
-          first = false;
-        } else {
-          write!(write, "\t")?;
-        }
-        match field.colname() {
-          Some(name) => write!(write, "{}", name),
-          None => write!(write, "col_{}", i),
-        }?;
-      }
-      // Print data
-      let mut visitor = CSVVisitor::new_custom(write, b'\t');
-      for raw_row in (&mmap[bytes_range]).chunks(row_byte_size) {
-        let mut de = DeserializerWithHeap::new(raw_row, &[0_u8; 0]);
-        visitor.starts_new_line();
-        row_schema.deserialize(&mut de, &mut visitor, CSVRowVisitor)?;
-      }
-      write!(write, "\n",).map_err(|e| e.into())
-    }
-    _ => Err(String::from("Secondary HDU not a BINTABLE!").into()),
+  let table = FitsTable::open(dir, HDUSelector::Index(1))?;
+  // Rows indices from the bytes range
+  let row_byte_size = table.header().row_byte_size();
+  let irows = (bytes_range.start - table.data_starting_byte()) / row_byte_size
+    ..(bytes_range.end - table.data_starting_byte()) / row_byte_size;
+  // Print header
+  write!(write, "{}", table.col_names().join("\t"))?;
+  // Print data
+  let mut visitor = CSVVisitor::new_custom(write, b'\t');
+  for irow in irows {
+    visitor.starts_new_line();
+    table.visit_row(irow, &mut visitor, CSVRowVisitor)?;
   }
+  write!(write, "\n",).map_err(|e| e.into())
 }
 
 // Print the index.html page
@@ -12,7 +12,7 @@ use std::{
 use clap::{Args, Subcommand};
 
 use cdshealpix::TWICE_PI;
-use fitstable::read::hidx::qidx;
+use fitstable::read::{checksum::write_checksums, hidx::qidx};
 use moc::{
   deser::{
     ascii::from_ascii_ivoa,
@@ -48,6 +48,9 @@ pub struct QIndex {
   /// Put a limit on the number of tuples returned
   #[clap(short = 'l', long = "limit")]
   limit: Option<usize>,
+  /// Compute and write the `CHECKSUM` and `DATASUM` keywords in all HDUs of the output file.
+  #[clap(long)]
+  checksum: bool,
   /// Sky region constraint
   #[command(subcommand)]
   region: SkyRegionEnum,
@@ -62,9 +65,13 @@ impl SkyRegionProcess for QIndex {
   type Error = Box<dyn Error>;
 
   fn exec<S: SkyRegion>(self, region: S) -> Result<Self::Output, Self::Error> {
-    let dest_file = File::create(self.output)?;
+    let dest_file = File::create(&self.output)?;
     let write = BufWriter::new(dest_file);
-    qidx(self.input, region, self.limit, write).map_err(|e| e.into())
+    qidx(self.input, region, self.limit, write)?;
+    if self.checksum {
+      write_checksums(self.output)?;
+    }
+    Ok(())
   }
 }
 
@@ -0,0 +1,35 @@
+use std::{error::Error, path::PathBuf};
+
+use clap::Args;
+use log::info;
+
+use fitstable::read::{checksum::write_checksums, select::select};
+
+/// Copies the Primary HDU and the rows of the first BINTABLE satisfying a boolean expression
+/// in a new FITS file.
+#[derive(Debug, Clone, Args)]
+pub struct Select {
+  /// Path of the input file
+  #[clap(value_name = "FILE")]
+  input: PathBuf,
+  /// Path of the output file
+  #[clap(value_name = "FILE")]
+  output: PathBuf,
+  /// Boolean expression the selected rows must satisfy (e.g. "phot_g_mean_mag < 12 && parallax > 1").
+  #[clap(short, long, value_name = "EXPR")]
+  filter: String,
+  /// Compute and write the `CHECKSUM` and `DATASUM` keywords in all HDUs of the output file.
+  #[clap(long)]
+  checksum: bool,
+}
+
+impl Select {
+  pub fn exec(self) -> Result<(), Box<dyn Error>> {
+    let n_rows = select(self.input, self.filter, &self.output)?;
+    info!("Number of selected rows: {}", n_rows);
+    if self.checksum {
+      write_checksums(self.output)?;
+    }
+    Ok(())
+  }
+}
@@ -2,7 +2,7 @@ use std::{error::Error, path::PathBuf};
 
 use clap::Args;
 
-use fitstable::read::hsort::hsort;
+use fitstable::read::{checksum::write_checksums, hsort::hsort, table::HDUSelector};
 
 /// Sorts a file (or sort and concatenate a list fo fles) by order 29 HEALPix NESTED indices,
 /// uses external sort to support huge files.
@@ -11,6 +11,10 @@ pub struct Sort {
   /// Input file or directory containing FITS files
   #[clap(value_name = "FILE")]
   input: PathBuf,
+  /// BINTABLE to be sorted: HDU index (0 being the primary HDU) or EXTNAME (or HDUNAME),
+  /// possibly followed by the EXTVER (e.g. 'EVENTS,2') [default: the first BINTABLE]
+  #[clap(long, value_name = "NAME|INDEX")]
+  hdu: Option<HDUSelector>,
   /// Field number of the longitude(in degrees) used to compute the HEALPix number, starting from 1.
   #[clap(short = 'l', long, value_name = "FIELD")]
   lon: usize,
@@ -37,6 +41,9 @@ pub struct Sort {
   /// the largest count map value is smaller than `chunk-size`.
   #[arg(short = 'd', long, default_value_t = 9_u8)]
   depth: u8,
+  /// Compute and write the `CHECKSUM` and `DATASUM` keywords in all HDUs of the output file.
+  #[clap(long)]
+  checksum: bool,
   /*/// Save the computed count map in the given FITS file path.
   #[arg(long)]
   count_map_path: Option<PathBuf>,*/
@@ -46,13 +53,18 @@ impl Sort {
   pub fn exec(self) -> Result<(), Box<dyn Error>> {
     hsort(
       self.input,
+      self.hdu.unwrap_or_default(),
       self.lon - 1,
       self.lat - 1,
-      self.output,
+      self.output.clone(),
       self.chunk_size,
       self.depth,
       Some(self.tmp_dir),
       self.parallel,
-    )
+    )?;
+    if self.checksum {
+      write_checksums(self.output)?;
+    }
+    Ok(())
   }
 }
@@ -2,7 +2,6 @@ use std::{error::Error, fmt::Debug, fs::File, path::PathBuf};
 
 use clap::Args;
 use log::info;
-use memmap2::MmapOptions;
 
 use fitstable::{
   common::keywords::naxis::NAxisn,
@@ -14,7 +13,10 @@ use fitstable::{
       image::header::ImageHeader, unknown::UnknownXtensionHeader,
     },
   },
-  read::slice::{FitsBytes, HDU},
+  read::{
+    decompress,
+    slice::{FitsBytes, HDU},
+  },
 };
 
 #[derive(Debug, Clone, Args)]
@@ -28,7 +30,7 @@ impl Struct {
   pub fn exec(self) -> Result<(), Box<dyn Error>> {
     info!("Open file {:?}", &self.input);
     let file = File::open(&self.input)?;
-    let mmap = unsafe { MmapOptions::new().map(&file)? };
+    let mmap = decompress::mmap(&file)?;
     for (i, hdu) in FitsBytes::from_slice(mmap.as_ref())
       .new_iterator::<Minimalist>()
       .enumerate()
@@ -48,6 +50,7 @@ fn print_hdu_struct(i: usize, hdu: HDU<Minimalist>) -> Result<(), Box<dyn Error>
     raw_header,
     parsed_header,
     data,
+    padding: _,
   } = hdu;
   println!(
     " * HEAD starting byte: {}; n_blocks: {}; byte size: {}",
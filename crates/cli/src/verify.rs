@@ -0,0 +1,73 @@
+use std::{
+  error::Error,
+  io::{BufWriter, Write, stdout},
+  path::PathBuf,
+};
+
+use clap::Args;
+use log::info;
+
+use fitstable::read::verify::{Severity, verify_file};
+
+/// Verify the conformance of a FITS file to the FITS standard, printing in CSV the list of all
+/// warnings and errors found (severity, HDU index, keyword record number, keyword, message).
+#[derive(Debug, Clone, Args)]
+pub struct Verify {
+  /// Path of the input file
+  #[clap(value_name = "FILE")]
+  input: PathBuf,
+  /// Only print errors, not warnings
+  #[clap(short = 'e', long)]
+  errors_only: bool,
+}
+
+impl Verify {
+  pub fn exec(self) -> Result<(), Box<dyn Error>> {
+    let issues = verify_file(&self.input)?;
+    let n_errors = issues
+      .iter()
+      .filter(|issue| issue.severity == Severity::Error)
+      .count();
+    let n_warnings = issues.len() - n_errors;
+
+    let mut write = BufWriter::new(stdout().lock());
+    writeln!(write, "severity,hdu,kw_record,keyword,message")?;
+    for issue in issues
+      .iter()
+      .filter(|issue| !self.errors_only || issue.severity == Severity::Error)
+    {
+      writeln!(
+        write,
+        "{},{},{},{},{}",
+        issue.severity,
+        issue.hdu,
+        issue
+          .kw_record
+          .map(|pos| pos.to_string())
+          .unwrap_or_default(),
+        escape(issue.keyword.as_deref().unwrap_or_default()),
+        escape(&issue.message)
+      )?;
+    }
+    write.flush()?;
+
+    info!(
+      "Number of errors: {}; of warnings: {}.",
+      n_errors, n_warnings
+    );
+    if n_errors > 0 {
+      Err(format!("{} error(s) found in {:?}.", n_errors, self.input).into())
+    } else {
+      Ok(())
+    }
+  }
+}
+
+/// Quote the given CSV field if needed.
+fn escape(field: &str) -> String {
+  if field.contains([',', '"', '\n']) {
+    format!("\"{}\"", field.replace('"', "\"\""))
+  } else {
+    String::from(field)
+  }
+}
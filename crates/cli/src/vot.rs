@@ -0,0 +1,32 @@
+use std::{error::Error, fs::File, io::stdout, path::PathBuf};
+
+use clap::Args;
+use log::info;
+
+use fitstable::read::tovot::{VOTDataFormat, fits_to_vot};
+
+/// Write the BINTABLEs of a FITS file in a VOTable (one TABLE per BINTABLE), using the
+/// VOTable metadata of FITS-plus files (if any) as a template.
+#[derive(Debug, Clone, Args)]
+pub struct Vot {
+  /// Path of the input FITS file
+  #[clap(value_name = "FILE")]
+  input: PathBuf,
+  /// Path of the output file [default: write to stdout]
+  #[clap(short = 'o', long = "out", value_name = "FILE")]
+  output: Option<PathBuf>,
+  /// Serialization of the VOTable data: 'tabledata', 'binary' or 'binary2'
+  #[clap(short, long, value_name = "FORMAT", default_value = "binary2")]
+  format: VOTDataFormat,
+}
+
+impl Vot {
+  pub fn exec(self) -> Result<(), Box<dyn Error>> {
+    match &self.output {
+      Some(path) => fits_to_vot(&self.input, self.format, File::create(path)?),
+      None => fits_to_vot(&self.input, self.format, stdout().lock()),
+    }
+    .map(|n_tables| info!("Number of TABLEs written: {}", n_tables))
+    .map_err(|e| e.into())
+  }
+}
@@ -0,0 +1,116 @@
+//! Provides the `#[derive(FitsRow)]` macro implementing the `fitstable` `FitsRow` trait, to
+//! deserialize BINTABLE rows into a structure having named fields.
+//!
+//! Each field is bound to the column having the same name, or the name given in a
+//! `#[fits(name = "...")]` attribute:
+//! ```ignore
+//! #[derive(FitsRow)]
+//! struct Star {
+//!   ra: f64,
+//!   dec: f64,
+//!   #[fits(name = "phot_g_mean_mag")]
+//!   g: Option<f32>,
+//! }
+//! ```
+//! The type of each field must implement the `fitstable` `FromField` trait.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{Data, DeriveInput, Error, Field, Fields, LitStr, parse_macro_input};
+
+#[proc_macro_derive(FitsRow, attributes(fits))]
+pub fn derive_fits_row(input: TokenStream) -> TokenStream {
+  let input = parse_macro_input!(input as DeriveInput);
+  impl_fits_row(&input)
+    .unwrap_or_else(Error::into_compile_error)
+    .into()
+}
+
+fn impl_fits_row(input: &DeriveInput) -> Result<TokenStream2, Error> {
+  let fields = match &input.data {
+    Data::Struct(data) => match &data.fields {
+      Fields::Named(fields) => &fields.named,
+      _ => {
+        return Err(Error::new_spanned(
+          input,
+          "FitsRow can only be derived for structs with named fields",
+        ));
+      }
+    },
+    _ => {
+      return Err(Error::new_spanned(
+        input,
+        "FitsRow can only be derived for structs",
+      ));
+    }
+  };
+  let idents = fields.iter().map(|field| &field.ident).collect::<Vec<_>>();
+  let types = fields.iter().map(|field| &field.ty).collect::<Vec<_>>();
+  let type_names = types
+    .iter()
+    .map(|ty| quote!(#ty).to_string().replace(' ', ""))
+    .collect::<Vec<_>>();
+  let col_names = fields
+    .iter()
+    .map(column_name)
+    .collect::<Result<Vec<_>, Error>>()?;
+  let indices = 0..fields.len();
+  let indices_bis = indices.clone();
+
+  let name = &input.ident;
+  let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+  let row = quote!(::fitstable::hdu::xtension::bintable::read::deser::row);
+  let schema = quote!(::fitstable::hdu::xtension::bintable::schema);
+  let error = quote!(::fitstable::error::Error);
+  Ok(quote! {
+    impl #impl_generics #row::FitsRow for #name #ty_generics #where_clause {
+      const COLUMNS: &'static [&'static str] = &[#(#col_names),*];
+
+      fn check_schema(fields_schemas: &[#schema::FieldSchema]) -> Result<(), #error> {
+        #(#row::check_field::<#types>(#col_names, &fields_schemas[#indices], #type_names)?;)*
+        Ok(())
+      }
+
+      fn deserialize<'de, D>(
+        fields_schemas: &[#schema::FieldSchema],
+        deserializer: &mut D,
+      ) -> Result<Self, #error>
+      where
+        D: ::fitstable::hdu::xtension::bintable::read::deser::Deserializer<'de>,
+      {
+        Ok(Self {
+          #(#idents: <#types as #row::FromField>::from_field(
+            &fields_schemas[#indices_bis],
+            deserializer,
+          )?,)*
+        })
+      }
+    }
+  })
+}
+
+/// Returns the name of the column the given field is bound to: either the name given in the
+/// `#[fits(name = "...")]` attribute, or the name of the field.
+fn column_name(field: &Field) -> Result<String, Error> {
+  let mut name = None;
+  for attr in field
+    .attrs
+    .iter()
+    .filter(|attr| attr.path().is_ident("fits"))
+  {
+    attr.parse_nested_meta(|meta| {
+      if meta.path.is_ident("name") {
+        name = Some(meta.value()?.parse::<LitStr>()?.value());
+        Ok(())
+      } else {
+        Err(meta.error("unsupported fits attribute, expected `name = \"...\"`"))
+      }
+    })?;
+  }
+  match (name, &field.ident) {
+    (Some(name), _) => Ok(name),
+    (None, Some(ident)) => Ok(ident.to_string().trim_start_matches("r#").to_string()),
+    (None, None) => Err(Error::new_spanned(field, "unnamed field")),
+  }
+}
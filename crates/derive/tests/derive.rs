@@ -0,0 +1,117 @@
+use std::io::Cursor;
+
+use fitstable::{
+  FitsRow,
+  hdu::{
+    header::{HDUHeader, builder::r#impl::bintable::Bintable, raw::RawHeader},
+    xtension::bintable::{
+      field::Field,
+      header::{BinTableColumnHeader, BinTableHeaderWithColInfo},
+      read::deser::row::RowBinding,
+      schema::{ArrayParam, Schema},
+      write::BinTableWriter,
+    },
+  },
+};
+
+#[derive(Debug, FitsRow)]
+struct Source {
+  #[fits(name = "ID")]
+  id: i32,
+  #[fits(name = "FLUX_G")]
+  flux: Option<f32>,
+  name: String,
+}
+
+#[derive(Debug, FitsRow)]
+#[allow(dead_code)]
+struct WrongType {
+  #[fits(name = "ID")]
+  id: f64,
+}
+
+#[derive(Debug, FitsRow)]
+#[allow(dead_code)]
+struct MissingColumn {
+  #[fits(name = "FLUX_R")]
+  flux: Option<f32>,
+}
+
+/// Returns the header and the data of a BINTABLE having the columns `name`, `FLUX_G` and `ID`
+/// (i.e. not in the structure fields order).
+fn bintable() -> (BinTableHeaderWithColInfo, Vec<u8>) {
+  let cols = [
+    ("name", Schema::AsciiString(ArrayParam::new(8))),
+    ("FLUX_G", Schema::Float),
+    ("ID", Schema::Int),
+  ]
+  .into_iter()
+  .enumerate()
+  .map(|(i, (name, schema))| {
+    BinTableColumnHeader::from_schema(i as u16, &schema).map(|mut col| {
+      col.set_colname(i as u16, name.to_string());
+      col
+    })
+  })
+  .collect::<Result<Vec<_>, _>>()
+  .unwrap();
+  let mut writer = BinTableWriter::new(Cursor::new(Vec::new()), cols).unwrap();
+  for (name, flux, id) in [("alpha", 1.5, 1), ("beta", f32::NAN, 2), ("gamma", -0.5, 3)] {
+    writer
+      .write_row(&[
+        Field::AsciiString(name.to_string()),
+        Field::Float(flux),
+        Field::Int(id),
+      ])
+      .unwrap();
+  }
+  let bytes = writer.finish().unwrap().into_inner();
+  let (raw_header, data) = RawHeader::<&[u8]>::from_slice(false, &bytes).unwrap();
+  match raw_header.build::<Bintable>(false).unwrap() {
+    HDUHeader::BinTable(header) => (header, data.to_vec()),
+    _ => panic!("BINTABLE header expected"),
+  }
+}
+
+#[test]
+fn test_derive_bind_and_deserialize() {
+  let (header, data) = bintable();
+  let binding = RowBinding::<Source>::from_header(&header).unwrap();
+  let rows = binding
+    .rows(&header, &data)
+    .unwrap()
+    .collect::<Result<Vec<Source>, _>>()
+    .unwrap();
+  assert_eq!(rows.len(), 3);
+  assert_eq!(rows.iter().map(|s| s.id).collect::<Vec<_>>(), vec![1, 2, 3]);
+  assert_eq!(
+    rows.iter().map(|s| s.flux).collect::<Vec<_>>(),
+    vec![Some(1.5), None, Some(-0.5)]
+  );
+  assert_eq!(
+    rows.iter().map(|s| s.name.as_str()).collect::<Vec<_>>(),
+    vec!["alpha", "beta", "gamma"]
+  );
+}
+
+#[test]
+fn test_derive_bind_errors() {
+  let (header, _) = bintable();
+  let col_names = header.build_col_names();
+  let row_schema = header.build_row_schema();
+  // An `Int` column can't be deserialized into a `f64`
+  assert!(RowBinding::<WrongType>::new(&col_names, &row_schema).is_err());
+  assert!(RowBinding::<MissingColumn>::new(&col_names, &row_schema).is_err());
+}
+
+#[test]
+fn test_derive_rows_data_too_short() {
+  let (header, data) = bintable();
+  let binding = RowBinding::<Source>::from_header(&header).unwrap();
+  let main_table_byte_size = header.main_table_byte_size();
+  assert!(
+    binding
+      .rows(&header, &data[..main_table_byte_size - 1])
+      .is_err()
+  );
+}
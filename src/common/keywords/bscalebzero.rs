@@ -0,0 +1,156 @@
+//! Defines the `BSCALE`, `BZERO` and `BLANK` keywords of image HDUs (primary HDU or `IMAGE`
+//! extension).
+//! `BSCALE` default value is 1.0 and `BZERO` default value is 0.0.
+//! They are used in equation:
+//! > `physical_value = BZERO + BSCALE * array_value`
+//!
+//! `BLANK` is the array value coding an undefined pixel, to be used with integer `BITPIX` only.
+
+use crate::{
+  common::{
+    FixedFormat, KwrFormatRead, ValueKwr,
+    keywords::tables::tscaltzero::UIF64,
+    read::bytes2str,
+    write::{FixedFormatWrite, KwrFormatWrite},
+  },
+  error::{Error, new_unexpected_value},
+};
+
+/// The `BSCALE` keyword.
+#[derive(Debug)]
+pub struct BScale(f64);
+
+impl BScale {
+  pub fn new(value: f64) -> Self {
+    Self(value)
+  }
+  pub fn get(&self) -> f64 {
+    self.0
+  }
+}
+
+impl ValueKwr for BScale {
+  const KEYWORD: &'static [u8; 8] = b"BSCALE  ";
+
+  fn check_value(&self, kwr_value_comment: &[u8; 70]) -> Result<(), Error> {
+    FixedFormat::parse_real_value(kwr_value_comment).and_then(|(val, _comment)| {
+      if val != self.0 {
+        Err(new_unexpected_value(self.0, val))
+      } else {
+        Ok(())
+      }
+    })
+  }
+
+  fn from_value_comment(kwr_value_comment: &[u8; 70]) -> Result<Self, Error> {
+    FixedFormat::parse_real_value(kwr_value_comment).map(|(val, _comment)| Self(val))
+  }
+
+  fn write_kw_record<'a, I>(&self, dest_kwr_it: &mut I) -> Result<(), Error>
+  where
+    I: Iterator<Item = Result<&'a mut [u8; 80], Error>>,
+  {
+    FixedFormatWrite::write_real_value_kw_record(
+      dest_kwr_it,
+      Self::KEYWORD,
+      self.0,
+      None,
+      Some("Scale factor of array values"),
+    )
+  }
+}
+
+/// The `BZERO` keyword.
+/// We use `UIF64` to exactly store the offset of unsigned 64-bit integer images.
+#[derive(Debug)]
+pub struct BZero(UIF64);
+
+impl BZero {
+  pub fn new(value: UIF64) -> Self {
+    Self(value)
+  }
+  pub fn get(&self) -> UIF64 {
+    self.0
+  }
+}
+
+impl ValueKwr for BZero {
+  const KEYWORD: &'static [u8; 8] = b"BZERO   ";
+
+  fn check_value(&self, kwr_value_comment: &[u8; 70]) -> Result<(), Error> {
+    FixedFormat::parse_real_value(kwr_value_comment).and_then(|(val, _comment)| {
+      if val != self.0.as_f64() {
+        Err(new_unexpected_value(self.0.as_f64(), val))
+      } else {
+        Ok(())
+      }
+    })
+  }
+
+  fn from_value_comment(kwr_value_comment: &[u8; 70]) -> Result<Self, Error> {
+    FixedFormat::parse_real_str_value(kwr_value_comment)
+      .and_then(|(val, _comment)| bytes2str(val).parse::<UIF64>())
+      .map(Self)
+  }
+
+  fn write_kw_record<'a, I>(&self, dest_kwr_it: &mut I) -> Result<(), Error>
+  where
+    I: Iterator<Item = Result<&'a mut [u8; 80], Error>>,
+  {
+    let comment = Some("Zero point (offset) of array values");
+    match self.0 {
+      UIF64::U64(val) => {
+        FixedFormatWrite::write_uint_value_kw_record(dest_kwr_it, Self::KEYWORD, val, comment)
+      }
+      UIF64::I64(val) => {
+        FixedFormatWrite::write_int_value_kw_record(dest_kwr_it, Self::KEYWORD, val, comment)
+      }
+      UIF64::F64(val) => {
+        FixedFormatWrite::write_real_value_kw_record(dest_kwr_it, Self::KEYWORD, val, None, comment)
+      }
+    }
+  }
+}
+
+/// The `BLANK` keyword.
+#[derive(Debug)]
+pub struct Blank(i64);
+
+impl Blank {
+  pub fn new(value: i64) -> Self {
+    Self(value)
+  }
+  pub fn get(&self) -> i64 {
+    self.0
+  }
+}
+
+impl ValueKwr for Blank {
+  const KEYWORD: &'static [u8; 8] = b"BLANK   ";
+
+  fn check_value(&self, kwr_value_comment: &[u8; 70]) -> Result<(), Error> {
+    FixedFormat::parse_integer_value(kwr_value_comment).and_then(|(val, _comment)| {
+      if val != self.0 {
+        Err(new_unexpected_value(self.0, val))
+      } else {
+        Ok(())
+      }
+    })
+  }
+
+  fn from_value_comment(kwr_value_comment: &[u8; 70]) -> Result<Self, Error> {
+    FixedFormat::parse_integer_value(kwr_value_comment).map(|(val, _comment)| Self(val))
+  }
+
+  fn write_kw_record<'a, I>(&self, dest_kwr_it: &mut I) -> Result<(), Error>
+  where
+    I: Iterator<Item = Result<&'a mut [u8; 80], Error>>,
+  {
+    FixedFormatWrite::write_int_value_kw_record(
+      dest_kwr_it,
+      Self::KEYWORD,
+      self.0,
+      Some("Array value of undefined pixels"),
+    )
+  }
+}
@@ -0,0 +1,111 @@
+//! Defines the `CHECKSUM` and `DATASUM` keywords (see the FITS checksum convention,
+//! Seaman et al., 2002), allowing to verify the integrity of a HDU.
+use crate::{
+  common::{
+    FreeFormat, KwrFormatRead, ValueKwr,
+    write::{FreeFormatWrite, KwrFormatWrite},
+  },
+  error::{Error, new_unexpected_value},
+};
+
+/// The `CHECKSUM` keyword, i.e. the 16 chars ASCII encoding of the 1's complement of the
+/// 32-bit 1's complement checksum of the whole HDU (header and data).
+pub struct CheckSum([u8; 16]);
+
+impl CheckSum {
+  /// Value of the keyword while computing the HDU checksum.
+  pub const ZERO: Self = Self([b'0'; 16]);
+
+  pub fn new(encoded_checksum: [u8; 16]) -> Self {
+    Self(encoded_checksum)
+  }
+  pub fn get(&self) -> &[u8; 16] {
+    &self.0
+  }
+}
+
+impl ValueKwr for CheckSum {
+  const KEYWORD: &'static [u8; 8] = b"CHECKSUM";
+
+  fn check_value(&self, kwr_value_comment: &[u8; 70]) -> Result<(), Error> {
+    Self::from_value_comment(kwr_value_comment).and_then(|found| {
+      if found.0 != self.0 {
+        Err(new_unexpected_value(
+          String::from_utf8_lossy(&self.0),
+          String::from_utf8_lossy(&found.0),
+        ))
+      } else {
+        Ok(())
+      }
+    })
+  }
+
+  fn from_value_comment(kwr_value_comment: &[u8; 70]) -> Result<Self, Error> {
+    FreeFormat::parse_string_value(kwr_value_comment).and_then(|(val, _comment)| {
+      <[u8; 16]>::try_from(val.trim_end().as_bytes())
+        .map(Self)
+        .map_err(|_| new_unexpected_value("16 chars encoded checksum", val))
+    })
+  }
+
+  fn write_kw_record<'a, I>(&self, dest_kwr_it: &mut I) -> Result<(), Error>
+  where
+    I: Iterator<Item = Result<&'a mut [u8; 80], Error>>,
+  {
+    FreeFormatWrite::write_string_value_kw_record(
+      dest_kwr_it,
+      Self::KEYWORD,
+      &String::from_utf8_lossy(&self.0),
+      Some("HDU checksum"),
+    )
+  }
+}
+
+/// The `DATASUM` keyword, i.e. the 32-bit 1's complement checksum of the data unit
+/// (the value is stored as an unsigned integer in a string).
+pub struct DataSum(u32);
+
+impl DataSum {
+  pub fn new(datasum: u32) -> Self {
+    Self(datasum)
+  }
+  pub fn get(&self) -> u32 {
+    self.0
+  }
+}
+
+impl ValueKwr for DataSum {
+  const KEYWORD: &'static [u8; 8] = b"DATASUM ";
+
+  fn check_value(&self, kwr_value_comment: &[u8; 70]) -> Result<(), Error> {
+    Self::from_value_comment(kwr_value_comment).and_then(|found| {
+      if found.0 != self.0 {
+        Err(new_unexpected_value(self.0, found.0))
+      } else {
+        Ok(())
+      }
+    })
+  }
+
+  fn from_value_comment(kwr_value_comment: &[u8; 70]) -> Result<Self, Error> {
+    FreeFormat::parse_string_value(kwr_value_comment).and_then(|(val, _comment)| {
+      val
+        .trim()
+        .parse::<u32>()
+        .map(Self)
+        .map_err(|_| new_unexpected_value("32-bit unsigned integer", val))
+    })
+  }
+
+  fn write_kw_record<'a, I>(&self, dest_kwr_it: &mut I) -> Result<(), Error>
+  where
+    I: Iterator<Item = Result<&'a mut [u8; 80], Error>>,
+  {
+    FreeFormatWrite::write_string_value_kw_record(
+      dest_kwr_it,
+      Self::KEYWORD,
+      &self.0.to_string(),
+      Some("Data unit checksum"),
+    )
+  }
+}
@@ -0,0 +1,130 @@
+//! Defines the keywords identifying an extension (see FITS standard v4.0, section 4.4.2.6):
+//! * `EXTNAME`: name of the extension
+//! * `EXTVER`: version of the extension, to distinguish extensions having the same name
+//! * `HDUNAME`: name of the HDU (HEASARC convention)
+use crate::{
+  common::{
+    FixedFormat, KwrFormatRead, ValueKwr,
+    write::{FixedFormatWrite, KwrFormatWrite},
+  },
+  error::{Error, new_unexpected_value},
+};
+
+/// The `EXTNAME` keyword.
+#[derive(Debug)]
+pub struct ExtName(String);
+
+impl ExtName {
+  pub fn new(name: String) -> Self {
+    Self(name)
+  }
+  pub fn get(&self) -> &str {
+    self.0.as_str()
+  }
+}
+
+impl ValueKwr for ExtName {
+  const KEYWORD: &'static [u8; 8] = b"EXTNAME ";
+
+  fn check_value(&self, _kwr_value_comment: &[u8; 70]) -> Result<(), Error> {
+    unreachable!() // not supposed to be called
+  }
+
+  fn from_value_comment(kwr_value_comment: &[u8; 70]) -> Result<Self, Error> {
+    FixedFormat::parse_string_value(kwr_value_comment)
+      .map(|(val, _comment)| Self(val.trim_end().to_string()))
+  }
+
+  fn write_kw_record<'a, I>(&self, dest_kwr_it: &mut I) -> Result<(), Error>
+  where
+    I: Iterator<Item = Result<&'a mut [u8; 80], Error>>,
+  {
+    FixedFormatWrite::write_string_value_kw_record(
+      dest_kwr_it,
+      Self::KEYWORD,
+      self.0.as_str(),
+      Some("Name of the extension"),
+    )
+  }
+}
+
+/// The `EXTVER` keyword (`1` if not present).
+#[derive(Debug)]
+pub struct ExtVer(i64);
+
+impl ExtVer {
+  pub fn new(version: i64) -> Self {
+    Self(version)
+  }
+  pub fn get(&self) -> i64 {
+    self.0
+  }
+}
+
+impl ValueKwr for ExtVer {
+  const KEYWORD: &'static [u8; 8] = b"EXTVER  ";
+
+  fn check_value(&self, kwr_value_comment: &[u8; 70]) -> Result<(), Error> {
+    FixedFormat::parse_integer_value(kwr_value_comment).and_then(|(val, _comment)| {
+      if val != self.0 {
+        Err(new_unexpected_value(self.0, val))
+      } else {
+        Ok(())
+      }
+    })
+  }
+
+  fn from_value_comment(kwr_value_comment: &[u8; 70]) -> Result<Self, Error> {
+    FixedFormat::parse_integer_value(kwr_value_comment).map(|(val, _comment)| Self(val))
+  }
+
+  fn write_kw_record<'a, I>(&self, dest_kwr_it: &mut I) -> Result<(), Error>
+  where
+    I: Iterator<Item = Result<&'a mut [u8; 80], Error>>,
+  {
+    FixedFormatWrite::write_int_value_kw_record(
+      dest_kwr_it,
+      Self::KEYWORD,
+      self.0,
+      Some("Version of the extension"),
+    )
+  }
+}
+
+/// The `HDUNAME` keyword.
+#[derive(Debug)]
+pub struct HduName(String);
+
+impl HduName {
+  pub fn new(name: String) -> Self {
+    Self(name)
+  }
+  pub fn get(&self) -> &str {
+    self.0.as_str()
+  }
+}
+
+impl ValueKwr for HduName {
+  const KEYWORD: &'static [u8; 8] = b"HDUNAME ";
+
+  fn check_value(&self, _kwr_value_comment: &[u8; 70]) -> Result<(), Error> {
+    unreachable!() // not supposed to be called
+  }
+
+  fn from_value_comment(kwr_value_comment: &[u8; 70]) -> Result<Self, Error> {
+    FixedFormat::parse_string_value(kwr_value_comment)
+      .map(|(val, _comment)| Self(val.trim_end().to_string()))
+  }
+
+  fn write_kw_record<'a, I>(&self, dest_kwr_it: &mut I) -> Result<(), Error>
+  where
+    I: Iterator<Item = Result<&'a mut [u8; 80], Error>>,
+  {
+    FixedFormatWrite::write_string_value_kw_record(
+      dest_kwr_it,
+      Self::KEYWORD,
+      self.0.as_str(),
+      Some("Name of the HDU"),
+    )
+  }
+}
@@ -1,4 +1,7 @@
 pub mod bitpix;
+pub mod bscalebzero;
+pub mod checksum;
+pub mod extname;
 pub mod naxis;
 pub mod pgcount;
 pub mod simple;
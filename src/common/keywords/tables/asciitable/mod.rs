@@ -0,0 +1,6 @@
+//! `ASCIITABLE` specific keywords.
+
+pub mod tbcol;
+pub mod tdisp;
+pub mod tform;
+pub mod tnull;
@@ -0,0 +1,72 @@
+//! Defines the `TBCOLn` (i.e. column starting position) keyword for `ASCIITABLE` extensions.
+use crate::{
+  common::{
+    DynValueKwr, FixedFormat, KwrFormatRead,
+    write::{FixedFormatWrite, KwrFormatWrite},
+  },
+  error::{Error, new_unexpected_value},
+};
+
+/// The `TBCOLn` keyword, i.e. position of the first character of a column in a row.
+#[derive(Debug)]
+pub struct TBCol {
+  n: u16,
+  value: u32,
+}
+
+impl TBCol {
+  /// # Params
+  /// * `n` the `TBCOLn` number in `[1, TFIELD]`.
+  /// * `value` value associated to this `TBCOLn` keyword, i.e. position, starting at 1,
+  ///   of the first character of the column number `n` in a row
+  pub fn new(n: u16, value: u32) -> Self {
+    Self { n, value }
+  }
+
+  pub fn col_nbr(&self) -> u16 {
+    self.n
+  }
+  /// Position of the first character of the column, starting at 1.
+  pub fn col_starting_pos(&self) -> u32 {
+    self.value
+  }
+  /// Index of the first byte of the column in a row, starting at 0.
+  pub fn col_starting_byte(&self) -> usize {
+    (self.value - 1) as usize
+  }
+}
+
+impl DynValueKwr for TBCol {
+  const KW_PREFIX: &'static [u8] = b"TBCOL";
+
+  fn n(&self) -> u16 {
+    self.n
+  }
+
+  fn check_value(&self, _kwr_value_comment: &[u8; 70]) -> Result<(), Error> {
+    unreachable!() // not supposed to be called
+  }
+
+  fn from_value_comment(n: u16, kwr_value_comment: &[u8; 70]) -> Result<Self, Error> {
+    FixedFormat::parse_integer_value(kwr_value_comment).and_then(|(val, _comment)| {
+      u32::try_from(val)
+        .ok()
+        .filter(|v| *v > 0)
+        .map(|v| Self::new(n, v))
+        .ok_or_else(|| new_unexpected_value("TBCOLn value in [1, 2^32)", val))
+    })
+  }
+
+  fn write_kw_record<'a, I>(&self, dest_kwr_it: &mut I) -> Result<(), Error>
+  where
+    I: Iterator<Item = Result<&'a mut [u8; 80], Error>>,
+  {
+    let comment = format!("Starting position of column #{}", self.n);
+    FixedFormatWrite::write_int_value_kw_record(
+      dest_kwr_it,
+      &Self::keyword(self.n),
+      self.value as i64,
+      Some(comment.as_str()),
+    )
+  }
+}
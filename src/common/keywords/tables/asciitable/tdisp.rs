@@ -0,0 +1,5 @@
+//! Defines the `TDISPn` keyword for `ASCIITABLE` extensions.
+//! The allowed values are a subset of the `BINTABLE` ones (no `L`, `B`, `O` nor `Z`),
+//! so we reuse the `BINTABLE` implementation.
+
+pub use crate::common::keywords::tables::bintable::tdisp::{TDispValue, TDispn};
@@ -0,0 +1,138 @@
+//! Defines the `TFORMn` (i.e. column data type) keyword for `ASCIITABLE` extensions.
+use std::{
+  fmt::{Display, Formatter},
+  str::FromStr,
+};
+
+use crate::{
+  common::{
+    DynValueKwr, FreeFormat,
+    read::KwrFormatRead,
+    write::{FreeFormatWrite, KwrFormatWrite},
+  },
+  error::{Error, new_parse_u16_err, new_unexpected_value},
+};
+
+/// Fortran-like format of an `ASCIITABLE` field, `w` being the field width (in bytes)
+/// and `d` the number of digits to the right of the decimal point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TFormValue {
+  /// Character string
+  A { w: u16 },
+  /// Integer
+  I { w: u16 },
+  /// Floating-point, fixed decimal notation
+  F { w: u16, d: u16 },
+  /// Floating-point, exponential notation
+  E { w: u16, d: u16 },
+  /// Floating-point, exponential notation (double precision)
+  D { w: u16, d: u16 },
+}
+impl TFormValue {
+  /// Width of the field, in bytes.
+  pub fn width(&self) -> u16 {
+    match self {
+      Self::A { w }
+      | Self::I { w }
+      | Self::F { w, d: _ }
+      | Self::E { w, d: _ }
+      | Self::D { w, d: _ } => *w,
+    }
+  }
+}
+impl FromStr for TFormValue {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let s = s.trim();
+    if s.is_empty() {
+      return Err(new_unexpected_value("TFORM starting with [AIFED]", s));
+    }
+    let (letter, tail) = s.split_at(1);
+    match letter {
+      "A" => parse_w(tail).map(|w| TFormValue::A { w }),
+      "I" => parse_w(tail).map(|w| TFormValue::I { w }),
+      "F" => parse_wd(tail).map(|(w, d)| TFormValue::F { w, d }),
+      "E" => parse_wd(tail).map(|(w, d)| TFormValue::E { w, d }),
+      "D" => parse_wd(tail).map(|(w, d)| TFormValue::D { w, d }),
+      _ => Err(new_unexpected_value("TFORM starting with [AIFED]", s)),
+    }
+    .map_err(|e| e.kw_val_context(b"TFORMn  ", s))
+  }
+}
+fn parse_w(s: &str) -> Result<u16, Error> {
+  s.parse::<u16>().map_err(new_parse_u16_err)
+}
+fn parse_wd(s: &str) -> Result<(u16, u16), Error> {
+  match s.split_once('.') {
+    Some((w, d)) => w
+      .parse::<u16>()
+      .and_then(|w| d.parse::<u16>().map(|d| (w, d)))
+      .map_err(new_parse_u16_err),
+    None => Err(new_unexpected_value("w.d", s)),
+  }
+}
+
+impl Display for TFormValue {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::A { w } => f.write_fmt(format_args!("A{}", w)),
+      Self::I { w } => f.write_fmt(format_args!("I{}", w)),
+      Self::F { w, d } => f.write_fmt(format_args!("F{}.{}", w, d)),
+      Self::E { w, d } => f.write_fmt(format_args!("E{}.{}", w, d)),
+      Self::D { w, d } => f.write_fmt(format_args!("D{}.{}", w, d)),
+    }
+  }
+}
+
+#[derive(Debug)]
+pub struct TFormn {
+  n: u16,
+  value: TFormValue,
+}
+impl TFormn {
+  /// # Params
+  /// * `n` the `TFORMn` number in `[1, TFIELD]`.
+  /// * `value` value associated to this `TFORMn` keyword, i.e. column data type
+  pub fn new(n: u16, value: TFormValue) -> Self {
+    Self { n, value }
+  }
+
+  /// Column number starts at 1.
+  pub fn col_nbr(&self) -> u16 {
+    self.n
+  }
+  pub fn tform_type(&self) -> &TFormValue {
+    &self.value
+  }
+}
+
+impl DynValueKwr for TFormn {
+  const KW_PREFIX: &'static [u8] = b"TFORM";
+
+  fn n(&self) -> u16 {
+    self.n
+  }
+
+  fn check_value(&self, _kwr_value_comment: &[u8; 70]) -> Result<(), Error> {
+    unreachable!() // not supposed to be called
+  }
+
+  fn from_value_comment(n: u16, kwr_value_comment: &[u8; 70]) -> Result<Self, Error> {
+    FreeFormat::parse_string_value_no_quote(kwr_value_comment)
+      .and_then(|(val, _comment)| val.parse::<TFormValue>().map(|v| Self::new(n, v)))
+  }
+
+  fn write_kw_record<'a, I>(&self, dest_kwr_it: &mut I) -> Result<(), Error>
+  where
+    I: Iterator<Item = Result<&'a mut [u8; 80], Error>>,
+  {
+    let comment = format!("Column {} data type", self.n);
+    FreeFormatWrite::write_string_value_kw_record(
+      dest_kwr_it,
+      &Self::keyword(self.n),
+      self.value.to_string().as_str(),
+      Some(comment.as_str()),
+    )
+  }
+}
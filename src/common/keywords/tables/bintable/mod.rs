@@ -2,5 +2,6 @@ pub mod tdim;
 pub mod tdisp;
 pub mod tform;
 pub mod theap;
+pub mod ztable;
 #[cfg(feature = "vot")]
 pub mod vot;
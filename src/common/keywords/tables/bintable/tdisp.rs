@@ -10,10 +10,11 @@ use crate::{
     write::{FreeFormatWrite, KwrFormatWrite},
   },
   error::{Error, new_parse_u16_err, new_unexpected_value},
+  hdu::xtension::bintable::field::Field,
 };
 
 /// Remainder: display '*****' (`w` times) if width of string representation is larger than `w`.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum TDispValue {
   // Char format
   A { w: u16 },
@@ -86,13 +87,309 @@ impl FromStr for TDispValue {
     .map_err(|e| e.kw_val_context(b"TDISPn  ", s))
   }
 }
-/*impl TDispValue {
-  pub fn display(&self, field: Field) -> String {
-    match (&self, field) {
+/// Formatting methods.
+/// The `format_xxx` methods return the representation of a value without padding it to the
+/// field width `w` (e.g. to be used in CSV), while `display` returns the fixed width representation.
+impl TDispValue {
+  /// `T` or `F` for the `L` (and `A`) format, `1` or `0` formatted as an integer else.
+  pub fn format_bool(&self, v: bool) -> String {
+    match self {
+      Self::A { .. } | Self::L { .. } => String::from(if v { "T" } else { "F" }),
+      _ => self.format_u64(v as u64),
+    }
+  }
+
+  pub fn format_i64(&self, v: i64) -> String {
+    match self {
+      Self::A { .. } => v.to_string(),
+      Self::L { .. } => self.format_bool(v != 0),
+      Self::I { .. } | Self::B { .. } | Self::O { .. } | Self::Z { .. } => {
+        let digits = self.format_digits(v.unsigned_abs());
+        if v < 0 {
+          format!("-{}", digits)
+        } else {
+          digits
+        }
+      }
+      _ => self.format_f64(v as f64),
+    }
+  }
 
+  pub fn format_u64(&self, v: u64) -> String {
+    match self {
+      Self::A { .. } => v.to_string(),
+      Self::L { .. } => self.format_bool(v != 0),
+      Self::I { .. } | Self::B { .. } | Self::O { .. } | Self::Z { .. } => self.format_digits(v),
+      _ => self.format_f64(v as f64),
+    }
+  }
+
+  /// Non-finite values (NaN and infinities) are formatted using the Rust default representation.
+  pub fn format_f64(&self, v: f64) -> String {
+    if !v.is_finite() {
+      return v.to_string();
+    }
+    match *self {
+      Self::A { .. } => v.to_string(),
+      Self::L { .. } => self.format_bool(v != 0.0),
+      Self::I { .. } | Self::B { .. } | Self::O { .. } | Self::Z { .. } => {
+        self.format_i64(v.round() as i64)
+      }
+      Self::F { w: _, d } => format!("{:.*}", d as usize, v),
+      Self::E { w: _, d, e } => format_e(v, d, e, 'E'),
+      Self::D { w: _, d, e } => format_e(v, d, e, 'D'),
+      Self::EN { w: _, d } => format_en(v, d),
+      Self::ES { w: _, d } => format_es(v, d),
+      Self::G { w: _, d, e } => format_g(v, d, e),
     }
   }
-}*/
+
+  /// Truncate the string to `w` characters in the case of the `A` format.
+  pub fn format_str<'a>(&self, v: &'a str) -> &'a str {
+    match *self {
+      Self::A { w } => v
+        .char_indices()
+        .nth(w as usize)
+        .map(|(i, _)| &v[..i])
+        .unwrap_or(v),
+      _ => v,
+    }
+  }
+
+  /// Right-justify the given (formatted) value in a field of `w` characters.
+  /// If the value is larger than `w`, the field is filled with `*` (a string is truncated).
+  pub fn justify(&self, s: &str) -> String {
+    let w = self.get_width_and_prec().0 as usize;
+    if s.chars().count() <= w {
+      format!("{:>w$}", s, w = w)
+    } else if let Self::A { .. } = self {
+      self.format_str(s).to_string()
+    } else {
+      "*".repeat(w)
+    }
+  }
+
+  /// Returns the fixed width representation of the given field, null values being displayed
+  /// as blanks. Array elements (and the real and imaginary parts of complex numbers) are
+  /// separated by a space.
+  pub fn display(&self, field: &Field) -> String {
+    match field {
+      Field::Empty => String::new(),
+      Field::NullableBoolean(v) => self.display_opt(v.map(|v| self.format_bool(v))),
+      Field::BitArray(v) => self.display_array(v.iter().map(|v| Some(self.format_u64(*v as u64)))),
+      Field::Byte(v) => self.justify(&self.format_i64(*v as i64)),
+      Field::Short(v) => self.justify(&self.format_i64(*v as i64)),
+      Field::Int(v) => self.justify(&self.format_i64(*v as i64)),
+      Field::Long(v) => self.justify(&self.format_i64(*v)),
+      Field::NullableByte(v) => self.display_opt(v.map(|v| self.format_i64(v as i64))),
+      Field::NullableShort(v) => self.display_opt(v.map(|v| self.format_i64(v as i64))),
+      Field::NullableInt(v) => self.display_opt(v.map(|v| self.format_i64(v as i64))),
+      Field::NullableLong(v) => self.display_opt(v.map(|v| self.format_i64(v))),
+      Field::UnsignedByte(v) => self.justify(&self.format_u64(*v as u64)),
+      Field::UnsignedShort(v) => self.justify(&self.format_u64(*v as u64)),
+      Field::UnsignedInt(v) => self.justify(&self.format_u64(*v as u64)),
+      Field::UnsignedLong(v) => self.justify(&self.format_u64(*v)),
+      Field::NullableUnsignedByte(v) => self.display_opt(v.map(|v| self.format_u64(v as u64))),
+      Field::NullableUnsignedShort(v) => self.display_opt(v.map(|v| self.format_u64(v as u64))),
+      Field::NullableUnsignedInt(v) => self.display_opt(v.map(|v| self.format_u64(v as u64))),
+      Field::NullableUnsignedLong(v) => self.display_opt(v.map(|v| self.format_u64(v))),
+      Field::Float(v) => self.display_opt(self.format_opt_f64(*v as f64)),
+      Field::Double(v) => self.display_opt(self.format_opt_f64(*v)),
+      Field::ComplexFloat(v) => self.display_array(
+        [v.real() as f64, v.img() as f64]
+          .into_iter()
+          .map(|v| self.format_opt_f64(v)),
+      ),
+      Field::ComplexDouble(v) => self.display_array(
+        [v.real(), v.img()]
+          .into_iter()
+          .map(|v| self.format_opt_f64(v)),
+      ),
+      Field::AsciiChar(v) => self.justify(&(*v as char).to_string()),
+      Field::NullableBooleanArray(v) => {
+        self.display_array(v.iter().map(|v| v.map(|v| self.format_bool(v))))
+      }
+      Field::ByteArray(v) => self.display_array(v.iter().map(|v| Some(self.format_i64(*v as i64)))),
+      Field::ShortArray(v) => {
+        self.display_array(v.iter().map(|v| Some(self.format_i64(*v as i64))))
+      }
+      Field::IntArray(v) => self.display_array(v.iter().map(|v| Some(self.format_i64(*v as i64)))),
+      Field::LongArray(v) => self.display_array(v.iter().map(|v| Some(self.format_i64(*v)))),
+      Field::NullableByteArray(v) => {
+        self.display_array(v.iter().map(|v| v.map(|v| self.format_i64(v as i64))))
+      }
+      Field::NullableShortArray(v) => {
+        self.display_array(v.iter().map(|v| v.map(|v| self.format_i64(v as i64))))
+      }
+      Field::NullableIntArray(v) => {
+        self.display_array(v.iter().map(|v| v.map(|v| self.format_i64(v as i64))))
+      }
+      Field::NullableLongArray(v) => {
+        self.display_array(v.iter().map(|v| v.map(|v| self.format_i64(v))))
+      }
+      Field::UnsignedByteArray(v) => {
+        self.display_array(v.iter().map(|v| Some(self.format_u64(*v as u64))))
+      }
+      Field::UnsignedShortArray(v) => {
+        self.display_array(v.iter().map(|v| Some(self.format_u64(*v as u64))))
+      }
+      Field::UnsignedIntArray(v) => {
+        self.display_array(v.iter().map(|v| Some(self.format_u64(*v as u64))))
+      }
+      Field::UnsignedLongArray(v) => {
+        self.display_array(v.iter().map(|v| Some(self.format_u64(*v))))
+      }
+      Field::NullableUnsignedByteArray(v) => {
+        self.display_array(v.iter().map(|v| v.map(|v| self.format_u64(v as u64))))
+      }
+      Field::NullableUnsignedShortArray(v) => {
+        self.display_array(v.iter().map(|v| v.map(|v| self.format_u64(v as u64))))
+      }
+      Field::NullableUnsignedIntArray(v) => {
+        self.display_array(v.iter().map(|v| v.map(|v| self.format_u64(v as u64))))
+      }
+      Field::NullableUnsignedLongArray(v) => {
+        self.display_array(v.iter().map(|v| v.map(|v| self.format_u64(v))))
+      }
+      Field::FloatArray(v) => self.display_array(v.iter().map(|v| self.format_opt_f64(*v as f64))),
+      Field::DoubleArray(v) => self.display_array(v.iter().map(|v| self.format_opt_f64(*v))),
+      Field::ComplexFloatArray(v) => self.display_array(
+        v.iter()
+          .flat_map(|v| [v.real() as f64, v.img() as f64])
+          .map(|v| self.format_opt_f64(v)),
+      ),
+      Field::ComplexDoubleArray(v) => self.display_array(
+        v.iter()
+          .flat_map(|v| [v.real(), v.img()])
+          .map(|v| self.format_opt_f64(v)),
+      ),
+      Field::AsciiString(v) => self.justify(v.trim_end()),
+    }
+  }
+
+  /// `None` for NaN values (i.e. null values).
+  fn format_opt_f64(&self, v: f64) -> Option<String> {
+    if v.is_nan() {
+      None
+    } else {
+      Some(self.format_f64(v))
+    }
+  }
+
+  fn display_opt(&self, v: Option<String>) -> String {
+    match v {
+      Some(s) => self.justify(&s),
+      None => " ".repeat(self.get_width_and_prec().0 as usize),
+    }
+  }
+
+  fn display_array<I>(&self, it: I) -> String
+  where
+    I: Iterator<Item = Option<String>>,
+  {
+    it.map(|v| self.display_opt(v))
+      .collect::<Vec<String>>()
+      .join(" ")
+  }
+
+  /// Regular, binary, octal or hexadecimal digits, with at least `m` digits (zero padded).
+  fn format_digits(&self, v: u64) -> String {
+    let (digits, m) = match *self {
+      Self::B { w: _, m } => (format!("{:b}", v), m),
+      Self::O { w: _, m } => (format!("{:o}", v), m),
+      Self::Z { w: _, m } => (format!("{:X}", v), m),
+      Self::I { w: _, m } => (v.to_string(), m),
+      _ => (v.to_string(), None),
+    };
+    match m {
+      Some(m) if digits.len() < m as usize => format!("{:0>m$}", digits, m = m as usize),
+      _ => digits,
+    }
+  }
+}
+
+/// Returns the mantissa and the exponent of the Rust scientific representation of the given
+/// value, with `prec` digits after the decimal point, e.g. `("1.235", 2)` for `123.456`.
+fn sci(v: f64, prec: usize) -> (String, i32) {
+  let s = format!("{:.*e}", prec, v);
+  match s.split_once('e') {
+    Some((mantissa, exp)) => (mantissa.to_string(), exp.parse().unwrap_or(0)),
+    None => (s, 0),
+  }
+}
+
+/// Exponent with its sign and at least `e` (default 2) digits, e.g. `E+03`.
+fn exponent(letter: char, exp: i32, e: Option<u16>) -> String {
+  format!(
+    "{}{}{:0>e$}",
+    letter,
+    if exp < 0 { '-' } else { '+' },
+    exp.unsigned_abs(),
+    e = e.unwrap_or(2) as usize
+  )
+}
+
+/// `Ew.dEe`: fraction in `[0.1, 1.0)` with `d` digits, e.g. `0.1235E+03` for `E10.4`.
+fn format_e(v: f64, d: u16, e: Option<u16>, letter: char) -> String {
+  let d = d.max(1) as usize;
+  if v == 0.0 {
+    return format!("{:.*}{}", d, 0.0, exponent(letter, 0, e));
+  }
+  let (mantissa, exp) = sci(v, d - 1);
+  let (sign, digits) = match mantissa.strip_prefix('-') {
+    Some(digits) => ("-", digits),
+    None => ("", mantissa.as_str()),
+  };
+  format!(
+    "{}0.{}{}",
+    sign,
+    digits.replace('.', ""),
+    exponent(letter, exp + 1, e)
+  )
+}
+
+/// `ESw.d`: fraction in `[1.0, 10)` with `d` digits after the decimal point, e.g. `1.2346E+02`.
+fn format_es(v: f64, d: u16) -> String {
+  let (mantissa, exp) = sci(v, d as usize);
+  format!("{}{}", mantissa, exponent('E', exp, None))
+}
+
+/// `ENw.d`: exponent multiple of 3, fraction in `[1.0, 1000)` with `d` digits after the decimal
+/// point, e.g. `123.4560E+00` or `12.3456E+03`.
+fn format_en(v: f64, d: u16) -> String {
+  let d = d as usize;
+  let (_, exp) = sci(v, d + 2);
+  let mut exp = exp.div_euclid(3) * 3;
+  let mut mantissa = format!("{:.*}", d, v / 10_f64.powi(exp));
+  // Rounding may lead to a fraction equal to 1000
+  if mantissa
+    .trim_start_matches('-')
+    .split('.')
+    .next()
+    .map_or(0, str::len)
+    > 3
+  {
+    exp += 3;
+    mantissa = format!("{:.*}", d, v / 10_f64.powi(exp));
+  }
+  format!("{}{}", mantissa, exponent('E', exp, None))
+}
+
+/// `Gw.dEe`: `F` format with `d` significant digits if the (rounded) value is in `[0.1, 10^d)`,
+/// `E` format else.
+fn format_g(v: f64, d: u16, e: Option<u16>) -> String {
+  let d = d.max(1) as i32;
+  if v == 0.0 {
+    return format!("{:.*}", (d - 1) as usize, v);
+  }
+  let (_, exp) = sci(v, (d - 1) as usize);
+  let k = exp + 1;
+  if (0..=d).contains(&k) {
+    format!("{:.*}", (d - k) as usize, v)
+  } else {
+    format_e(v, d as u16, e, 'E')
+  }
+}
 fn parse_w(s: &str) -> Result<u16, Error> {
   s.parse::<u16>().map_err(new_parse_u16_err)
 }
@@ -139,8 +436,8 @@ impl Display for TDispValue {
         None => f.write_fmt(format_args!("B{}", w)),
       },
       Self::O { w, m } => match m {
-        Some(m) => f.write_fmt(format_args!("D{}.{}", w, m)),
-        None => f.write_fmt(format_args!("D{}", w)),
+        Some(m) => f.write_fmt(format_args!("O{}.{}", w, m)),
+        None => f.write_fmt(format_args!("O{}", w)),
       },
       Self::Z { w, m } => match m {
         Some(m) => f.write_fmt(format_args!("Z{}.{}", w, m)),
@@ -188,7 +485,7 @@ impl TDispn {
 }
 
 impl DynValueKwr for TDispn {
-  const KW_PREFIX: &'static [u8] = b"TFORM";
+  const KW_PREFIX: &'static [u8] = b"TDISP";
 
   fn n(&self) -> u16 {
     self.n
@@ -216,3 +513,79 @@ impl DynValueKwr for TDispn {
     )
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Check the formatting of each `(TDISPn value, value, expected string)` case.
+  fn check_f64(cases: &[(&str, f64, &str)]) {
+    for (tdisp, v, expected) in cases {
+      let tdisp = tdisp.parse::<TDispValue>().unwrap();
+      assert_eq!(tdisp.format_f64(*v), *expected, "{} of {}", tdisp, v);
+    }
+  }
+
+  #[test]
+  fn test_format_e() {
+    check_f64(&[
+      ("E10.4", 123.456, "0.1235E+03"),
+      ("E10.4", -123.456, "-0.1235E+03"),
+      ("E10.4", 0.0, "0.0000E+00"),
+      ("E10.3", 0.000999996, "0.100E-02"),
+      ("E12.4E3", 123.456, "0.1235E+003"),
+      ("D10.4", 123.456, "0.1235D+03"),
+      // d = 0 is handled as d = 1
+      ("E10.0", 123.456, "0.1E+03"),
+      ("ES10.4", 123.456, "1.2346E+02"),
+    ]);
+  }
+
+  #[test]
+  fn test_format_en() {
+    check_f64(&[
+      ("EN12.4", 123.456, "123.4560E+00"),
+      ("EN12.4", 12345.6, "12.3456E+03"),
+      ("EN12.3", 0.001234, "1.234E-03"),
+      ("EN12.3", -0.001234, "-1.234E-03"),
+      // The fraction rounded to 1000 leads to the next exponent
+      ("EN12.3", 999.9996, "1.000E+03"),
+      ("EN12.3", -999.9996, "-1.000E+03"),
+    ]);
+  }
+
+  #[test]
+  fn test_format_g() {
+    check_f64(&[
+      ("G10.3", 0.0, "0.00"),
+      ("G10.3", 0.1234, "0.123"),
+      ("G10.3", 0.01234, "0.123E-01"),
+      ("G10.3", 9.996, "10.0"),
+      ("G10.3", 123.4, "123"),
+      // Switch to E at 10^d, after rounding
+      ("G10.3", 999.6, "0.100E+04"),
+      ("G10.3", 1234.0, "0.123E+04"),
+      ("G10.3E3", -1234.0, "-0.123E+004"),
+    ]);
+  }
+
+  #[test]
+  fn test_format_digits() {
+    for (tdisp, v, expected) in [
+      ("I5", 42, "42"),
+      ("I5.4", 42, "0042"),
+      ("I5.3", -5, "-005"),
+      ("B8", 5, "101"),
+      ("B8.8", 5, "00000101"),
+      ("B8", -5, "-101"),
+      ("O5", 8, "10"),
+      ("O5", -8, "-10"),
+      ("Z5", 255, "FF"),
+      ("Z5", -255, "-FF"),
+      ("Z5.4", -255, "-00FF"),
+    ] {
+      let tdisp = tdisp.parse::<TDispValue>().unwrap();
+      assert_eq!(tdisp.format_i64(v), expected, "{} of {}", tdisp, v);
+    }
+  }
+}
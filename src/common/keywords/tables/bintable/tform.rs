@@ -58,7 +58,7 @@ impl From<ZeroOrOneRepeatCountAndExtraChar> for RepeatCountAndExtraChar {
 }*/
 
 /// Store `r`, `X`, `nbr`, `a` in `r[PQ]X(nbr)a`.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct VariableLenghtArrayInfo {
   /// Value is 0 et Some(false), else it is 1.
   r_is_1: Option<bool>,
@@ -124,6 +124,10 @@ impl VariableLenghtArrayInfo {
   pub fn max_len(&self) -> u16 {
     self.max_len
   }
+  /// Returns a copy of this information, with the given maximum number of elements.
+  pub fn with_max_len(&self, max_len: u16) -> Self {
+    Self { max_len, ..*self }
+  }
 }
 
 /*
@@ -181,6 +185,16 @@ impl VariableLenghtArrayDataType {
       Self::M => 'M',
     }
   }
+  /// Number of bytes of an element of the array (stored in the heap).
+  pub fn byte_size(&self) -> usize {
+    match self {
+      Self::L | Self::B | Self::A => 1,
+      Self::I => 2,
+      Self::J | Self::E => 4,
+      Self::K | Self::D | Self::C => 8,
+      Self::M => 16,
+    }
+  }
   pub fn from_char(c: u8) -> Result<Self, Error> {
     match c {
       b'L' => Ok(Self::L),
@@ -246,6 +260,36 @@ pub enum TFormValue {
 }
 
 impl TFormValue {
+  /// Number of bytes of an element of the column, i.e. of a single value (a complex value for
+  /// `C` and `M`, a byte of bits for `X`), or of an array descriptor for `P` and `Q`.
+  pub fn elem_byte_size(&self) -> usize {
+    match self {
+      Self::L(_) | Self::X(_) | Self::B(_) | Self::A(_) => 1,
+      Self::I(_) => 2,
+      Self::J(_) | Self::E(_) => 4,
+      Self::K(_) | Self::D(_) | Self::C(_) | Self::P(_) => 8,
+      Self::M(_) | Self::Q(_) => 16,
+    }
+  }
+
+  /// Number of bytes of the column in a row of the main table.
+  pub fn byte_size(&self) -> usize {
+    match self {
+      Self::X(rc) => (rc.repeat_count() as usize).div_ceil(8),
+      Self::L(rc)
+      | Self::B(rc)
+      | Self::A(rc)
+      | Self::I(rc)
+      | Self::J(rc)
+      | Self::E(rc)
+      | Self::K(rc)
+      | Self::D(rc)
+      | Self::C(rc)
+      | Self::M(rc) => rc.repeat_count() as usize * self.elem_byte_size(),
+      Self::P(info) | Self::Q(info) => info.is_repeat_count_eq_1() as usize * self.elem_byte_size(),
+    }
+  }
+
   /*fn letter(&self) -> u8 {
     match self {
       Self::L(_) => b'L',
@@ -269,8 +313,15 @@ impl FromStr for TFormValue {
   type Err = Error;
 
   fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let s = s.trim_end();
     let bytes = s.as_bytes();
-    let il = bytes.partition_point(|s| s.is_ascii_digit());
+    let il = bytes
+      .iter()
+      .position(|b| !b.is_ascii_digit())
+      .unwrap_or(bytes.len());
+    if il == bytes.len() {
+      return Err(new_unexpected_value("(\\d+)?[LXBIJKAEDCMPQ]\\D?", s));
+    }
     let r = if il == 0 {
       None
     } else {
@@ -300,7 +351,10 @@ impl FromStr for TFormValue {
           return Err(err);
         }
         let bytes = &bytes[il + 3..];
-        let il = bytes.partition_point(|s| s.is_ascii_digit());
+        let il = bytes
+          .iter()
+          .position(|b| !b.is_ascii_digit())
+          .unwrap_or(bytes.len());
         if il == 0 {
           return Err(err);
         }
@@ -309,14 +363,14 @@ impl FromStr for TFormValue {
         let max_len = unsafe { str::from_utf8_unchecked(&bytes[..il]) }
           .parse::<u16>()
           .unwrap();
-        if bytes[il] != b')' {
+        if bytes.get(il) != Some(&b')') {
           return Err(err);
         }
         // Parse extra char (if any)
         let a = if il + 1 < bytes.len() {
-          None
-        } else {
           Some(bytes[il + 1])
+        } else {
+          None
         };
         match main_dt {
           b'P' => Ok(Self::P(VariableLenghtArrayInfo::new(
@@ -332,9 +386,9 @@ impl FromStr for TFormValue {
       }
     } else {
       let a = if il + 1 < bytes.len() {
-        None
-      } else {
         Some(bytes[il + 1])
+      } else {
+        None
       };
       let rcec = RepeatCountAndExtraChar::new(r, a);
       match bytes[il] {
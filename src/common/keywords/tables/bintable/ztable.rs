@@ -0,0 +1,384 @@
+//! Defines the keywords of the tiled table compression convention (see FITS standard v4.0,
+//! section 10.3), i.e. of `BINTABLE` extensions containing a compressed table:
+//! * `ZTABLE`: `T` if the `BINTABLE` contains a tile-compressed table
+//! * `ZNAXIS1`, `ZNAXIS2`, `ZPCOUNT`: `NAXIS1`, `NAXIS2` and `PCOUNT` of the uncompressed table
+//! * `ZTILELEN`: number of rows in each tile (i.e. in each row of the compressed table)
+//! * `ZFORMn`: `TFORMn` of the uncompressed column
+//! * `ZCTYPn`: compression algorithm of the column
+use std::{
+  fmt::{Display, Formatter},
+  str::FromStr,
+};
+
+use crate::{
+  common::{
+    DynValueKwr, FixedFormat, KwrFormatRead, ValueKwr,
+    keywords::tables::bintable::tform::{TFormValue, TFormn},
+    write::{FixedFormatWrite, FreeFormatWrite, KwrFormatWrite},
+  },
+  error::{Error, new_unexpected_value, new_unexpected_value_list},
+};
+
+/// The `ZTABLE` keyword.
+#[derive(Debug)]
+pub struct ZTable(bool);
+
+impl ZTable {
+  pub fn new(value: bool) -> Self {
+    Self(value)
+  }
+  pub fn get(&self) -> bool {
+    self.0
+  }
+}
+
+impl ValueKwr for ZTable {
+  const KEYWORD: &'static [u8; 8] = b"ZTABLE  ";
+
+  fn check_value(&self, kwr_value_comment: &[u8; 70]) -> Result<(), Error> {
+    FixedFormat::parse_logical_value(kwr_value_comment).and_then(|(val, _comment)| {
+      if val != self.0 {
+        Err(new_unexpected_value(self.0, val))
+      } else {
+        Ok(())
+      }
+    })
+  }
+
+  fn from_value_comment(kwr_value_comment: &[u8; 70]) -> Result<Self, Error> {
+    FixedFormat::parse_logical_value(kwr_value_comment).map(|(val, _comment)| Self(val))
+  }
+
+  fn write_kw_record<'a, I>(&self, dest_kwr_it: &mut I) -> Result<(), Error>
+  where
+    I: Iterator<Item = Result<&'a mut [u8; 80], Error>>,
+  {
+    FixedFormatWrite::write_boolean_value_kw_record(
+      dest_kwr_it,
+      Self::KEYWORD,
+      self.0,
+      Some("This is a tile-compressed table"),
+    )
+  }
+}
+
+/// The `ZNAXIS1` keyword, i.e. the byte size of a row of the uncompressed table.
+#[derive(Debug)]
+pub struct ZNAxis1(u32);
+
+impl ZNAxis1 {
+  pub fn new(row_byte_size: u32) -> Self {
+    Self(row_byte_size)
+  }
+  pub fn get(&self) -> u32 {
+    self.0
+  }
+}
+
+impl ValueKwr for ZNAxis1 {
+  const KEYWORD: &'static [u8; 8] = b"ZNAXIS1 ";
+
+  fn check_value(&self, _kwr_value_comment: &[u8; 70]) -> Result<(), Error> {
+    unreachable!() // not supposed to be called
+  }
+
+  fn from_value_comment(kwr_value_comment: &[u8; 70]) -> Result<Self, Error> {
+    FixedFormat::parse_integer_value(kwr_value_comment).and_then(|(val, _comment)| {
+      u32::try_from(val)
+        .map(Self)
+        .map_err(|_| new_unexpected_value("positive 32-bit integer", val))
+    })
+  }
+
+  fn write_kw_record<'a, I>(&self, dest_kwr_it: &mut I) -> Result<(), Error>
+  where
+    I: Iterator<Item = Result<&'a mut [u8; 80], Error>>,
+  {
+    FixedFormatWrite::write_int_value_kw_record(
+      dest_kwr_it,
+      Self::KEYWORD,
+      self.0 as i64,
+      Some("Uncompressed row byte size"),
+    )
+  }
+}
+
+/// The `ZNAXIS2` keyword, i.e. the number of rows in the uncompressed table.
+#[derive(Debug)]
+pub struct ZNAxis2(u64);
+
+impl ZNAxis2 {
+  pub fn new(n_rows: u64) -> Self {
+    Self(n_rows)
+  }
+  pub fn get(&self) -> u64 {
+    self.0
+  }
+}
+
+impl ValueKwr for ZNAxis2 {
+  const KEYWORD: &'static [u8; 8] = b"ZNAXIS2 ";
+
+  fn check_value(&self, _kwr_value_comment: &[u8; 70]) -> Result<(), Error> {
+    unreachable!() // not supposed to be called
+  }
+
+  fn from_value_comment(kwr_value_comment: &[u8; 70]) -> Result<Self, Error> {
+    FixedFormat::parse_integer_value(kwr_value_comment).and_then(|(val, _comment)| {
+      if val >= 0 {
+        Ok(Self(val as u64))
+      } else {
+        Err(new_unexpected_value("positive integer", val))
+      }
+    })
+  }
+
+  fn write_kw_record<'a, I>(&self, dest_kwr_it: &mut I) -> Result<(), Error>
+  where
+    I: Iterator<Item = Result<&'a mut [u8; 80], Error>>,
+  {
+    FixedFormatWrite::write_uint_value_kw_record(
+      dest_kwr_it,
+      Self::KEYWORD,
+      self.0,
+      Some("Number of rows in the uncompressed table"),
+    )
+  }
+}
+
+/// The `ZPCOUNT` keyword, i.e. the heap byte size of the uncompressed table.
+#[derive(Debug)]
+pub struct ZPCount(usize);
+
+impl ZPCount {
+  pub fn new(heap_byte_size: usize) -> Self {
+    Self(heap_byte_size)
+  }
+  pub fn get(&self) -> usize {
+    self.0
+  }
+}
+
+impl ValueKwr for ZPCount {
+  const KEYWORD: &'static [u8; 8] = b"ZPCOUNT ";
+
+  fn check_value(&self, _kwr_value_comment: &[u8; 70]) -> Result<(), Error> {
+    unreachable!() // not supposed to be called
+  }
+
+  fn from_value_comment(kwr_value_comment: &[u8; 70]) -> Result<Self, Error> {
+    FixedFormat::parse_integer_value(kwr_value_comment).and_then(|(val, _comment)| {
+      if val >= 0 {
+        Ok(Self(val as usize))
+      } else {
+        Err(new_unexpected_value("positive integer", val))
+      }
+    })
+  }
+
+  fn write_kw_record<'a, I>(&self, dest_kwr_it: &mut I) -> Result<(), Error>
+  where
+    I: Iterator<Item = Result<&'a mut [u8; 80], Error>>,
+  {
+    FixedFormatWrite::write_int_value_kw_record(
+      dest_kwr_it,
+      Self::KEYWORD,
+      self.0 as i64,
+      Some("Uncompressed heap byte size"),
+    )
+  }
+}
+
+/// The `ZTILELEN` keyword, i.e. the number of rows in each tile (except possibly the last one).
+#[derive(Debug)]
+pub struct ZTileLen(u64);
+
+impl ZTileLen {
+  pub fn new(n_rows: u64) -> Self {
+    Self(n_rows)
+  }
+  pub fn get(&self) -> u64 {
+    self.0
+  }
+}
+
+impl ValueKwr for ZTileLen {
+  const KEYWORD: &'static [u8; 8] = b"ZTILELEN";
+
+  fn check_value(&self, _kwr_value_comment: &[u8; 70]) -> Result<(), Error> {
+    unreachable!() // not supposed to be called
+  }
+
+  fn from_value_comment(kwr_value_comment: &[u8; 70]) -> Result<Self, Error> {
+    FixedFormat::parse_integer_value(kwr_value_comment).and_then(|(val, _comment)| {
+      if val > 0 {
+        Ok(Self(val as u64))
+      } else {
+        Err(new_unexpected_value("strictly positive integer", val))
+      }
+    })
+  }
+
+  fn write_kw_record<'a, I>(&self, dest_kwr_it: &mut I) -> Result<(), Error>
+  where
+    I: Iterator<Item = Result<&'a mut [u8; 80], Error>>,
+  {
+    FixedFormatWrite::write_uint_value_kw_record(
+      dest_kwr_it,
+      Self::KEYWORD,
+      self.0,
+      Some("Number of rows per tile"),
+    )
+  }
+}
+
+/// The `ZFORMn` keyword, i.e. the `TFORMn` of the uncompressed column.
+#[derive(Debug)]
+pub struct ZFormn(TFormn);
+
+impl ZFormn {
+  /// # Params
+  /// * `n` the `ZFORMn` number in `[1, TFIELD]`.
+  /// * `value` data type of the uncompressed column
+  pub fn new(n: u16, value: TFormValue) -> Self {
+    Self(TFormn::new(n, value))
+  }
+
+  /// Column number starts at 1.
+  pub fn col_nbr(&self) -> u16 {
+    self.0.n
+  }
+  pub fn tform_type(&self) -> &TFormValue {
+    &self.0.value
+  }
+
+  /// Returns the `TFORMn` keyword of the uncompressed column.
+  pub fn into_tform(self) -> TFormn {
+    self.0
+  }
+}
+
+impl DynValueKwr for ZFormn {
+  const KW_PREFIX: &'static [u8] = b"ZFORM";
+
+  fn n(&self) -> u16 {
+    self.0.n
+  }
+
+  fn check_value(&self, _kwr_value_comment: &[u8; 70]) -> Result<(), Error> {
+    unreachable!() // not supposed to be called
+  }
+
+  fn from_value_comment(n: u16, kwr_value_comment: &[u8; 70]) -> Result<Self, Error> {
+    TFormn::from_value_comment(n, kwr_value_comment).map(Self)
+  }
+
+  fn write_kw_record<'a, I>(&self, dest_kwr_it: &mut I) -> Result<(), Error>
+  where
+    I: Iterator<Item = Result<&'a mut [u8; 80], Error>>,
+  {
+    let comment = format!("Column {} uncompressed data type", self.0.n);
+    FreeFormatWrite::write_string_value_kw_record(
+      dest_kwr_it,
+      &Self::keyword(self.0.n),
+      self.0.value.to_string().as_str(),
+      Some(comment.as_str()),
+    )
+  }
+}
+
+/// Compression algorithms allowed for tile-compressed tables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZCTypeValue {
+  /// Gzip compression of the big-endian bytes.
+  Gzip1,
+  /// Same as `Gzip1`, but bytes are first shuffled: first bytes of all values, then second bytes, ...
+  Gzip2,
+  /// Rice compression, for integer columns only (`B`, `I` and `J`).
+  Rice1,
+}
+
+impl ZCTypeValue {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      Self::Gzip1 => "GZIP_1",
+      Self::Gzip2 => "GZIP_2",
+      Self::Rice1 => "RICE_1",
+    }
+  }
+}
+
+impl FromStr for ZCTypeValue {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.trim() {
+      "GZIP_1" => Ok(Self::Gzip1),
+      "GZIP_2" => Ok(Self::Gzip2),
+      "RICE_1" => Ok(Self::Rice1),
+      _ => Err(new_unexpected_value_list(
+        &["GZIP_1", "GZIP_2", "RICE_1"],
+        s.as_bytes(),
+      )),
+    }
+  }
+}
+
+impl Display for ZCTypeValue {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+/// The `ZCTYPn` keyword, i.e. the compression algorithm of a column.
+#[derive(Debug)]
+pub struct ZCTypn {
+  n: u16,
+  value: ZCTypeValue,
+}
+
+impl ZCTypn {
+  /// # Params
+  /// * `n` the `ZCTYPn` number in `[1, TFIELD]`.
+  /// * `value` compression algorithm of the column number `n`
+  pub fn new(n: u16, value: ZCTypeValue) -> Self {
+    Self { n, value }
+  }
+
+  pub fn col_nbr(&self) -> u16 {
+    self.n
+  }
+  pub fn get(&self) -> ZCTypeValue {
+    self.value
+  }
+}
+
+impl DynValueKwr for ZCTypn {
+  const KW_PREFIX: &'static [u8] = b"ZCTYP";
+
+  fn n(&self) -> u16 {
+    self.n
+  }
+
+  fn check_value(&self, _kwr_value_comment: &[u8; 70]) -> Result<(), Error> {
+    unreachable!() // not supposed to be called
+  }
+
+  fn from_value_comment(n: u16, kwr_value_comment: &[u8; 70]) -> Result<Self, Error> {
+    FixedFormat::parse_string_value(kwr_value_comment)
+      .and_then(|(val, _comment)| val.parse::<ZCTypeValue>().map(|v| Self::new(n, v)))
+  }
+
+  fn write_kw_record<'a, I>(&self, dest_kwr_it: &mut I) -> Result<(), Error>
+  where
+    I: Iterator<Item = Result<&'a mut [u8; 80], Error>>,
+  {
+    let comment = format!("Column {} compression algorithm", self.n);
+    FixedFormatWrite::write_string_value_kw_record(
+      dest_kwr_it,
+      &Self::keyword(self.n),
+      self.value.as_str(),
+      Some(comment.as_str()),
+    )
+  }
+}
@@ -356,6 +356,10 @@ impl KwrFormatWrite for FixedFormatWrite {
         }
       }
     }
+    // Fixed-format real values must be right justified
+    let len = v.trim_ascii_end().len();
+    v.copy_within(0..len, 20 - len);
+    v[..20 - len].fill(b' ');
     Self::write_comment_if_any(c, comment)
   }
 
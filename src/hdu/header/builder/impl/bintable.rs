@@ -1,5 +1,5 @@
 //! Build the minimal header allowing to explore the structure of a FITS file,
-//! plus parse and store information needed to read a BINTABLE data
+//! plus parse and store information needed to read a BINTABLE or an ASCIITABLE data
 //! (column information to read each field).
 
 use crate::{
@@ -8,7 +8,7 @@ use crate::{
     header::{builder::HeaderBuilder, Header},
     primary::header::PrimaryHeader,
     xtension::{
-      asciitable::header::AsciiTableHeader,
+      asciitable::header::{AsciiTableHeader, AsciiTableHeaderWithColInfo},
       bintable::header::{BinTableHeader, BinTableHeaderWithColInfo},
       image::header::ImageHeader,
       unknown::UnknownXtensionHeader,
@@ -27,7 +27,7 @@ impl HeaderBuilder for Bintable {
   type PriH = PrimaryHeader;
   #[cfg(feature = "vot")]
   type PriH = PrimaryHeaderWithVOTable;
-  type AscH = AsciiTableHeader;
+  type AscH = AsciiTableHeaderWithColInfo;
   type BinH = BinTableHeaderWithColInfo;
   type ImgH = ImageHeader;
   type UnkH = UnknownXtensionHeader;
@@ -56,12 +56,15 @@ impl HeaderBuilder for Bintable {
 
   fn build_asciitable<'a, I>(
     header: AsciiTableHeader,
-    _kw_records_it: &mut I,
+    kw_records_it: &mut I,
   ) -> Result<Self::AscH, Error>
   where
     I: Iterator<Item = (usize, &'a [u8; 80])>,
   {
-    Ok(header)
+    let mut header: AsciiTableHeaderWithColInfo = header.into();
+    header
+      .consume_remaining_kw_records(kw_records_it)
+      .map(|()| header)
   }
 
   fn build_bintable<'a, I>(
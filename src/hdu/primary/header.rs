@@ -1,15 +1,22 @@
+use std::io::Write;
+
 #[cfg(feature = "vot")]
-use crate::common::{
-  keywords::tables::bintable::vot::{ntable::NTable, votmeta::VOTMeta},
-  read::{is_value_indicator, FixedFormatRead, KwrFormatRead},
+use crate::{
+  common::{
+    keywords::tables::bintable::vot::{ntable::NTable, votmeta::VOTMeta},
+    read::{is_value_indicator, FixedFormatRead, KwrFormatRead},
+  },
+  error::new_io_err,
 };
 use crate::{
   common::{
+    header::HeaderWriter,
     keywords::{
       bitpix::BitPix,
       naxis::{NAxis, NAxisn},
       simple::Simple,
     },
+    write::{FixedFormatWrite, KwrFormatWrite},
     DynValueKwr, ValueKwr,
   },
   error::Error,
@@ -41,6 +48,23 @@ impl PrimaryHeader {
   pub fn n_kw_records(&self) -> usize {
     3 + self.naxisn.len()
   }
+
+  /// Write a primary HDU containing no data and having `EXTEND = T`,
+  /// e.g. to be followed by BINTABLE extensions.
+  pub fn write_empty_hdu<W: Write>(writer: &mut W) -> Result<(), Error> {
+    let mut header_writer = HeaderWriter::new(writer);
+    Self::new(true, BitPix::U8, vec![])
+      .write_starting_mandatory_kw_records(&mut header_writer)
+      .and_then(|()| {
+        FixedFormatWrite::write_boolean_value_kw_record(
+          &mut header_writer,
+          b"EXTEND  ",
+          true,
+          Some("FITS dataset may contain extensions"),
+        )
+      })
+      .and_then(|()| header_writer.finalize())
+  }
 }
 
 impl Header for PrimaryHeader {
@@ -123,6 +147,10 @@ impl PrimaryHeaderWithVOTable {
       ntable: None,
     }
   }
+  /// Minimal required header.
+  pub fn primary(&self) -> &PrimaryHeader {
+    &self.mrh
+  }
   pub fn is_fits_plus(&self) -> bool {
     self
       .votmeta
@@ -133,6 +161,43 @@ impl PrimaryHeaderWithVOTable {
   pub fn n_bintable_hdu(&self) -> u16 {
     self.ntable.as_ref().map(|kw| kw.get()).unwrap_or(0)
   }
+
+  /// Write the primary HDU of a FITS Plus file, i.e. a header having `NTABLE` and `VOTMETA = T`,
+  /// and a data part containing the VOTable (in XML) describing the BINTABLEs following the HDU.
+  /// # Params
+  /// * `n_table`: number of BINTABLE extensions following the primary HDU
+  /// * `votable`: the VOTable XML bytes
+  pub fn write_fits_plus_hdu<W: Write>(
+    writer: &mut W,
+    n_table: u16,
+    votable: &[u8],
+  ) -> Result<(), Error> {
+    let mut header_writer = HeaderWriter::new(writer);
+    PrimaryHeader::new(true, BitPix::U8, vec![votable.len()])
+      .write_starting_mandatory_kw_records(&mut header_writer)
+      .and_then(|()| {
+        FixedFormatWrite::write_boolean_value_kw_record(
+          &mut header_writer,
+          b"EXTEND  ",
+          true,
+          Some("FITS dataset may contain extensions"),
+        )
+      })
+      .and_then(|()| NTable::new(n_table).write_kw_record(&mut header_writer))
+      .and_then(|()| VOTMeta::new(true).write_kw_record(&mut header_writer))
+      .and_then(|()| header_writer.finalize())?;
+    let rem2880 = votable.len() % 2880;
+    writer
+      .write_all(votable)
+      .and_then(|()| {
+        if rem2880 != 0 {
+          writer.write_all(vec![0_u8; 2880 - rem2880].as_slice())
+        } else {
+          Ok(())
+        }
+      })
+      .map_err(new_io_err)
+  }
 }
 
 #[cfg(feature = "vot")]
@@ -0,0 +1,189 @@
+//! Read the rows of an `ASCIITABLE`, feeding the same `Visitor` as for `BINTABLE` fields.
+//!
+//! Mapping between `TFORMn` and visitor methods:
+//! * `Aw` -> `visit_ascii_string` (trailing spaces removed);
+//! * `Iw` -> `visit_opt_i64`, or `visit_f64` if `TSCALn` or `TZEROn` is set;
+//! * `Fw.d`, `Ew.d`, `Dw.d` -> `visit_f64`.
+//!
+//! A numeric field is NULL if it is blank or if its (trimmed) content equals the (trimmed)
+//! `TNULLn` value. A NULL string is provided as an empty string and a NULL float as `NaN`.
+
+use std::str;
+
+use crate::{
+  common::keywords::tables::asciitable::tform::TFormValue,
+  error::{Error, new_custom},
+  hdu::xtension::bintable::read::visitor::{FieldVisitorProvider, RowVisitor, Visitor},
+};
+
+#[derive(Debug, Clone)]
+pub struct AsciiRowSchema {
+  fields_schemas: Vec<AsciiFieldSchema>,
+}
+
+impl AsciiRowSchema {
+  pub fn n_cols(&self) -> usize {
+    self.fields_schemas.len()
+  }
+
+  pub fn fields_schemas(&self) -> &[AsciiFieldSchema] {
+    self.fields_schemas.as_slice()
+  }
+
+  /// Returns a new schema containing only the fields of the given indices, in the given order.
+  pub fn project(&self, icols: &[usize]) -> Result<Self, Error> {
+    icols
+      .iter()
+      .map(|&i| {
+        self.fields_schemas.get(i).cloned().ok_or_else(|| {
+          new_custom(format!(
+            "Column index {} out of bounds. Number of columns: {}.",
+            i,
+            self.fields_schemas.len()
+          ))
+        })
+      })
+      .collect()
+  }
+
+  /// # Params
+  /// * `row`: the `NAXIS1` bytes of a row
+  pub fn deserialize<F, R>(
+    &self,
+    row: &[u8],
+    field_visitor_provider: &mut F,
+    row_visitor: R,
+  ) -> Result<R::Value, Error>
+  where
+    F: FieldVisitorProvider,
+    R: RowVisitor<FieldValue = F::FieldValue>,
+  {
+    row_visitor.visit_row(
+      self
+        .fields_schemas
+        .iter()
+        .map(|field_schema| field_schema.deserialize(row, field_visitor_provider.field_visitor())),
+    )
+  }
+}
+/// So we can use `collect()` to build a `AsciiRowSchema`!
+impl FromIterator<AsciiFieldSchema> for AsciiRowSchema {
+  fn from_iter<T: IntoIterator<Item = AsciiFieldSchema>>(iter: T) -> Self {
+    Self {
+      fields_schemas: iter.into_iter().collect(),
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct AsciiFieldSchema {
+  /// Index of the first byte of the field in a row, i.e. `TBCOLn - 1`.
+  starting_byte: usize,
+  /// Field format, providing the field width.
+  tform: TFormValue,
+  /// Trimmed `TNULLn` value, if any.
+  null: Option<String>,
+  /// `TSCALn` and `TZEROn`, if at least one of the two is provided.
+  scale_offset: Option<(f64, f64)>,
+}
+
+impl AsciiFieldSchema {
+  pub fn new(
+    starting_byte: usize,
+    tform: TFormValue,
+    null: Option<String>,
+    scale_offset: Option<(f64, f64)>,
+  ) -> Self {
+    Self {
+      starting_byte,
+      tform,
+      null,
+      scale_offset,
+    }
+  }
+
+  pub fn starting_byte(&self) -> usize {
+    self.starting_byte
+  }
+
+  /// Index of the byte following the last byte of the field in a row.
+  pub fn ending_byte(&self) -> usize {
+    self.starting_byte + self.tform.width() as usize
+  }
+
+  pub fn tform(&self) -> &TFormValue {
+    &self.tform
+  }
+
+  pub fn deserialize<V: Visitor>(&self, row: &[u8], visitor: V) -> Result<V::Value, Error> {
+    let bytes = row
+      .get(self.starting_byte..self.ending_byte())
+      .ok_or_else(|| {
+        new_custom(format!(
+          "Field bytes [{}, {}) out of the row of size {}.",
+          self.starting_byte,
+          self.ending_byte(),
+          row.len()
+        ))
+      })?;
+    let s =
+      str::from_utf8(bytes).map_err(|e| new_custom(format!("Non ASCII field content: {}", e)))?;
+    let trimmed = s.trim();
+    let is_null = self.null.as_deref().is_some_and(|null| null == trimmed);
+    match self.tform {
+      TFormValue::A { w: _ } => {
+        if is_null {
+          visitor.visit_ascii_string("")
+        } else {
+          visitor.visit_ascii_string(s.trim_end())
+        }
+      }
+      TFormValue::I { w: _ } => {
+        let v = if is_null || trimmed.is_empty() {
+          None
+        } else {
+          Some(parse_int(trimmed)?)
+        };
+        match self.scale_offset {
+          None => visitor.visit_opt_i64(v),
+          Some((scale, offset)) => {
+            visitor.visit_f64(v.map(|v| offset + scale * v as f64).unwrap_or(f64::NAN))
+          }
+        }
+      }
+      TFormValue::F { w: _, d } | TFormValue::E { w: _, d } | TFormValue::D { w: _, d } => {
+        let v = if is_null || trimmed.is_empty() {
+          f64::NAN
+        } else {
+          parse_real(trimmed, d)?
+        };
+        match self.scale_offset {
+          None => visitor.visit_f64(v),
+          Some((scale, offset)) => visitor.visit_f64(offset + scale * v),
+        }
+      }
+    }
+  }
+}
+
+fn parse_int(s: &str) -> Result<i64, Error> {
+  s.parse::<i64>()
+    .map_err(|e| new_custom(format!("Error parsing integer field '{}': {}", s, e)))
+}
+
+/// Parse a Fortran-like real value: the exponent may be introduced by `D` instead of `E`, and
+/// if the mantissa contains no decimal point, its `d` rightmost digits are the decimal part.
+fn parse_real(s: &str, d: u16) -> Result<f64, Error> {
+  let has_point = s.contains('.');
+  let v = if s.contains(['D', 'd']) {
+    s.replace(['D', 'd'], "E").parse::<f64>()
+  } else {
+    s.parse::<f64>()
+  }
+  .map_err(|e| new_custom(format!("Error parsing real field '{}': {}", s, e)))?;
+  if has_point || d == 0 {
+    Ok(v)
+  } else {
+    Ok(v / 10_f64.powi(d as i32))
+  }
+}
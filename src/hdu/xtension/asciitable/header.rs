@@ -1,16 +1,35 @@
 use crate::{
   common::{
-    ValueKwr,
+    DynValueKwr, ValueKwr,
     keywords::{
       bitpix::BitPix,
       naxis::{NAxis, NAxis1, NAxis2},
       pgcount::{GCount, PCount},
-      tables::tfields::TFields,
+      tables::{
+        asciitable::{
+          tbcol::TBCol,
+          tdisp::{TDispValue, TDispn},
+          tform::{TFormValue, TFormn},
+          tnull::TNull,
+        },
+        tcomm::TComm,
+        tdminmax::{TDMax, TDMin},
+        tfields::TFields,
+        tscaltzero::{TScal, TZero},
+        ttype::TType,
+        tucd::TUCD,
+        tunit::TUnit,
+      },
       xtension::Xtension,
     },
+    read::{FixedFormatRead, KwrFormatRead, is_value_indicator},
+  },
+  error::{Error, new_custom},
+  hdu::{
+    HDUType,
+    header::Header,
+    xtension::asciitable::data::{AsciiFieldSchema, AsciiRowSchema},
   },
-  error::Error,
-  hdu::{HDUType, header::Header},
 };
 
 pub const XTENSION: Xtension = Xtension::AsciiTable;
@@ -112,3 +131,331 @@ impl Header for AsciiTableHeader {
       .and_then(|()| self.tfield.write_kw_record(dest))
   }
 }
+
+#[derive(Default, Debug)]
+pub struct AsciiTableColumnHeader {
+  /// Column name
+  ttype: Option<TType>,
+  /// Position of the first character of the column in a row (mandatory)
+  tbcol: Option<TBCol>,
+  /// Data type (mandatory)
+  tform: Option<TFormn>,
+  /// Display info
+  tdisp: Option<TDispn>,
+  /// Unit
+  tunit: Option<TUnit>,
+  /// UCD
+  tucd: Option<TUCD>,
+  /// Description
+  tcomm: Option<TComm>,
+  /// String coding a null value
+  tnull: Option<TNull>,
+  /// To be used with in: `field_value = TZERO + TSCAL * stored_value`
+  tscal: Option<TScal>,
+  /// To be used with in: `field_value = TZERO + TSCAL * stored_value`
+  tzero: Option<TZero>,
+  /// Min column value
+  tdmin: Option<TDMin>,
+  /// Max column value
+  tdmax: Option<TDMax>,
+}
+
+impl AsciiTableColumnHeader {
+  pub fn colname(&self) -> Option<&str> {
+    self.ttype.as_ref().map(|ttype| ttype.col_name())
+  }
+  pub fn unit(&self) -> Option<&str> {
+    self.tunit.as_ref().map(|tunit| tunit.col_unit())
+  }
+  pub fn ucd(&self) -> Option<&str> {
+    self.tucd.as_ref().map(|tucd| tucd.col_ucd())
+  }
+  pub fn description(&self) -> Option<&str> {
+    self.tcomm.as_ref().map(|tcomm| tcomm.col_description())
+  }
+  pub fn min(&self) -> Option<&str> {
+    self.tdmin.as_ref().map(|tdmin| tdmin.min_value())
+  }
+  pub fn max(&self) -> Option<&str> {
+    self.tdmax.as_ref().map(|tdmax| tdmax.max_value())
+  }
+  pub fn disp(&self) -> Option<&TDispValue> {
+    self.tdisp.as_ref().map(|tdisp| tdisp.data_type())
+  }
+  pub fn tform(&self) -> Option<&TFormValue> {
+    self.tform.as_ref().map(|tform| tform.tform_type())
+  }
+  pub fn null_value(&self) -> Option<&str> {
+    self.tnull.as_ref().map(|tnull| tnull.col_null_value())
+  }
+
+  /// Returns `None` if either `TBCOLn` or `TFORMn` is missing.
+  pub fn schema(&self) -> Option<AsciiFieldSchema> {
+    match (&self.tbcol, &self.tform) {
+      (Some(tbcol), Some(tform)) => {
+        let scale = self.tscal.as_ref().map(|tscal| tscal.scale());
+        let offset = self.tzero.as_ref().map(|tzero| tzero.zero_as_f64());
+        let scale_offset = match (scale, offset) {
+          (None, None) => None,
+          (s, o) => Some((s.unwrap_or(1.0), o.unwrap_or(0.0))),
+        };
+        Some(AsciiFieldSchema::new(
+          tbcol.col_starting_byte(),
+          *tform.tform_type(),
+          self.null_value().map(|s| s.trim().to_string()),
+          scale_offset,
+        ))
+      }
+      _ => None,
+    }
+  }
+}
+
+/// A header storing information to access ASCII tables data: rows, columns, fields content, ...
+pub struct AsciiTableHeaderWithColInfo {
+  /// Minimal Required Header to be able to skip data
+  mrh: AsciiTableHeader,
+  /// Columns metadata
+  cols: Vec<AsciiTableColumnHeader>,
+}
+impl AsciiTableHeaderWithColInfo {
+  fn check_n(&self, n: u16) -> Result<(), Error> {
+    if n as usize > self.cols.len() {
+      Err(new_custom(format!(
+        "Out of bound column number. Expected: max {}. Actual: {}.",
+        self.cols.len(),
+        n
+      )))
+    } else {
+      Ok(())
+    }
+  }
+
+  /// Number of leading mandatory keyword records (from `XTENSION`, inclusive, to `TFIELD`, inclusive).
+  pub fn n_kw_records(&self) -> usize {
+    self.mrh.n_kw_records()
+  }
+
+  pub fn n_cols(&self) -> usize {
+    self.mrh.n_cols()
+  }
+
+  pub fn n_rows(&self) -> usize {
+    self.mrh.n_rows()
+  }
+
+  pub fn row_byte_size(&self) -> usize {
+    self.mrh.row_byte_size()
+  }
+
+  pub fn table(&self) -> &AsciiTableHeader {
+    &self.mrh
+  }
+
+  pub fn cols(&self) -> &[AsciiTableColumnHeader] {
+    self.cols.as_slice()
+  }
+
+  pub fn cols_mut(&mut self) -> &mut [AsciiTableColumnHeader] {
+    self.cols.as_mut_slice()
+  }
+
+  pub fn build_col_names(&self) -> Vec<String> {
+    self
+      .cols()
+      .iter()
+      .enumerate()
+      .map(|(i, col_header)| {
+        col_header
+          .colname()
+          .map(String::from)
+          .unwrap_or_else(|| format!("col_{}", i))
+      })
+      .collect()
+  }
+
+  /// Returns an error if a column lacks `TBCOLn` or `TFORMn`, or if a field exceeds the row.
+  pub fn build_row_schema(&self) -> Result<AsciiRowSchema, Error> {
+    let row_byte_size = self.row_byte_size();
+    self
+      .cols()
+      .iter()
+      .enumerate()
+      .map(|(i, col_header)| {
+        col_header
+          .schema()
+          .ok_or_else(|| {
+            new_custom(format!(
+              "Unable to create schema for column {}: TBCOL or TFORM probably missing!",
+              i + 1
+            ))
+          })
+          .and_then(|schema| {
+            if schema.ending_byte() > row_byte_size {
+              Err(new_custom(format!(
+                "Column {} ends at byte {}, i.e. after the end of the row ({} bytes).",
+                i + 1,
+                schema.ending_byte(),
+                row_byte_size
+              )))
+            } else {
+              Ok(schema)
+            }
+          })
+      })
+      .collect()
+  }
+}
+
+impl Header for AsciiTableHeaderWithColInfo {
+  fn from_starting_mandatory_kw_records<'a, I>(
+    hdu_type: HDUType,
+    kw_records_it: &mut I,
+  ) -> Result<Self, Error>
+  where
+    I: Iterator<Item = (usize, &'a [u8; 80])>,
+  {
+    AsciiTableHeader::from_starting_mandatory_kw_records(hdu_type, kw_records_it).map(|v| v.into())
+  }
+
+  fn data_byte_size(&self) -> u64 {
+    self.mrh.data_byte_size()
+  }
+
+  fn write_starting_mandatory_kw_records<'a, I>(&self, dest: &mut I) -> Result<(), Error>
+  where
+    I: Iterator<Item = Result<&'a mut [u8; 80], Error>>,
+  {
+    self.mrh.write_starting_mandatory_kw_records(dest)
+  }
+
+  fn consume_remaining_kw_records<'a, I>(&mut self, kw_records_it: &mut I) -> Result<(), Error>
+  where
+    I: Iterator<Item = (usize, &'a [u8; 80])>,
+  {
+    fn get_n(bytes: &[u8]) -> Option<u16> {
+      unsafe { str::from_utf8_unchecked(bytes) }
+        .trim()
+        .parse::<u16>()
+        .ok()
+    }
+
+    for (_, kwr) in kw_records_it {
+      let (kw, ind, kw_value_comment) = FixedFormatRead::split_kw_indicator_value(kwr);
+      // Skip keyword if it does not contain a value indicator
+      if !is_value_indicator(ind) {
+        continue;
+      }
+      // Analyse keyword
+      match kw {
+        [b'T', b'B', b'C', b'O', b'L', nbr @ ..] => {
+          if let Some(n) = get_n(nbr) {
+            self
+              .check_n(n)
+              .and_then(|()| TBCol::from_value_comment(n, kw_value_comment))
+              .map(|kwo| self.cols[(n - 1) as usize].tbcol.replace(kwo))?;
+          }
+        }
+        [b'T', b'T', b'Y', b'P', b'E', nbr @ ..] => {
+          if let Some(n) = get_n(nbr) {
+            self
+              .check_n(n)
+              .and_then(|()| TType::from_value_comment(n, kw_value_comment))
+              .map(|kwo| self.cols[(n - 1) as usize].ttype.replace(kwo))?;
+          }
+        }
+        [b'T', b'F', b'O', b'R', b'M', nbr @ ..] => {
+          if let Some(n) = get_n(nbr) {
+            self
+              .check_n(n)
+              .and_then(|()| TFormn::from_value_comment(n, kw_value_comment))
+              .map(|kwo| self.cols[(n - 1) as usize].tform.replace(kwo))?;
+          }
+        }
+        [b'T', b'D', b'I', b'S', b'P', nbr @ ..] => {
+          if let Some(n) = get_n(nbr) {
+            self
+              .check_n(n)
+              .and_then(|()| TDispn::from_value_comment(n, kw_value_comment))
+              .map(|kwo| self.cols[(n - 1) as usize].tdisp.replace(kwo))?;
+          }
+        }
+        [b'T', b'U', b'C', b'D', nbr @ ..] => {
+          if let Some(n) = get_n(nbr) {
+            self
+              .check_n(n)
+              .and_then(|()| TUCD::from_value_comment(n, kw_value_comment))
+              .map(|kwo| self.cols[(n - 1) as usize].tucd.replace(kwo))?;
+          }
+        }
+        [b'T', b'U', b'N', b'I', b'T', nbr @ ..] => {
+          if let Some(n) = get_n(nbr) {
+            self
+              .check_n(n)
+              .and_then(|()| TUnit::from_value_comment(n, kw_value_comment))
+              .map(|kwo| self.cols[(n - 1) as usize].tunit.replace(kwo))?;
+          }
+        }
+        [b'T', b'C', b'O', b'M', b'M', nbr @ ..] => {
+          if let Some(n) = get_n(nbr) {
+            self
+              .check_n(n)
+              .and_then(|()| TComm::from_value_comment(n, kw_value_comment))
+              .map(|kwo| self.cols[(n - 1) as usize].tcomm.replace(kwo))?;
+          }
+        }
+        [b'T', b'N', b'U', b'L', b'L', nbr @ ..] => {
+          if let Some(n) = get_n(nbr) {
+            self
+              .check_n(n)
+              .and_then(|()| TNull::from_value_comment(n, kw_value_comment))
+              .map(|kwo| self.cols[(n - 1) as usize].tnull.replace(kwo))?;
+          }
+        }
+        [b'T', b'S', b'C', b'A', b'L', nbr @ ..] => {
+          if let Some(n) = get_n(nbr) {
+            self
+              .check_n(n)
+              .and_then(|()| TScal::from_value_comment(n, kw_value_comment))
+              .map(|kwo| self.cols[(n - 1) as usize].tscal.replace(kwo))?;
+          }
+        }
+        [b'T', b'Z', b'E', b'R', b'O', nbr @ ..] => {
+          if let Some(n) = get_n(nbr) {
+            self
+              .check_n(n)
+              .and_then(|()| TZero::from_value_comment(n, kw_value_comment))
+              .map(|kwo| self.cols[(n - 1) as usize].tzero.replace(kwo))?;
+          }
+        }
+        [b'T', b'D', b'M', b'I', b'N', nbr @ ..] => {
+          if let Some(n) = get_n(nbr) {
+            self
+              .check_n(n)
+              .and_then(|()| TDMin::from_value_comment(n, kw_value_comment))
+              .map(|kwo| self.cols[(n - 1) as usize].tdmin.replace(kwo))?;
+          }
+        }
+        [b'T', b'D', b'M', b'A', b'X', nbr @ ..] => {
+          if let Some(n) = get_n(nbr) {
+            self
+              .check_n(n)
+              .and_then(|()| TDMax::from_value_comment(n, kw_value_comment))
+              .map(|kwo| self.cols[(n - 1) as usize].tdmax.replace(kwo))?;
+          }
+        }
+        _ => {}
+      }
+    }
+    Ok(())
+  }
+}
+
+impl From<AsciiTableHeader> for AsciiTableHeaderWithColInfo {
+  fn from(mrh: AsciiTableHeader) -> Self {
+    let cols = (0..mrh.n_cols())
+      .map(|_| AsciiTableColumnHeader::default())
+      .collect();
+    Self { mrh, cols }
+  }
+}
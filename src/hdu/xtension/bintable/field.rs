@@ -65,6 +65,7 @@ pub enum Field {
   AsciiString(String), // Empty string = null. We could express ASCII string as a Vec of Char (ascii char), but it is a nightly features
 }
 
+#[derive(Clone, Copy)]
 pub struct ComplexF32 {
   real: f32,
   img: f32,
@@ -81,6 +82,7 @@ impl ComplexF32 {
   }
 }
 
+#[derive(Clone, Copy)]
 pub struct ComplexF64 {
   real: f64,
   img: f64,
@@ -1,24 +1,24 @@
 use log::warn;
 
-#[cfg(feature = "vot")]
-use crate::common::keywords::tables::bintable::{
-  tdisp::TDispValue,
-  tform::{RepeatCountAndExtraChar, VariableLenghtArrayInfo},
-};
 use crate::hdu::xtension::bintable::schema::EmptySchema;
 use crate::{
   common::{
     DynValueKwr, ValueKwr,
     keywords::{
       bitpix::BitPix,
+      extname::{ExtName, ExtVer, HduName},
       naxis::{NAxis, NAxis1, NAxis2},
       pgcount::{GCount, PCount},
       tables::{
         bintable::{
           tdim::TDim,
-          tdisp::TDispn,
-          tform::{TFormValue, TFormn, VariableLenghtArrayDataType},
+          tdisp::{TDispValue, TDispn},
+          tform::{
+            RepeatCountAndExtraChar, TFormValue, TFormn, VariableLenghtArrayDataType,
+            VariableLenghtArrayInfo,
+          },
           theap::THeap,
+          ztable::{ZCTypeValue, ZCTypn, ZFormn, ZNAxis1, ZNAxis2, ZPCount, ZTable, ZTileLen},
         },
         tcomm::TComm,
         tdminmax::{TDMax, TDMin},
@@ -84,6 +84,10 @@ pub struct BinTableColumnHeader {
   tdmin: Option<TDMin>,
   /// Max column value
   tdmax: Option<TDMax>,
+  /// Data type of the uncompressed column, in tile-compressed tables
+  zform: Option<ZFormn>,
+  /// Compression algorithm, in tile-compressed tables
+  zctyp: Option<ZCTypn>,
   // TO be implemented?
   // TLMAX, TLMIN,
 }
@@ -110,23 +114,48 @@ impl BinTableColumnHeader {
   pub fn disp(&self) -> Option<&TDispValue> {
     self.tdisp.as_ref().map(|tdisp| tdisp.data_type())
   }
+  pub fn tform(&self) -> Option<&TFormn> {
+    self.tform.as_ref()
+  }
+  /// Data type of the uncompressed column, in tile-compressed tables.
+  pub fn zform(&self) -> Option<&ZFormn> {
+    self.zform.as_ref()
+  }
+  /// Compression algorithm, in tile-compressed tables.
+  pub fn zctyp(&self) -> Option<&ZCTypn> {
+    self.zctyp.as_ref()
+  }
 
-  // format (tdips)
-
-  /// Returns the VOTable arraysize of this field, given the data type length and TDISP
-  #[cfg(feature = "vot")]
-  pub fn to_arraysize(&self, len: usize) -> ArraySize {
-    match &self.tdim {
-      None => ArraySize::Fixed1D { size: len as u32 },
-      Some(tdim) => {
-        assert_eq!(len as u16, tdim.col_nbr());
-        ArraySize::FixedND {
-          sizes: tdim.dimensions().iter().map(|v| *v as u32).collect(),
-        }
-      }
-    }
+  /// Transforms the header of a tile-compressed column into the header of the uncompressed column,
+  /// i.e. replaces `TFORMn` by `ZFORMn` and removes `ZCTYPn`.
+  /// Returns the `TFORMn` of the compressed column and the compression algorithm.
+  pub(crate) fn take_tile_compression(
+    &mut self,
+    icol: u16,
+  ) -> Result<(TFormn, ZCTypeValue), Error> {
+    let zform = self.zform.take().ok_or_else(|| {
+      new_custom(format!(
+        "Keyword ZFORM{} not found in tile-compressed table.",
+        icol + 1
+      ))
+    })?;
+    let zctyp = self.zctyp.take().ok_or_else(|| {
+      new_custom(format!(
+        "Keyword ZCTYP{} not found in tile-compressed table.",
+        icol + 1
+      ))
+    })?;
+    let tform = self.tform.replace(zform.into_tform()).ok_or_else(|| {
+      new_custom(format!(
+        "Keyword TFORM{} not found in tile-compressed table.",
+        icol + 1
+      ))
+    })?;
+    Ok((tform, zctyp.get()))
   }
 
+  // format (tdips)
+
   /// Compute the VOTable Field corresponding to this column.
   /// If no name is define in FITS, use the given column index to build name.
   #[cfg(feature = "vot")]
@@ -234,121 +263,132 @@ impl BinTableColumnHeader {
       Schema::DoubleFromDouble(_so) | Schema::DoubleFromInt(_so) | Schema::DoubleFromLong(_so) => {
         Ok(VOTField::new(name, VOTDatatype::Double))
       }
-      Schema::ComplexFloat => Ok(VOTField::new(name, VOTDatatype::ComplexFloat)),
-      Schema::ComplexDouble => Ok(VOTField::new(name, VOTDatatype::ComplexDouble)),
+      Schema::ComplexFloat | Schema::ComplexFloatFromFloat(_) => {
+        Ok(VOTField::new(name, VOTDatatype::ComplexFloat))
+      }
+      Schema::ComplexDouble | Schema::ComplexDoubleFromDouble(_) => {
+        Ok(VOTField::new(name, VOTDatatype::ComplexDouble))
+      }
       Schema::AsciiChar => Ok(VOTField::new(name, VOTDatatype::CharASCII)),
       Schema::NullableBooleanArray(ap) => {
-        Ok(VOTField::new(name, VOTDatatype::Logical).set_arraysize(self.to_arraysize(ap.get_len())))
+        Ok(VOTField::new(name, VOTDatatype::Logical).set_arraysize(to_arraysize(ap)))
       }
       Schema::ByteArray(ap) => Ok(
         VOTField::new(name, VOTDatatype::Byte)
           .set_xtype("signed")
-          .set_arraysize(self.to_arraysize(ap.get_len())),
+          .set_arraysize(to_arraysize(ap)),
       ),
       Schema::ShortArray(ap) => Ok(
-        VOTField::new(name, VOTDatatype::ShortInt).set_arraysize(self.to_arraysize(ap.get_len())),
+        VOTField::new(name, VOTDatatype::ShortInt).set_arraysize(to_arraysize(ap)),
       ),
       Schema::IntArray(ap) => {
-        Ok(VOTField::new(name, VOTDatatype::Int).set_arraysize(self.to_arraysize(ap.get_len())))
+        Ok(VOTField::new(name, VOTDatatype::Int).set_arraysize(to_arraysize(ap)))
       }
       Schema::LongArray(ap) => {
-        Ok(VOTField::new(name, VOTDatatype::LongInt).set_arraysize(self.to_arraysize(ap.get_len())))
+        Ok(VOTField::new(name, VOTDatatype::LongInt).set_arraysize(to_arraysize(ap)))
       }
       Schema::NullableByteArray { null, p } => Ok(
         VOTField::new(name, VOTDatatype::Byte)
           .set_values(Values::new().set_null(to_i8(*null).to_string()))
           .set_xtype("signed")
-          .set_arraysize(self.to_arraysize(p.get_len())),
+          .set_arraysize(to_arraysize(p)),
       ),
       Schema::NullableShortArray { null, p } => Ok(
         VOTField::new(name, VOTDatatype::ShortInt)
           .set_values(Values::new().set_null(null.to_string()))
-          .set_arraysize(self.to_arraysize(p.get_len())),
+          .set_arraysize(to_arraysize(p)),
       ),
       Schema::NullableIntArray { null, p } => Ok(
         VOTField::new(name, VOTDatatype::Int)
           .set_values(Values::new().set_null(null.to_string()))
-          .set_arraysize(self.to_arraysize(p.get_len())),
+          .set_arraysize(to_arraysize(p)),
       ),
       Schema::NullableLongArray { null, p } => Ok(
         VOTField::new(name, VOTDatatype::LongInt)
           .set_values(Values::new().set_null(null.to_string()))
-          .set_arraysize(self.to_arraysize(p.get_len())),
+          .set_arraysize(to_arraysize(p)),
       ),
       Schema::UnsignedByteArray(ap) => {
-        Ok(VOTField::new(name, VOTDatatype::Byte).set_arraysize(self.to_arraysize(ap.get_len())))
+        Ok(VOTField::new(name, VOTDatatype::Byte).set_arraysize(to_arraysize(ap)))
       }
       Schema::UnsignedShortArray(ap) => Ok(
         VOTField::new(name, VOTDatatype::ShortInt)
           .set_xtype("unsigned")
-          .set_arraysize(self.to_arraysize(ap.get_len())),
+          .set_arraysize(to_arraysize(ap)),
       ),
       Schema::UnsignedIntArray(ap) => Ok(
         VOTField::new(name, VOTDatatype::Int)
           .set_xtype("unsigned")
-          .set_arraysize(self.to_arraysize(ap.get_len())),
+          .set_arraysize(to_arraysize(ap)),
       ),
       Schema::UnsignedLongArray(ap) => Ok(
         VOTField::new(name, VOTDatatype::LongInt)
           .set_xtype("unsigned")
-          .set_arraysize(self.to_arraysize(ap.get_len())),
+          .set_arraysize(to_arraysize(ap)),
       ),
       Schema::NullableUnsignedByteArray { null, p } => Ok(
         VOTField::new(name, VOTDatatype::Byte)
           .set_values(Values::new().set_null(null.to_string()))
-          .set_arraysize(self.to_arraysize(p.get_len())),
+          .set_arraysize(to_arraysize(p)),
       ),
       Schema::NullableUnsignedShortArray { null, p } => Ok(
         VOTField::new(name, VOTDatatype::ShortInt)
           .set_values(Values::new().set_null(to_u16(*null).to_string()))
           .set_xtype("unsigned")
-          .set_arraysize(self.to_arraysize(p.get_len())),
+          .set_arraysize(to_arraysize(p)),
       ),
       Schema::NullableUnsignedIntArray { null, p } => Ok(
         VOTField::new(name, VOTDatatype::Int)
           .set_values(Values::new().set_null(to_u32(*null).to_string()))
           .set_xtype("unsigned")
-          .set_arraysize(self.to_arraysize(p.get_len())),
+          .set_arraysize(to_arraysize(p)),
       ),
       Schema::NullableUnsignedLongArray { null, p } => Ok(
         VOTField::new(name, VOTDatatype::LongInt)
           .set_values(Values::new().set_null(to_u64(*null).to_string()))
           .set_xtype("unsigned")
-          .set_arraysize(self.to_arraysize(p.get_len())),
+          .set_arraysize(to_arraysize(p)),
       ),
       Schema::FloatArray(ap) => {
-        Ok(VOTField::new(name, VOTDatatype::Float).set_arraysize(self.to_arraysize(ap.get_len())))
+        Ok(VOTField::new(name, VOTDatatype::Float).set_arraysize(to_arraysize(ap)))
       }
       Schema::FloatArrayFromFloat(apwso)
       | Schema::FloatArrayFromBytes(apwso)
       | Schema::FloatArrayFromShort(apwso) => Ok(
-        VOTField::new(name, VOTDatatype::Float).set_arraysize(ArraySize::Fixed1D {
-          size: apwso.get_len() as u32,
-        }),
+        VOTField::new(name, VOTDatatype::Float)
+          .set_arraysize(to_arraysize(apwso.get_array_param())),
       ),
       Schema::DoubleArray(ap) => {
-        Ok(VOTField::new(name, VOTDatatype::Double).set_arraysize(self.to_arraysize(ap.get_len())))
+        Ok(VOTField::new(name, VOTDatatype::Double).set_arraysize(to_arraysize(ap)))
       }
       Schema::DoubleArrayFromDouble(apwso)
       | Schema::DoubleArrayFromInt(apwso)
       | Schema::DoubleArrayFromLong(apwso) => Ok(
-        VOTField::new(name, VOTDatatype::Double).set_arraysize(ArraySize::Fixed1D {
-          size: apwso.get_len() as u32,
-        }),
+        VOTField::new(name, VOTDatatype::Double)
+          .set_arraysize(to_arraysize(apwso.get_array_param())),
       ),
       Schema::ComplexFloatArray(ap) => Ok(
         VOTField::new(name, VOTDatatype::ComplexFloat)
-          .set_arraysize(self.to_arraysize(ap.get_len())),
+          .set_arraysize(to_arraysize(ap)),
       ),
       Schema::ComplexDoubleArray(ap) => Ok(
         VOTField::new(name, VOTDatatype::ComplexDouble)
-          .set_arraysize(self.to_arraysize(ap.get_len())),
+          .set_arraysize(to_arraysize(ap)),
+      ),
+      Schema::ComplexFloatArrayFromFloat(apwso) => Ok(
+        VOTField::new(name, VOTDatatype::ComplexFloat)
+          .set_arraysize(to_arraysize(apwso.get_array_param())),
+      ),
+      Schema::ComplexDoubleArrayFromDouble(apwso) => Ok(
+        VOTField::new(name, VOTDatatype::ComplexDouble)
+          .set_arraysize(to_arraysize(apwso.get_array_param())),
       ),
       Schema::AsciiString(ap) => Ok(
-        VOTField::new(name, VOTDatatype::CharASCII).set_arraysize(self.to_arraysize(ap.get_len())),
+        VOTField::new(name, VOTDatatype::CharASCII).set_arraysize(to_arraysize(ap)),
       ),
-      Schema::HeapArrayPtr32(_has) => todo!(),
-      Schema::HeapArrayPtr64(_has) => todo!(),
+      Schema::HeapArrayPtr32(has) | Schema::HeapArrayPtr64(has) => {
+        Ok(Self::heap_array_to_vot_field(name, has).set_arraysize(ArraySize::Variable1D))
+      }
     }?;
     if let Some(unit) = self.unit() {
       vot_field.set_unit_by_ref(unit);
@@ -377,12 +417,88 @@ impl BinTableColumnHeader {
       let (w, p) = disp.get_width_and_prec();
       vot_field.set_width_by_ref(w);
       if let Some(p) = p {
-        vot_field.set_width_by_ref(p);
+        vot_field.set_precision_by_ref(match disp {
+          TDispValue::F { .. } => Precision::new_dec(p as u8),
+          _ => Precision::new_sci(p as u8),
+        });
       }
     }
     Ok(vot_field)
   }
 
+  /// Compute the VOTable Field (without `arraysize`) corresponding to a variable length array column.
+  #[cfg(feature = "vot")]
+  fn heap_array_to_vot_field(name: String, has: &HeapArraySchema) -> VOTField {
+    match has {
+      HeapArraySchema::HeapNullableBooleanArray(_) => VOTField::new(name, VOTDatatype::Logical),
+      HeapArraySchema::HeapByteArray(_) => {
+        VOTField::new(name, VOTDatatype::Byte).set_xtype("signed")
+      }
+      HeapArraySchema::HeapShortArray(_) => VOTField::new(name, VOTDatatype::ShortInt),
+      HeapArraySchema::HeapIntArray(_) => VOTField::new(name, VOTDatatype::Int),
+      HeapArraySchema::HeapLongArray(_) => VOTField::new(name, VOTDatatype::LongInt),
+      HeapArraySchema::HeapNullableByteArray { null, .. } => VOTField::new(name, VOTDatatype::Byte)
+        .set_xtype("signed")
+        .set_values(Values::new().set_null(to_i8(*null).to_string())),
+      HeapArraySchema::HeapNullableShortArray { null, .. } => {
+        VOTField::new(name, VOTDatatype::ShortInt)
+          .set_values(Values::new().set_null(null.to_string()))
+      }
+      HeapArraySchema::HeapNullableIntArray { null, .. } => {
+        VOTField::new(name, VOTDatatype::Int).set_values(Values::new().set_null(null.to_string()))
+      }
+      HeapArraySchema::HeapNullableLongArray { null, .. } => {
+        VOTField::new(name, VOTDatatype::LongInt)
+          .set_values(Values::new().set_null(null.to_string()))
+      }
+      HeapArraySchema::HeapUnsignedByteArray(_) => VOTField::new(name, VOTDatatype::Byte),
+      HeapArraySchema::HeapUnsignedShortArray(_) => {
+        VOTField::new(name, VOTDatatype::ShortInt).set_xtype("unsigned")
+      }
+      HeapArraySchema::HeapUnsignedIntArray(_) => {
+        VOTField::new(name, VOTDatatype::Int).set_xtype("unsigned")
+      }
+      HeapArraySchema::HeapUnsignedLongArray(_) => {
+        VOTField::new(name, VOTDatatype::LongInt).set_xtype("unsigned")
+      }
+      HeapArraySchema::HeapNullableUnsignedByteArray { null, .. } => {
+        VOTField::new(name, VOTDatatype::Byte).set_values(Values::new().set_null(null.to_string()))
+      }
+      HeapArraySchema::HeapNullableUnsignedShortArray { null, .. } => {
+        VOTField::new(name, VOTDatatype::ShortInt)
+          .set_values(Values::new().set_null(to_u16(*null).to_string()))
+          .set_xtype("unsigned")
+      }
+      HeapArraySchema::HeapNullableUnsignedIntArray { null, .. } => {
+        VOTField::new(name, VOTDatatype::Int)
+          .set_values(Values::new().set_null(to_u32(*null).to_string()))
+          .set_xtype("unsigned")
+      }
+      HeapArraySchema::HeapNullableUnsignedLongArray { null, .. } => {
+        VOTField::new(name, VOTDatatype::LongInt)
+          .set_values(Values::new().set_null(to_u64(*null).to_string()))
+          .set_xtype("unsigned")
+      }
+      HeapArraySchema::HeapFloatArray(_)
+      | HeapArraySchema::HeapFloatArrayFromFloat(_)
+      | HeapArraySchema::HeapFloatArrayFromByte(_)
+      | HeapArraySchema::HeapFloatArrayFromShort(_) => VOTField::new(name, VOTDatatype::Float),
+      HeapArraySchema::HeapDoubleArray(_)
+      | HeapArraySchema::HeapDoubleArrayFromDouble(_)
+      | HeapArraySchema::HeapDoubleArrayFromInt(_)
+      | HeapArraySchema::HeapDoubleArrayFromLong(_) => VOTField::new(name, VOTDatatype::Double),
+      HeapArraySchema::HeapComplexFloatArray(_)
+      | HeapArraySchema::HeapComplexFloatArrayFromFloat(_) => {
+        VOTField::new(name, VOTDatatype::ComplexFloat)
+      }
+      HeapArraySchema::HeapComplexDoubleArray(_)
+      | HeapArraySchema::HeapComplexDoubleArrayFromDouble(_) => {
+        VOTField::new(name, VOTDatatype::ComplexDouble)
+      }
+      HeapArraySchema::HeapAsciiString(_) => VOTField::new(name, VOTDatatype::CharASCII),
+    }
+  }
+
   /// Replace the empty elements by the ones provided in the given VOTable field.
   /// If the option `overwrite` is set to `true`, elements are overwritten (except the ones defining the
   /// datatype, i.e. TFORM, TDIM, TNULL, TSCAL and TZERO;  and TDISP).
@@ -630,6 +746,389 @@ impl BinTableColumnHeader {
     }
   }
 
+  /// Build a column header from the given schema, i.e. set `TFORMn` and, if needed,
+  /// `TDIMn`, `TNULLn`, `TSCALn` and `TZEROn` (this is the reverse operation of the `schema` method).
+  /// # Params
+  /// * `icol`: index of the column, starting at 0.
+  /// * `schema`: schema of the column.
+  pub fn from_schema(icol: u16, schema: &Schema) -> Result<Self, Error> {
+    let n = icol + 1;
+    let rc = |len: usize| -> Result<RepeatCountAndExtraChar, Error> {
+      match len {
+        1 => Ok(RepeatCountAndExtraChar::default()),
+        _ => u16::try_from(len)
+          .map(|r| RepeatCountAndExtraChar::default().with_r(r))
+          .map_err(|_| new_custom(format!("Col {}. Too large repeat count: {}.", n, len))),
+      }
+    };
+    let max_len = |len: usize| -> Result<u16, Error> {
+      u16::try_from(len).map_err(|_| {
+        new_custom(format!(
+          "Col {}. Too large variable length array max length: {}.",
+          n, len
+        ))
+      })
+    };
+    let so32 = |scale: f32, offset: f32| (Some(scale as f64), Some(UIF64::F64(offset as f64)));
+    let so64 = |scale: f64, offset: f64| (Some(scale), Some(UIF64::F64(offset)));
+    let no_so = (None, None);
+    let i8_so = (None, Some(UIF64::I64(-128)));
+    let u16_so = (None, Some(UIF64::U64(32768)));
+    let u32_so = (None, Some(UIF64::U64(2147483648)));
+    let u64_so = (None, Some(UIF64::U64(9223372036854775808)));
+
+    let (tform, tnull, (tscal, tzero)): (TFormValue, Option<i64>, (Option<f64>, Option<UIF64>)) =
+      match schema {
+        Schema::Empty(empty) => {
+          let r0 = RepeatCountAndExtraChar::default().with_r(0);
+          match empty {
+            EmptySchema::Bool => (TFormValue::L(r0), None, no_so),
+            EmptySchema::Bit => (TFormValue::X(r0), None, no_so),
+            EmptySchema::Byte => (TFormValue::B(r0), None, i8_so),
+            EmptySchema::Short => (TFormValue::I(r0), None, no_so),
+            EmptySchema::Int => (TFormValue::J(r0), None, no_so),
+            EmptySchema::Long => (TFormValue::K(r0), None, no_so),
+            EmptySchema::UnsignedByte => (TFormValue::B(r0), None, no_so),
+            EmptySchema::UnsignedShort => (TFormValue::I(r0), None, u16_so),
+            EmptySchema::UnsignedInt => (TFormValue::J(r0), None, u32_so),
+            EmptySchema::UnsignedLong => (TFormValue::K(r0), None, u64_so),
+            EmptySchema::AsciiChar => (TFormValue::A(r0), None, no_so),
+            EmptySchema::Float => (TFormValue::E(r0), None, no_so),
+            EmptySchema::Double => (TFormValue::D(r0), None, no_so),
+            EmptySchema::ComplexFloat => (TFormValue::C(r0), None, no_so),
+            EmptySchema::ComplexDouble => (TFormValue::M(r0), None, no_so),
+            EmptySchema::ArrayDesc32 => (
+              TFormValue::P(VariableLenghtArrayInfo::new(
+                Some(false),
+                VariableLenghtArrayDataType::B,
+                0,
+                None,
+              )),
+              None,
+              no_so,
+            ),
+            EmptySchema::ArrayDesc64 => (
+              TFormValue::Q(VariableLenghtArrayInfo::new(
+                Some(false),
+                VariableLenghtArrayDataType::B,
+                0,
+                None,
+              )),
+              None,
+              no_so,
+            ),
+          }
+        }
+        // Bool
+        Schema::NullableBoolean => (TFormValue::L(rc(1)?), None, no_so),
+        Schema::Bits { n_bits } => (TFormValue::X(rc(*n_bits)?), None, no_so),
+        // Signed integer
+        Schema::Byte => (TFormValue::B(rc(1)?), None, i8_so),
+        Schema::Short => (TFormValue::I(rc(1)?), None, no_so),
+        Schema::Int => (TFormValue::J(rc(1)?), None, no_so),
+        Schema::Long => (TFormValue::K(rc(1)?), None, no_so),
+        Schema::NullableByte { null } => (TFormValue::B(rc(1)?), Some(*null as i64), i8_so),
+        Schema::NullableShort { null } => (TFormValue::I(rc(1)?), Some(*null as i64), no_so),
+        Schema::NullableInt { null } => (TFormValue::J(rc(1)?), Some(*null as i64), no_so),
+        Schema::NullableLong { null } => (TFormValue::K(rc(1)?), Some(*null), no_so),
+        // Unsigned integer
+        Schema::UnsignedByte => (TFormValue::B(rc(1)?), None, no_so),
+        Schema::UnsignedShort => (TFormValue::I(rc(1)?), None, u16_so),
+        Schema::UnsignedInt => (TFormValue::J(rc(1)?), None, u32_so),
+        Schema::UnsignedLong => (TFormValue::K(rc(1)?), None, u64_so),
+        Schema::NullableUnsignedByte { null } => (TFormValue::B(rc(1)?), Some(*null as i64), no_so),
+        Schema::NullableUnsignedShort { null } => {
+          (TFormValue::I(rc(1)?), Some(*null as i64), u16_so)
+        }
+        Schema::NullableUnsignedInt { null } => (TFormValue::J(rc(1)?), Some(*null as i64), u32_so),
+        Schema::NullableUnsignedLong { null } => (TFormValue::K(rc(1)?), Some(*null), u64_so),
+        // Real
+        Schema::Float => (TFormValue::E(rc(1)?), None, no_so),
+        Schema::FloatFromFloat(so) => (
+          TFormValue::E(rc(1)?),
+          None,
+          so32(so.get_scale(), so.get_offset()),
+        ),
+        Schema::FloatFromByte(so) => (
+          TFormValue::B(rc(1)?),
+          None,
+          so32(so.get_scale(), so.get_offset()),
+        ),
+        Schema::FloatFromShort(so) => (
+          TFormValue::I(rc(1)?),
+          None,
+          so32(so.get_scale(), so.get_offset()),
+        ),
+        Schema::Double => (TFormValue::D(rc(1)?), None, no_so),
+        Schema::DoubleFromDouble(so) => (
+          TFormValue::D(rc(1)?),
+          None,
+          so64(so.get_scale(), so.get_offset()),
+        ),
+        Schema::DoubleFromInt(so) => (
+          TFormValue::J(rc(1)?),
+          None,
+          so64(so.get_scale(), so.get_offset()),
+        ),
+        Schema::DoubleFromLong(so) => (
+          TFormValue::K(rc(1)?),
+          None,
+          so64(so.get_scale(), so.get_offset()),
+        ),
+        // Complex
+        Schema::ComplexFloat => (TFormValue::C(rc(1)?), None, no_so),
+        Schema::ComplexFloatFromFloat(so) => (
+          TFormValue::C(rc(1)?),
+          None,
+          so32(so.get_scale(), so.get_offset()),
+        ),
+        Schema::ComplexDouble => (TFormValue::M(rc(1)?), None, no_so),
+        Schema::ComplexDoubleFromDouble(so) => (
+          TFormValue::M(rc(1)?),
+          None,
+          so64(so.get_scale(), so.get_offset()),
+        ),
+        // ASCII char
+        Schema::AsciiChar => (TFormValue::A(rc(1)?), None, no_so),
+        // Fixed length arrays
+        Schema::NullableBooleanArray(p) => (TFormValue::L(rc(p.get_len())?), None, no_so),
+        Schema::ByteArray(p) => (TFormValue::B(rc(p.get_len())?), None, i8_so),
+        Schema::ShortArray(p) => (TFormValue::I(rc(p.get_len())?), None, no_so),
+        Schema::IntArray(p) => (TFormValue::J(rc(p.get_len())?), None, no_so),
+        Schema::LongArray(p) => (TFormValue::K(rc(p.get_len())?), None, no_so),
+        Schema::NullableByteArray { null, p } => {
+          (TFormValue::B(rc(p.get_len())?), Some(*null as i64), i8_so)
+        }
+        Schema::NullableShortArray { null, p } => {
+          (TFormValue::I(rc(p.get_len())?), Some(*null as i64), no_so)
+        }
+        Schema::NullableIntArray { null, p } => {
+          (TFormValue::J(rc(p.get_len())?), Some(*null as i64), no_so)
+        }
+        Schema::NullableLongArray { null, p } => {
+          (TFormValue::K(rc(p.get_len())?), Some(*null), no_so)
+        }
+        Schema::UnsignedByteArray(p) => (TFormValue::B(rc(p.get_len())?), None, no_so),
+        Schema::UnsignedShortArray(p) => (TFormValue::I(rc(p.get_len())?), None, u16_so),
+        Schema::UnsignedIntArray(p) => (TFormValue::J(rc(p.get_len())?), None, u32_so),
+        Schema::UnsignedLongArray(p) => (TFormValue::K(rc(p.get_len())?), None, u64_so),
+        Schema::NullableUnsignedByteArray { null, p } => {
+          (TFormValue::B(rc(p.get_len())?), Some(*null as i64), no_so)
+        }
+        Schema::NullableUnsignedShortArray { null, p } => {
+          (TFormValue::I(rc(p.get_len())?), Some(*null as i64), u16_so)
+        }
+        Schema::NullableUnsignedIntArray { null, p } => {
+          (TFormValue::J(rc(p.get_len())?), Some(*null as i64), u32_so)
+        }
+        Schema::NullableUnsignedLongArray { null, p } => {
+          (TFormValue::K(rc(p.get_len())?), Some(*null), u64_so)
+        }
+        Schema::FloatArray(p) => (TFormValue::E(rc(p.get_len())?), None, no_so),
+        Schema::FloatArrayFromFloat(p) => (
+          TFormValue::E(rc(p.get_len())?),
+          None,
+          so32(p.get_scale(), p.get_offset()),
+        ),
+        Schema::FloatArrayFromBytes(p) => (
+          TFormValue::B(rc(p.get_len())?),
+          None,
+          so32(p.get_scale(), p.get_offset()),
+        ),
+        Schema::FloatArrayFromShort(p) => (
+          TFormValue::I(rc(p.get_len())?),
+          None,
+          so32(p.get_scale(), p.get_offset()),
+        ),
+        Schema::DoubleArray(p) => (TFormValue::D(rc(p.get_len())?), None, no_so),
+        Schema::DoubleArrayFromDouble(p) => (
+          TFormValue::D(rc(p.get_len())?),
+          None,
+          so64(p.get_scale(), p.get_offset()),
+        ),
+        Schema::DoubleArrayFromInt(p) => (
+          TFormValue::J(rc(p.get_len())?),
+          None,
+          so64(p.get_scale(), p.get_offset()),
+        ),
+        Schema::DoubleArrayFromLong(p) => (
+          TFormValue::K(rc(p.get_len())?),
+          None,
+          so64(p.get_scale(), p.get_offset()),
+        ),
+        Schema::ComplexFloatArray(p) => (TFormValue::C(rc(p.get_len())?), None, no_so),
+        Schema::ComplexFloatArrayFromFloat(p) => (
+          TFormValue::C(rc(p.get_len())?),
+          None,
+          so32(p.get_scale(), p.get_offset()),
+        ),
+        Schema::ComplexDoubleArray(p) => (TFormValue::M(rc(p.get_len())?), None, no_so),
+        Schema::ComplexDoubleArrayFromDouble(p) => (
+          TFormValue::M(rc(p.get_len())?),
+          None,
+          so64(p.get_scale(), p.get_offset()),
+        ),
+        Schema::AsciiString(p) => (TFormValue::A(rc(p.get_len())?), None, no_so),
+        // Variable length arrays
+        Schema::HeapArrayPtr32(has) | Schema::HeapArrayPtr64(has) => {
+          let (len, so) = match has {
+            HeapArraySchema::HeapNullableBooleanArray(p)
+            | HeapArraySchema::HeapShortArray(p)
+            | HeapArraySchema::HeapIntArray(p)
+            | HeapArraySchema::HeapLongArray(p)
+            | HeapArraySchema::HeapUnsignedByteArray(p)
+            | HeapArraySchema::HeapFloatArray(p)
+            | HeapArraySchema::HeapDoubleArray(p)
+            | HeapArraySchema::HeapComplexFloatArray(p)
+            | HeapArraySchema::HeapComplexDoubleArray(p)
+            | HeapArraySchema::HeapAsciiString(p) => (p.get_max_len(), no_so),
+            HeapArraySchema::HeapByteArray(p) => (p.get_max_len(), i8_so),
+            HeapArraySchema::HeapUnsignedShortArray(p) => (p.get_max_len(), u16_so),
+            HeapArraySchema::HeapUnsignedIntArray(p) => (p.get_max_len(), u32_so),
+            HeapArraySchema::HeapUnsignedLongArray(p) => (p.get_max_len(), u64_so),
+            HeapArraySchema::HeapFloatArrayFromFloat(p)
+            | HeapArraySchema::HeapFloatArrayFromByte(p)
+            | HeapArraySchema::HeapFloatArrayFromShort(p)
+            | HeapArraySchema::HeapComplexFloatArrayFromFloat(p) => {
+              (p.get_max_len(), so32(p.get_scale(), p.get_offset()))
+            }
+            HeapArraySchema::HeapDoubleArrayFromDouble(p)
+            | HeapArraySchema::HeapDoubleArrayFromInt(p)
+            | HeapArraySchema::HeapDoubleArrayFromLong(p)
+            | HeapArraySchema::HeapComplexDoubleArrayFromDouble(p) => {
+              (p.get_max_len(), so64(p.get_scale(), p.get_offset()))
+            }
+            HeapArraySchema::HeapNullableByteArray { .. }
+            | HeapArraySchema::HeapNullableShortArray { .. }
+            | HeapArraySchema::HeapNullableIntArray { .. }
+            | HeapArraySchema::HeapNullableLongArray { .. }
+            | HeapArraySchema::HeapNullableUnsignedByteArray { .. }
+            | HeapArraySchema::HeapNullableUnsignedShortArray { .. }
+            | HeapArraySchema::HeapNullableUnsignedIntArray { .. }
+            | HeapArraySchema::HeapNullableUnsignedLongArray { .. } => {
+              return Err(new_custom(format!(
+                "Col {}. TNULL not yet supported in variable length arrays: '{}'.",
+                n, has
+              )));
+            }
+          };
+          let vla_info = VariableLenghtArrayInfo::new(None, has.data_type(), max_len(len)?, None);
+          let tform = match schema {
+            Schema::HeapArrayPtr32(_) => TFormValue::P(vla_info),
+            _ => TFormValue::Q(vla_info),
+          };
+          (tform, None, so)
+        }
+      };
+    let tdim = schema
+      .shape()
+      .map(|shape| {
+        shape
+          .iter()
+          .map(|d| {
+            u16::try_from(*d)
+              .map_err(|_| new_custom(format!("Col {}. Too large TDIM dimension: {}.", n, d)))
+          })
+          .collect::<Result<Vec<u16>, Error>>()
+          .map(|dims| TDim::new(n, dims.into()))
+      })
+      .transpose()?;
+    Ok(Self {
+      tform: Some(TFormn::new(n, tform)),
+      tdim,
+      tnull: tnull.map(|null| TNull::new(n, null)),
+      tscal: tscal.map(|scale| TScal::new(n, scale)),
+      tzero: tzero.map(|zero| TZero::new(n, zero)),
+      ..Default::default()
+    })
+  }
+
+  /// Set the column name (`TTYPEn`).
+  /// # Params
+  /// * `icol`: index of the column, starting at 0.
+  pub fn set_colname(&mut self, icol: u16, name: String) {
+    self.ttype = Some(TType::new(icol + 1, name));
+  }
+  /// Set the column unit (`TUNITn`).
+  pub fn set_unit(&mut self, icol: u16, unit: String) {
+    self.tunit = Some(TUnit::new(icol + 1, unit));
+  }
+  /// Set the column UCD (`TUCDn`).
+  pub fn set_ucd(&mut self, icol: u16, ucd: String) {
+    self.tucd = Some(TUCD::new(icol + 1, ucd));
+  }
+  /// Set the column description (`TCOMMn`).
+  pub fn set_description(&mut self, icol: u16, description: String) {
+    self.tcomm = Some(TComm::new(icol + 1, description));
+  }
+  /// Set the column display format (`TDISPn`).
+  pub fn set_disp(&mut self, icol: u16, disp: TDispValue) {
+    self.tdisp = Some(TDispn::new(icol + 1, disp));
+  }
+  /// Set the value coding NULL (`TNULLn`), for integer columns only.
+  pub fn set_null(&mut self, icol: u16, null: i64) {
+    self.tnull = Some(TNull::new(icol + 1, null));
+  }
+
+  /// Write the keyword records of the column (only the ones having a value).
+  pub fn write_kw_records<'a, I>(&self, dest: &mut I) -> Result<(), Error>
+  where
+    I: Iterator<Item = Result<&'a mut [u8; 80], Error>>,
+  {
+    if let Some(kw) = &self.ttype {
+      kw.write_kw_record(dest)?;
+    }
+    if let Some(kw) = &self.tform {
+      kw.write_kw_record(dest)?;
+    }
+    if let Some(kw) = &self.tunit {
+      kw.write_kw_record(dest)?;
+    }
+    if let Some(kw) = &self.tucd {
+      kw.write_kw_record(dest)?;
+    }
+    if let Some(kw) = &self.tcomm {
+      kw.write_kw_record(dest)?;
+    }
+    if let Some(kw) = &self.tnull {
+      kw.write_kw_record(dest)?;
+    }
+    if let Some(kw) = &self.tscal {
+      kw.write_kw_record(dest)?;
+    }
+    if let Some(kw) = &self.tzero {
+      kw.write_kw_record(dest)?;
+    }
+    if let Some(kw) = &self.tdisp {
+      kw.write_kw_record(dest)?;
+    }
+    if let Some(kw) = &self.tdim {
+      kw.write_kw_record(dest)?;
+    }
+    if let Some(kw) = &self.tdmin {
+      kw.write_kw_record(dest)?;
+    }
+    if let Some(kw) = &self.tdmax {
+      kw.write_kw_record(dest)?;
+    }
+    Ok(())
+  }
+
+  /// Returns the parameters of a fixed length array column of given repeat count, including
+  /// the dimensions provided by `TDIMn` (ignored if their product differs from the repeat count).
+  fn array_param(&self, len: usize) -> ArrayParam {
+    match &self.tdim {
+      None => ArrayParam::new(len),
+      Some(tdim) => {
+        let dims: Vec<usize> = tdim.dimensions().iter().map(|d| *d as usize).collect();
+        ArrayParam::new(len).with_dims(dims).unwrap_or_else(|e| {
+          warn!("TDIM{} ignored. {}", tdim.col_nbr(), e);
+          ArrayParam::new(len)
+        })
+      }
+    }
+  }
+
   pub fn schema(&self) -> Option<Schema> {
     let scale = self.tscal.as_ref().map(|s| s.scale()).unwrap_or(1.0);
     let offset = self
@@ -646,7 +1145,7 @@ impl BinTableColumnHeader {
         match rc.repeat_count() {
           0 => Schema::Empty(EmptySchema::Bool),
           1 => Schema::NullableBoolean,
-          len => Schema::NullableBooleanArray(ArrayParam::new(len as usize)),
+          len => Schema::NullableBooleanArray(self.array_param(len as usize)),
         }
       }
 
@@ -665,7 +1164,7 @@ impl BinTableColumnHeader {
 
       // Unsigned Byte (u8)
       // -- normal case
-      TFormValue::B(rc) if scale == 1.0 || offset.is_0() => match rc.repeat_count() {
+      TFormValue::B(rc) if scale == 1.0 && offset.is_0() => match rc.repeat_count() {
         0 => Schema::Empty(EmptySchema::UnsignedByte),
         1 => match &self.tnull {
           None => Schema::UnsignedByte,
@@ -674,7 +1173,7 @@ impl BinTableColumnHeader {
           },
         },
         len => {
-          let p = ArrayParam::new(len as usize);
+          let p = self.array_param(len as usize);
           match &self.tnull {
             None => Schema::UnsignedByteArray(p),
             Some(null) => Schema::NullableUnsignedByteArray {
@@ -694,7 +1193,7 @@ impl BinTableColumnHeader {
           },
         },
         len => {
-          let p = ArrayParam::new(len as usize);
+          let p = self.array_param(len as usize);
           match &self.tnull {
             None => Schema::ByteArray(p),
             Some(null) => Schema::NullableByteArray {
@@ -714,14 +1213,14 @@ impl BinTableColumnHeader {
           0 => Schema::Empty(EmptySchema::Float),
           1 => Schema::FloatFromByte(transform),
           len => Schema::FloatArrayFromBytes(
-            ArrayParam::new(len as usize).with_scale_offset_32(transform),
+            self.array_param(len as usize).with_scale_offset_32(transform),
           ),
         }
       }
 
       // Short integer (i16)
       // -- normal case
-      TFormValue::I(rc) if scale == 1.0 || offset.is_0() => match rc.repeat_count() {
+      TFormValue::I(rc) if scale == 1.0 && offset.is_0() => match rc.repeat_count() {
         0 => Schema::Empty(EmptySchema::Short),
         1 => match &self.tnull {
           None => Schema::Short,
@@ -730,7 +1229,7 @@ impl BinTableColumnHeader {
           },
         },
         len => {
-          let p = ArrayParam::new(len as usize);
+          let p = self.array_param(len as usize);
           match &self.tnull {
             None => Schema::ShortArray(p),
             Some(null) => Schema::NullableShortArray {
@@ -750,7 +1249,7 @@ impl BinTableColumnHeader {
           },
         },
         len => {
-          let p = ArrayParam::new(len as usize);
+          let p = self.array_param(len as usize);
           match &self.tnull {
             None => Schema::UnsignedShortArray(p),
             Some(null) => Schema::NullableUnsignedShortArray {
@@ -770,13 +1269,13 @@ impl BinTableColumnHeader {
           0 => Schema::Empty(EmptySchema::Float),
           1 => Schema::FloatFromShort(transform),
           len => Schema::FloatArrayFromShort(
-            ArrayParam::new(len as usize).with_scale_offset_32(transform),
+            self.array_param(len as usize).with_scale_offset_32(transform),
           ),
         }
       }
       // Integer (i32)
       // -- normal case
-      TFormValue::J(rc) if scale == 1.0 || offset.is_0() => match rc.repeat_count() {
+      TFormValue::J(rc) if scale == 1.0 && offset.is_0() => match rc.repeat_count() {
         0 => Schema::Empty(EmptySchema::Int),
         1 => match &self.tnull {
           None => Schema::Int,
@@ -785,7 +1284,7 @@ impl BinTableColumnHeader {
           },
         },
         len => {
-          let p = ArrayParam::new(len as usize);
+          let p = self.array_param(len as usize);
           match &self.tnull {
             None => Schema::IntArray(p),
             Some(null) => Schema::NullableIntArray {
@@ -805,7 +1304,7 @@ impl BinTableColumnHeader {
           },
         },
         len => {
-          let p = ArrayParam::new(len as usize);
+          let p = self.array_param(len as usize);
           match &self.tnull {
             None => Schema::UnsignedIntArray(p),
             Some(null) => Schema::NullableUnsignedIntArray {
@@ -822,12 +1321,12 @@ impl BinTableColumnHeader {
           0 => Schema::Empty(EmptySchema::Double),
           1 => Schema::DoubleFromInt(transform),
           len => Schema::DoubleArrayFromInt(
-            ArrayParam::new(len as usize).with_scale_offset_64(transform),
+            self.array_param(len as usize).with_scale_offset_64(transform),
           ),
         }
       }
       // Long integer (i64) -> should be float80 i computations.
-      TFormValue::K(rc) if scale == 1.0 || offset.is_0() => match rc.repeat_count() {
+      TFormValue::K(rc) if scale == 1.0 && offset.is_0() => match rc.repeat_count() {
         0 => Schema::Empty(EmptySchema::Long),
         1 => match &self.tnull {
           None => Schema::Long,
@@ -836,7 +1335,7 @@ impl BinTableColumnHeader {
           },
         },
         len => {
-          let p = ArrayParam::new(len as usize);
+          let p = self.array_param(len as usize);
           match &self.tnull {
             None => Schema::LongArray(p),
             Some(null) => Schema::NullableLongArray {
@@ -856,7 +1355,7 @@ impl BinTableColumnHeader {
           },
         },
         len => {
-          let p = ArrayParam::new(len as usize);
+          let p = self.array_param(len as usize);
           match &self.tnull {
             None => Schema::UnsignedLongArray(p),
             Some(null) => Schema::NullableUnsignedLongArray {
@@ -873,7 +1372,7 @@ impl BinTableColumnHeader {
           0 => Schema::Empty(EmptySchema::Double),
           1 => Schema::DoubleFromLong(transform), // Should be a float with 64 bits mantissa...
           len => Schema::DoubleArrayFromLong(
-            ArrayParam::new(len as usize).with_scale_offset_64(transform),
+            self.array_param(len as usize).with_scale_offset_64(transform),
           ),
         }
       }
@@ -885,14 +1384,14 @@ impl BinTableColumnHeader {
         match rc.repeat_count() {
           0 => Schema::Empty(EmptySchema::AsciiChar),
           1 => Schema::AsciiChar,
-          len => Schema::AsciiString(ArrayParam::new(len as usize)),
+          len => Schema::AsciiString(self.array_param(len as usize)),
         }
       }
       // Float (f32)
-      TFormValue::E(rc) if scale == 1.0 || offset.is_0() => match rc.repeat_count() {
+      TFormValue::E(rc) if scale == 1.0 && offset.is_0() => match rc.repeat_count() {
         0 => Schema::Empty(EmptySchema::Float),
         1 => Schema::Float,
-        len => Schema::FloatArray(ArrayParam::new(len as usize)),
+        len => Schema::FloatArray(self.array_param(len as usize)),
       },
       TFormValue::E(rc) => {
         let transform = ScaleOffset32::new(scale as f32, offset.as_f32());
@@ -900,15 +1399,15 @@ impl BinTableColumnHeader {
           0 => Schema::Empty(EmptySchema::Float),
           1 => Schema::FloatFromFloat(transform),
           len => Schema::FloatArrayFromFloat(
-            ArrayParam::new(len as usize).with_scale_offset_32(transform),
+            self.array_param(len as usize).with_scale_offset_32(transform),
           ),
         }
       }
       // Double (f64)
-      TFormValue::D(rc) if scale == 1.0 || offset.is_0() => match rc.repeat_count() {
+      TFormValue::D(rc) if scale == 1.0 && offset.is_0() => match rc.repeat_count() {
         0 => Schema::Empty(EmptySchema::Double),
         1 => Schema::Double,
-        len => Schema::DoubleArray(ArrayParam::new(len as usize)),
+        len => Schema::DoubleArray(self.array_param(len as usize)),
       },
       TFormValue::D(rc) => {
         let transform = ScaleOffset64::new(scale, offset.as_f64());
@@ -916,30 +1415,44 @@ impl BinTableColumnHeader {
           0 => Schema::Empty(EmptySchema::Double),
           1 => Schema::DoubleFromDouble(transform),
           len => Schema::DoubleArrayFromDouble(
-            ArrayParam::new(len as usize).with_scale_offset_64(transform),
+            self.array_param(len as usize).with_scale_offset_64(transform),
           ),
         }
       }
 
       // Complex f32 (f32, f32)
-      TFormValue::C(rc) if scale == 1.0 || offset.is_0() => match rc.repeat_count() {
+      TFormValue::C(rc) if scale == 1.0 && offset.is_0() => match rc.repeat_count() {
         0 => Schema::Empty(EmptySchema::ComplexFloat),
         1 => Schema::ComplexFloat,
-        len => Schema::ComplexFloatArray(ArrayParam::new(len as usize)),
+        len => Schema::ComplexFloatArray(self.array_param(len as usize)),
       },
-      TFormValue::C(_rc) => {
-        // scale + offset only on the real part? Why not scale on modulus and offset on angle?
-        todo!()
+      // -- scale on both the real and imaginary parts, offset on the real part only
+      TFormValue::C(rc) => {
+        let transform = ScaleOffset32::new(scale as f32, offset.as_f32());
+        match rc.repeat_count() {
+          0 => Schema::Empty(EmptySchema::ComplexFloat),
+          1 => Schema::ComplexFloatFromFloat(transform),
+          len => Schema::ComplexFloatArrayFromFloat(
+            self.array_param(len as usize).with_scale_offset_32(transform),
+          ),
+        }
       }
       // Complex f64 (f64, f64)
-      TFormValue::M(rc) if scale == 1.0 || offset.is_0() => match rc.repeat_count() {
+      TFormValue::M(rc) if scale == 1.0 && offset.is_0() => match rc.repeat_count() {
         0 => Schema::Empty(EmptySchema::ComplexDouble),
         1 => Schema::ComplexDouble,
-        len => Schema::ComplexDoubleArray(ArrayParam::new(len as usize)),
+        len => Schema::ComplexDoubleArray(self.array_param(len as usize)),
       },
-      TFormValue::M(_rc) => {
-        // scale + offset only on the real part? Why not scale on modulus and offset on angle?
-        todo!()
+      // -- scale on both the real and imaginary parts, offset on the real part only
+      TFormValue::M(rc) => {
+        let transform = ScaleOffset64::new(scale, offset.as_f64());
+        match rc.repeat_count() {
+          0 => Schema::Empty(EmptySchema::ComplexDouble),
+          1 => Schema::ComplexDoubleFromDouble(transform),
+          len => Schema::ComplexDoubleArrayFromDouble(
+            self.array_param(len as usize).with_scale_offset_64(transform),
+          ),
+        }
       }
       // Array descriptor 32-bit (u32)
       TFormValue::P(zo) => {
@@ -969,6 +1482,19 @@ impl BinTableColumnHeader {
   }
 }
 
+/// Returns the VOTable arraysize of a fixed length array, given its parameters.
+#[cfg(feature = "vot")]
+fn to_arraysize(p: &ArrayParam) -> ArraySize {
+  match p.get_dims() {
+    Some(dims) if dims.len() > 1 => ArraySize::FixedND {
+      sizes: dims.iter().map(|d| *d as u32).collect(),
+    },
+    _ => ArraySize::Fixed1D {
+      size: p.get_len() as u32,
+    },
+  }
+}
+
 fn heap_array_data_type(
   vdt: VariableLenghtArrayDataType,
   hap: HeapArrayParam,
@@ -1039,22 +1565,19 @@ fn heap_array_data_type(
     VariableLenghtArrayDataType::C if scale == 1.0 && offset.is_0() => {
       HeapArraySchema::HeapComplexFloatArray(hap)
     }
-    VariableLenghtArrayDataType::C => {
-      // scale + offset only on the real part? Why not scale on modulus and offset on angle?
-      todo!()
-    }
+    VariableLenghtArrayDataType::C => HeapArraySchema::HeapComplexFloatArrayFromFloat(
+      hap.with_scale_offset_32(ScaleOffset32::new(scale as f32, offset.as_f32())),
+    ),
     VariableLenghtArrayDataType::M if scale == 1.0 && offset.is_0() => {
       HeapArraySchema::HeapComplexDoubleArray(hap)
     }
-    VariableLenghtArrayDataType::M => {
-      // scale + offset only on the real part? Why not scale on modulus and offset on angle?
-      todo!()
-    }
+    VariableLenghtArrayDataType::M => HeapArraySchema::HeapComplexDoubleArrayFromDouble(
+      hap.with_scale_offset_64(ScaleOffset64::new(scale, offset.as_f64())),
+    ),
   }
 }
 
 // Oher table keywords:
-// * look at compression.
 // * ...
 
 pub struct BinTableHeader {
@@ -1172,10 +1695,141 @@ pub struct BinTableHeaderWithColInfo {
   /// Index of the first starting byte of the HEAP, from the starting data byte.
   /// **Cannot** be lower than `naxis1 * naxis2`.
   theap: Option<THeap>,
+  /// Tile-compressed table keywords, if any
+  ztable: ZTableKeywords,
+  /// Extension identification keywords, if any
+  extid: ExtIdKeywords,
   /// Columns metadata
   cols: Vec<BinTableColumnHeader>,
 }
+
+/// Table level keywords of the tiled table compression convention.
+#[derive(Default, Debug)]
+pub struct ZTableKeywords {
+  ztable: Option<ZTable>,
+  znaxis1: Option<ZNAxis1>,
+  znaxis2: Option<ZNAxis2>,
+  zpcount: Option<ZPCount>,
+  ztilelen: Option<ZTileLen>,
+}
+
+/// Keywords identifying the extension: `EXTNAME`, `EXTVER` and `HDUNAME`.
+#[derive(Default, Debug)]
+pub struct ExtIdKeywords {
+  extname: Option<ExtName>,
+  extver: Option<ExtVer>,
+  hduname: Option<HduName>,
+}
+
+impl ExtIdKeywords {
+  /// Value of `EXTNAME`, if any.
+  pub fn extname(&self) -> Option<&str> {
+    self.extname.as_ref().map(|k| k.get())
+  }
+  /// Value of `EXTVER`, if any (`1` being assumed if not present).
+  pub fn extver(&self) -> Option<i64> {
+    self.extver.as_ref().map(|k| k.get())
+  }
+  /// Value of `HDUNAME`, if any.
+  pub fn hduname(&self) -> Option<&str> {
+    self.hduname.as_ref().map(|k| k.get())
+  }
+  /// Tells whether the extension has the given name (`EXTNAME` or `HDUNAME`, case insensitive)
+  /// and, if provided, the given version (`EXTVER`, `1` if not present).
+  pub fn matches(&self, name: &str, version: Option<i64>) -> bool {
+    let name = name.trim();
+    [self.extname(), self.hduname()]
+      .into_iter()
+      .flatten()
+      .any(|n| n.trim().eq_ignore_ascii_case(name))
+      && version
+        .map(|v| v == self.extver().unwrap_or(1))
+        .unwrap_or(true)
+  }
+}
+
+impl ZTableKeywords {
+  /// `true` if `ZTABLE = T`.
+  pub fn is_tile_compressed(&self) -> bool {
+    self.ztable.as_ref().map(|z| z.get()).unwrap_or(false)
+  }
+  /// Byte size of a row of the uncompressed table.
+  pub fn row_byte_size(&self) -> Option<u32> {
+    self.znaxis1.as_ref().map(|z| z.get())
+  }
+  /// Number of rows of the uncompressed table.
+  pub fn n_rows(&self) -> Option<u64> {
+    self.znaxis2.as_ref().map(|z| z.get())
+  }
+  /// Heap byte size of the uncompressed table.
+  pub fn heap_byte_size(&self) -> Option<usize> {
+    self.zpcount.as_ref().map(|z| z.get())
+  }
+  /// Number of rows per tile.
+  pub fn tile_len(&self) -> Option<u64> {
+    self.ztilelen.as_ref().map(|z| z.get())
+  }
+}
 impl BinTableHeaderWithColInfo {
+  /// Build the header of a BINTABLE from its columns headers.
+  /// # Params
+  /// * `n_rows`: number of rows in the table
+  /// * `heap_byte_size`: size of the heap (for variable length arrays), if any
+  /// * `cols`: columns headers, all having a `TFORMn` keyword
+  pub fn new(
+    n_rows: u64,
+    heap_byte_size: usize,
+    cols: Vec<BinTableColumnHeader>,
+  ) -> Result<Self, Error> {
+    let tfield = u16::try_from(cols.len())
+      .map_err(|_| new_custom(format!("Too many columns: {}.", cols.len())))?;
+    let row_byte_size = cols
+      .iter()
+      .enumerate()
+      .map(|(i, col)| {
+        col.schema().map(|s| s.stored_byte_len()).ok_or_else(|| {
+          new_custom(format!(
+            "Unable to create schema for column {}: TFORM probably missing!",
+            i + 1
+          ))
+        })
+      })
+      .sum::<Result<usize, Error>>()?;
+    let naxis1 = u32::try_from(row_byte_size)
+      .map_err(|_| new_custom(format!("Too large row byte size: {}.", row_byte_size)))?;
+    Ok(Self {
+      mrh: BinTableHeader::new(naxis1, n_rows, heap_byte_size, tfield),
+      theap: None,
+      ztable: ZTableKeywords::default(),
+      extid: ExtIdKeywords::default(),
+      cols,
+    })
+  }
+
+  /// Write all the keyword records of the header, except the `END` keyword record.
+  pub fn write_kw_records<'a, I>(&self, dest: &mut I) -> Result<(), Error>
+  where
+    I: Iterator<Item = Result<&'a mut [u8; 80], Error>>,
+  {
+    self.write_starting_mandatory_kw_records(dest)?;
+    if let Some(theap) = &self.theap {
+      theap.write_kw_record(dest)?;
+    }
+    if let Some(extname) = &self.extid.extname {
+      extname.write_kw_record(dest)?;
+    }
+    if let Some(extver) = &self.extid.extver {
+      extver.write_kw_record(dest)?;
+    }
+    if let Some(hduname) = &self.extid.hduname {
+      hduname.write_kw_record(dest)?;
+    }
+    for col in &self.cols {
+      col.write_kw_records(dest)?;
+    }
+    Ok(())
+  }
+
   fn check_n(&self, n: u16) -> Result<(), Error> {
     if n as usize > self.cols.len() {
       Err(new_custom(format!(
@@ -1236,6 +1890,54 @@ impl BinTableHeaderWithColInfo {
       .unwrap_or(0)
   }
 
+  /// `true` if the table is a tile-compressed table (`ZTABLE = T`), in which case the columns
+  /// are heap pointers to compressed data, see the `ztable` module.
+  pub fn is_tile_compressed(&self) -> bool {
+    self.ztable.is_tile_compressed()
+  }
+
+  /// Keywords identifying the extension (`EXTNAME`, `EXTVER`, `HDUNAME`).
+  pub fn extid(&self) -> &ExtIdKeywords {
+    &self.extid
+  }
+
+  pub fn ztable(&self) -> &ZTableKeywords {
+    &self.ztable
+  }
+
+  /// Transforms the header of a tile-compressed table into the header of the uncompressed table,
+  /// i.e. the header using `ZNAXIS2` rows and the `ZFORMn` data types.
+  /// Also returns, for each column, the `TFORMn` of the compressed column and the compression
+  /// algorithm.
+  pub(crate) fn into_uncompressed(mut self) -> Result<(Self, Vec<(TFormn, ZCTypeValue)>), Error> {
+    if !self.is_tile_compressed() {
+      return Err(new_custom(
+        "Not a tile-compressed table: ZTABLE = T not found.",
+      ));
+    }
+    let n_rows = self
+      .ztable
+      .n_rows()
+      .ok_or_else(|| new_custom("Keyword ZNAXIS2 not found in tile-compressed table."))?;
+    let compressed_cols = self
+      .cols
+      .iter_mut()
+      .enumerate()
+      .map(|(icol, col)| col.take_tile_compression(icol as u16))
+      .collect::<Result<Vec<_>, Error>>()?;
+    let uncompressed = Self::new(n_rows, 0, self.cols)?;
+    match self.ztable.row_byte_size() {
+      Some(row_byte_size) if row_byte_size as usize != uncompressed.row_byte_size() => {
+        Err(new_custom(format!(
+          "Wrong ZNAXIS1 value. Expected (from ZFORMn): {}. Actual: {}.",
+          uncompressed.row_byte_size(),
+          row_byte_size
+        )))
+      }
+      _ => Ok((uncompressed, compressed_cols)),
+    }
+  }
+
   pub fn cols(&self) -> &[BinTableColumnHeader] {
     self.cols.as_slice()
   }
@@ -1318,6 +2020,53 @@ impl Header for BinTableHeaderWithColInfo {
         [b'T', b'H', b'E', b'A', b'P', b' ', b' ', b' '] => {
           THeap::from_value_comment(kw_value_comment).map(|kwo| self.theap.replace(kwo))?;
         }
+        ExtName::KEYWORD => {
+          ExtName::from_value_comment(kw_value_comment)
+            .map(|kwo| self.extid.extname.replace(kwo))?;
+        }
+        ExtVer::KEYWORD => {
+          ExtVer::from_value_comment(kw_value_comment).map(|kwo| self.extid.extver.replace(kwo))?;
+        }
+        HduName::KEYWORD => {
+          HduName::from_value_comment(kw_value_comment)
+            .map(|kwo| self.extid.hduname.replace(kwo))?;
+        }
+        ZTable::KEYWORD => {
+          ZTable::from_value_comment(kw_value_comment)
+            .map(|kwo| self.ztable.ztable.replace(kwo))?;
+        }
+        ZNAxis1::KEYWORD => {
+          ZNAxis1::from_value_comment(kw_value_comment)
+            .map(|kwo| self.ztable.znaxis1.replace(kwo))?;
+        }
+        ZNAxis2::KEYWORD => {
+          ZNAxis2::from_value_comment(kw_value_comment)
+            .map(|kwo| self.ztable.znaxis2.replace(kwo))?;
+        }
+        ZPCount::KEYWORD => {
+          ZPCount::from_value_comment(kw_value_comment)
+            .map(|kwo| self.ztable.zpcount.replace(kwo))?;
+        }
+        ZTileLen::KEYWORD => {
+          ZTileLen::from_value_comment(kw_value_comment)
+            .map(|kwo| self.ztable.ztilelen.replace(kwo))?;
+        }
+        [b'Z', b'F', b'O', b'R', b'M', nbr @ ..] => {
+          if let Some(n) = get_n(nbr) {
+            self
+              .check_n(n)
+              .and_then(|()| ZFormn::from_value_comment(n, kw_value_comment))
+              .map(|kwo| self.cols[(n - 1) as usize].zform.replace(kwo))?;
+          }
+        }
+        [b'Z', b'C', b'T', b'Y', b'P', nbr @ ..] => {
+          if let Some(n) = get_n(nbr) {
+            self
+              .check_n(n)
+              .and_then(|()| ZCTypn::from_value_comment(n, kw_value_comment))
+              .map(|kwo| self.cols[(n - 1) as usize].zctyp.replace(kwo))?;
+          }
+        }
         [b'T', b'T', b'Y', b'P', b'E', nbr @ ..] => {
           if let Some(n) = get_n(nbr) {
             // 'kwo' stands for keyword object
@@ -1450,6 +2199,8 @@ impl From<BinTableHeader> for BinTableHeaderWithColInfo {
     Self {
       mrh,
       theap: None,
+      ztable: ZTableKeywords::default(),
+      extid: ExtIdKeywords::default(),
       cols,
     }
   }
@@ -2,3 +2,5 @@ pub mod field;
 pub mod header;
 pub mod read;
 pub mod schema;
+pub mod write;
+pub mod ztable;
@@ -0,0 +1,172 @@
+//! Typed access to the values of a single BINTABLE column.
+//! Only the bytes of the column are read in each row (using the column starting byte), the other
+//! fields being skipped. `TSCALn`, `TZEROn` and `TNULLn` are applied according to the column
+//! `Schema`.
+
+use std::marker::PhantomData;
+
+use crate::{
+  error::{Error, new_custom},
+  hdu::xtension::bintable::{
+    header::BinTableHeaderWithColInfo,
+    read::deser::{
+      row::{FromField, check_field},
+      sliceheap::DeserializerWithHeap,
+    },
+    schema::FieldSchema,
+  },
+};
+
+/// Iterates over the values of a single BINTABLE column.
+/// The iterator can be split in chunks of rows (see [ColumnIter::chunks]) to be processed in
+/// parallel.
+pub struct ColumnIter<'u, T: FromField> {
+  field_schema: FieldSchema,
+  row_byte_size: usize,
+  /// Part of the main table not yet read.
+  main: &'u [u8],
+  heap: &'u [u8],
+  // `fn() -> T` so that the iterator is `Send` and `Sync` whatever `T`.
+  _marker: PhantomData<fn() -> T>,
+}
+
+impl<'u, T: FromField> ColumnIter<'u, T> {
+  /// # Params
+  /// * `header`: the BINTABLE header
+  /// * `data`: the BINTABLE data part (main table, gap and heap)
+  /// * `col_name`: the name of the column
+  /// # Errors
+  /// If the column is not found, if its values can't be deserialized into `T`, or if `data` is
+  /// shorter than the main table and the heap.
+  pub fn new(
+    header: &BinTableHeaderWithColInfo,
+    data: &'u [u8],
+    col_name: &str,
+  ) -> Result<Self, Error> {
+    header
+      .build_col_names()
+      .iter()
+      .position(|name| name == col_name)
+      .ok_or_else(|| new_custom(format!("Column '{}' not found.", col_name)))
+      .and_then(|icol| Self::from_index(header, data, icol))
+  }
+
+  /// Same as [ColumnIter::new], but providing the column index (starting at 0).
+  pub fn from_index(
+    header: &BinTableHeaderWithColInfo,
+    data: &'u [u8],
+    icol: usize,
+  ) -> Result<Self, Error> {
+    if header.is_tile_compressed() {
+      return Err(new_custom(
+        "Tile-compressed BINTABLE: decompress it first (see the ztable module).",
+      ));
+    }
+    let row_schema = header.build_row_schema().project(&[icol])?;
+    let field_schema = row_schema.fields_schemas()[0].clone();
+    let col_name = header.build_col_names().swap_remove(icol);
+    check_field::<T>(&col_name, &field_schema, std::any::type_name::<T>())?;
+    let main_table_byte_size = header.main_table_byte_size();
+    let data_byte_size = main_table_byte_size + header.heap_byte_size();
+    let (main, heap) = match (
+      data.get(..main_table_byte_size),
+      data.get(main_table_byte_size + header.gap_byte_size()..data_byte_size),
+    ) {
+      (Some(main), Some(heap)) => (main, heap),
+      _ => {
+        return Err(new_custom(format!(
+          "BINTABLE data too short. Expected: at least {}. Actual: {}.",
+          data_byte_size,
+          data.len()
+        )));
+      }
+    };
+    Ok(Self {
+      field_schema,
+      row_byte_size: header.row_byte_size(),
+      main,
+      heap,
+      _marker: PhantomData,
+    })
+  }
+
+  /// Schema of the column, including its starting byte in each row.
+  pub fn field_schema(&self) -> &FieldSchema {
+    &self.field_schema
+  }
+
+  /// Returns an iterator over sub-iterators of (at most) `n_rows` values each.
+  /// Each sub-iterator is independent, so that chunks can be processed in parallel.
+  pub fn chunks(self, n_rows: usize) -> ColumnChunks<'u, T> {
+    ColumnChunks {
+      iter: self,
+      n_rows: n_rows.max(1),
+    }
+  }
+
+  fn n_remaining_rows(&self) -> usize {
+    self.main.len().checked_div(self.row_byte_size).unwrap_or(0)
+  }
+
+  /// Split off the first `n_rows` rows in a new iterator.
+  fn split_off_front(&mut self, n_rows: usize) -> Self {
+    let (rows, rem) = self
+      .main
+      .split_at((self.row_byte_size * n_rows).min(self.main.len()));
+    self.main = rem;
+    Self {
+      field_schema: self.field_schema.clone(),
+      row_byte_size: self.row_byte_size,
+      main: rows,
+      heap: self.heap,
+      _marker: PhantomData,
+    }
+  }
+}
+
+impl<'u, T: FromField> Iterator for ColumnIter<'u, T> {
+  type Item = Result<T, Error>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.n_remaining_rows() == 0 {
+      return None;
+    }
+    let (row, rem) = self.main.split_at(self.row_byte_size);
+    self.main = rem;
+    let mut de = DeserializerWithHeap::new(row, self.heap);
+    Some(T::from_field(&self.field_schema, &mut de))
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    let n = self.n_remaining_rows();
+    (n, Some(n))
+  }
+}
+
+impl<'u, T: FromField> ExactSizeIterator for ColumnIter<'u, T> {}
+
+/// Iterates over chunks of the values of a single BINTABLE column, see [ColumnIter::chunks].
+pub struct ColumnChunks<'u, T: FromField> {
+  iter: ColumnIter<'u, T>,
+  /// Number of rows per chunk.
+  n_rows: usize,
+}
+
+impl<'u, T: FromField> Iterator for ColumnChunks<'u, T> {
+  type Item = ColumnIter<'u, T>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.iter.n_remaining_rows() == 0 {
+      None
+    } else {
+      Some(self.iter.split_off_front(self.n_rows))
+    }
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    let n = self.iter.n_remaining_rows().div_ceil(self.n_rows);
+    (n, Some(n))
+  }
+}
+
+impl<'u, T: FromField> ExactSizeIterator for ColumnChunks<'u, T> {}
@@ -1,6 +1,7 @@
 use crate::error::Error;
 
 use super::visitor::Visitor;
+pub mod row;
 pub mod sliceheap;
 
 pub trait Deserialize<'de>: Sized {
@@ -234,6 +235,28 @@ pub trait Deserializer<'de> {
   where
     V: Visitor;
 
+  /// Both the real and the imaginary parts are scaled, but the (real) offset is only added to
+  /// the real part: `offset + scale * (real + i * img)`.
+  fn deserialize_complex_float_with_scale_offset<V>(
+    &mut self,
+    from: usize,
+    scale: f32,
+    offset: f32,
+    visitor: V,
+  ) -> Result<V::Value, Error>
+  where
+    V: Visitor;
+
+  fn deserialize_complex_double_with_scale_offset<V>(
+    &mut self,
+    from: usize,
+    scale: f64,
+    offset: f64,
+    visitor: V,
+  ) -> Result<V::Value, Error>
+  where
+    V: Visitor;
+
   fn deserialize_ascii_char<V>(&mut self, from: usize, visitor: V) -> Result<V::Value, Error>
   where
     V: Visitor;
@@ -501,6 +524,28 @@ pub trait Deserializer<'de> {
   where
     V: Visitor;
 
+  fn deserialize_complex_float_with_scale_offset_array<V>(
+    &mut self,
+    len: usize,
+    from: usize,
+    scale: f32,
+    offset: f32,
+    visitor: V,
+  ) -> Result<V::Value, Error>
+  where
+    V: Visitor;
+
+  fn deserialize_complex_double_with_scale_offset_array<V>(
+    &mut self,
+    len: usize,
+    from: usize,
+    scale: f64,
+    offset: f64,
+    visitor: V,
+  ) -> Result<V::Value, Error>
+  where
+    V: Visitor;
+
   fn deserialize_ascii_string_fixed_length<V>(
     &mut self,
     n_chars: usize,
@@ -746,6 +791,26 @@ pub trait Deserializer<'de> {
   where
     V: Visitor;
 
+  fn deserialize_complex_float_with_scale_offset_vararray_ptr32<V>(
+    &mut self,
+    from: usize,
+    scale: f32,
+    offset: f32,
+    visitor: V,
+  ) -> Result<V::Value, Error>
+  where
+    V: Visitor;
+
+  fn deserialize_complex_double_with_scale_offset_vararray_ptr32<V>(
+    &mut self,
+    from: usize,
+    scale: f64,
+    offset: f64,
+    visitor: V,
+  ) -> Result<V::Value, Error>
+  where
+    V: Visitor;
+
   fn deserialize_ascii_string_var_ptr32<V>(
     &mut self,
     from: usize,
@@ -990,6 +1055,26 @@ pub trait Deserializer<'de> {
   where
     V: Visitor;
 
+  fn deserialize_complex_float_with_scale_offset_vararray_ptr64<V>(
+    &mut self,
+    from: usize,
+    scale: f32,
+    offset: f32,
+    visitor: V,
+  ) -> Result<V::Value, Error>
+  where
+    V: Visitor;
+
+  fn deserialize_complex_double_with_scale_offset_vararray_ptr64<V>(
+    &mut self,
+    from: usize,
+    scale: f64,
+    offset: f64,
+    visitor: V,
+  ) -> Result<V::Value, Error>
+  where
+    V: Visitor;
+
   fn deserialize_ascii_string_var_ptr64<V>(
     &mut self,
     from: usize,
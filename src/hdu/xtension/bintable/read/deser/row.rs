@@ -0,0 +1,614 @@
+//! Deserialization of BINTABLE rows into user defined structures.
+//!
+//! The [FitsRow] trait is usually implemented with the `#[derive(FitsRow)]` macro (`derive`
+//! feature), each field of the structure being bound to the column having the same name
+//! (or the name given in a `#[fits(name = "...")]` attribute):
+//! ```ignore
+//! #[derive(FitsRow)]
+//! struct Star {
+//!   ra: f64,
+//!   dec: f64,
+//!   #[fits(name = "phot_g_mean_mag")]
+//!   g: Option<f32>,
+//! }
+//! let binding = RowBinding::<Star>::from_header(&bintable_header)?;
+//! for star in binding.rows(&bintable_header, data)? {
+//!   let star = star?;
+//! }
+//! ```
+//! The types of the structure fields are checked against the columns `Schema` when binding, so
+//! that deserializing a row only fails on a NULL value read in a non-`Option` field.
+
+use std::marker::PhantomData;
+
+use crate::{
+  error::{Error, new_custom},
+  hdu::xtension::bintable::{
+    field::{ComplexF32, ComplexF64},
+    header::BinTableHeaderWithColInfo,
+    read::{
+      deser::{DeserializeSeed, Deserializer, sliceheap::DeserializerWithHeap},
+      visitor::{Visitor, primitive::get_visitor},
+    },
+    schema::{FieldSchema, HeapArraySchema, RowSchema, Schema},
+  },
+};
+
+/// Trait implemented by structures a BINTABLE row can be deserialized into.
+/// Usually implemented with `#[derive(FitsRow)]`.
+pub trait FitsRow: Sized {
+  /// Names of the columns the structure fields are bound to, in the structure fields order.
+  const COLUMNS: &'static [&'static str];
+
+  /// Check that the columns of the given schemas (one per column in `COLUMNS`, in the same
+  /// order) can be deserialized into the structure fields.
+  fn check_schema(fields_schemas: &[FieldSchema]) -> Result<(), Error>;
+
+  /// Deserialize the columns of the given schemas (one per column in `COLUMNS`, in the same
+  /// order) into a new structure.
+  fn deserialize<'de, D>(
+    fields_schemas: &[FieldSchema],
+    deserializer: &mut D,
+  ) -> Result<Self, Error>
+  where
+    D: Deserializer<'de>;
+}
+
+/// Trait implemented by the types a single column value can be deserialized into.
+pub trait FromField: Sized {
+  /// Returns `true` if a column of the given schema can be deserialized into `Self`.
+  fn accepts(schema: &Schema) -> bool;
+
+  /// Deserialize the value of the given field.
+  fn from_field<'de, D>(field_schema: &FieldSchema, deserializer: &mut D) -> Result<Self, Error>
+  where
+    D: Deserializer<'de>;
+}
+
+/// Check that the column of the given name and schema can be deserialized into the type `T`
+/// (named `type_name` in the error message).
+pub fn check_field<T: FromField>(
+  col_name: &str,
+  field_schema: &FieldSchema,
+  type_name: &str,
+) -> Result<(), Error> {
+  if T::accepts(&field_schema.schema) {
+    Ok(())
+  } else {
+    Err(new_custom(format!(
+      "Column '{}' of type '{}' can't be deserialized into '{}'.",
+      col_name, field_schema.schema, type_name
+    )))
+  }
+}
+
+/// Binding, by name, of the columns of a BINTABLE to the fields of a [FitsRow] structure.
+pub struct RowBinding<T: FitsRow> {
+  row_schema: RowSchema,
+  _marker: PhantomData<T>,
+}
+
+impl<T: FitsRow> RowBinding<T> {
+  /// Bind the columns of the given names and schema to the fields of `T`, checking their types.
+  /// # Params
+  /// * `col_names`: the names of all the BINTABLE columns
+  /// * `row_schema`: the schema of all the BINTABLE columns
+  pub fn new(col_names: &[String], row_schema: &RowSchema) -> Result<Self, Error> {
+    let icols = T::COLUMNS
+      .iter()
+      .map(|name| {
+        col_names
+          .iter()
+          .position(|col_name| col_name == name)
+          .ok_or_else(|| new_custom(format!("Column '{}' not found.", name)))
+      })
+      .collect::<Result<Vec<usize>, Error>>()?;
+    let row_schema = row_schema.project(&icols)?;
+    T::check_schema(row_schema.fields_schemas()).map(|()| Self {
+      row_schema,
+      _marker: PhantomData,
+    })
+  }
+
+  /// Bind the columns of the given BINTABLE header to the fields of `T`, checking their types.
+  pub fn from_header(header: &BinTableHeaderWithColInfo) -> Result<Self, Error> {
+    Self::new(&header.build_col_names(), &header.build_row_schema())
+  }
+
+  /// Schema of the bound columns only, in the `T` fields order.
+  pub fn row_schema(&self) -> &RowSchema {
+    &self.row_schema
+  }
+
+  pub fn deserialize<'de, D>(&self, deserializer: &mut D) -> Result<T, Error>
+  where
+    D: Deserializer<'de>,
+  {
+    T::deserialize(self.row_schema.fields_schemas(), deserializer)
+  }
+
+  /// Deserialize a single row.
+  /// # Params
+  /// * `row`: the bytes of a single row in the main table
+  /// * `heap`: all byte of the heap, if any.
+  pub fn deserialize_row(&self, row: &[u8], heap: &[u8]) -> Result<T, Error> {
+    self.deserialize(&mut DeserializerWithHeap::new(row, heap))
+  }
+
+  /// Returns an iterator over the deserialized rows of the given BINTABLE data.
+  /// # Params
+  /// * `header`: the header of the BINTABLE
+  /// * `data`: the whole data part of the BINTABLE (main table, gap and heap)
+  /// # Errors
+  /// If `data` is shorter than the size given by the header.
+  pub fn rows<'a>(
+    &'a self,
+    header: &BinTableHeaderWithColInfo,
+    data: &'a [u8],
+  ) -> Result<impl Iterator<Item = Result<T, Error>> + 'a, Error> {
+    let row_byte_size = header.row_byte_size();
+    let main_table_byte_size = header.main_table_byte_size();
+    let data_byte_size = main_table_byte_size + header.heap_byte_size();
+    match (
+      data.get(..main_table_byte_size),
+      data.get(main_table_byte_size + header.gap_byte_size()..data_byte_size),
+    ) {
+      // Rows are not iterated with `chunks_exact` which panics on a zero row size (NAXIS1 = 0)
+      (Some(main), Some(heap)) => Ok((0..header.n_rows()).map(move |irow| {
+        let from = irow * row_byte_size;
+        self.deserialize_row(&main[from..from + row_byte_size], heap)
+      })),
+      _ => Err(new_custom(format!(
+        "BINTABLE data too short. Expected: at least {}. Actual: {}.",
+        data_byte_size,
+        data.len()
+      ))),
+    }
+  }
+}
+
+/// Returns the schema of a variable length array column.
+fn heap_schema(schema: &Schema) -> Option<&HeapArraySchema> {
+  match schema {
+    Schema::HeapArrayPtr32(has) | Schema::HeapArrayPtr64(has) => Some(has),
+    _ => None,
+  }
+}
+
+macro_rules! from_field_prim {
+  ($ty:ty, $($schema:pat_param)|+) => {
+    impl FromField for $ty {
+      fn accepts(schema: &Schema) -> bool {
+        matches!(schema, $($schema)|+)
+      }
+
+      fn from_field<'de, D>(field_schema: &FieldSchema, deserializer: &mut D) -> Result<Self, Error>
+      where
+        D: Deserializer<'de>,
+      {
+        field_schema.deserialize(deserializer, get_visitor::<$ty>())
+      }
+    }
+  };
+}
+
+macro_rules! from_field_array {
+  ($ty:ty, $($schema:pat_param)|+, $($heap_schema:pat_param)|+) => {
+    impl FromField for Vec<$ty> {
+      fn accepts(schema: &Schema) -> bool {
+        matches!(schema, $($schema)|+) || matches!(heap_schema(schema), Some($($heap_schema)|+))
+      }
+
+      fn from_field<'de, D>(field_schema: &FieldSchema, deserializer: &mut D) -> Result<Self, Error>
+      where
+        D: Deserializer<'de>,
+      {
+        field_schema.deserialize(deserializer, get_visitor::<Vec<$ty>>())
+      }
+    }
+  };
+}
+
+/// Visitor of a (possibly nullable) primitive, returning `None` for NULL (or NaN) values.
+struct OptVisitor<E> {
+  _marker: PhantomData<E>,
+}
+
+impl<E> OptVisitor<E> {
+  fn new() -> Self {
+    Self {
+      _marker: PhantomData,
+    }
+  }
+}
+
+macro_rules! from_field_opt_int {
+  ($ty:ty, $doc:tt, $method:ident, $opt_method:ident, $($schema:pat_param)|+) => {
+    impl Visitor for OptVisitor<$ty> {
+      type Value = Option<$ty>;
+
+      fn expecting(&self) -> &str {
+        $doc
+      }
+
+      fn $method(self, v: $ty) -> Result<Self::Value, Error> {
+        Ok(Some(v))
+      }
+
+      fn $opt_method(self, v: Option<$ty>) -> Result<Self::Value, Error> {
+        Ok(v)
+      }
+    }
+
+    impl FromField for Option<$ty> {
+      fn accepts(schema: &Schema) -> bool {
+        matches!(schema, $($schema)|+)
+      }
+
+      fn from_field<'de, D>(field_schema: &FieldSchema, deserializer: &mut D) -> Result<Self, Error>
+      where
+        D: Deserializer<'de>,
+      {
+        field_schema.deserialize(deserializer, OptVisitor::<$ty>::new())
+      }
+    }
+  };
+}
+
+macro_rules! from_field_opt_float {
+  ($ty:ty, $doc:tt, $method:ident, $($schema:pat_param)|+) => {
+    impl Visitor for OptVisitor<$ty> {
+      type Value = Option<$ty>;
+
+      fn expecting(&self) -> &str {
+        $doc
+      }
+
+      fn $method(self, v: $ty) -> Result<Self::Value, Error> {
+        Ok(Some(v).filter(|v| !v.is_nan()))
+      }
+    }
+
+    impl FromField for Option<$ty> {
+      fn accepts(schema: &Schema) -> bool {
+        matches!(schema, $($schema)|+)
+      }
+
+      fn from_field<'de, D>(field_schema: &FieldSchema, deserializer: &mut D) -> Result<Self, Error>
+      where
+        D: Deserializer<'de>,
+      {
+        field_schema.deserialize(deserializer, OptVisitor::<$ty>::new())
+      }
+    }
+  };
+}
+
+/// Visitor of a non-nullable value, failing on NULL values.
+struct NotNullVisitor<E> {
+  _marker: PhantomData<E>,
+}
+
+impl<E> NotNullVisitor<E> {
+  fn new() -> Self {
+    Self {
+      _marker: PhantomData,
+    }
+  }
+}
+
+impl Visitor for NotNullVisitor<bool> {
+  type Value = bool;
+
+  fn expecting(&self) -> &str {
+    "bool"
+  }
+
+  fn visit_opt_bool(self, v: Option<bool>) -> Result<Self::Value, Error> {
+    v.ok_or_else(|| {
+      new_custom(String::from(
+        "NULL value can't be deserialized into 'bool'.",
+      ))
+    })
+  }
+}
+
+impl FromField for bool {
+  fn accepts(schema: &Schema) -> bool {
+    matches!(schema, Schema::NullableBoolean)
+  }
+
+  fn from_field<'de, D>(field_schema: &FieldSchema, deserializer: &mut D) -> Result<Self, Error>
+  where
+    D: Deserializer<'de>,
+  {
+    field_schema.deserialize(deserializer, NotNullVisitor::<bool>::new())
+  }
+}
+
+impl Visitor for NotNullVisitor<char> {
+  type Value = char;
+
+  fn expecting(&self) -> &str {
+    "char"
+  }
+
+  fn visit_ascii_char(self, v: u8) -> Result<Self::Value, Error> {
+    Ok(v as char)
+  }
+}
+
+impl FromField for char {
+  fn accepts(schema: &Schema) -> bool {
+    matches!(schema, Schema::AsciiChar)
+  }
+
+  fn from_field<'de, D>(field_schema: &FieldSchema, deserializer: &mut D) -> Result<Self, Error>
+  where
+    D: Deserializer<'de>,
+  {
+    field_schema.deserialize(deserializer, NotNullVisitor::<char>::new())
+  }
+}
+
+impl Visitor for NotNullVisitor<ComplexF32> {
+  type Value = ComplexF32;
+
+  fn expecting(&self) -> &str {
+    "ComplexF32"
+  }
+
+  fn visit_cf32(self, v: ComplexF32) -> Result<Self::Value, Error> {
+    Ok(v)
+  }
+}
+
+impl FromField for ComplexF32 {
+  fn accepts(schema: &Schema) -> bool {
+    matches!(
+      schema,
+      Schema::ComplexFloat | Schema::ComplexFloatFromFloat(_)
+    )
+  }
+
+  fn from_field<'de, D>(field_schema: &FieldSchema, deserializer: &mut D) -> Result<Self, Error>
+  where
+    D: Deserializer<'de>,
+  {
+    field_schema.deserialize(deserializer, NotNullVisitor::<ComplexF32>::new())
+  }
+}
+
+impl Visitor for NotNullVisitor<ComplexF64> {
+  type Value = ComplexF64;
+
+  fn expecting(&self) -> &str {
+    "ComplexF64"
+  }
+
+  fn visit_cf64(self, v: ComplexF64) -> Result<Self::Value, Error> {
+    Ok(v)
+  }
+}
+
+impl FromField for ComplexF64 {
+  fn accepts(schema: &Schema) -> bool {
+    matches!(
+      schema,
+      Schema::ComplexDouble | Schema::ComplexDoubleFromDouble(_)
+    )
+  }
+
+  fn from_field<'de, D>(field_schema: &FieldSchema, deserializer: &mut D) -> Result<Self, Error>
+  where
+    D: Deserializer<'de>,
+  {
+    field_schema.deserialize(deserializer, NotNullVisitor::<ComplexF64>::new())
+  }
+}
+
+impl FromField for String {
+  fn accepts(schema: &Schema) -> bool {
+    matches!(schema, Schema::AsciiString(_))
+      || matches!(
+        heap_schema(schema),
+        Some(HeapArraySchema::HeapAsciiString(_))
+      )
+  }
+
+  fn from_field<'de, D>(field_schema: &FieldSchema, deserializer: &mut D) -> Result<Self, Error>
+  where
+    D: Deserializer<'de>,
+  {
+    field_schema.deserialize(deserializer, get_visitor::<String>())
+  }
+}
+
+from_field_prim!(u8, Schema::UnsignedByte);
+from_field_prim!(u16, Schema::UnsignedShort);
+from_field_prim!(u32, Schema::UnsignedInt);
+from_field_prim!(u64, Schema::UnsignedLong);
+from_field_prim!(i8, Schema::Byte);
+from_field_prim!(i16, Schema::Short);
+from_field_prim!(i32, Schema::Int);
+from_field_prim!(i64, Schema::Long);
+from_field_prim!(
+  f32,
+  Schema::Float | Schema::FloatFromFloat(_) | Schema::FloatFromByte(_) | Schema::FloatFromShort(_)
+);
+from_field_prim!(
+  f64,
+  Schema::Double
+    | Schema::DoubleFromDouble(_)
+    | Schema::DoubleFromInt(_)
+    | Schema::DoubleFromLong(_)
+);
+from_field_prim!(Option<bool>, Schema::NullableBoolean);
+
+from_field_opt_int!(
+  u8,
+  "Option<u8>",
+  visit_u8,
+  visit_opt_u8,
+  Schema::UnsignedByte | Schema::NullableUnsignedByte { .. }
+);
+from_field_opt_int!(
+  u16,
+  "Option<u16>",
+  visit_u16,
+  visit_opt_u16,
+  Schema::UnsignedShort | Schema::NullableUnsignedShort { .. }
+);
+from_field_opt_int!(
+  u32,
+  "Option<u32>",
+  visit_u32,
+  visit_opt_u32,
+  Schema::UnsignedInt | Schema::NullableUnsignedInt { .. }
+);
+from_field_opt_int!(
+  u64,
+  "Option<u64>",
+  visit_u64,
+  visit_opt_u64,
+  Schema::UnsignedLong | Schema::NullableUnsignedLong { .. }
+);
+from_field_opt_int!(
+  i8,
+  "Option<i8>",
+  visit_i8,
+  visit_opt_i8,
+  Schema::Byte | Schema::NullableByte { .. }
+);
+from_field_opt_int!(
+  i16,
+  "Option<i16>",
+  visit_i16,
+  visit_opt_i16,
+  Schema::Short | Schema::NullableShort { .. }
+);
+from_field_opt_int!(
+  i32,
+  "Option<i32>",
+  visit_i32,
+  visit_opt_i32,
+  Schema::Int | Schema::NullableInt { .. }
+);
+from_field_opt_int!(
+  i64,
+  "Option<i64>",
+  visit_i64,
+  visit_opt_i64,
+  Schema::Long | Schema::NullableLong { .. }
+);
+from_field_opt_float!(
+  f32,
+  "Option<f32>",
+  visit_f32,
+  Schema::Float | Schema::FloatFromFloat(_) | Schema::FloatFromByte(_) | Schema::FloatFromShort(_)
+);
+from_field_opt_float!(
+  f64,
+  "Option<f64>",
+  visit_f64,
+  Schema::Double
+    | Schema::DoubleFromDouble(_)
+    | Schema::DoubleFromInt(_)
+    | Schema::DoubleFromLong(_)
+);
+
+from_field_array!(
+  u8,
+  Schema::UnsignedByteArray(_),
+  HeapArraySchema::HeapUnsignedByteArray(_)
+);
+from_field_array!(
+  u16,
+  Schema::UnsignedShortArray(_),
+  HeapArraySchema::HeapUnsignedShortArray(_)
+);
+from_field_array!(
+  u32,
+  Schema::UnsignedIntArray(_),
+  HeapArraySchema::HeapUnsignedIntArray(_)
+);
+from_field_array!(
+  u64,
+  Schema::UnsignedLongArray(_),
+  HeapArraySchema::HeapUnsignedLongArray(_)
+);
+from_field_array!(i8, Schema::ByteArray(_), HeapArraySchema::HeapByteArray(_));
+from_field_array!(
+  i16,
+  Schema::ShortArray(_),
+  HeapArraySchema::HeapShortArray(_)
+);
+from_field_array!(i32, Schema::IntArray(_), HeapArraySchema::HeapIntArray(_));
+from_field_array!(i64, Schema::LongArray(_), HeapArraySchema::HeapLongArray(_));
+from_field_array!(
+  f32,
+  Schema::FloatArray(_)
+    | Schema::FloatArrayFromFloat(_)
+    | Schema::FloatArrayFromBytes(_)
+    | Schema::FloatArrayFromShort(_),
+  HeapArraySchema::HeapFloatArray(_)
+    | HeapArraySchema::HeapFloatArrayFromFloat(_)
+    | HeapArraySchema::HeapFloatArrayFromByte(_)
+    | HeapArraySchema::HeapFloatArrayFromShort(_)
+);
+from_field_array!(
+  f64,
+  Schema::DoubleArray(_)
+    | Schema::DoubleArrayFromDouble(_)
+    | Schema::DoubleArrayFromInt(_)
+    | Schema::DoubleArrayFromLong(_),
+  HeapArraySchema::HeapDoubleArray(_)
+    | HeapArraySchema::HeapDoubleArrayFromDouble(_)
+    | HeapArraySchema::HeapDoubleArrayFromInt(_)
+    | HeapArraySchema::HeapDoubleArrayFromLong(_)
+);
+from_field_array!(
+  Option<bool>,
+  Schema::NullableBooleanArray(_),
+  HeapArraySchema::HeapNullableBooleanArray(_)
+);
+from_field_array!(
+  Option<u8>,
+  Schema::NullableUnsignedByteArray { .. },
+  HeapArraySchema::HeapNullableUnsignedByteArray { .. }
+);
+from_field_array!(
+  Option<u16>,
+  Schema::NullableUnsignedShortArray { .. },
+  HeapArraySchema::HeapNullableUnsignedShortArray { .. }
+);
+from_field_array!(
+  Option<u32>,
+  Schema::NullableUnsignedIntArray { .. },
+  HeapArraySchema::HeapNullableUnsignedIntArray { .. }
+);
+from_field_array!(
+  Option<u64>,
+  Schema::NullableUnsignedLongArray { .. },
+  HeapArraySchema::HeapNullableUnsignedLongArray { .. }
+);
+from_field_array!(
+  Option<i8>,
+  Schema::NullableByteArray { .. },
+  HeapArraySchema::HeapNullableByteArray { .. }
+);
+from_field_array!(
+  Option<i16>,
+  Schema::NullableShortArray { .. },
+  HeapArraySchema::HeapNullableShortArray { .. }
+);
+from_field_array!(
+  Option<i32>,
+  Schema::NullableIntArray { .. },
+  HeapArraySchema::HeapNullableIntArray { .. }
+);
+from_field_array!(
+  Option<i64>,
+  Schema::NullableLongArray { .. },
+  HeapArraySchema::HeapNullableLongArray { .. }
+);
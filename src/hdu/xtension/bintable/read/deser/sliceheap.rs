@@ -332,6 +332,36 @@ impl<'de> Deserializer<'de> for DeserializerWithHeap<'de> {
     visitor.visit_cf64(ComplexF64::new(real, img))
   }
 
+  fn deserialize_complex_float_with_scale_offset<V>(
+    &mut self,
+    from: usize,
+    scale: f32,
+    offset: f32,
+    visitor: V,
+  ) -> Result<V::Value, Error>
+  where
+    V: Visitor,
+  {
+    let real = self.row.read_f32(from);
+    let img = self.row.read_f32(from + size_of::<f32>());
+    visitor.visit_cf32(ComplexF32::new(real * scale + offset, img * scale))
+  }
+
+  fn deserialize_complex_double_with_scale_offset<V>(
+    &mut self,
+    from: usize,
+    scale: f64,
+    offset: f64,
+    visitor: V,
+  ) -> Result<V::Value, Error>
+  where
+    V: Visitor,
+  {
+    let real = self.row.read_f64(from);
+    let img = self.row.read_f64(from + size_of::<f64>());
+    visitor.visit_cf64(ComplexF64::new(real * scale + offset, img * scale))
+  }
+
   fn deserialize_ascii_char<V>(&mut self, from: usize, visitor: V) -> Result<V::Value, Error>
   where
     V: Visitor,
@@ -773,6 +803,40 @@ impl<'de> Deserializer<'de> for DeserializerWithHeap<'de> {
     ))
   }
 
+  fn deserialize_complex_float_with_scale_offset_array<V>(
+    &mut self,
+    len: usize,
+    from: usize,
+    scale: f32,
+    offset: f32,
+    visitor: V,
+  ) -> Result<V::Value, Error>
+  where
+    V: Visitor,
+  {
+    visitor.visit_cf32_array(
+      ComplexF32Iterator::new(self.row.read_f32_array(from, len << 1))
+        .map(|v| ComplexF32::new(v.real() * scale + offset, v.img() * scale)),
+    )
+  }
+
+  fn deserialize_complex_double_with_scale_offset_array<V>(
+    &mut self,
+    len: usize,
+    from: usize,
+    scale: f64,
+    offset: f64,
+    visitor: V,
+  ) -> Result<V::Value, Error>
+  where
+    V: Visitor,
+  {
+    visitor.visit_cf64_array(
+      ComplexF64Iterator::new(self.row.read_f64_array(from, len << 1))
+        .map(|v| ComplexF64::new(v.real() * scale + offset, v.img() * scale)),
+    )
+  }
+
   fn deserialize_ascii_string_fixed_length<V>(
     &mut self,
     n_chars: usize,
@@ -1219,6 +1283,40 @@ impl<'de> Deserializer<'de> for DeserializerWithHeap<'de> {
     ))
   }
 
+  fn deserialize_complex_float_with_scale_offset_vararray_ptr32<V>(
+    &mut self,
+    from: usize,
+    scale: f32,
+    offset: f32,
+    visitor: V,
+  ) -> Result<V::Value, Error>
+  where
+    V: Visitor,
+  {
+    let (len, from) = self.get_len_offset_ptr32(from);
+    visitor.visit_cf32_array(
+      ComplexF32Iterator::new(self.heap.read_f32_array(from, len << 1))
+        .map(|v| ComplexF32::new(v.real() * scale + offset, v.img() * scale)),
+    )
+  }
+
+  fn deserialize_complex_double_with_scale_offset_vararray_ptr32<V>(
+    &mut self,
+    from: usize,
+    scale: f64,
+    offset: f64,
+    visitor: V,
+  ) -> Result<V::Value, Error>
+  where
+    V: Visitor,
+  {
+    let (len, from) = self.get_len_offset_ptr32(from);
+    visitor.visit_cf64_array(
+      ComplexF64Iterator::new(self.heap.read_f64_array(from, len << 1))
+        .map(|v| ComplexF64::new(v.real() * scale + offset, v.img() * scale)),
+    )
+  }
+
   fn deserialize_ascii_string_var_ptr32<V>(
     &mut self,
     from: usize,
@@ -1665,6 +1763,40 @@ impl<'de> Deserializer<'de> for DeserializerWithHeap<'de> {
     ))
   }
 
+  fn deserialize_complex_float_with_scale_offset_vararray_ptr64<V>(
+    &mut self,
+    from: usize,
+    scale: f32,
+    offset: f32,
+    visitor: V,
+  ) -> Result<V::Value, Error>
+  where
+    V: Visitor,
+  {
+    let (len, from) = self.get_len_offset_ptr64(from);
+    visitor.visit_cf32_array(
+      ComplexF32Iterator::new(self.heap.read_f32_array(from, len << 1))
+        .map(|v| ComplexF32::new(v.real() * scale + offset, v.img() * scale)),
+    )
+  }
+
+  fn deserialize_complex_double_with_scale_offset_vararray_ptr64<V>(
+    &mut self,
+    from: usize,
+    scale: f64,
+    offset: f64,
+    visitor: V,
+  ) -> Result<V::Value, Error>
+  where
+    V: Visitor,
+  {
+    let (len, from) = self.get_len_offset_ptr64(from);
+    visitor.visit_cf64_array(
+      ComplexF64Iterator::new(self.heap.read_f64_array(from, len << 1))
+        .map(|v| ComplexF64::new(v.real() * scale + offset, v.img() * scale)),
+    )
+  }
+
   fn deserialize_ascii_string_var_ptr64<V>(
     &mut self,
     from: usize,
@@ -2,7 +2,7 @@
 //! filter rows.
 
 use log::warn;
-use std::marker::PhantomData;
+use std::{borrow::Cow, marker::PhantomData};
 
 use expreval::{
   compile_expression,
@@ -12,12 +12,15 @@ use expreval::{
   table::{Row, Table},
 };
 
-use crate::hdu::xtension::bintable::{
-  read::{
-    deser::{DeserializeSeed, sliceheap::DeserializerWithHeap},
-    visitor::primitive::get_visitor,
+use crate::{
+  error::Error,
+  hdu::xtension::bintable::{
+    read::{
+      deser::{DeserializeSeed, sliceheap::DeserializerWithHeap},
+      visitor::{Visitor, primitive::get_visitor},
+    },
+    schema::{FieldSchema, Schema},
   },
-  schema::{FieldSchema, Schema},
 };
 
 pub struct TableSchema<'a, 'b> {
@@ -48,6 +51,10 @@ impl<'a, 'b> TableSchema<'a, 'b> {
   ) -> Result<impl Fn(&ExprEvalRow<'b>) -> bool + Sync + Send + 'b, String> {
     compile_bool_expr(expression, &self)
   }
+
+  pub fn compile_column_expr(&self, expression: String) -> Result<ColumnExpr<'b>, String> {
+    compile_column_expr(expression, self)
+  }
 }
 
 impl<'a, 'b> Table for TableSchema<'a, 'b> {
@@ -105,7 +112,7 @@ impl<'a, 'b> Table for TableSchema<'a, 'b> {
 
 pub struct ExprEvalRow<'a> {
   schema: &'a [FieldSchema],
-  row: &'a [u8],
+  row: Cow<'a, [u8]>,
   heap: &'a [u8],
 }
 impl<'a> ExprEvalRow<'a> {
@@ -114,7 +121,31 @@ impl<'a> ExprEvalRow<'a> {
   /// * `row`: the bytes of a single row in the main table
   /// * `heap`: all byte of the heap, if any.
   pub fn new(schema: &'a [FieldSchema], row: &'a [u8], heap: &'a [u8]) -> Self {
-    Self { schema, row, heap }
+    Self {
+      schema,
+      row: Cow::Borrowed(row),
+      heap,
+    }
+  }
+
+  /// Same as [ExprEvalRow::new], but storing a copy of the row bytes, set with
+  /// [ExprEvalRow::set_row].
+  /// Compiled expressions are bound to the lifetime of the rows they are evaluated on: copying the
+  /// rows allows to compile expressions once and to evaluate them on rows of successive chunks
+  /// (e.g. decompressed tiles) not living as long as the expressions.
+  pub fn new_owned(schema: &'a [FieldSchema], heap: &'a [u8]) -> Self {
+    Self {
+      schema,
+      row: Cow::Owned(Vec::new()),
+      heap,
+    }
+  }
+
+  /// Replace the row bytes by a copy of the given ones.
+  pub fn set_row(&mut self, row: &[u8]) {
+    let bytes = self.row.to_mut();
+    bytes.clear();
+    bytes.extend_from_slice(row);
   }
 }
 
@@ -126,7 +157,7 @@ impl<'a> Row for ExprEvalRow<'a> {
   fn field_bool(&self, col_index: u16) -> bool {
     self.schema[col_index as usize]
       .deserialize(
-        &mut DeserializerWithHeap::new(self.row, self.heap),
+        &mut DeserializerWithHeap::new(&self.row, self.heap),
         get_visitor::<Option<bool>>(),
       )
       .map(|opt_bool| match opt_bool {
@@ -143,7 +174,7 @@ impl<'a> Row for ExprEvalRow<'a> {
   fn field_char(&self, col_index: u16) -> char {
     self.schema[col_index as usize]
       .deserialize(
-        &mut DeserializerWithHeap::new(self.row, self.heap),
+        &mut DeserializerWithHeap::new(&self.row, self.heap),
         get_visitor::<u8>(),
       )
       .unwrap() as char
@@ -152,7 +183,7 @@ impl<'a> Row for ExprEvalRow<'a> {
   fn field_u8(&self, col_index: u16) -> u8 {
     self.schema[col_index as usize]
       .deserialize(
-        &mut DeserializerWithHeap::new(self.row, self.heap),
+        &mut DeserializerWithHeap::new(&self.row, self.heap),
         get_visitor::<u8>(),
       )
       .unwrap()
@@ -161,7 +192,7 @@ impl<'a> Row for ExprEvalRow<'a> {
   fn field_u16(&self, col_index: u16) -> u16 {
     self.schema[col_index as usize]
       .deserialize(
-        &mut DeserializerWithHeap::new(self.row, self.heap),
+        &mut DeserializerWithHeap::new(&self.row, self.heap),
         get_visitor::<u16>(),
       )
       .unwrap()
@@ -170,7 +201,7 @@ impl<'a> Row for ExprEvalRow<'a> {
   fn field_u32(&self, col_index: u16) -> u32 {
     self.schema[col_index as usize]
       .deserialize(
-        &mut DeserializerWithHeap::new(self.row, self.heap),
+        &mut DeserializerWithHeap::new(&self.row, self.heap),
         get_visitor::<u32>(),
       )
       .unwrap()
@@ -179,7 +210,7 @@ impl<'a> Row for ExprEvalRow<'a> {
   fn field_u64(&self, col_index: u16) -> u64 {
     self.schema[col_index as usize]
       .deserialize(
-        &mut DeserializerWithHeap::new(self.row, self.heap),
+        &mut DeserializerWithHeap::new(&self.row, self.heap),
         get_visitor::<u64>(),
       )
       .unwrap()
@@ -188,7 +219,7 @@ impl<'a> Row for ExprEvalRow<'a> {
   fn field_i8(&self, col_index: u16) -> i8 {
     self.schema[col_index as usize]
       .deserialize(
-        &mut DeserializerWithHeap::new(self.row, self.heap),
+        &mut DeserializerWithHeap::new(&self.row, self.heap),
         get_visitor::<i8>(),
       )
       .unwrap()
@@ -197,7 +228,7 @@ impl<'a> Row for ExprEvalRow<'a> {
   fn field_i16(&self, col_index: u16) -> i16 {
     self.schema[col_index as usize]
       .deserialize(
-        &mut DeserializerWithHeap::new(self.row, self.heap),
+        &mut DeserializerWithHeap::new(&self.row, self.heap),
         get_visitor::<i16>(),
       )
       .unwrap()
@@ -206,7 +237,7 @@ impl<'a> Row for ExprEvalRow<'a> {
   fn field_i32(&self, col_index: u16) -> i32 {
     self.schema[col_index as usize]
       .deserialize(
-        &mut DeserializerWithHeap::new(self.row, self.heap),
+        &mut DeserializerWithHeap::new(&self.row, self.heap),
         get_visitor::<i32>(),
       )
       .unwrap()
@@ -215,7 +246,7 @@ impl<'a> Row for ExprEvalRow<'a> {
   fn field_i64(&self, col_index: u16) -> i64 {
     self.schema[col_index as usize]
       .deserialize(
-        &mut DeserializerWithHeap::new(self.row, self.heap),
+        &mut DeserializerWithHeap::new(&self.row, self.heap),
         get_visitor::<i64>(),
       )
       .unwrap()
@@ -224,7 +255,7 @@ impl<'a> Row for ExprEvalRow<'a> {
   fn field_f32(&self, col_index: u16) -> f32 {
     self.schema[col_index as usize]
       .deserialize(
-        &mut DeserializerWithHeap::new(self.row, self.heap),
+        &mut DeserializerWithHeap::new(&self.row, self.heap),
         get_visitor::<f32>(),
       )
       .unwrap()
@@ -233,7 +264,7 @@ impl<'a> Row for ExprEvalRow<'a> {
   fn field_f64(&self, col_index: u16) -> f64 {
     self.schema[col_index as usize]
       .deserialize(
-        &mut DeserializerWithHeap::new(self.row, self.heap),
+        &mut DeserializerWithHeap::new(&self.row, self.heap),
         get_visitor::<f64>(),
       )
       .unwrap()
@@ -242,7 +273,7 @@ impl<'a> Row for ExprEvalRow<'a> {
   fn field_str(&self, col_index: u16) -> String {
     self.schema[col_index as usize]
       .deserialize(
-        &mut DeserializerWithHeap::new(self.row, self.heap),
+        &mut DeserializerWithHeap::new(&self.row, self.heap),
         get_visitor::<String>(),
       )
       .unwrap()
@@ -251,7 +282,7 @@ impl<'a> Row for ExprEvalRow<'a> {
   fn field_opt_bool(&self, col_index: u16) -> Option<bool> {
     self.schema[col_index as usize]
       .deserialize(
-        &mut DeserializerWithHeap::new(self.row, self.heap),
+        &mut DeserializerWithHeap::new(&self.row, self.heap),
         get_visitor::<Option<bool>>(),
       )
       .unwrap()
@@ -261,7 +292,7 @@ impl<'a> Row for ExprEvalRow<'a> {
     Some(
       self.schema[col_index as usize]
         .deserialize(
-          &mut DeserializerWithHeap::new(self.row, self.heap),
+          &mut DeserializerWithHeap::new(&self.row, self.heap),
           get_visitor::<u8>(),
         )
         .unwrap() as char,
@@ -271,7 +302,7 @@ impl<'a> Row for ExprEvalRow<'a> {
   fn field_opt_u8(&self, col_index: u16) -> Option<u8> {
     self.schema[col_index as usize]
       .deserialize(
-        &mut DeserializerWithHeap::new(self.row, self.heap),
+        &mut DeserializerWithHeap::new(&self.row, self.heap),
         get_visitor::<Option<u8>>(),
       )
       .unwrap()
@@ -280,7 +311,7 @@ impl<'a> Row for ExprEvalRow<'a> {
   fn field_opt_u16(&self, col_index: u16) -> Option<u16> {
     self.schema[col_index as usize]
       .deserialize(
-        &mut DeserializerWithHeap::new(self.row, self.heap),
+        &mut DeserializerWithHeap::new(&self.row, self.heap),
         get_visitor::<Option<u16>>(),
       )
       .unwrap()
@@ -289,7 +320,7 @@ impl<'a> Row for ExprEvalRow<'a> {
   fn field_opt_u32(&self, col_index: u16) -> Option<u32> {
     self.schema[col_index as usize]
       .deserialize(
-        &mut DeserializerWithHeap::new(self.row, self.heap),
+        &mut DeserializerWithHeap::new(&self.row, self.heap),
         get_visitor::<Option<u32>>(),
       )
       .unwrap()
@@ -298,7 +329,7 @@ impl<'a> Row for ExprEvalRow<'a> {
   fn field_opt_u64(&self, col_index: u16) -> Option<u64> {
     self.schema[col_index as usize]
       .deserialize(
-        &mut DeserializerWithHeap::new(self.row, self.heap),
+        &mut DeserializerWithHeap::new(&self.row, self.heap),
         get_visitor::<Option<u64>>(),
       )
       .unwrap()
@@ -307,7 +338,7 @@ impl<'a> Row for ExprEvalRow<'a> {
   fn field_opt_i8(&self, col_index: u16) -> Option<i8> {
     self.schema[col_index as usize]
       .deserialize(
-        &mut DeserializerWithHeap::new(self.row, self.heap),
+        &mut DeserializerWithHeap::new(&self.row, self.heap),
         get_visitor::<Option<i8>>(),
       )
       .unwrap()
@@ -316,7 +347,7 @@ impl<'a> Row for ExprEvalRow<'a> {
   fn field_opt_i16(&self, col_index: u16) -> Option<i16> {
     self.schema[col_index as usize]
       .deserialize(
-        &mut DeserializerWithHeap::new(self.row, self.heap),
+        &mut DeserializerWithHeap::new(&self.row, self.heap),
         get_visitor::<Option<i16>>(),
       )
       .unwrap()
@@ -325,7 +356,7 @@ impl<'a> Row for ExprEvalRow<'a> {
   fn field_opt_i32(&self, col_index: u16) -> Option<i32> {
     self.schema[col_index as usize]
       .deserialize(
-        &mut DeserializerWithHeap::new(self.row, self.heap),
+        &mut DeserializerWithHeap::new(&self.row, self.heap),
         get_visitor::<Option<i32>>(),
       )
       .unwrap()
@@ -334,7 +365,7 @@ impl<'a> Row for ExprEvalRow<'a> {
   fn field_opt_i64(&self, col_index: u16) -> Option<i64> {
     self.schema[col_index as usize]
       .deserialize(
-        &mut DeserializerWithHeap::new(self.row, self.heap),
+        &mut DeserializerWithHeap::new(&self.row, self.heap),
         get_visitor::<Option<i64>>(),
       )
       .unwrap()
@@ -343,7 +374,7 @@ impl<'a> Row for ExprEvalRow<'a> {
   fn field_opt_f32(&self, col_index: u16) -> Option<f32> {
     self.schema[col_index as usize]
       .deserialize(
-        &mut DeserializerWithHeap::new(self.row, self.heap),
+        &mut DeserializerWithHeap::new(&self.row, self.heap),
         get_visitor::<f32>(),
       )
       .map(|val| if val.is_finite() { Some(val) } else { None })
@@ -353,7 +384,7 @@ impl<'a> Row for ExprEvalRow<'a> {
   fn field_opt_f64(&self, col_index: u16) -> Option<f64> {
     self.schema[col_index as usize]
       .deserialize(
-        &mut DeserializerWithHeap::new(self.row, self.heap),
+        &mut DeserializerWithHeap::new(&self.row, self.heap),
         get_visitor::<f64>(),
       )
       .map(|val| if val.is_finite() { Some(val) } else { None })
@@ -364,7 +395,7 @@ impl<'a> Row for ExprEvalRow<'a> {
     Some(
       self.schema[col_index as usize]
         .deserialize(
-          &mut DeserializerWithHeap::new(self.row, self.heap),
+          &mut DeserializerWithHeap::new(&self.row, self.heap),
           get_visitor::<String>(),
         )
         .unwrap(),
@@ -391,6 +422,45 @@ fn compile_bool_expr<'a, 'b>(
   }
 }
 
+/// "Compiled" expression used to compute a new column.
+pub enum ColumnExpr<'b> {
+  F64(Box<dyn Fn(&ExprEvalRow<'b>) -> f64 + Sync + Send + 'b>),
+  Bool(Box<dyn Fn(&ExprEvalRow<'b>) -> bool + Sync + Send + 'b>),
+  Str(Box<dyn Fn(&ExprEvalRow<'b>) -> String + Sync + Send + 'b>),
+}
+impl<'b> ColumnExpr<'b> {
+  /// Evaluate the expression on the given row and visit the result.
+  pub fn visit<V: Visitor>(&self, row: &ExprEvalRow<'b>, visitor: V) -> Result<V::Value, Error> {
+    match self {
+      Self::F64(f) => visitor.visit_f64(f(row)),
+      Self::Bool(f) => visitor.visit_opt_bool(Some(f(row))),
+      Self::Str(f) => visitor.visit_ascii_string(f(row).as_str()),
+    }
+  }
+}
+
+/// "Compiled" an expression returning a f64, a boolean or a string value.
+fn compile_column_expr<'a, 'b>(
+  expression: String,
+  table_schema: &TableSchema<'a, 'b>,
+) -> Result<ColumnExpr<'b>, String> {
+  let node = if expression.starts_with('\"') && expression.ends_with('\"') {
+    compile_expression(&expression[1..expression.len() - 1], table_schema)
+  } else {
+    compile_expression(&expression, table_schema)
+  }?;
+  match node {
+    Node::F64(f) => Ok(ColumnExpr::F64(Box::new(move |row: &ExprEvalRow<'b>| f(row)))),
+    Node::Bool(f) => Ok(ColumnExpr::Bool(Box::new(move |row: &ExprEvalRow<'b>| f(row)))),
+    Node::Str(f) => Ok(ColumnExpr::Str(Box::new(move |row: &ExprEvalRow<'b>| f(row)))),
+    node => Err(format!(
+      "Wrong type returned by expression: {}. Expected: f64, bool or string. Actual: {:?}.",
+      &expression,
+      node.get_type()
+    )),
+  }
+}
+
 /*
 /// Decorate the given row iterator with a filter based on a boolean expression.
 fn filter<'a, 'b, T>(
@@ -1,4 +1,5 @@
 pub mod bytes;
+pub mod column;
 pub mod deser;
 #[cfg(feature = "expreval")]
 pub mod expreval;
@@ -0,0 +1,812 @@
+//! Visitor appending the visited fields to Arrow array builders, i.e. converting rows in columns.
+//!
+//! Mapping between `Schema` and Arrow `DataType`:
+//! * `Nullable*` -> nullable fields, NULL values being stored in validity bitmaps;
+//! * `Unsigned*` -> `UInt*`;
+//! * `*From*` (i.e. with `TSCALn` and/or `TZEROn`) -> `Float32` or `Float64`;
+//! * `ComplexFloat`, `ComplexDouble` -> `FixedSizeList` of 2 (real, imaginary) `Float32` or `Float64`;
+//! * `Bits` -> `FixedSizeBinary`;
+//! * `AsciiChar`, `AsciiString` -> `Utf8`;
+//! * fixed length arrays -> `FixedSizeList`, nested for multi-dimensional arrays (`TDIMn`), the
+//!   innermost list containing the elements of the first dimension;
+//! * variable length arrays -> `List`, except strings -> `Utf8`.
+
+use std::{collections::HashMap, sync::Arc};
+
+use arrow::{
+  array::{
+    ArrayBuilder, ArrayRef, BooleanBuilder, FixedSizeBinaryBuilder, FixedSizeListBuilder,
+    Float32Builder, Float64Builder, Int8Builder, Int16Builder, Int32Builder, Int64Builder,
+    ListBuilder, NullBuilder, RecordBatch, StringBuilder, UInt8Builder, UInt16Builder,
+    UInt32Builder, UInt64Builder, make_builder,
+  },
+  datatypes::{DataType, Field, Schema as ArrowSchema, SchemaRef},
+};
+
+use crate::{
+  error::{Error, new_custom},
+  hdu::xtension::bintable::{
+    field::{ComplexF32, ComplexF64},
+    header::BinTableHeaderWithColInfo,
+    read::deser::sliceheap::DeserializerWithHeap,
+    schema::{HeapArraySchema, RowSchema, Schema},
+  },
+};
+
+use super::{FieldVisitorProvider, RowVisitor, Visitor};
+
+type DynListBuilder = ListBuilder<Box<dyn ArrayBuilder>>;
+type DynFixedSizeListBuilder = FixedSizeListBuilder<Box<dyn ArrayBuilder>>;
+
+/// Returns the Arrow schema of a table having the given column names and row schema.
+pub fn arrow_schema(col_names: &[String], row_schema: &RowSchema) -> ArrowSchema {
+  ArrowSchema::new(
+    col_names
+      .iter()
+      .zip(row_schema.fields_schemas())
+      .map(|(name, field_schema)| arrow_field(name, &field_schema.schema))
+      .collect::<Vec<Field>>(),
+  )
+}
+
+/// Returns the Arrow field of the column of given name and schema.
+pub fn arrow_field(name: &str, schema: &Schema) -> Field {
+  Field::new(name, arrow_data_type(schema), is_nullable(schema))
+}
+
+/// Returns the Arrow schema of the given BINTABLE, the values of the `TUNITn`, `TUCDn` and
+/// `TCOMMn` keywords being stored in the fields metadata (keys `TUNIT`, `TUCD` and `TCOMM`).
+pub fn bintable_arrow_schema(
+  header: &BinTableHeaderWithColInfo,
+  row_schema: &RowSchema,
+) -> ArrowSchema {
+  ArrowSchema::new(
+    header
+      .build_col_names()
+      .iter()
+      .zip(header.cols())
+      .zip(row_schema.fields_schemas())
+      .map(|((name, col_header), field_schema)| {
+        let metadata: HashMap<String, String> = [
+          ("TUNIT", col_header.unit()),
+          ("TUCD", col_header.ucd()),
+          ("TCOMM", col_header.description()),
+        ]
+        .into_iter()
+        .filter_map(|(key, val)| val.map(|val| (String::from(key), String::from(val))))
+        .collect();
+        arrow_field(name, &field_schema.schema).with_metadata(metadata)
+      })
+      .collect::<Vec<Field>>(),
+  )
+}
+
+/// Returns `true` if the given schema may lead to NULL values.
+fn is_nullable(schema: &Schema) -> bool {
+  matches!(
+    schema,
+    Schema::Empty(_)
+      | Schema::NullableBoolean
+      | Schema::NullableByte { .. }
+      | Schema::NullableShort { .. }
+      | Schema::NullableInt { .. }
+      | Schema::NullableLong { .. }
+      | Schema::NullableUnsignedByte { .. }
+      | Schema::NullableUnsignedShort { .. }
+      | Schema::NullableUnsignedInt { .. }
+      | Schema::NullableUnsignedLong { .. }
+      | Schema::AsciiChar
+  )
+}
+
+/// Returns the Arrow data type corresponding to the given schema.
+pub fn arrow_data_type(schema: &Schema) -> DataType {
+  let data_type = flat_arrow_data_type(schema);
+  match (schema.shape(), data_type) {
+    // Multi-dimensional arrays
+    (Some(shape), DataType::FixedSizeList(item, _)) => shape[1..].iter().fold(
+      fixed_size_list(item.data_type().clone(), item.is_nullable(), shape[0]),
+      |data_type, len| fixed_size_list(data_type, false, *len),
+    ),
+    // Arrays of strings
+    (Some(shape), DataType::Utf8) => shape[1..].iter().fold(DataType::Utf8, |data_type, len| {
+      fixed_size_list(data_type, false, *len)
+    }),
+    (_, data_type) => data_type,
+  }
+}
+
+/// Returns the Arrow data type corresponding to the given schema, not taking into account the
+/// dimensions of multi-dimensional arrays.
+fn flat_arrow_data_type(schema: &Schema) -> DataType {
+  match schema {
+    Schema::Empty(_) => DataType::Null,
+    Schema::NullableBoolean => DataType::Boolean,
+    Schema::Bits { n_bits } => DataType::FixedSizeBinary(n_bits.div_ceil(8) as i32),
+    Schema::Byte | Schema::NullableByte { .. } => DataType::Int8,
+    Schema::Short | Schema::NullableShort { .. } => DataType::Int16,
+    Schema::Int | Schema::NullableInt { .. } => DataType::Int32,
+    Schema::Long | Schema::NullableLong { .. } => DataType::Int64,
+    Schema::UnsignedByte | Schema::NullableUnsignedByte { .. } => DataType::UInt8,
+    Schema::UnsignedShort | Schema::NullableUnsignedShort { .. } => DataType::UInt16,
+    Schema::UnsignedInt | Schema::NullableUnsignedInt { .. } => DataType::UInt32,
+    Schema::UnsignedLong | Schema::NullableUnsignedLong { .. } => DataType::UInt64,
+    Schema::Float
+    | Schema::FloatFromFloat(_)
+    | Schema::FloatFromByte(_)
+    | Schema::FloatFromShort(_) => DataType::Float32,
+    Schema::Double
+    | Schema::DoubleFromDouble(_)
+    | Schema::DoubleFromInt(_)
+    | Schema::DoubleFromLong(_) => DataType::Float64,
+    Schema::ComplexFloat | Schema::ComplexFloatFromFloat(_) => complex(DataType::Float32),
+    Schema::ComplexDouble | Schema::ComplexDoubleFromDouble(_) => complex(DataType::Float64),
+    Schema::AsciiChar | Schema::AsciiString(_) => DataType::Utf8,
+    Schema::NullableBooleanArray(p) => fixed_size_list(DataType::Boolean, true, p.get_len()),
+    Schema::ByteArray(p) => fixed_size_list(DataType::Int8, false, p.get_len()),
+    Schema::ShortArray(p) => fixed_size_list(DataType::Int16, false, p.get_len()),
+    Schema::IntArray(p) => fixed_size_list(DataType::Int32, false, p.get_len()),
+    Schema::LongArray(p) => fixed_size_list(DataType::Int64, false, p.get_len()),
+    Schema::NullableByteArray { null: _, p } => fixed_size_list(DataType::Int8, true, p.get_len()),
+    Schema::NullableShortArray { null: _, p } => {
+      fixed_size_list(DataType::Int16, true, p.get_len())
+    }
+    Schema::NullableIntArray { null: _, p } => fixed_size_list(DataType::Int32, true, p.get_len()),
+    Schema::NullableLongArray { null: _, p } => fixed_size_list(DataType::Int64, true, p.get_len()),
+    Schema::UnsignedByteArray(p) => fixed_size_list(DataType::UInt8, false, p.get_len()),
+    Schema::UnsignedShortArray(p) => fixed_size_list(DataType::UInt16, false, p.get_len()),
+    Schema::UnsignedIntArray(p) => fixed_size_list(DataType::UInt32, false, p.get_len()),
+    Schema::UnsignedLongArray(p) => fixed_size_list(DataType::UInt64, false, p.get_len()),
+    Schema::NullableUnsignedByteArray { null: _, p } => {
+      fixed_size_list(DataType::UInt8, true, p.get_len())
+    }
+    Schema::NullableUnsignedShortArray { null: _, p } => {
+      fixed_size_list(DataType::UInt16, true, p.get_len())
+    }
+    Schema::NullableUnsignedIntArray { null: _, p } => {
+      fixed_size_list(DataType::UInt32, true, p.get_len())
+    }
+    Schema::NullableUnsignedLongArray { null: _, p } => {
+      fixed_size_list(DataType::UInt64, true, p.get_len())
+    }
+    Schema::FloatArray(p) => fixed_size_list(DataType::Float32, false, p.get_len()),
+    Schema::FloatArrayFromFloat(p)
+    | Schema::FloatArrayFromBytes(p)
+    | Schema::FloatArrayFromShort(p) => fixed_size_list(DataType::Float32, false, p.get_len()),
+    Schema::DoubleArray(p) => fixed_size_list(DataType::Float64, false, p.get_len()),
+    Schema::DoubleArrayFromDouble(p)
+    | Schema::DoubleArrayFromInt(p)
+    | Schema::DoubleArrayFromLong(p) => fixed_size_list(DataType::Float64, false, p.get_len()),
+    Schema::ComplexFloatArray(p) => fixed_size_list(complex(DataType::Float32), false, p.get_len()),
+    Schema::ComplexFloatArrayFromFloat(p) => {
+      fixed_size_list(complex(DataType::Float32), false, p.get_len())
+    }
+    Schema::ComplexDoubleArray(p) => {
+      fixed_size_list(complex(DataType::Float64), false, p.get_len())
+    }
+    Schema::ComplexDoubleArrayFromDouble(p) => {
+      fixed_size_list(complex(DataType::Float64), false, p.get_len())
+    }
+    Schema::HeapArrayPtr32(has) | Schema::HeapArrayPtr64(has) => heap_array_data_type(has),
+  }
+}
+
+fn heap_array_data_type(heap_array_schema: &HeapArraySchema) -> DataType {
+  match heap_array_schema {
+    HeapArraySchema::HeapNullableBooleanArray(_) => list(DataType::Boolean, true),
+    HeapArraySchema::HeapByteArray(_) => list(DataType::Int8, false),
+    HeapArraySchema::HeapShortArray(_) => list(DataType::Int16, false),
+    HeapArraySchema::HeapIntArray(_) => list(DataType::Int32, false),
+    HeapArraySchema::HeapLongArray(_) => list(DataType::Int64, false),
+    HeapArraySchema::HeapNullableByteArray { .. } => list(DataType::Int8, true),
+    HeapArraySchema::HeapNullableShortArray { .. } => list(DataType::Int16, true),
+    HeapArraySchema::HeapNullableIntArray { .. } => list(DataType::Int32, true),
+    HeapArraySchema::HeapNullableLongArray { .. } => list(DataType::Int64, true),
+    HeapArraySchema::HeapUnsignedByteArray(_) => list(DataType::UInt8, false),
+    HeapArraySchema::HeapUnsignedShortArray(_) => list(DataType::UInt16, false),
+    HeapArraySchema::HeapUnsignedIntArray(_) => list(DataType::UInt32, false),
+    HeapArraySchema::HeapUnsignedLongArray(_) => list(DataType::UInt64, false),
+    HeapArraySchema::HeapNullableUnsignedByteArray { .. } => list(DataType::UInt8, true),
+    HeapArraySchema::HeapNullableUnsignedShortArray { .. } => list(DataType::UInt16, true),
+    HeapArraySchema::HeapNullableUnsignedIntArray { .. } => list(DataType::UInt32, true),
+    HeapArraySchema::HeapNullableUnsignedLongArray { .. } => list(DataType::UInt64, true),
+    HeapArraySchema::HeapFloatArray(_)
+    | HeapArraySchema::HeapFloatArrayFromFloat(_)
+    | HeapArraySchema::HeapFloatArrayFromByte(_)
+    | HeapArraySchema::HeapFloatArrayFromShort(_) => list(DataType::Float32, false),
+    HeapArraySchema::HeapDoubleArray(_)
+    | HeapArraySchema::HeapDoubleArrayFromDouble(_)
+    | HeapArraySchema::HeapDoubleArrayFromInt(_)
+    | HeapArraySchema::HeapDoubleArrayFromLong(_) => list(DataType::Float64, false),
+    HeapArraySchema::HeapComplexFloatArray(_)
+    | HeapArraySchema::HeapComplexFloatArrayFromFloat(_) => list(complex(DataType::Float32), false),
+    HeapArraySchema::HeapComplexDoubleArray(_)
+    | HeapArraySchema::HeapComplexDoubleArrayFromDouble(_) => {
+      list(complex(DataType::Float64), false)
+    }
+    HeapArraySchema::HeapAsciiString(_) => DataType::Utf8,
+  }
+}
+
+/// A complex is stored as a list of 2 elements: the real and the imaginary parts.
+fn complex(component: DataType) -> DataType {
+  fixed_size_list(component, false, 2)
+}
+
+fn fixed_size_list(item: DataType, nullable_item: bool, len: usize) -> DataType {
+  DataType::FixedSizeList(
+    Arc::new(Field::new_list_field(item, nullable_item)),
+    len as i32,
+  )
+}
+
+fn list(item: DataType, nullable_item: bool) -> DataType {
+  DataType::List(Arc::new(Field::new_list_field(item, nullable_item)))
+}
+
+/// Downcast the given dynamic builder into a builder of the given type.
+fn downcast<B: ArrayBuilder>(builder: &mut dyn ArrayBuilder) -> Result<&mut B, Error> {
+  builder.as_any_mut().downcast_mut::<B>().ok_or_else(|| {
+    new_custom(format!(
+      "Wrong Arrow builder: {} expected.",
+      std::any::type_name::<B>()
+    ))
+  })
+}
+
+/// Iterates over the rows of a BINTABLE, returning them in Arrow `RecordBatch`es containing
+/// (at most) a given number of rows.
+pub struct RecordBatchIter<'u> {
+  schema: SchemaRef,
+  row_schema: RowSchema,
+  row_byte_size: usize,
+  /// Number of rows per record batch.
+  n_rows: usize,
+  /// Part of the main table not yet read.
+  main: &'u [u8],
+  heap: &'u [u8],
+}
+
+impl<'u> RecordBatchIter<'u> {
+  /// # Params
+  /// * `header`: the BINTABLE header
+  /// * `data`: the BINTABLE data part (main table, gap and heap)
+  /// * `n_rows`: the number of rows per record batch
+  pub fn new(header: &BinTableHeaderWithColInfo, data: &'u [u8], n_rows: usize) -> Self {
+    let row_schema = header.build_row_schema();
+    let (main, rem) = data.split_at(header.main_table_byte_size());
+    let heap = &rem[header.gap_byte_size()..];
+    Self {
+      schema: Arc::new(bintable_arrow_schema(header, &row_schema)),
+      row_schema,
+      row_byte_size: header.row_byte_size(),
+      n_rows: n_rows.max(1),
+      main,
+      heap,
+    }
+  }
+
+  /// Returns the Arrow schema of the record batches.
+  pub fn schema(&self) -> SchemaRef {
+    self.schema.clone()
+  }
+}
+
+impl<'u> Iterator for RecordBatchIter<'u> {
+  type Item = Result<RecordBatch, Error>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.main.is_empty() || self.row_byte_size == 0 {
+      return None;
+    }
+    let (rows, rem) = self
+      .main
+      .split_at((self.row_byte_size * self.n_rows).min(self.main.len()));
+    self.main = rem;
+    let mut visitor = ArrowVisitor::new(&self.row_schema, rows.len() / self.row_byte_size);
+    Some(
+      rows
+        .chunks(self.row_byte_size)
+        .try_for_each(|row| {
+          let mut de = DeserializerWithHeap::new(row, self.heap);
+          self
+            .row_schema
+            .deserialize(&mut de, &mut visitor, ArrowRowVisitor)
+        })
+        .and_then(|()| {
+          RecordBatch::try_new(self.schema.clone(), visitor.finish())
+            .map_err(|e| new_custom(format!("Error building Arrow record batch: {}", e)))
+        }),
+    )
+  }
+}
+
+pub struct ArrowRowVisitor;
+
+impl RowVisitor for ArrowRowVisitor {
+  type Value = ();
+  type FieldValue = ();
+
+  fn visit_row<I>(self, fields_it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = Result<Self::FieldValue, Error>>,
+  {
+    for res in fields_it {
+      res?;
+    }
+    Ok(())
+  }
+}
+
+/// Holds one Arrow array builder per column, the visited fields being appended to the builders.
+pub struct ArrowVisitor {
+  builders: Vec<Box<dyn ArrayBuilder>>,
+  /// Shape of each column, for multi-dimensional arrays.
+  shapes: Vec<Option<Vec<usize>>>,
+  /// Index of the column of the next visited field.
+  icol: usize,
+}
+
+impl ArrowVisitor {
+  /// # Params
+  /// * `row_schema`: the schema of the visited rows
+  /// * `capacity`: the expected number of rows
+  pub fn new(row_schema: &RowSchema, capacity: usize) -> Self {
+    Self {
+      builders: row_schema
+        .fields_schemas()
+        .iter()
+        .map(|field_schema| make_builder(&arrow_data_type(&field_schema.schema), capacity))
+        .collect(),
+      shapes: row_schema
+        .fields_schemas()
+        .iter()
+        .map(|field_schema| field_schema.schema.shape().map(|shape| shape.to_vec()))
+        .collect(),
+      icol: 0,
+    }
+  }
+
+  /// Number of rows appended to the builders since the last call to `finish`.
+  pub fn n_rows(&self) -> usize {
+    self.builders.first().map(|b| b.len()).unwrap_or(0)
+  }
+
+  /// Returns the arrays built from the rows visited so far, and reset the builders.
+  pub fn finish(&mut self) -> Vec<ArrayRef> {
+    self.icol = 0;
+    self.builders.iter_mut().map(|b| b.finish()).collect()
+  }
+}
+
+impl FieldVisitorProvider for ArrowVisitor {
+  type FieldValue = ();
+  type FieldVisitor<'v>
+    = ColumnVisitor<'v>
+  where
+    Self: 'v;
+
+  fn field_visitor(&mut self) -> Self::FieldVisitor<'_> {
+    // Starts a new row
+    if self.icol == self.builders.len() {
+      self.icol = 0;
+    }
+    let builder = &mut self.builders[self.icol];
+    let shape = self.shapes[self.icol].as_deref();
+    self.icol += 1;
+    ColumnVisitor { builder, shape }
+  }
+}
+
+/// Append the visited field to the builder of a column.
+pub struct ColumnVisitor<'v> {
+  builder: &'v mut Box<dyn ArrayBuilder>,
+  /// Shape of the column, for multi-dimensional arrays.
+  shape: Option<&'v [usize]>,
+}
+
+impl<'v> ColumnVisitor<'v> {
+  fn builder<B: ArrayBuilder>(self) -> Result<&'v mut B, Error> {
+    downcast(self.builder.as_mut())
+  }
+
+  /// Append the values of a fixed (`FixedSizeList`) or variable (`List`) length array.
+  fn append_array<B, T, I, F>(self, it: I, append: F) -> Result<(), Error>
+  where
+    B: ArrayBuilder,
+    I: Iterator<Item = T>,
+    F: Fn(&mut B, T) -> Result<(), Error>,
+  {
+    if let Some(shape) = self.shape {
+      return append_nd_array(self.builder.as_mut(), shape, &mut it.into_iter(), &append);
+    }
+    let builder = self.builder.as_any_mut();
+    if let Some(list_builder) = builder.downcast_mut::<DynFixedSizeListBuilder>() {
+      let values = downcast::<B>(list_builder.values().as_mut())?;
+      for v in it {
+        append(values, v)?;
+      }
+      list_builder.append(true);
+      Ok(())
+    } else if let Some(list_builder) = builder.downcast_mut::<DynListBuilder>() {
+      let values = downcast::<B>(list_builder.values().as_mut())?;
+      for v in it {
+        append(values, v)?;
+      }
+      list_builder.append(true);
+      Ok(())
+    } else {
+      Err(new_custom("Wrong Arrow builder: list builder expected."))
+    }
+  }
+}
+
+/// Append the values of a multi-dimensional array of given shape in nested `FixedSizeList`s.
+fn append_nd_array<B, T, I, F>(
+  builder: &mut dyn ArrayBuilder,
+  shape: &[usize],
+  it: &mut I,
+  append: &F,
+) -> Result<(), Error>
+where
+  B: ArrayBuilder,
+  I: Iterator<Item = T>,
+  F: Fn(&mut B, T) -> Result<(), Error>,
+{
+  let list_builder = downcast::<DynFixedSizeListBuilder>(builder)?;
+  match shape.split_last() {
+    Some((len, dims)) if !dims.is_empty() => {
+      for _ in 0..*len {
+        append_nd_array(list_builder.values().as_mut(), dims, it, append)?;
+      }
+    }
+    _ => {
+      let values = downcast::<B>(list_builder.values().as_mut())?;
+      for v in it.by_ref().take(shape.first().copied().unwrap_or(0)) {
+        append(values, v)?;
+      }
+    }
+  }
+  list_builder.append(true);
+  Ok(())
+}
+
+fn append_cf32(builder: &mut DynFixedSizeListBuilder, v: ComplexF32) -> Result<(), Error> {
+  let values = downcast::<Float32Builder>(builder.values().as_mut())?;
+  values.append_value(v.real());
+  values.append_value(v.img());
+  builder.append(true);
+  Ok(())
+}
+
+fn append_cf64(builder: &mut DynFixedSizeListBuilder, v: ComplexF64) -> Result<(), Error> {
+  let values = downcast::<Float64Builder>(builder.values().as_mut())?;
+  values.append_value(v.real());
+  values.append_value(v.img());
+  builder.append(true);
+  Ok(())
+}
+
+impl<'v> Visitor for ColumnVisitor<'v> {
+  type Value = ();
+
+  fn expecting(&self) -> &str {
+    "Unreachable for Arrow visitor"
+  }
+
+  fn visit_empty(self) -> Result<Self::Value, Error> {
+    self.builder::<NullBuilder>().map(|b| b.append_null())
+  }
+
+  fn visit_opt_bool(self, v: Option<bool>) -> Result<Self::Value, Error> {
+    self.builder::<BooleanBuilder>().map(|b| b.append_option(v))
+  }
+
+  fn visit_ascii_char(self, v: u8) -> Result<Self::Value, Error> {
+    self.builder::<StringBuilder>().map(|b| match v {
+      b'\0' => b.append_null(),
+      _ => b.append_value(char::from(v).encode_utf8(&mut [0_u8; 4])),
+    })
+  }
+
+  fn visit_i8(self, v: i8) -> Result<Self::Value, Error> {
+    self.builder::<Int8Builder>().map(|b| b.append_value(v))
+  }
+  fn visit_i16(self, v: i16) -> Result<Self::Value, Error> {
+    self.builder::<Int16Builder>().map(|b| b.append_value(v))
+  }
+  fn visit_i32(self, v: i32) -> Result<Self::Value, Error> {
+    self.builder::<Int32Builder>().map(|b| b.append_value(v))
+  }
+  fn visit_i64(self, v: i64) -> Result<Self::Value, Error> {
+    self.builder::<Int64Builder>().map(|b| b.append_value(v))
+  }
+
+  fn visit_opt_i8(self, v: Option<i8>) -> Result<Self::Value, Error> {
+    self.builder::<Int8Builder>().map(|b| b.append_option(v))
+  }
+  fn visit_opt_i16(self, v: Option<i16>) -> Result<Self::Value, Error> {
+    self.builder::<Int16Builder>().map(|b| b.append_option(v))
+  }
+  fn visit_opt_i32(self, v: Option<i32>) -> Result<Self::Value, Error> {
+    self.builder::<Int32Builder>().map(|b| b.append_option(v))
+  }
+  fn visit_opt_i64(self, v: Option<i64>) -> Result<Self::Value, Error> {
+    self.builder::<Int64Builder>().map(|b| b.append_option(v))
+  }
+
+  fn visit_u8(self, v: u8) -> Result<Self::Value, Error> {
+    self.builder::<UInt8Builder>().map(|b| b.append_value(v))
+  }
+  fn visit_u16(self, v: u16) -> Result<Self::Value, Error> {
+    self.builder::<UInt16Builder>().map(|b| b.append_value(v))
+  }
+  fn visit_u32(self, v: u32) -> Result<Self::Value, Error> {
+    self.builder::<UInt32Builder>().map(|b| b.append_value(v))
+  }
+  fn visit_u64(self, v: u64) -> Result<Self::Value, Error> {
+    self.builder::<UInt64Builder>().map(|b| b.append_value(v))
+  }
+
+  fn visit_opt_u8(self, v: Option<u8>) -> Result<Self::Value, Error> {
+    self.builder::<UInt8Builder>().map(|b| b.append_option(v))
+  }
+  fn visit_opt_u16(self, v: Option<u16>) -> Result<Self::Value, Error> {
+    self.builder::<UInt16Builder>().map(|b| b.append_option(v))
+  }
+  fn visit_opt_u32(self, v: Option<u32>) -> Result<Self::Value, Error> {
+    self.builder::<UInt32Builder>().map(|b| b.append_option(v))
+  }
+  fn visit_opt_u64(self, v: Option<u64>) -> Result<Self::Value, Error> {
+    self.builder::<UInt64Builder>().map(|b| b.append_option(v))
+  }
+
+  fn visit_f32(self, v: f32) -> Result<Self::Value, Error> {
+    self.builder::<Float32Builder>().map(|b| b.append_value(v))
+  }
+
+  fn visit_f64(self, v: f64) -> Result<Self::Value, Error> {
+    self.builder::<Float64Builder>().map(|b| b.append_value(v))
+  }
+
+  fn visit_cf32(self, v: ComplexF32) -> Result<Self::Value, Error> {
+    self
+      .builder::<DynFixedSizeListBuilder>()
+      .and_then(|b| append_cf32(b, v))
+  }
+
+  fn visit_cf64(self, v: ComplexF64) -> Result<Self::Value, Error> {
+    self
+      .builder::<DynFixedSizeListBuilder>()
+      .and_then(|b| append_cf64(b, v))
+  }
+
+  fn visit_bit_array(self, v: &[u8]) -> Result<Self::Value, Error> {
+    self.builder::<FixedSizeBinaryBuilder>().and_then(|b| {
+      b.append_value(v)
+        .map_err(|e| new_custom(format!("Error appending bit array: {}", e)))
+    })
+  }
+
+  fn visit_ascii_string(self, v: &str) -> Result<Self::Value, Error> {
+    if let Some(shape) = self.shape {
+      // Array of strings, the first dimension being the length of each string
+      return append_nd_array(
+        self.builder.as_mut(),
+        &shape[1..],
+        &mut v.as_bytes().chunks(shape[0]),
+        &|b: &mut StringBuilder, s: &[u8]| {
+          b.append_value(String::from_utf8_lossy(s).trim_end());
+          Ok(())
+        },
+      );
+    }
+    self
+      .builder::<StringBuilder>()
+      .map(|b| b.append_value(v.trim_end()))
+  }
+
+  fn visit_opt_bool_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = Option<bool>>,
+  {
+    self.append_array(it, |b: &mut BooleanBuilder, v| {
+      b.append_option(v);
+      Ok(())
+    })
+  }
+
+  fn visit_i8_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = i8>,
+  {
+    self.append_array(it, |b: &mut Int8Builder, v| {
+      b.append_value(v);
+      Ok(())
+    })
+  }
+
+  fn visit_i16_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = i16>,
+  {
+    self.append_array(it, |b: &mut Int16Builder, v| {
+      b.append_value(v);
+      Ok(())
+    })
+  }
+
+  fn visit_i32_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = i32>,
+  {
+    self.append_array(it, |b: &mut Int32Builder, v| {
+      b.append_value(v);
+      Ok(())
+    })
+  }
+
+  fn visit_i64_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = i64>,
+  {
+    self.append_array(it, |b: &mut Int64Builder, v| {
+      b.append_value(v);
+      Ok(())
+    })
+  }
+
+  fn visit_opt_i8_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = Option<i8>>,
+  {
+    self.append_array(it, |b: &mut Int8Builder, v| {
+      b.append_option(v);
+      Ok(())
+    })
+  }
+
+  fn visit_opt_i16_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = Option<i16>>,
+  {
+    self.append_array(it, |b: &mut Int16Builder, v| {
+      b.append_option(v);
+      Ok(())
+    })
+  }
+
+  fn visit_opt_i32_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = Option<i32>>,
+  {
+    self.append_array(it, |b: &mut Int32Builder, v| {
+      b.append_option(v);
+      Ok(())
+    })
+  }
+
+  fn visit_opt_i64_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = Option<i64>>,
+  {
+    self.append_array(it, |b: &mut Int64Builder, v| {
+      b.append_option(v);
+      Ok(())
+    })
+  }
+
+  fn visit_u8_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = u8>,
+  {
+    self.append_array(it, |b: &mut UInt8Builder, v| {
+      b.append_value(v);
+      Ok(())
+    })
+  }
+
+  fn visit_u16_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = u16>,
+  {
+    self.append_array(it, |b: &mut UInt16Builder, v| {
+      b.append_value(v);
+      Ok(())
+    })
+  }
+
+  fn visit_u32_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = u32>,
+  {
+    self.append_array(it, |b: &mut UInt32Builder, v| {
+      b.append_value(v);
+      Ok(())
+    })
+  }
+
+  fn visit_u64_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = u64>,
+  {
+    self.append_array(it, |b: &mut UInt64Builder, v| {
+      b.append_value(v);
+      Ok(())
+    })
+  }
+
+  fn visit_opt_u8_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = Option<u8>>,
+  {
+    self.append_array(it, |b: &mut UInt8Builder, v| {
+      b.append_option(v);
+      Ok(())
+    })
+  }
+
+  fn visit_opt_u16_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = Option<u16>>,
+  {
+    self.append_array(it, |b: &mut UInt16Builder, v| {
+      b.append_option(v);
+      Ok(())
+    })
+  }
+
+  fn visit_opt_u32_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = Option<u32>>,
+  {
+    self.append_array(it, |b: &mut UInt32Builder, v| {
+      b.append_option(v);
+      Ok(())
+    })
+  }
+
+  fn visit_opt_u64_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = Option<u64>>,
+  {
+    self.append_array(it, |b: &mut UInt64Builder, v| {
+      b.append_option(v);
+      Ok(())
+    })
+  }
+
+  fn visit_f32_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = f32>,
+  {
+    self.append_array(it, |b: &mut Float32Builder, v| {
+      b.append_value(v);
+      Ok(())
+    })
+  }
+
+  fn visit_f64_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = f64>,
+  {
+    self.append_array(it, |b: &mut Float64Builder, v| {
+      b.append_value(v);
+      Ok(())
+    })
+  }
+
+  fn visit_cf32_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = ComplexF32>,
+  {
+    self.append_array(it, append_cf32)
+  }
+
+  fn visit_cf64_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = ComplexF64>,
+  {
+    self.append_array(it, append_cf64)
+  }
+}
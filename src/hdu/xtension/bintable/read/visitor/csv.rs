@@ -1,6 +1,7 @@
 use std::{fmt::Display, io::Write};
 
 use crate::{
+  common::keywords::tables::bintable::tdisp::TDispValue,
   error::{new_io_err, new_unsupported_by_visitor, Error},
   hdu::xtension::bintable::field::{ComplexF32, ComplexF64},
 };
@@ -28,93 +29,193 @@ pub struct CSVVisitor<'a, W: Write> {
   writer: &'a mut W,
   sep: u8,
   field_sep: u8,
+  /// Shape of the multi-dimensional array being visited (empty if none).
+  shape: Vec<usize>,
+  /// Display format (`TDISPn`) of each column (empty if values are written using Rust defaults).
+  tdisps: Vec<Option<TDispValue>>,
+  /// Index of the next column to be written in the current line.
+  next_col: usize,
 }
 impl<'a, W: Write> CSVVisitor<'a, W> {
   pub fn new(writer: &'a mut W) -> Self {
     Self::new_custom(writer, b',')
   }
   pub fn new_custom(writer: &'a mut W, field_sep: u8) -> Self {
-    Self { writer, sep: b'\n', field_sep }
+    Self {
+      writer,
+      sep: b'\n',
+      field_sep,
+      shape: Vec::new(),
+      tdisps: Vec::new(),
+      next_col: 0,
+    }
+  }
+
+  /// Format the values of the columns according to the given display formats (`TDISPn`), in the
+  /// order of the written columns. Columns having no display format (`None`, or an index larger
+  /// than the number of provided formats) are written using Rust defaults.
+  /// Contrary to the fixed width representation, values are neither padded nor replaced by `*`.
+  pub fn set_tdisps(&mut self, tdisps: Vec<Option<TDispValue>>) {
+    self.tdisps = tdisps;
   }
 
   pub fn starts_new_line(&mut self) {
     self.sep = b'\n';
+    self.next_col = 0;
+  }
+
+  /// Display format of the next column to be written (to be called before `write_sep`).
+  fn next_tdisp(&self) -> Option<TDispValue> {
+    self.tdisps.get(self.next_col).copied().flatten()
   }
 
   fn write_sep(&mut self) -> Result<(), std::io::Error> {
     let res = self.writer.write_all(&[self.sep]);
     self.sep = self.field_sep;
+    self.next_col += 1;
     res
   }
 
-  fn write<V: Display>(&mut self, v: V) -> Result<(), Error> {
-    self
-      .write_sep()
-      .and_then(|()| write!(self.writer, "{}", v))
-      .map_err(new_io_err)
+  fn write<V: Display + TDispFormat>(&mut self, v: V) -> Result<(), Error> {
+    self.write_opt(Some(v))
   }
 
-  fn write_opt<V: Display>(&mut self, v: Option<V>) -> Result<(), Error> {
+  fn write_opt<V: Display + TDispFormat>(&mut self, v: Option<V>) -> Result<(), Error> {
+    let tdisp = self.next_tdisp();
     self
       .write_sep()
       .and_then(|()| match v {
-        Some(v) => write!(self.writer, "{}", v),
+        Some(v) => write_value(self.writer, v, tdisp.as_ref()),
         None => Ok(()),
       })
       .map_err(new_io_err)
   }
 
-  fn write_array<V, I>(&mut self, mut it: I) -> Result<(), Error>
+  fn write_array<V, I>(&mut self, it: I) -> Result<(), Error>
   where
-    V: Display,
+    V: Display + TDispFormat,
     I: Iterator<Item = V>,
   {
-    self
-      .write_sep()
-      .and_then(|()| {
-        if let Some(v) = it.next() {
-          write!(self.writer, "\"[{}", v)?;
-          for v in it {
-            write!(self.writer, ", {}", v)?;
-          }
-          self.writer.write_all(b"]\"")
-        } else {
-          Ok(())
-        }
-      })
-      .map_err(new_io_err)
+    let tdisp = self.next_tdisp();
+    self.write_nd_array(it, |w, v| write_value(w, v, tdisp.as_ref()))
   }
 
-  fn write_opt_array<V, I>(&mut self, mut it: I) -> Result<(), Error>
+  fn write_opt_array<V, I>(&mut self, it: I) -> Result<(), Error>
   where
-    V: Display,
+    V: Display + TDispFormat,
     I: Iterator<Item = Option<V>>,
   {
-    self
+    let tdisp = self.next_tdisp();
+    self.write_nd_array(it, |w, v| match v {
+      Some(v) => write_value(w, v, tdisp.as_ref()),
+      None => Ok(()),
+    })
+  }
+
+  /// Write the given array elements, with nested brackets in the case of a multi-dimensional
+  /// array (the last dimension being the outermost one), e.g. `"[[1, 2, 3], [4, 5, 6]]"` for
+  /// `TDIM = '(3,2)'`.
+  fn write_nd_array<V, I, F>(&mut self, it: I, mut write_elem: F) -> Result<(), Error>
+  where
+    I: Iterator<Item = V>,
+    F: FnMut(&mut W, V) -> Result<(), std::io::Error>,
+  {
+    let res = self
       .write_sep()
       .and_then(|()| {
-        if let Some(v) = it.next() {
-          // We do no write firts "[ and then the possible the value to use ? once instead of twice.
-          match v {
-            Some(v) => write!(self.writer, "\"[{}", v),
-            None => self.writer.write_all(b"\"["),
-          }?;
-          for v in it {
-            // We do no write firts "[ and then the possible the value to use ? once instead of twice.
-            match v {
-              Some(v) => write!(self.writer, ", {}", v),
-              None => self.writer.write_all(b", "),
-            }?;
+        let mut it = it.enumerate().peekable();
+        if it.peek().is_none() {
+          return Ok(());
+        }
+        let depth = self.shape.len().max(1);
+        self.writer.write_all(b"\"")?;
+        write_n(self.writer, b'[', depth)?;
+        for (i, v) in it {
+          if i > 0 {
+            let n = n_ended_dims(&self.shape, i);
+            write_n(self.writer, b']', n)?;
+            self.writer.write_all(b", ")?;
+            write_n(self.writer, b'[', n)?;
           }
-          self.writer.write_all(b"]\"")
-        } else {
-          Ok(())
+          write_elem(self.writer, v)?;
         }
+        write_n(self.writer, b']', depth)?;
+        self.writer.write_all(b"\"")
       })
-      .map_err(new_io_err)
+      .map_err(new_io_err);
+    self.shape.clear();
+    res
+  }
+}
+
+/// Values which can be formatted according to a display format (`TDISPn`).
+trait TDispFormat {
+  fn tdisp_format(&self, tdisp: &TDispValue) -> String;
+}
+macro_rules! impl_tdisp_format {
+  ($t:ty, $f:ident, $as:ty) => {
+    impl TDispFormat for $t {
+      fn tdisp_format(&self, tdisp: &TDispValue) -> String {
+        tdisp.$f(*self as $as)
+      }
+    }
+  };
+}
+impl_tdisp_format!(i8, format_i64, i64);
+impl_tdisp_format!(i16, format_i64, i64);
+impl_tdisp_format!(i32, format_i64, i64);
+impl_tdisp_format!(i64, format_i64, i64);
+impl_tdisp_format!(u8, format_u64, u64);
+impl_tdisp_format!(u16, format_u64, u64);
+impl_tdisp_format!(u32, format_u64, u64);
+impl_tdisp_format!(u64, format_u64, u64);
+impl_tdisp_format!(f32, format_f64, f64);
+impl_tdisp_format!(f64, format_f64, f64);
+
+/// Write the given value, using its display format if any, Rust defaults else.
+fn write_value<W: Write, V: Display + TDispFormat>(
+  writer: &mut W,
+  v: V,
+  tdisp: Option<&TDispValue>,
+) -> Result<(), std::io::Error> {
+  match tdisp {
+    Some(tdisp) => writer.write_all(v.tdisp_format(tdisp).as_bytes()),
+    None => write!(writer, "{}", v),
   }
 }
 
+/// Write the given complex number as `(real, img)`.
+fn write_complex<W: Write, V: Display + TDispFormat>(
+  writer: &mut W,
+  real: V,
+  img: V,
+  tdisp: Option<&TDispValue>,
+) -> Result<(), std::io::Error> {
+  writer.write_all(b"(")?;
+  write_value(writer, real, tdisp)?;
+  writer.write_all(b", ")?;
+  write_value(writer, img, tdisp)?;
+  writer.write_all(b")")
+}
+
+/// Write `n` times the given byte.
+fn write_n<W: Write>(writer: &mut W, byte: u8, n: usize) -> Result<(), std::io::Error> {
+  (0..n).try_for_each(|_| writer.write_all(&[byte]))
+}
+
+/// Returns the number of dimensions (starting from the first one, varying most rapidly)
+/// ending just before the element of given index (> 0) in a multi-dimensional array.
+fn n_ended_dims(shape: &[usize], i: usize) -> usize {
+  shape
+    .iter()
+    .scan(1, |len, d| {
+      *len *= d;
+      Some(*len)
+    })
+    .take_while(|len| i.is_multiple_of(*len))
+    .count()
+}
+
 impl<'a, W: Write> FieldVisitorProvider for CSVVisitor<'a, W> {
   type FieldValue = ();
   type FieldVisitor<'v>
@@ -149,17 +250,28 @@ impl<'a, W: Write> Visitor for &mut CSVVisitor<'a, W> {
     "Unreachable for CSV visitor"
   }
 
+  fn visit_nd_array<F>(self, shape: &[usize], visit_flat: F) -> Result<Self::Value, Error>
+  where
+    F: FnOnce(Self) -> Result<Self::Value, Error>,
+  {
+    self.shape.clear();
+    self.shape.extend_from_slice(shape);
+    visit_flat(self)
+  }
+
   fn visit_empty(self) -> Result<Self::Value, Error> {
     self.write_sep().map_err(new_io_err)
   }
 
   fn visit_opt_bool(self, v: Option<bool>) -> Result<Self::Value, Error> {
+    let tdisp = self.next_tdisp();
     self
       .write_sep()
-      .and_then(|()| match v {
-        Some(true) => self.writer.write_all(b"true"),
-        Some(false) => self.writer.write_all(b"false"),
-        None => Ok(()),
+      .and_then(|()| match (v, tdisp) {
+        (Some(v), Some(tdisp)) => self.writer.write_all(tdisp.format_bool(v).as_bytes()),
+        (Some(true), None) => self.writer.write_all(b"true"),
+        (Some(false), None) => self.writer.write_all(b"false"),
+        (None, _) => Ok(()),
       })
       .map_err(new_io_err)
   }
@@ -234,42 +346,50 @@ impl<'a, W: Write> Visitor for &mut CSVVisitor<'a, W> {
   }
 
   fn visit_f32(self, v: f32) -> Result<Self::Value, Error> {
+    let tdisp = self.next_tdisp();
     self
       .write_sep()
-      .and_then(|()| {
-        if !v.is_nan() {
-          write!(self.writer, "{:?}", v)
-        } else {
-          Ok(())
-        }
+      .and_then(|()| match tdisp {
+        _ if v.is_nan() => Ok(()),
+        Some(tdisp) => self.writer.write_all(v.tdisp_format(&tdisp).as_bytes()),
+        None => write!(self.writer, "{:?}", v),
       })
       .map_err(new_io_err)
   }
 
   fn visit_f64(self, v: f64) -> Result<Self::Value, Error> {
+    let tdisp = self.next_tdisp();
     self
       .write_sep()
-      .and_then(|()| {
-        if !v.is_nan() {
-          write!(self.writer, "{:?}", v)
-        } else {
-          Ok(())
-        }
+      .and_then(|()| match tdisp {
+        _ if v.is_nan() => Ok(()),
+        Some(tdisp) => self.writer.write_all(v.tdisp_format(&tdisp).as_bytes()),
+        None => write!(self.writer, "{:?}", v),
       })
       .map_err(new_io_err)
   }
 
   fn visit_cf32(self, v: ComplexF32) -> Result<Self::Value, Error> {
+    let tdisp = self.next_tdisp();
     self
       .write_sep()
-      .and_then(|()| write!(self.writer, "\"({}, {})\"", v.real(), v.img()))
+      .and_then(|()| {
+        self.writer.write_all(b"\"")?;
+        write_complex(self.writer, v.real(), v.img(), tdisp.as_ref())?;
+        self.writer.write_all(b"\"")
+      })
       .map_err(new_io_err)
   }
 
   fn visit_cf64(self, v: ComplexF64) -> Result<Self::Value, Error> {
+    let tdisp = self.next_tdisp();
     self
       .write_sep()
-      .and_then(|()| write!(self.writer, "\"({}, {})\"", v.real(), v.img()))
+      .and_then(|()| {
+        self.writer.write_all(b"\"")?;
+        write_complex(self.writer, v.real(), v.img(), tdisp.as_ref())?;
+        self.writer.write_all(b"\"")
+      })
       .map_err(new_io_err)
   }
 
@@ -280,6 +400,18 @@ impl<'a, W: Write> Visitor for &mut CSVVisitor<'a, W> {
   }
 
   fn visit_ascii_string(self, v: &str) -> Result<Self::Value, Error> {
+    if !self.shape.is_empty() {
+      // Array of strings, the first dimension being the length of each string
+      let str_len = self.shape.remove(0);
+      return self.write_nd_array(v.as_bytes().chunks(str_len), |w, s| {
+        // Inside a quoted field: " are replaced by ""
+        w.write_all(String::from_utf8_lossy(s).trim_end().replace('"', "\"\"").as_bytes())
+      });
+    }
+    let v = match self.next_tdisp() {
+      Some(tdisp) => tdisp.format_str(v),
+      None => v,
+    };
     self
       .write_sep()
       .and_then(|()| {
@@ -293,34 +425,17 @@ impl<'a, W: Write> Visitor for &mut CSVVisitor<'a, W> {
       .map_err(new_io_err)
   }
 
-  fn visit_opt_bool_array<I>(self, mut it: I) -> Result<Self::Value, Error>
+  fn visit_opt_bool_array<I>(self, it: I) -> Result<Self::Value, Error>
   where
     I: Iterator<Item = Option<bool>>,
   {
-    self
-      .write_sep()
-      .and_then(|()| {
-        if let Some(v) = it.next() {
-          // We do no write firts "[ and then the possible the value to use ? once instead of twice.
-          match v {
-            Some(true) => self.writer.write_all(b"\"[T"),
-            Some(false) => self.writer.write_all(b"\"[F"),
-            None => self.writer.write_all(b"\"["),
-          }?;
-          for v in it {
-            // We do no write firts ", " and then the possible the value to use ? once instead of twice.
-            match v {
-              Some(true) => self.writer.write_all(b", T"),
-              Some(false) => self.writer.write_all(b", F"),
-              None => self.writer.write_all(b", "),
-            }?;
-          }
-          self.writer.write_all(b"]\"")
-        } else {
-          Ok(())
-        }
-      })
-      .map_err(new_io_err)
+    let tdisp = self.next_tdisp();
+    self.write_nd_array(it, |w, v| match (v, tdisp) {
+      (Some(v), Some(tdisp)) => w.write_all(tdisp.format_bool(v).as_bytes()),
+      (Some(true), None) => w.write_all(b"T"),
+      (Some(false), None) => w.write_all(b"F"),
+      (None, _) => Ok(()),
+    })
   }
 
   fn visit_i8_array<I>(self, it: I) -> Result<Self::Value, Error>
@@ -435,107 +550,51 @@ impl<'a, W: Write> Visitor for &mut CSVVisitor<'a, W> {
     self.write_opt_array(it)
   }
 
-  fn visit_f32_array<I>(self, mut it: I) -> Result<Self::Value, Error>
+  fn visit_f32_array<I>(self, it: I) -> Result<Self::Value, Error>
   where
     I: Iterator<Item = f32>,
   {
-    // Simple but not optimal since we have to test on for NaN and once the Option
-    // self.write_opt_array(it.map(|v| if !v.is_nan() { Some(v) } else { None }))
-    self
-      .write_sep()
-      .and_then(|()| {
-        if let Some(v) = it.next() {
-          // We do no write firts "[ and then the possible the value to use ? once instead of twice.
-          if !v.is_nan() {
-            write!(self.writer, "\"[{}", v)
-          } else {
-            self.writer.write_all(b"\"[")
-          }?;
-          for v in it {
-            // We do no write firts ", " and then the possible the value to use ? once instead of twice.
-            if !v.is_nan() {
-              write!(self.writer, ", {}", v)
-            } else {
-              self.writer.write_all(b", ")
-            }?;
-          }
-          self.writer.write_all(b"]\"")
-        } else {
-          Ok(())
-        }
-      })
-      .map_err(new_io_err)
+    let tdisp = self.next_tdisp();
+    self.write_nd_array(it, |w, v| {
+      if !v.is_nan() {
+        write_value(w, v, tdisp.as_ref())
+      } else {
+        Ok(())
+      }
+    })
   }
 
-  fn visit_f64_array<I>(self, mut it: I) -> Result<Self::Value, Error>
+  fn visit_f64_array<I>(self, it: I) -> Result<Self::Value, Error>
   where
     I: Iterator<Item = f64>,
   {
-    // Simple but not optimal since we have to test on for NaN and once the Option
-    // self.write_opt_array(it.map(|v| if !v.is_nan() { Some(v) } else { None }))
-    self
-      .write_sep()
-      .and_then(|()| {
-        if let Some(v) = it.next() {
-          // We do no write firts "[ and then the possible the value to use ? once instead of twice.
-          if !v.is_nan() {
-            write!(self.writer, "\"[{}", v)
-          } else {
-            self.writer.write_all(b"\"[")
-          }?;
-          for v in it {
-            // We do no write firts ", " and then the possible the value to use ? once instead of twice.
-            if !v.is_nan() {
-              write!(self.writer, ", {}", v)
-            } else {
-              self.writer.write_all(b", ")
-            }?;
-          }
-          self.writer.write_all(b"]\"")
-        } else {
-          Ok(())
-        }
-      })
-      .map_err(new_io_err)
+    let tdisp = self.next_tdisp();
+    self.write_nd_array(it, |w, v| {
+      if !v.is_nan() {
+        write_value(w, v, tdisp.as_ref())
+      } else {
+        Ok(())
+      }
+    })
   }
 
-  fn visit_cf32_array<I>(self, mut it: I) -> Result<Self::Value, Error>
+  fn visit_cf32_array<I>(self, it: I) -> Result<Self::Value, Error>
   where
     I: Iterator<Item = ComplexF32>,
   {
-    self
-      .write_sep()
-      .and_then(|()| {
-        if let Some(v) = it.next() {
-          write!(self.writer, "\"[({}, {})", v.real(), v.img())?;
-          for v in it {
-            write!(self.writer, ", ({}, {})", v.real(), v.img())?;
-          }
-          self.writer.write_all(b"]\"")
-        } else {
-          Ok(())
-        }
-      })
-      .map_err(new_io_err)
+    let tdisp = self.next_tdisp();
+    self.write_nd_array(it, |w, v| {
+      write_complex(w, v.real(), v.img(), tdisp.as_ref())
+    })
   }
 
-  fn visit_cf64_array<I>(self, mut it: I) -> Result<Self::Value, Error>
+  fn visit_cf64_array<I>(self, it: I) -> Result<Self::Value, Error>
   where
     I: Iterator<Item = ComplexF64>,
   {
-    self
-      .write_sep()
-      .and_then(|()| {
-        if let Some(v) = it.next() {
-          write!(self.writer, "\"[({}, {})", v.real(), v.img())?;
-          for v in it {
-            write!(self.writer, ", ({}, {})", v.real(), v.img())?;
-          }
-          self.writer.write_all(b"]\"")
-        } else {
-          Ok(())
-        }
-      })
-      .map_err(new_io_err)
+    let tdisp = self.next_tdisp();
+    self.write_nd_array(it, |w, v| {
+      write_complex(w, v.real(), v.img(), tdisp.as_ref())
+    })
   }
 }
@@ -3,9 +3,13 @@ use crate::{
   hdu::xtension::bintable::field::{ComplexF32, ComplexF64},
 };
 
+#[cfg(feature = "arrow")]
+pub mod arrow;
 pub mod csv;
 pub mod field;
 pub mod primitive;
+#[cfg(feature = "vot")]
+pub mod vot;
 
 pub trait FieldVisitorProvider {
   type FieldValue;
@@ -39,6 +43,19 @@ pub trait Visitor: Sized {
     "nothing (default impl)"
   }
 
+  /// Visit a multi-dimensional fixed length array (see `TDIMn`) of given shape, the first
+  /// dimension varying most rapidly (for ASCII strings, the first dimension is the length of each
+  /// string).
+  /// The `visit_flat` function visits the array, with the visitor it is given, as a regular
+  /// (flat) array, i.e. calling one of the `visit_xx_array` (or `visit_ascii_string`) methods.
+  /// By default, the shape is ignored.
+  fn visit_nd_array<F>(self, _shape: &[usize], visit_flat: F) -> Result<Self::Value, Error>
+  where
+    F: FnOnce(Self) -> Result<Self::Value, Error>,
+  {
+    visit_flat(self)
+  }
+
   fn visit_empty(self) -> Result<Self::Value, Error> {
     Err(new_unsupported_by_visitor(self.expecting(), "Empty column"))
   }
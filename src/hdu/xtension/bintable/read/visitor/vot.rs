@@ -0,0 +1,453 @@
+//! Visitor building VOTable values, converting the visited fields according to the schema of
+//! the VOTable `FIELD`s they are written in.
+//!
+//! Since VOTable has no unsigned integer types (but `unsignedByte`) nor signed byte, integers
+//! are converted with `as` (i.e. bit-casted when the `FIELD` has the same size, see the `xtype`
+//! set by `BinTableColumnHeader::to_vot_field`).
+
+use votable::impls::{Schema as VOTSchema, VOTableValue};
+
+use crate::{
+  error::{Error, new_custom},
+  hdu::xtension::bintable::field::{ComplexF32, ComplexF64},
+};
+
+use super::{FieldVisitorProvider, RowVisitor, Visitor};
+
+pub struct VOTableRowVisitor;
+
+impl RowVisitor for VOTableRowVisitor {
+  type Value = Vec<VOTableValue>;
+  type FieldValue = VOTableValue;
+
+  fn visit_row<I>(self, fields_it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = Result<Self::FieldValue, Error>>,
+  {
+    fields_it.collect()
+  }
+}
+
+/// Holds the VOTable schema of each column, to convert the visited fields accordingly.
+pub struct VOTableVisitor {
+  schemas: Vec<VOTSchema>,
+  /// Index of the column of the next visited field.
+  icol: usize,
+}
+
+impl VOTableVisitor {
+  /// # Params
+  /// * `schemas`: VOTable schema of each column (see `votable::impls::TableSchema`)
+  pub fn new(schemas: Vec<VOTSchema>) -> Self {
+    Self { schemas, icol: 0 }
+  }
+}
+
+impl FieldVisitorProvider for VOTableVisitor {
+  type FieldValue = VOTableValue;
+  type FieldVisitor<'v>
+    = VOTableFieldVisitor<'v>
+  where
+    Self: 'v;
+
+  fn field_visitor(&mut self) -> Self::FieldVisitor<'_> {
+    // Starts a new row
+    if self.icol == self.schemas.len() {
+      self.icol = 0;
+    }
+    let schema = &self.schemas[self.icol];
+    self.icol += 1;
+    VOTableFieldVisitor { schema }
+  }
+}
+
+/// Converts the visited field into a VOTable value of the given schema.
+pub struct VOTableFieldVisitor<'v> {
+  schema: &'v VOTSchema,
+}
+
+impl VOTableFieldVisitor<'_> {
+  fn err(&self, visited: &str) -> Error {
+    new_custom(format!(
+      "Unable to convert a FITS {} into a VOTable value of schema {:?}.",
+      visited, self.schema
+    ))
+  }
+
+  /// Pads (with `\0`) or truncates the string to the size of fixed length string schemas,
+  /// since the VOTable binary serialization does not.
+  fn string(&self, mut s: String) -> VOTableValue {
+    match self.schema {
+      VOTSchema::FixedLengthStringUTF8 { n_bytes: n }
+      | VOTSchema::FixedLengthStringUnicode { n_chars: n } => {
+        let len = s.chars().count();
+        if len < *n {
+          s.extend(std::iter::repeat_n('\0', *n - len));
+        } else if len > *n {
+          s = s.chars().take(*n).collect();
+        }
+        VOTableValue::String(s)
+      }
+      _ => VOTableValue::String(s),
+    }
+  }
+
+  fn integer(self, v: i64) -> Result<VOTableValue, Error> {
+    match self.schema {
+      VOTSchema::Byte { .. } => Ok(VOTableValue::Byte(v as u8)),
+      VOTSchema::Short { .. } => Ok(VOTableValue::Short(v as i16)),
+      VOTSchema::Int { .. } => Ok(VOTableValue::Int(v as i32)),
+      VOTSchema::Long { .. } => Ok(VOTableValue::Long(v)),
+      VOTSchema::Float => Ok(VOTableValue::Float(v as f32)),
+      VOTSchema::Double => Ok(VOTableValue::Double(v as f64)),
+      _ => Err(self.err("integer")),
+    }
+  }
+
+  fn opt_integer(self, v: Option<i64>) -> Result<VOTableValue, Error> {
+    match v {
+      Some(v) => self.integer(v),
+      None => Ok(VOTableValue::Null),
+    }
+  }
+
+  fn real(self, v: f64) -> Result<VOTableValue, Error> {
+    match self.schema {
+      VOTSchema::Float => Ok(VOTableValue::Float(v as f32)),
+      VOTSchema::Double => Ok(VOTableValue::Double(v)),
+      _ => Err(self.err("real")),
+    }
+  }
+
+  fn complex(self, real: f64, img: f64) -> Result<VOTableValue, Error> {
+    match self.schema {
+      VOTSchema::ComplexFloat => Ok(VOTableValue::ComplexFloat((real as f32, img as f32))),
+      VOTSchema::ComplexDouble => Ok(VOTableValue::ComplexDouble((real, img))),
+      _ => Err(self.err("complex")),
+    }
+  }
+
+  /// Integer array, `None` elements being replaced by the `FIELD` `null` value (if any), or by
+  /// the type minimum value.
+  fn integer_array<I>(self, it: I) -> Result<VOTableValue, Error>
+  where
+    I: Iterator<Item = Option<i64>>,
+  {
+    match self.schema.primitive_schema() {
+      VOTSchema::Byte { null } => Ok(VOTableValue::ByteArray(
+        it.map(|v| v.map(|v| v as u8).or(*null).unwrap_or(u8::MAX))
+          .collect(),
+      )),
+      VOTSchema::Short { null } => Ok(VOTableValue::ShortArray(
+        it.map(|v| v.map(|v| v as i16).or(*null).unwrap_or(i16::MIN))
+          .collect(),
+      )),
+      VOTSchema::Int { null } => Ok(VOTableValue::IntArray(
+        it.map(|v| v.map(|v| v as i32).or(*null).unwrap_or(i32::MIN))
+          .collect(),
+      )),
+      VOTSchema::Long { null } => Ok(VOTableValue::LongArray(
+        it.map(|v| v.or(*null).unwrap_or(i64::MIN)).collect(),
+      )),
+      VOTSchema::Float => Ok(VOTableValue::FloatArray(
+        it.map(|v| v.map(|v| v as f32).unwrap_or(f32::NAN))
+          .collect(),
+      )),
+      VOTSchema::Double => Ok(VOTableValue::DoubleArray(
+        it.map(|v| v.map(|v| v as f64).unwrap_or(f64::NAN))
+          .collect(),
+      )),
+      _ => Err(self.err("integer array")),
+    }
+  }
+
+  fn real_array<I>(self, it: I) -> Result<VOTableValue, Error>
+  where
+    I: Iterator<Item = f64>,
+  {
+    match self.schema.primitive_schema() {
+      VOTSchema::Float => Ok(VOTableValue::FloatArray(it.map(|v| v as f32).collect())),
+      VOTSchema::Double => Ok(VOTableValue::DoubleArray(it.collect())),
+      _ => Err(self.err("real array")),
+    }
+  }
+
+  fn complex_array<I>(self, it: I) -> Result<VOTableValue, Error>
+  where
+    I: Iterator<Item = (f64, f64)>,
+  {
+    match self.schema.primitive_schema() {
+      VOTSchema::ComplexFloat => Ok(VOTableValue::ComplexFloatArray(
+        it.map(|(r, i)| (r as f32, i as f32)).collect(),
+      )),
+      VOTSchema::ComplexDouble => Ok(VOTableValue::ComplexDoubleArray(it.collect())),
+      _ => Err(self.err("complex array")),
+    }
+  }
+}
+
+impl Visitor for VOTableFieldVisitor<'_> {
+  type Value = VOTableValue;
+
+  fn expecting(&self) -> &str {
+    "a field convertible into a VOTable value"
+  }
+
+  fn visit_empty(self) -> Result<Self::Value, Error> {
+    Ok(VOTableValue::Null)
+  }
+
+  fn visit_opt_bool(self, v: Option<bool>) -> Result<Self::Value, Error> {
+    match (self.schema, v) {
+      (VOTSchema::Bool, Some(v)) => Ok(VOTableValue::Bool(v)),
+      (VOTSchema::Bool, None) => Ok(VOTableValue::Null),
+      _ => Err(self.err("boolean")),
+    }
+  }
+
+  fn visit_ascii_char(self, v: u8) -> Result<Self::Value, Error> {
+    match (self.schema, v) {
+      (_, b'\0') => Ok(VOTableValue::Null),
+      (VOTSchema::CharASCII, _) => Ok(VOTableValue::CharASCII(v as char)),
+      (VOTSchema::CharUnicode, _) => Ok(VOTableValue::CharUnicode(v as char)),
+      (
+        VOTSchema::FixedLengthStringUTF8 { .. }
+        | VOTSchema::FixedLengthStringUnicode { .. }
+        | VOTSchema::VariableLengthStringUTF8 { .. }
+        | VOTSchema::VariableLengthStringUnicode { .. },
+        _,
+      ) => Ok(self.string(String::from(v as char))),
+      _ => Err(self.err("ASCII char")),
+    }
+  }
+
+  fn visit_i8(self, v: i8) -> Result<Self::Value, Error> {
+    self.integer(v as i64)
+  }
+  fn visit_i16(self, v: i16) -> Result<Self::Value, Error> {
+    self.integer(v as i64)
+  }
+  fn visit_i32(self, v: i32) -> Result<Self::Value, Error> {
+    self.integer(v as i64)
+  }
+  fn visit_i64(self, v: i64) -> Result<Self::Value, Error> {
+    self.integer(v)
+  }
+
+  fn visit_opt_i8(self, v: Option<i8>) -> Result<Self::Value, Error> {
+    self.opt_integer(v.map(|v| v as i64))
+  }
+  fn visit_opt_i16(self, v: Option<i16>) -> Result<Self::Value, Error> {
+    self.opt_integer(v.map(|v| v as i64))
+  }
+  fn visit_opt_i32(self, v: Option<i32>) -> Result<Self::Value, Error> {
+    self.opt_integer(v.map(|v| v as i64))
+  }
+  fn visit_opt_i64(self, v: Option<i64>) -> Result<Self::Value, Error> {
+    self.opt_integer(v)
+  }
+
+  fn visit_u8(self, v: u8) -> Result<Self::Value, Error> {
+    self.integer(v as i64)
+  }
+  fn visit_u16(self, v: u16) -> Result<Self::Value, Error> {
+    self.integer(v as i64)
+  }
+  fn visit_u32(self, v: u32) -> Result<Self::Value, Error> {
+    self.integer(v as i64)
+  }
+  fn visit_u64(self, v: u64) -> Result<Self::Value, Error> {
+    self.integer(v as i64)
+  }
+
+  fn visit_opt_u8(self, v: Option<u8>) -> Result<Self::Value, Error> {
+    self.opt_integer(v.map(|v| v as i64))
+  }
+  fn visit_opt_u16(self, v: Option<u16>) -> Result<Self::Value, Error> {
+    self.opt_integer(v.map(|v| v as i64))
+  }
+  fn visit_opt_u32(self, v: Option<u32>) -> Result<Self::Value, Error> {
+    self.opt_integer(v.map(|v| v as i64))
+  }
+  fn visit_opt_u64(self, v: Option<u64>) -> Result<Self::Value, Error> {
+    self.opt_integer(v.map(|v| v as i64))
+  }
+
+  fn visit_f32(self, v: f32) -> Result<Self::Value, Error> {
+    self.real(v as f64)
+  }
+
+  fn visit_f64(self, v: f64) -> Result<Self::Value, Error> {
+    self.real(v)
+  }
+
+  fn visit_cf32(self, v: ComplexF32) -> Result<Self::Value, Error> {
+    self.complex(v.real() as f64, v.img() as f64)
+  }
+
+  fn visit_cf64(self, v: ComplexF64) -> Result<Self::Value, Error> {
+    self.complex(v.real(), v.img())
+  }
+
+  fn visit_ascii_string(self, v: &str) -> Result<Self::Value, Error> {
+    match self.schema {
+      VOTSchema::FixedLengthStringUTF8 { .. }
+      | VOTSchema::FixedLengthStringUnicode { .. }
+      | VOTSchema::VariableLengthStringUTF8 { .. }
+      | VOTSchema::VariableLengthStringUnicode { .. } => Ok(self.string(v.to_owned())),
+      VOTSchema::CharASCII => Ok(VOTableValue::CharASCII(v.chars().next().unwrap_or('\0'))),
+      VOTSchema::CharUnicode => Ok(VOTableValue::CharUnicode(v.chars().next().unwrap_or('\0'))),
+      _ => Err(self.err("ASCII string")),
+    }
+  }
+
+  fn visit_opt_bool_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = Option<bool>>,
+  {
+    match self.schema.primitive_schema() {
+      VOTSchema::Bool => Ok(VOTableValue::BooleanArray(it.collect())),
+      _ => Err(self.err("boolean array")),
+    }
+  }
+
+  fn visit_i8_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = i8>,
+  {
+    self.integer_array(it.map(|v| Some(v as i64)))
+  }
+
+  fn visit_i16_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = i16>,
+  {
+    self.integer_array(it.map(|v| Some(v as i64)))
+  }
+
+  fn visit_i32_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = i32>,
+  {
+    self.integer_array(it.map(|v| Some(v as i64)))
+  }
+
+  fn visit_i64_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = i64>,
+  {
+    self.integer_array(it.map(Some))
+  }
+
+  fn visit_opt_i8_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = Option<i8>>,
+  {
+    self.integer_array(it.map(|v| v.map(|v| v as i64)))
+  }
+
+  fn visit_opt_i16_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = Option<i16>>,
+  {
+    self.integer_array(it.map(|v| v.map(|v| v as i64)))
+  }
+
+  fn visit_opt_i32_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = Option<i32>>,
+  {
+    self.integer_array(it.map(|v| v.map(|v| v as i64)))
+  }
+
+  fn visit_opt_i64_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = Option<i64>>,
+  {
+    self.integer_array(it)
+  }
+
+  fn visit_u8_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = u8>,
+  {
+    self.integer_array(it.map(|v| Some(v as i64)))
+  }
+
+  fn visit_u16_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = u16>,
+  {
+    self.integer_array(it.map(|v| Some(v as i64)))
+  }
+
+  fn visit_u32_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = u32>,
+  {
+    self.integer_array(it.map(|v| Some(v as i64)))
+  }
+
+  fn visit_u64_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = u64>,
+  {
+    self.integer_array(it.map(|v| Some(v as i64)))
+  }
+
+  fn visit_opt_u8_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = Option<u8>>,
+  {
+    self.integer_array(it.map(|v| v.map(|v| v as i64)))
+  }
+
+  fn visit_opt_u16_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = Option<u16>>,
+  {
+    self.integer_array(it.map(|v| v.map(|v| v as i64)))
+  }
+
+  fn visit_opt_u32_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = Option<u32>>,
+  {
+    self.integer_array(it.map(|v| v.map(|v| v as i64)))
+  }
+
+  fn visit_opt_u64_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = Option<u64>>,
+  {
+    self.integer_array(it.map(|v| v.map(|v| v as i64)))
+  }
+
+  fn visit_f32_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = f32>,
+  {
+    self.real_array(it.map(|v| v as f64))
+  }
+
+  fn visit_f64_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = f64>,
+  {
+    self.real_array(it)
+  }
+
+  fn visit_cf32_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = ComplexF32>,
+  {
+    self.complex_array(it.map(|v| (v.real() as f64, v.img() as f64)))
+  }
+
+  fn visit_cf64_array<I>(self, it: I) -> Result<Self::Value, Error>
+  where
+    I: Iterator<Item = ComplexF64>,
+  {
+    self.complex_array(it.map(|v| (v.real(), v.img())))
+  }
+}
@@ -4,7 +4,10 @@ use super::read::{
   deser::{DeserializeSeed, Deserializer},
   visitor::{FieldVisitorProvider, RowVisitor, Visitor},
 };
-use crate::error::Error;
+use crate::{
+  common::keywords::tables::bintable::tform::VariableLenghtArrayDataType,
+  error::{Error, new_custom},
+};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct RowSchema {
@@ -32,6 +35,35 @@ impl RowSchema {
     self.fields_schemas.as_slice()
   }
 
+  /// Returns `true` if at least one field stores its data in the HEAP.
+  pub fn has_heap_array(&self) -> bool {
+    self.fields_schemas.iter().any(|fs| {
+      matches!(
+        fs.schema,
+        Schema::HeapArrayPtr32(_) | Schema::HeapArrayPtr64(_)
+      )
+    })
+  }
+
+  /// Returns a new schema containing only the fields of the given indices, in the given order.
+  /// Each field keeps its starting byte in the row, so that the fields not selected are simply
+  /// skipped (not read) when deserializing a row.
+  pub fn project(&self, icols: &[usize]) -> Result<Self, Error> {
+    icols
+      .iter()
+      .map(|&i| {
+        self.fields_schemas.get(i).cloned().ok_or_else(|| {
+          new_custom(format!(
+            "Column index {} out of bounds. Number of columns: {}.",
+            i,
+            self.fields_schemas.len()
+          ))
+        })
+      })
+      .collect::<Result<Vec<FieldSchema>, Error>>()
+      .map(|fields_schemas| Self { fields_schemas })
+  }
+
   pub fn deserialize<'de, D, F, R>(
     &self,
     deserializer: &mut D,
@@ -101,6 +133,19 @@ impl FieldSchema {
 }
 impl<'de, 'a> DeserializeSeed<'de> for FieldSchema {
   fn deserialize<D, V>(&self, deserializer: &mut D, visitor: V) -> Result<V::Value, Error>
+  where
+    D: Deserializer<'de>,
+    V: Visitor,
+  {
+    match self.schema.shape() {
+      Some(shape) => visitor.visit_nd_array(shape, |v| self.deserialize_flat(deserializer, v)),
+      None => self.deserialize_flat(deserializer, visitor),
+    }
+  }
+}
+impl FieldSchema {
+  /// Deserialize the field, multi-dimensional arrays being visited as flat arrays.
+  fn deserialize_flat<'de, D, V>(&self, deserializer: &mut D, visitor: V) -> Result<V::Value, Error>
   where
     D: Deserializer<'de>,
     V: Visitor,
@@ -149,7 +194,13 @@ impl<'de, 'a> DeserializeSeed<'de> for FieldSchema {
         de.deserialize_double_with_scale_offset_from_long(from, p.scale, p.offset, v)
       }
       Schema::ComplexFloat => de.deserialize_complex_float(from, v),
+      Schema::ComplexFloatFromFloat(p) => {
+        de.deserialize_complex_float_with_scale_offset(from, p.scale, p.offset, v)
+      }
       Schema::ComplexDouble => de.deserialize_complex_double(from, v),
+      Schema::ComplexDoubleFromDouble(p) => {
+        de.deserialize_complex_double_with_scale_offset(from, p.scale, p.offset, v)
+      }
       Schema::AsciiChar => de.deserialize_ascii_char(from, v),
       Schema::NullableBooleanArray(p) => de.deserialize_opt_bool_array(p.len, from, v),
       Schema::ByteArray(p) => de.deserialize_byte_array(p.len, from, v),
@@ -223,7 +274,23 @@ impl<'de, 'a> DeserializeSeed<'de> for FieldSchema {
         v,
       ),
       Schema::ComplexFloatArray(p) => de.deserialize_complex_float_array(p.len, from, v),
+      Schema::ComplexFloatArrayFromFloat(p) => de
+        .deserialize_complex_float_with_scale_offset_array(
+          p.array_params.len,
+          from,
+          p.scale_offset.scale,
+          p.scale_offset.offset,
+          v,
+        ),
       Schema::ComplexDoubleArray(p) => de.deserialize_complex_double_array(p.len, from, v),
+      Schema::ComplexDoubleArrayFromDouble(p) => de
+        .deserialize_complex_double_with_scale_offset_array(
+          p.array_params.len,
+          from,
+          p.scale_offset.scale,
+          p.scale_offset.offset,
+          v,
+        ),
       Schema::AsciiString(p) => de.deserialize_ascii_string_fixed_length(p.len, from, v),
       Schema::HeapArrayPtr32(has) => match has {
         HeapArraySchema::HeapNullableBooleanArray(_) => {
@@ -316,9 +383,23 @@ impl<'de, 'a> DeserializeSeed<'de> for FieldSchema {
         HeapArraySchema::HeapComplexFloatArray(_p) => {
           de.deserialize_complex_float_vararray_ptr32(from, v)
         }
+        HeapArraySchema::HeapComplexFloatArrayFromFloat(p) => de
+          .deserialize_complex_float_with_scale_offset_vararray_ptr32(
+            from,
+            p.scale_offset.scale,
+            p.scale_offset.offset,
+            v,
+          ),
         HeapArraySchema::HeapComplexDoubleArray(_p) => {
           de.deserialize_complex_double_vararray_ptr32(from, v)
         }
+        HeapArraySchema::HeapComplexDoubleArrayFromDouble(p) => de
+          .deserialize_complex_double_with_scale_offset_vararray_ptr32(
+            from,
+            p.scale_offset.scale,
+            p.scale_offset.offset,
+            v,
+          ),
         HeapArraySchema::HeapAsciiString(_p) => de.deserialize_ascii_string_var_ptr32(from, v),
       },
       Schema::HeapArrayPtr64(has) => match has {
@@ -412,9 +493,23 @@ impl<'de, 'a> DeserializeSeed<'de> for FieldSchema {
         HeapArraySchema::HeapComplexFloatArray(_p) => {
           de.deserialize_complex_float_vararray_ptr64(from, v)
         }
+        HeapArraySchema::HeapComplexFloatArrayFromFloat(p) => de
+          .deserialize_complex_float_with_scale_offset_vararray_ptr64(
+            from,
+            p.scale_offset.scale,
+            p.scale_offset.offset,
+            v,
+          ),
         HeapArraySchema::HeapComplexDoubleArray(_p) => {
           de.deserialize_complex_double_vararray_ptr64(from, v)
         }
+        HeapArraySchema::HeapComplexDoubleArrayFromDouble(p) => de
+          .deserialize_complex_double_with_scale_offset_vararray_ptr64(
+            from,
+            p.scale_offset.scale,
+            p.scale_offset.offset,
+            v,
+          ),
         HeapArraySchema::HeapAsciiString(_p) => de.deserialize_ascii_string_var_ptr64(from, v),
       },
     }
@@ -461,18 +556,42 @@ impl ScaleOffset64 {
   }
 }
 
-/// Regular array parameter (only the length so far).
+/// Regular array parameter: the length and, for multi-dimensional arrays (`TDIMn`),
+/// the dimensions.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ArrayParam {
   len: usize,
+  /// Dimensions, the first one varying most rapidly (product equal to `len`).
+  dims: Option<Vec<usize>>,
 }
 impl ArrayParam {
   pub fn new(len: usize) -> Self {
-    Self { len }
+    Self { len, dims: None }
+  }
+  /// Set the dimensions of the array.
+  /// # Errors
+  /// If the product of the dimensions is not equal to the length of the array.
+  pub fn with_dims(mut self, dims: Vec<usize>) -> Result<Self, Error> {
+    let product = dims.iter().try_fold(1_usize, |acc, d| acc.checked_mul(*d));
+    if product == Some(self.len) {
+      self.dims = Some(dims);
+      Ok(self)
+    } else {
+      Err(new_custom(format!(
+        "Wrong product of the array dimensions {:?}. Expected: {}. Actual: {}.",
+        dims,
+        self.len,
+        product.map_or_else(|| String::from("overflow"), |p| p.to_string())
+      )))
+    }
   }
   pub fn get_len(&self) -> usize {
     self.len
   }
+  /// Returns the dimensions of the array, if set, the first one varying most rapidly.
+  pub fn get_dims(&self) -> Option<&[usize]> {
+    self.dims.as_deref()
+  }
   pub fn with_scale_offset_32(self, scale_offset: ScaleOffset32) -> ArrayParamWithScaleOffset32 {
     ArrayParamWithScaleOffset32::new(self, scale_offset)
   }
@@ -480,6 +599,21 @@ impl ArrayParam {
     ArrayParamWithScaleOffset64::new(self, scale_offset)
   }
 }
+impl Display for ArrayParam {
+  /// Write the length, or the dimensions separated by `x` (like a VOTable `arraysize`).
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match &self.dims {
+      Some(dims) if dims.len() > 1 => {
+        let mut it = dims.iter();
+        if let Some(d) = it.next() {
+          write!(f, "{}", d)?;
+        }
+        it.try_for_each(|d| write!(f, "x{}", d))
+      }
+      _ => write!(f, "{}", self.len),
+    }
+  }
+}
 impl From<&HeapArrayParam> for ArrayParam {
   fn from(p: &HeapArrayParam) -> Self {
     Self::new(p.max_len)
@@ -499,6 +633,9 @@ impl ArrayParamWithScaleOffset32 {
       scale_offset,
     }
   }
+  pub fn get_array_param(&self) -> &ArrayParam {
+    &self.array_params
+  }
   pub fn get_len(&self) -> usize {
     self.array_params.len
   }
@@ -528,6 +665,9 @@ impl ArrayParamWithScaleOffset64 {
       scale_offset,
     }
   }
+  pub fn get_array_param(&self) -> &ArrayParam {
+    &self.array_params
+  }
   pub fn get_len(&self) -> usize {
     self.array_params.len
   }
@@ -554,6 +694,9 @@ impl HeapArrayParam {
   pub fn new(max_len: usize) -> Self {
     Self { max_len }
   }
+  pub fn get_max_len(&self) -> usize {
+    self.max_len
+  }
   pub fn with_scale_offset_32(
     self,
     scale_offset: ScaleOffset32,
@@ -586,6 +729,15 @@ impl HeapArrayParamWithScaleOffset32 {
       scale_offset,
     }
   }
+  pub fn get_max_len(&self) -> usize {
+    self.heap_params.max_len
+  }
+  pub fn get_scale(&self) -> f32 {
+    self.scale_offset.get_scale()
+  }
+  pub fn get_offset(&self) -> f32 {
+    self.scale_offset.get_offset()
+  }
 }
 
 /// Variable length array parameter with scaled and offset for 64-bit floats.
@@ -601,6 +753,15 @@ impl HeapArrayParamWithScaleOffset64 {
       scale_offset,
     }
   }
+  pub fn get_max_len(&self) -> usize {
+    self.heap_params.max_len
+  }
+  pub fn get_scale(&self) -> f64 {
+    self.scale_offset.get_scale()
+  }
+  pub fn get_offset(&self) -> f64 {
+    self.scale_offset.get_offset()
+  }
 }
 
 /// Enum made to know the type of an emtpy column.
@@ -688,9 +849,9 @@ pub enum Schema {
   DoubleFromLong(ScaleOffset64), // with null value
   // Complex
   ComplexFloat,
-  // TODO: ComplexFloatFromFloat(ScaleOffset32),
+  ComplexFloatFromFloat(ScaleOffset32),
   ComplexDouble,
-  // TODO: ComplexDoubleFromDouble(ScaleOffset64),
+  ComplexDoubleFromDouble(ScaleOffset64),
   // AsciiChar
   AsciiChar,
 
@@ -727,19 +888,65 @@ pub enum Schema {
   DoubleArrayFromLong(ArrayParamWithScaleOffset64),
   // Complex
   ComplexFloatArray(ArrayParam),
-  // TODO: ComplexFloatArrayFromFloat(ArrayParamWithScaleOffset32),
+  ComplexFloatArrayFromFloat(ArrayParamWithScaleOffset32),
   ComplexDoubleArray(ArrayParam),
-  // TODO: ComplexDoubleArrayFromDouble(ArrayParamWithScaleOffset64),
+  ComplexDoubleArrayFromDouble(ArrayParamWithScaleOffset64),
   // String
   AsciiString(ArrayParam),
 
   // Variable length ARRAYS //
   HeapArrayPtr32(HeapArraySchema),
   HeapArrayPtr64(HeapArraySchema),
-  // multi-dimension array with TDIM ?? Add TDIM to ArrayParam?
 }
 
 impl Schema {
+  /// Returns the array parameters of a fixed length array.
+  pub fn array_param(&self) -> Option<&ArrayParam> {
+    match self {
+      Self::NullableBooleanArray(p)
+      | Self::ByteArray(p)
+      | Self::ShortArray(p)
+      | Self::IntArray(p)
+      | Self::LongArray(p)
+      | Self::NullableByteArray { null: _, p }
+      | Self::NullableShortArray { null: _, p }
+      | Self::NullableIntArray { null: _, p }
+      | Self::NullableLongArray { null: _, p }
+      | Self::UnsignedByteArray(p)
+      | Self::UnsignedShortArray(p)
+      | Self::UnsignedIntArray(p)
+      | Self::UnsignedLongArray(p)
+      | Self::NullableUnsignedByteArray { null: _, p }
+      | Self::NullableUnsignedShortArray { null: _, p }
+      | Self::NullableUnsignedIntArray { null: _, p }
+      | Self::NullableUnsignedLongArray { null: _, p }
+      | Self::FloatArray(p)
+      | Self::DoubleArray(p)
+      | Self::ComplexFloatArray(p)
+      | Self::ComplexDoubleArray(p)
+      | Self::AsciiString(p) => Some(p),
+      Self::FloatArrayFromFloat(p)
+      | Self::FloatArrayFromBytes(p)
+      | Self::FloatArrayFromShort(p) => Some(p.get_array_param()),
+      Self::ComplexFloatArrayFromFloat(p) => Some(p.get_array_param()),
+      Self::DoubleArrayFromDouble(p)
+      | Self::DoubleArrayFromInt(p)
+      | Self::DoubleArrayFromLong(p)
+      | Self::ComplexDoubleArrayFromDouble(p) => Some(p.get_array_param()),
+      _ => None,
+    }
+  }
+
+  /// Returns the shape of a multi-dimensional fixed length array (at least 2 dimensions),
+  /// the first dimension varying most rapidly.
+  /// For `AsciiString`, the first dimension is the length of each string.
+  pub fn shape(&self) -> Option<&[usize]> {
+    self
+      .array_param()
+      .and_then(|p| p.get_dims())
+      .filter(|dims| dims.len() > 1)
+  }
+
   /// Number of byte required to store a value in the main bintable. Thus this is not:
   /// * the size in memory (e.g. a float or a double may be store on a byte or an integer).
   /// * for variable length array, this return the size of the pointer and not the (variable) size in the BINTABLE heap.
@@ -772,81 +979,90 @@ impl Schema {
       | Self::NullableUnsignedLong { .. }
       | Self::Double
       | Self::ComplexFloat
+      | Self::ComplexFloatFromFloat(_)
       | Self::DoubleFromDouble(_)
       | Self::DoubleFromLong(_)
       | Self::HeapArrayPtr32(_) => 8, // + all array descriptor 32bits
-      Self::ComplexDouble | Self::HeapArrayPtr64(_) => 16, // + all array descriptor 64bits
+      Self::ComplexDouble | Self::ComplexDoubleFromDouble(_) | Self::HeapArrayPtr64(_) => 16, // + all array descriptor 64bits
       // Arrays
-      Self::NullableBooleanArray(ArrayParam { len })
-      | Self::ByteArray(ArrayParam { len })
+      Self::NullableBooleanArray(ArrayParam { len, .. })
+      | Self::ByteArray(ArrayParam { len, .. })
       | Self::NullableByteArray {
         null: _,
-        p: ArrayParam { len },
+        p: ArrayParam { len, .. },
       }
-      | Self::UnsignedByteArray(ArrayParam { len })
+      | Self::UnsignedByteArray(ArrayParam { len, .. })
       | Self::NullableUnsignedByteArray {
         null: _,
-        p: ArrayParam { len },
+        p: ArrayParam { len, .. },
       }
-      | Self::AsciiString(ArrayParam { len })
+      | Self::AsciiString(ArrayParam { len, .. })
       | Self::FloatArrayFromBytes(ArrayParamWithScaleOffset32 {
-        array_params: ArrayParam { len },
+        array_params: ArrayParam { len, .. },
         ..
       }) => *len,
-      Self::ShortArray(ArrayParam { len })
+      Self::ShortArray(ArrayParam { len, .. })
       | Self::NullableShortArray {
         null: _,
-        p: ArrayParam { len },
+        p: ArrayParam { len, .. },
       }
-      | Self::UnsignedShortArray(ArrayParam { len })
+      | Self::UnsignedShortArray(ArrayParam { len, .. })
       | Self::NullableUnsignedShortArray {
         null: _,
-        p: ArrayParam { len },
+        p: ArrayParam { len, .. },
       }
       | Self::FloatArrayFromShort(ArrayParamWithScaleOffset32 {
-        array_params: ArrayParam { len },
+        array_params: ArrayParam { len, .. },
         ..
       }) => 2 * *len,
-      Self::IntArray(ArrayParam { len })
+      Self::IntArray(ArrayParam { len, .. })
       | Self::NullableIntArray {
         null: _,
-        p: ArrayParam { len },
+        p: ArrayParam { len, .. },
       }
-      | Self::UnsignedIntArray(ArrayParam { len })
+      | Self::UnsignedIntArray(ArrayParam { len, .. })
       | Self::NullableUnsignedIntArray {
         null: _,
-        p: ArrayParam { len },
+        p: ArrayParam { len, .. },
       }
-      | Self::FloatArray(ArrayParam { len })
+      | Self::FloatArray(ArrayParam { len, .. })
       | Self::FloatArrayFromFloat(ArrayParamWithScaleOffset32 {
-        array_params: ArrayParam { len },
+        array_params: ArrayParam { len, .. },
         ..
       })
       | Self::DoubleArrayFromInt(ArrayParamWithScaleOffset64 {
-        array_params: ArrayParam { len },
+        array_params: ArrayParam { len, .. },
         ..
       }) => 4 * *len,
-      Self::LongArray(ArrayParam { len })
+      Self::LongArray(ArrayParam { len, .. })
       | Self::NullableLongArray {
         null: _,
-        p: ArrayParam { len },
+        p: ArrayParam { len, .. },
       }
-      | Self::UnsignedLongArray(ArrayParam { len })
+      | Self::UnsignedLongArray(ArrayParam { len, .. })
       | Self::NullableUnsignedLongArray {
         null: _,
-        p: ArrayParam { len },
+        p: ArrayParam { len, .. },
       }
-      | Self::DoubleArray(ArrayParam { len })
-      | Self::ComplexFloatArray(ArrayParam { len })
+      | Self::DoubleArray(ArrayParam { len, .. })
+      | Self::ComplexFloatArray(ArrayParam { len, .. })
+      | Self::ComplexFloatArrayFromFloat(ArrayParamWithScaleOffset32 {
+        array_params: ArrayParam { len, .. },
+        ..
+      })
       | Self::DoubleArrayFromDouble(ArrayParamWithScaleOffset64 {
-        array_params: ArrayParam { len },
+        array_params: ArrayParam { len, .. },
         ..
       })
       | Self::DoubleArrayFromLong(ArrayParamWithScaleOffset64 {
-        array_params: ArrayParam { len },
+        array_params: ArrayParam { len, .. },
         ..
       }) => 8 * *len,
-      Self::ComplexDoubleArray(ArrayParam { len }) => 16 * *len,
+      Self::ComplexDoubleArray(ArrayParam { len, .. })
+      | Self::ComplexDoubleArrayFromDouble(ArrayParamWithScaleOffset64 {
+        array_params: ArrayParam { len, .. },
+        ..
+      }) => 16 * *len,
     }
   }
 }
@@ -882,36 +1098,40 @@ impl Display for Schema {
       Self::DoubleFromInt(_) => write!(f, "f64(i32)"),
       Self::DoubleFromLong(_) => write!(f, "f64(i64)"),
       Self::ComplexFloat => write!(f, "C32"),
+      Self::ComplexFloatFromFloat(_) => write!(f, "C32(C32)"),
       Self::ComplexDouble => write!(f, "C64"),
+      Self::ComplexDoubleFromDouble(_) => write!(f, "C64(C64)"),
       Self::AsciiChar => write!(f, "c"),
-      Self::NullableBooleanArray(p) => write!(f, "bool?[{}]", p.len),
-      Self::ByteArray(p) => write!(f, "i8[{}]", p.len),
-      Self::ShortArray(p) => write!(f, "i16[{}]", p.len),
-      Self::IntArray(p) => write!(f, "i32[{}]", p.len),
-      Self::LongArray(p) => write!(f, "i64[{}]", p.len),
-      Self::NullableByteArray { null: _, p } => write!(f, "i8?[{}]", p.len),
-      Self::NullableShortArray { null: _, p } => write!(f, "i16?[{}]", p.len),
-      Self::NullableIntArray { null: _, p } => write!(f, "i32?[{}]", p.len),
-      Self::NullableLongArray { null: _, p } => write!(f, "i64?[{}]", p.len),
-      Self::UnsignedByteArray(p) => write!(f, "u8[{}]", p.len),
-      Self::UnsignedShortArray(p) => write!(f, "u16[{}]", p.len),
-      Self::UnsignedIntArray(p) => write!(f, "u32[{}]", p.len),
-      Self::UnsignedLongArray(p) => write!(f, "u64[{}]", p.len),
-      Self::NullableUnsignedByteArray { null: _, p } => write!(f, "u8?[{}]", p.len),
-      Self::NullableUnsignedShortArray { null: _, p } => write!(f, "u16?[{}]", p.len),
-      Self::NullableUnsignedIntArray { null: _, p } => write!(f, "u32?[{}]", p.len),
-      Self::NullableUnsignedLongArray { null: _, p } => write!(f, "u64?[{}]", p.len),
-      Self::FloatArray(p) => write!(f, "f32[{}]", p.len),
-      Self::FloatArrayFromFloat(p) => write!(f, "f32(f32)[{}]", p.array_params.len),
-      Self::FloatArrayFromBytes(p) => write!(f, "f32(i8)[{}]", p.array_params.len),
-      Self::FloatArrayFromShort(p) => write!(f, "f32(i16)[{}]", p.array_params.len),
-      Self::DoubleArray(p) => write!(f, "f64[{}]", p.len),
-      Self::DoubleArrayFromDouble(p) => write!(f, "f64(f64)[{}]", p.array_params.len),
-      Self::DoubleArrayFromInt(p) => write!(f, "f64(i32)[{}]", p.array_params.len),
-      Self::DoubleArrayFromLong(p) => write!(f, "f64(i64)[{}]", p.array_params.len),
-      Self::ComplexFloatArray(p) => write!(f, "C32[{}]", p.len),
-      Self::ComplexDoubleArray(p) => write!(f, "C64[{}]", p.len),
-      Self::AsciiString(p) => write!(f, "s[{}]", p.len),
+      Self::NullableBooleanArray(p) => write!(f, "bool?[{}]", p),
+      Self::ByteArray(p) => write!(f, "i8[{}]", p),
+      Self::ShortArray(p) => write!(f, "i16[{}]", p),
+      Self::IntArray(p) => write!(f, "i32[{}]", p),
+      Self::LongArray(p) => write!(f, "i64[{}]", p),
+      Self::NullableByteArray { null: _, p } => write!(f, "i8?[{}]", p),
+      Self::NullableShortArray { null: _, p } => write!(f, "i16?[{}]", p),
+      Self::NullableIntArray { null: _, p } => write!(f, "i32?[{}]", p),
+      Self::NullableLongArray { null: _, p } => write!(f, "i64?[{}]", p),
+      Self::UnsignedByteArray(p) => write!(f, "u8[{}]", p),
+      Self::UnsignedShortArray(p) => write!(f, "u16[{}]", p),
+      Self::UnsignedIntArray(p) => write!(f, "u32[{}]", p),
+      Self::UnsignedLongArray(p) => write!(f, "u64[{}]", p),
+      Self::NullableUnsignedByteArray { null: _, p } => write!(f, "u8?[{}]", p),
+      Self::NullableUnsignedShortArray { null: _, p } => write!(f, "u16?[{}]", p),
+      Self::NullableUnsignedIntArray { null: _, p } => write!(f, "u32?[{}]", p),
+      Self::NullableUnsignedLongArray { null: _, p } => write!(f, "u64?[{}]", p),
+      Self::FloatArray(p) => write!(f, "f32[{}]", p),
+      Self::FloatArrayFromFloat(p) => write!(f, "f32(f32)[{}]", p.array_params),
+      Self::FloatArrayFromBytes(p) => write!(f, "f32(i8)[{}]", p.array_params),
+      Self::FloatArrayFromShort(p) => write!(f, "f32(i16)[{}]", p.array_params),
+      Self::DoubleArray(p) => write!(f, "f64[{}]", p),
+      Self::DoubleArrayFromDouble(p) => write!(f, "f64(f64)[{}]", p.array_params),
+      Self::DoubleArrayFromInt(p) => write!(f, "f64(i32)[{}]", p.array_params),
+      Self::DoubleArrayFromLong(p) => write!(f, "f64(i64)[{}]", p.array_params),
+      Self::ComplexFloatArray(p) => write!(f, "C32[{}]", p),
+      Self::ComplexFloatArrayFromFloat(p) => write!(f, "C32(C32)[{}]", p.array_params),
+      Self::ComplexDoubleArray(p) => write!(f, "C64[{}]", p),
+      Self::ComplexDoubleArrayFromDouble(p) => write!(f, "C64(C64)[{}]", p.array_params),
+      Self::AsciiString(p) => write!(f, "s[{}]", p),
       Self::HeapArrayPtr32(hp) => write!(f, "h32({})", hp.to_string()),
       Self::HeapArrayPtr64(hp) => write!(f, "h64({})", hp.to_string()),
     }
@@ -951,12 +1171,55 @@ pub enum HeapArraySchema {
   HeapDoubleArrayFromLong(HeapArrayParamWithScaleOffset64),
   // Complex
   HeapComplexFloatArray(HeapArrayParam),
-  // TODO: HeapComplexFloatArrayFromFloat(HeapArrayParamWithScaleOffset32),
+  HeapComplexFloatArrayFromFloat(HeapArrayParamWithScaleOffset32),
   HeapComplexDoubleArray(HeapArrayParam),
-  // TODO: HeapComplexDoubleArrayFromDouble(HeapArrayParamWithScaleOffset64),
+  HeapComplexDoubleArrayFromDouble(HeapArrayParamWithScaleOffset64),
   // String
   HeapAsciiString(HeapArrayParam),
 }
+impl HeapArraySchema {
+  /// Data type of the elements of the array, as stored in the heap.
+  pub fn data_type(&self) -> VariableLenghtArrayDataType {
+    match self {
+      Self::HeapNullableBooleanArray(_) => VariableLenghtArrayDataType::L,
+      Self::HeapByteArray(_)
+      | Self::HeapNullableByteArray { .. }
+      | Self::HeapUnsignedByteArray(_)
+      | Self::HeapNullableUnsignedByteArray { .. }
+      | Self::HeapFloatArrayFromByte(_) => VariableLenghtArrayDataType::B,
+      Self::HeapShortArray(_)
+      | Self::HeapNullableShortArray { .. }
+      | Self::HeapUnsignedShortArray(_)
+      | Self::HeapNullableUnsignedShortArray { .. }
+      | Self::HeapFloatArrayFromShort(_) => VariableLenghtArrayDataType::I,
+      Self::HeapIntArray(_)
+      | Self::HeapNullableIntArray { .. }
+      | Self::HeapUnsignedIntArray(_)
+      | Self::HeapNullableUnsignedIntArray { .. }
+      | Self::HeapDoubleArrayFromInt(_) => VariableLenghtArrayDataType::J,
+      Self::HeapLongArray(_)
+      | Self::HeapNullableLongArray { .. }
+      | Self::HeapUnsignedLongArray(_)
+      | Self::HeapNullableUnsignedLongArray { .. }
+      | Self::HeapDoubleArrayFromLong(_) => VariableLenghtArrayDataType::K,
+      Self::HeapFloatArray(_) | Self::HeapFloatArrayFromFloat(_) => VariableLenghtArrayDataType::E,
+      Self::HeapDoubleArray(_) | Self::HeapDoubleArrayFromDouble(_) => {
+        VariableLenghtArrayDataType::D
+      }
+      Self::HeapComplexFloatArray(_) | Self::HeapComplexFloatArrayFromFloat(_) => {
+        VariableLenghtArrayDataType::C
+      }
+      Self::HeapComplexDoubleArray(_) | Self::HeapComplexDoubleArrayFromDouble(_) => {
+        VariableLenghtArrayDataType::M
+      }
+      Self::HeapAsciiString(_) => VariableLenghtArrayDataType::A,
+    }
+  }
+  /// Number of bytes used to store a single element of the array in the heap.
+  pub fn stored_elem_byte_len(&self) -> usize {
+    self.data_type().byte_size()
+  }
+}
 impl Display for HeapArraySchema {
   fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
     match self {
@@ -1018,7 +1281,13 @@ impl Display for HeapArraySchema {
       Self::HeapDoubleArrayFromInt(p) => Schema::DoubleArrayFromInt(p.into()).fmt(f),
       Self::HeapDoubleArrayFromLong(p) => Schema::DoubleArrayFromLong(p.into()).fmt(f),
       Self::HeapComplexFloatArray(p) => Schema::ComplexFloatArray(p.into()).fmt(f),
+      Self::HeapComplexFloatArrayFromFloat(p) => {
+        Schema::ComplexFloatArrayFromFloat(p.into()).fmt(f)
+      }
       Self::HeapComplexDoubleArray(p) => Schema::ComplexDoubleArray(p.into()).fmt(f),
+      Self::HeapComplexDoubleArrayFromDouble(p) => {
+        Schema::ComplexDoubleArrayFromDouble(p.into()).fmt(f)
+      }
       Self::HeapAsciiString(p) => Schema::AsciiString(p.into()).fmt(f),
     }
   }
@@ -0,0 +1,908 @@
+//! Write a BINTABLE HDU, row by row.
+//!
+//! The header is written first, with `NAXIS2 = 0` and `PCOUNT = 0`: both values are updated
+//! when calling `finish`, once all rows (and the heap) have been written, together with the
+//! maximum number of elements of the variable length array columns (`TFORMn`), if exceeded.
+//! This is why the writer must implement `Seek` (use a `BufWriter<File>` for better performances).
+
+use std::io::{Seek, SeekFrom, Write};
+
+use crate::{
+  common::{
+    DynValueKwr, ValueKwr,
+    header::HeaderWriter,
+    keywords::{
+      naxis::NAxis2,
+      pgcount::PCount,
+      tables::bintable::tform::{TFormValue, TFormn, VariableLenghtArrayInfo},
+    },
+  },
+  error::{Error, new_custom, new_io_err},
+  hdu::xtension::bintable::{
+    field::{ComplexF32, ComplexF64, Field},
+    header::{BinTableColumnHeader, BinTableHeaderWithColInfo},
+    schema::{HeapArraySchema, RowSchema, Schema},
+  },
+};
+
+/// Index of the `NAXIS2` keyword record in a BINTABLE header
+/// (`PCOUNT` is the next keyword record).
+const NAXIS2_KWR_INDEX: u64 = 4;
+
+/// Write a BINTABLE HDU: header, rows and heap (if any).
+pub struct BinTableWriter<W: Write + Seek> {
+  writer: W,
+  /// Position of the first byte of the header in the writer.
+  header_starting_byte: u64,
+  row_schema: RowSchema,
+  /// Buffer in which a row is serialized before being written.
+  row: Vec<u8>,
+  /// Data of the variable length arrays, written after the main table.
+  heap: Vec<u8>,
+  /// Data of the variable length arrays of the row being serialized, appended to the heap
+  /// only once the full row has been successfully serialized.
+  row_heap: Vec<u8>,
+  /// `TFORMn` of the variable length array columns.
+  heap_tforms: Vec<HeapColumnTForm>,
+  /// Maximum number of elements of the variable length arrays written in each column.
+  max_lens: Vec<usize>,
+  /// Number of elements of the variable length arrays of the row being serialized.
+  row_lens: Vec<usize>,
+  n_rows: u64,
+}
+
+/// `TFORMn` of a variable length array column, updated in `finish` if arrays having more
+/// elements than its maximum have been written.
+struct HeapColumnTForm {
+  /// Index of the column.
+  icol: usize,
+  /// Index of the `TFORMn` keyword record in the header.
+  kwr_index: u64,
+  /// `true` for 64-bit (`Q`) array descriptors, `false` for 32-bit (`P`) ones.
+  is_q: bool,
+  info: VariableLenghtArrayInfo,
+}
+
+impl<W: Write + Seek> BinTableWriter<W> {
+  /// Write the header of the BINTABLE and returns a writer ready to write rows.
+  /// # Params
+  /// * `writer`: destination, positioned where the HDU starts (i.e. at a multiple of 2880 bytes)
+  /// * `cols`: columns headers, all having a `TFORMn` keyword (see `BinTableColumnHeader::from_schema`)
+  pub fn new(mut writer: W, cols: Vec<BinTableColumnHeader>) -> Result<Self, Error> {
+    let header = BinTableHeaderWithColInfo::new(0, 0, cols)?;
+    let row_schema = header
+      .cols()
+      .iter()
+      .enumerate()
+      .map(|(i, col_header)| {
+        col_header.schema().ok_or_else(|| {
+          new_custom(format!(
+            "Unable to create schema for column {}: TFORM probably missing!",
+            i + 1
+          ))
+        })
+      })
+      .collect::<Result<RowSchema, Error>>()?;
+    let header_starting_byte = writer.stream_position().map_err(new_io_err)?;
+    let mut header_bytes = Vec::new();
+    let mut header_writer = HeaderWriter::new(&mut header_bytes);
+    header
+      .write_kw_records(&mut header_writer)
+      .and_then(|()| header_writer.finalize())?;
+    // Locate the TFORMn keyword records of the variable length array columns
+    let mut heap_tforms = Vec::new();
+    for (icol, col_header) in header.cols().iter().enumerate() {
+      let (is_q, info) = match col_header.tform().map(|tform| tform.tform_type()) {
+        Some(TFormValue::P(info)) => (false, info),
+        Some(TFormValue::Q(info)) => (true, info),
+        _ => continue,
+      };
+      let keyword = TFormn::keyword(icol as u16 + 1);
+      let kwr_index = header_bytes
+        .chunks_exact(80)
+        .position(|kwr| kwr[..8] == keyword)
+        .ok_or_else(|| new_custom(format!("Keyword record TFORM{} not found.", icol + 1)))?;
+      heap_tforms.push(HeapColumnTForm {
+        icol,
+        kwr_index: kwr_index as u64,
+        is_q,
+        info: *info,
+      });
+    }
+    writer.write_all(&header_bytes).map_err(new_io_err)?;
+    Ok(Self {
+      writer,
+      header_starting_byte,
+      row: vec![0_u8; header.row_byte_size()],
+      max_lens: vec![0; row_schema.n_cols()],
+      row_lens: vec![0; row_schema.n_cols()],
+      row_schema,
+      heap: Vec::new(),
+      row_heap: Vec::new(),
+      heap_tforms,
+      n_rows: 0,
+    })
+  }
+
+  /// Same as `new`, building the columns headers (without names) from the given row schema.
+  pub fn from_row_schema(writer: W, row_schema: &RowSchema) -> Result<Self, Error> {
+    row_schema
+      .fields_schemas()
+      .iter()
+      .enumerate()
+      .map(|(i, field_schema)| BinTableColumnHeader::from_schema(i as u16, &field_schema.schema))
+      .collect::<Result<Vec<_>, Error>>()
+      .and_then(|cols| Self::new(writer, cols))
+  }
+
+  pub fn row_schema(&self) -> &RowSchema {
+    &self.row_schema
+  }
+
+  /// Number of rows already written.
+  pub fn n_rows(&self) -> u64 {
+    self.n_rows
+  }
+
+  /// Write a row already serialized.
+  /// # Warning
+  /// Columns of variable length arrays are not supported since no data is added to the heap.
+  pub fn write_raw_row(&mut self, row: &[u8]) -> Result<(), Error> {
+    if row.len() != self.row.len() {
+      return Err(new_custom(format!(
+        "Wrong row byte size. Expected: {}. Actual: {}.",
+        self.row.len(),
+        row.len()
+      )));
+    }
+    self.writer.write_all(row).map_err(new_io_err)?;
+    self.n_rows += 1;
+    Ok(())
+  }
+
+  /// Serialize and write a row, the fields must match the row schema.
+  /// Variable length arrays may have more elements than the maximum in their column schema
+  /// (at most 65535 elements): the `TFORMn` maximum is then updated in `finish`.
+  pub fn write_row(&mut self, fields: &[Field]) -> Result<(), Error> {
+    if fields.len() != self.row_schema.n_cols() {
+      return Err(new_custom(format!(
+        "Wrong number of fields. Expected: {}. Actual: {}.",
+        self.row_schema.n_cols(),
+        fields.len()
+      )));
+    }
+    self.row_heap.clear();
+    self.row_lens.fill(0);
+    for (i, (field_schema, field)) in self
+      .row_schema
+      .fields_schemas()
+      .iter()
+      .zip(fields)
+      .enumerate()
+    {
+      let from = field_schema.starting_byte;
+      let to = from + field_schema.schema.stored_byte_len();
+      let dest = &mut self.row[from..to];
+      match &field_schema.schema {
+        Schema::HeapArrayPtr32(_) | Schema::HeapArrayPtr64(_) => {
+          let heap_offset = self.heap.len() + self.row_heap.len();
+          encode_heap_field(
+            &field_schema.schema,
+            field,
+            dest,
+            heap_offset,
+            &mut self.row_heap,
+          )
+          .map(|len| self.row_lens[i] = len)
+        }
+        schema => encode_field(schema, field, dest),
+      }
+      .map_err(|e| new_custom(format!("Error writing column {}: {}", i + 1, e)))?;
+    }
+    self.writer.write_all(&self.row).map_err(new_io_err)?;
+    self.heap.append(&mut self.row_heap);
+    for (max_len, len) in self.max_lens.iter_mut().zip(&self.row_lens) {
+      *max_len = (*max_len).max(*len);
+    }
+    self.n_rows += 1;
+    Ok(())
+  }
+
+  /// Write the heap (if any) and the padding bytes, and update `NAXIS2` and `PCOUNT` in the header,
+  /// together with the `TFORMn` of the variable length array columns whose maximum number of
+  /// elements has been exceeded.
+  /// The writer is returned, positioned at the end of the HDU.
+  pub fn finish(mut self) -> Result<W, Error> {
+    write_heap_and_update_header(
+      &mut self.writer,
+      self.header_starting_byte,
+      self.n_rows,
+      self.row.len(),
+      &self.heap,
+    )
+    .and_then(|()| self.update_heap_tforms())
+    .and_then(|()| self.writer.flush().map_err(new_io_err))
+    .map(|()| self.writer)
+  }
+
+  /// Overwrite the `TFORMn` keyword records of the variable length array columns containing
+  /// arrays larger than the maximum number of elements of their `TFORMn`.
+  fn update_heap_tforms(&mut self) -> Result<(), Error> {
+    let end = self.writer.stream_position().map_err(new_io_err)?;
+    for heap_tform in &self.heap_tforms {
+      let max_len = self.max_lens[heap_tform.icol];
+      if max_len <= heap_tform.info.max_len() as usize {
+        continue;
+      }
+      // No overflow since the number of elements is checked when writing the rows
+      let info = heap_tform.info.with_max_len(max_len as u16);
+      let value = if heap_tform.is_q {
+        TFormValue::Q(info)
+      } else {
+        TFormValue::P(info)
+      };
+      let mut kw_records = [[b' '; 80]; 1];
+      let mut kwr_it = kw_records.iter_mut().map(Ok);
+      TFormn::new(heap_tform.icol as u16 + 1, value).write_kw_record(&mut kwr_it)?;
+      self
+        .writer
+        .seek(SeekFrom::Start(
+          self.header_starting_byte + heap_tform.kwr_index * 80,
+        ))
+        .and_then(|_| self.writer.write_all(kw_records.as_flattened()))
+        .map_err(new_io_err)?;
+    }
+    self
+      .writer
+      .seek(SeekFrom::Start(end))
+      .map(|_| ())
+      .map_err(new_io_err)
+  }
+}
+
+/// Write the heap and the padding bytes following the `n_rows` rows of a BINTABLE, and update
+/// `NAXIS2` and `PCOUNT` in its header (written before the rows, with any value).
+/// The writer is left positioned at the end of the HDU.
+/// # Params
+/// * `header_starting_byte`: position, in the writer, of the first byte of the BINTABLE header
+pub(crate) fn write_heap_and_update_header<W: Write + Seek>(
+  writer: &mut W,
+  header_starting_byte: u64,
+  n_rows: u64,
+  row_byte_size: usize,
+  heap: &[u8],
+) -> Result<(), Error> {
+  writer.write_all(heap).map_err(new_io_err)?;
+  let data_byte_size = n_rows * row_byte_size as u64 + heap.len() as u64;
+  let rem2880 = data_byte_size % 2880;
+  if rem2880 != 0 {
+    writer
+      .write_all(vec![0_u8; (2880 - rem2880) as usize].as_slice())
+      .map_err(new_io_err)?;
+  }
+  // Update NAXIS2 and PCOUNT
+  let mut kw_records = [[b' '; 80]; 2];
+  let mut kwr_it = kw_records.iter_mut().map(Ok);
+  NAxis2::new(n_rows)
+    .write_kw_record(&mut kwr_it)
+    .and_then(|()| PCount::new(heap.len()).write_kw_record(&mut kwr_it))?;
+  let end = writer.stream_position().map_err(new_io_err)?;
+  writer
+    .seek(SeekFrom::Start(
+      header_starting_byte + NAXIS2_KWR_INDEX * 80,
+    ))
+    .and_then(|_| writer.write_all(kw_records.as_flattened()))
+    .and_then(|()| writer.seek(SeekFrom::Start(end)))
+    .map(|_| ())
+    .map_err(new_io_err)
+}
+
+/// Serialize the given field in `dest` (of size the stored byte size of the schema).
+/// Variable length arrays are serialized by `encode_heap_field`.
+fn encode_field(schema: &Schema, field: &Field, dest: &mut [u8]) -> Result<(), Error> {
+  match (schema, field) {
+    (Schema::Empty(_), Field::Empty) => Ok(()),
+    // Bool
+    (Schema::NullableBoolean, Field::NullableBoolean(v)) => write_array(dest, &[*v], from_opt_bool),
+    (Schema::Bits { .. }, Field::BitArray(v)) => write_array(dest, v, |v| [*v]),
+    // Signed integer
+    (Schema::Byte, Field::Byte(v)) => write_array(dest, &[*v], from_i8),
+    (Schema::Short, Field::Short(v)) => write_array(dest, &[*v], |v| v.to_be_bytes()),
+    (Schema::Int, Field::Int(v)) => write_array(dest, &[*v], |v| v.to_be_bytes()),
+    (Schema::Long, Field::Long(v)) => write_array(dest, &[*v], |v| v.to_be_bytes()),
+    (Schema::NullableByte { null }, Field::NullableByte(v)) => {
+      write_array(dest, &[*v], |v| v.as_ref().map(from_i8).unwrap_or([*null]))
+    }
+    (Schema::NullableShort { null }, Field::NullableShort(v)) => {
+      write_array(dest, &[*v], |v| v.unwrap_or(*null).to_be_bytes())
+    }
+    (Schema::NullableInt { null }, Field::NullableInt(v)) => {
+      write_array(dest, &[*v], |v| v.unwrap_or(*null).to_be_bytes())
+    }
+    (Schema::NullableLong { null }, Field::NullableLong(v)) => {
+      write_array(dest, &[*v], |v| v.unwrap_or(*null).to_be_bytes())
+    }
+    // Unsigned integer
+    (Schema::UnsignedByte, Field::UnsignedByte(v)) => write_array(dest, &[*v], |v| [*v]),
+    (Schema::UnsignedShort, Field::UnsignedShort(v)) => write_array(dest, &[*v], from_u16),
+    (Schema::UnsignedInt, Field::UnsignedInt(v)) => write_array(dest, &[*v], from_u32),
+    (Schema::UnsignedLong, Field::UnsignedLong(v)) => write_array(dest, &[*v], from_u64),
+    (Schema::NullableUnsignedByte { null }, Field::NullableUnsignedByte(v)) => {
+      write_array(dest, &[*v], |v| [v.unwrap_or(*null)])
+    }
+    (Schema::NullableUnsignedShort { null }, Field::NullableUnsignedShort(v)) => {
+      write_array(dest, &[*v], |v| {
+        v.as_ref().map(from_u16).unwrap_or(null.to_be_bytes())
+      })
+    }
+    (Schema::NullableUnsignedInt { null }, Field::NullableUnsignedInt(v)) => {
+      write_array(dest, &[*v], |v| {
+        v.as_ref().map(from_u32).unwrap_or(null.to_be_bytes())
+      })
+    }
+    (Schema::NullableUnsignedLong { null }, Field::NullableUnsignedLong(v)) => {
+      write_array(dest, &[*v], |v| {
+        v.as_ref().map(from_u64).unwrap_or(null.to_be_bytes())
+      })
+    }
+    // Real
+    (Schema::Float, Field::Float(v)) => write_array(dest, &[*v], |v| v.to_be_bytes()),
+    (Schema::FloatFromFloat(so), Field::Float(v)) => write_array(dest, &[*v], |v| {
+      ((v - so.get_offset()) / so.get_scale()).to_be_bytes()
+    }),
+    (Schema::FloatFromByte(so), Field::Float(v)) => write_array(dest, &[*v], |v| {
+      [((v - so.get_offset()) / so.get_scale()).round() as u8]
+    }),
+    (Schema::FloatFromShort(so), Field::Float(v)) => write_array(dest, &[*v], |v| {
+      (((v - so.get_offset()) / so.get_scale()).round() as i16).to_be_bytes()
+    }),
+    (Schema::Double, Field::Double(v)) => write_array(dest, &[*v], |v| v.to_be_bytes()),
+    (Schema::DoubleFromDouble(so), Field::Double(v)) => write_array(dest, &[*v], |v| {
+      ((v - so.get_offset()) / so.get_scale()).to_be_bytes()
+    }),
+    (Schema::DoubleFromInt(so), Field::Double(v)) => write_array(dest, &[*v], |v| {
+      (((v - so.get_offset()) / so.get_scale()).round() as i32).to_be_bytes()
+    }),
+    (Schema::DoubleFromLong(so), Field::Double(v)) => write_array(dest, &[*v], |v| {
+      (((v - so.get_offset()) / so.get_scale()).round() as i64).to_be_bytes()
+    }),
+    // Complex
+    (Schema::ComplexFloat, Field::ComplexFloat(v)) => {
+      write_array(dest, std::slice::from_ref(v), from_cf32)
+    }
+    (Schema::ComplexFloatFromFloat(so), Field::ComplexFloat(v)) => {
+      write_array(dest, std::slice::from_ref(v), |v| {
+        from_cf32(&unscale_cf32(v, so.get_scale(), so.get_offset()))
+      })
+    }
+    (Schema::ComplexDouble, Field::ComplexDouble(v)) => {
+      write_array(dest, std::slice::from_ref(v), from_cf64)
+    }
+    (Schema::ComplexDoubleFromDouble(so), Field::ComplexDouble(v)) => {
+      write_array(dest, std::slice::from_ref(v), |v| {
+        from_cf64(&unscale_cf64(v, so.get_scale(), so.get_offset()))
+      })
+    }
+    // ASCII
+    (Schema::AsciiChar, Field::AsciiChar(v)) => write_array(dest, &[*v], |v| [*v]),
+    (Schema::AsciiString(_), Field::AsciiString(v)) => write_ascii_string(dest, v),
+    // Fixed length arrays
+    (Schema::NullableBooleanArray(_), Field::NullableBooleanArray(v)) => {
+      write_array(dest, v, from_opt_bool)
+    }
+    (Schema::ByteArray(_), Field::ByteArray(v)) => write_array(dest, v, from_i8),
+    (Schema::ShortArray(_), Field::ShortArray(v)) => write_array(dest, v, |v| v.to_be_bytes()),
+    (Schema::IntArray(_), Field::IntArray(v)) => write_array(dest, v, |v| v.to_be_bytes()),
+    (Schema::LongArray(_), Field::LongArray(v)) => write_array(dest, v, |v| v.to_be_bytes()),
+    (Schema::NullableByteArray { null, .. }, Field::NullableByteArray(v)) => {
+      write_array(dest, v, |v| v.as_ref().map(from_i8).unwrap_or([*null]))
+    }
+    (Schema::NullableShortArray { null, .. }, Field::NullableShortArray(v)) => {
+      write_array(dest, v, |v| v.unwrap_or(*null).to_be_bytes())
+    }
+    (Schema::NullableIntArray { null, .. }, Field::NullableIntArray(v)) => {
+      write_array(dest, v, |v| v.unwrap_or(*null).to_be_bytes())
+    }
+    (Schema::NullableLongArray { null, .. }, Field::NullableLongArray(v)) => {
+      write_array(dest, v, |v| v.unwrap_or(*null).to_be_bytes())
+    }
+    (Schema::UnsignedByteArray(_), Field::UnsignedByteArray(v)) => write_array(dest, v, |v| [*v]),
+    (Schema::UnsignedShortArray(_), Field::UnsignedShortArray(v)) => write_array(dest, v, from_u16),
+    (Schema::UnsignedIntArray(_), Field::UnsignedIntArray(v)) => write_array(dest, v, from_u32),
+    (Schema::UnsignedLongArray(_), Field::UnsignedLongArray(v)) => write_array(dest, v, from_u64),
+    (Schema::NullableUnsignedByteArray { null, .. }, Field::NullableUnsignedByteArray(v)) => {
+      write_array(dest, v, |v| [v.unwrap_or(*null)])
+    }
+    (Schema::NullableUnsignedShortArray { null, .. }, Field::NullableUnsignedShortArray(v)) => {
+      write_array(dest, v, |v| {
+        v.as_ref().map(from_u16).unwrap_or(null.to_be_bytes())
+      })
+    }
+    (Schema::NullableUnsignedIntArray { null, .. }, Field::NullableUnsignedIntArray(v)) => {
+      write_array(dest, v, |v| {
+        v.as_ref().map(from_u32).unwrap_or(null.to_be_bytes())
+      })
+    }
+    (Schema::NullableUnsignedLongArray { null, .. }, Field::NullableUnsignedLongArray(v)) => {
+      write_array(dest, v, |v| {
+        v.as_ref().map(from_u64).unwrap_or(null.to_be_bytes())
+      })
+    }
+    (Schema::FloatArray(_), Field::FloatArray(v)) => write_array(dest, v, |v| v.to_be_bytes()),
+    (Schema::FloatArrayFromFloat(p), Field::FloatArray(v)) => write_array(dest, v, |v| {
+      ((v - p.get_offset()) / p.get_scale()).to_be_bytes()
+    }),
+    (Schema::FloatArrayFromBytes(p), Field::FloatArray(v)) => write_array(dest, v, |v| {
+      [((v - p.get_offset()) / p.get_scale()).round() as u8]
+    }),
+    (Schema::FloatArrayFromShort(p), Field::FloatArray(v)) => write_array(dest, v, |v| {
+      (((v - p.get_offset()) / p.get_scale()).round() as i16).to_be_bytes()
+    }),
+    (Schema::DoubleArray(_), Field::DoubleArray(v)) => write_array(dest, v, |v| v.to_be_bytes()),
+    (Schema::DoubleArrayFromDouble(p), Field::DoubleArray(v)) => write_array(dest, v, |v| {
+      ((v - p.get_offset()) / p.get_scale()).to_be_bytes()
+    }),
+    (Schema::DoubleArrayFromInt(p), Field::DoubleArray(v)) => write_array(dest, v, |v| {
+      (((v - p.get_offset()) / p.get_scale()).round() as i32).to_be_bytes()
+    }),
+    (Schema::DoubleArrayFromLong(p), Field::DoubleArray(v)) => write_array(dest, v, |v| {
+      (((v - p.get_offset()) / p.get_scale()).round() as i64).to_be_bytes()
+    }),
+    (Schema::ComplexFloatArray(_), Field::ComplexFloatArray(v)) => write_array(dest, v, from_cf32),
+    (Schema::ComplexFloatArrayFromFloat(p), Field::ComplexFloatArray(v)) => {
+      write_array(dest, v, |v| {
+        from_cf32(&unscale_cf32(v, p.get_scale(), p.get_offset()))
+      })
+    }
+    (Schema::ComplexDoubleArray(_), Field::ComplexDoubleArray(v)) => {
+      write_array(dest, v, from_cf64)
+    }
+    (Schema::ComplexDoubleArrayFromDouble(p), Field::ComplexDoubleArray(v)) => {
+      write_array(dest, v, |v| {
+        from_cf64(&unscale_cf64(v, p.get_scale(), p.get_offset()))
+      })
+    }
+    (schema, _) => Err(new_custom(format!(
+      "Field type incompatible with schema '{}'.",
+      schema
+    ))),
+  }
+}
+
+/// Serialize the descriptor of the given variable length array in `dest`, appending the array
+/// data to `heap`, whose first byte is at `heap_offset` in the BINTABLE heap.
+/// Returns the number of elements in the array.
+/// # Remark
+/// On error, bytes may have been appended to `heap`.
+fn encode_heap_field(
+  schema: &Schema,
+  field: &Field,
+  dest: &mut [u8],
+  heap_offset: usize,
+  heap: &mut Vec<u8>,
+) -> Result<usize, Error> {
+  let (has, is_q) = match schema {
+    Schema::HeapArrayPtr32(has) => (has, false),
+    Schema::HeapArrayPtr64(has) => (has, true),
+    _ => {
+      return Err(new_custom(format!(
+        "Variable length array schema expected. Actual: '{}'.",
+        schema
+      )));
+    }
+  };
+  let len = encode_heap_array(has, field, heap)?;
+  if len > u16::MAX as usize {
+    return Err(new_custom(format!(
+      "Too large variable length array. Expected: at most {} elements. Actual: {}.",
+      u16::MAX,
+      len
+    )));
+  }
+  if is_q {
+    write_array(dest, &[len as i64, heap_offset as i64], |v| v.to_be_bytes())
+  } else {
+    match i32::try_from(heap_offset) {
+      Ok(offset) => write_array(dest, &[len as i32, offset], |v| v.to_be_bytes()),
+      Err(_) => Err(new_custom(
+        "Heap too large for 32-bit array descriptors (use 'Q' instead of 'P').",
+      )),
+    }
+  }
+  .map(|()| len)
+}
+
+/// Append the variable length array to the heap, returns the number of elements in the array.
+fn encode_heap_array(
+  schema: &HeapArraySchema,
+  field: &Field,
+  heap: &mut Vec<u8>,
+) -> Result<usize, Error> {
+  match (schema, field) {
+    (HeapArraySchema::HeapNullableBooleanArray(_), Field::NullableBooleanArray(v)) => {
+      Ok(push_array(heap, v, from_opt_bool))
+    }
+    (HeapArraySchema::HeapByteArray(_), Field::ByteArray(v)) => Ok(push_array(heap, v, from_i8)),
+    (HeapArraySchema::HeapShortArray(_), Field::ShortArray(v)) => {
+      Ok(push_array(heap, v, |v| v.to_be_bytes()))
+    }
+    (HeapArraySchema::HeapIntArray(_), Field::IntArray(v)) => {
+      Ok(push_array(heap, v, |v| v.to_be_bytes()))
+    }
+    (HeapArraySchema::HeapLongArray(_), Field::LongArray(v)) => {
+      Ok(push_array(heap, v, |v| v.to_be_bytes()))
+    }
+    (HeapArraySchema::HeapNullableByteArray { null, .. }, Field::NullableByteArray(v)) => {
+      Ok(push_array(heap, v, |v| {
+        v.as_ref().map(from_i8).unwrap_or([*null])
+      }))
+    }
+    (HeapArraySchema::HeapNullableShortArray { null, .. }, Field::NullableShortArray(v)) => {
+      Ok(push_array(heap, v, |v| v.unwrap_or(*null).to_be_bytes()))
+    }
+    (HeapArraySchema::HeapNullableIntArray { null, .. }, Field::NullableIntArray(v)) => {
+      Ok(push_array(heap, v, |v| v.unwrap_or(*null).to_be_bytes()))
+    }
+    (HeapArraySchema::HeapNullableLongArray { null, .. }, Field::NullableLongArray(v)) => {
+      Ok(push_array(heap, v, |v| v.unwrap_or(*null).to_be_bytes()))
+    }
+    (HeapArraySchema::HeapUnsignedByteArray(_), Field::UnsignedByteArray(v)) => {
+      Ok(push_array(heap, v, |v| [*v]))
+    }
+    (HeapArraySchema::HeapUnsignedShortArray(_), Field::UnsignedShortArray(v)) => {
+      Ok(push_array(heap, v, from_u16))
+    }
+    (HeapArraySchema::HeapUnsignedIntArray(_), Field::UnsignedIntArray(v)) => {
+      Ok(push_array(heap, v, from_u32))
+    }
+    (HeapArraySchema::HeapUnsignedLongArray(_), Field::UnsignedLongArray(v)) => {
+      Ok(push_array(heap, v, from_u64))
+    }
+    (
+      HeapArraySchema::HeapNullableUnsignedByteArray { null, .. },
+      Field::NullableUnsignedByteArray(v),
+    ) => Ok(push_array(heap, v, |v| [v.unwrap_or(*null)])),
+    (
+      HeapArraySchema::HeapNullableUnsignedShortArray { null, .. },
+      Field::NullableUnsignedShortArray(v),
+    ) => Ok(push_array(heap, v, |v| {
+      v.as_ref().map(from_u16).unwrap_or(null.to_be_bytes())
+    })),
+    (
+      HeapArraySchema::HeapNullableUnsignedIntArray { null, .. },
+      Field::NullableUnsignedIntArray(v),
+    ) => Ok(push_array(heap, v, |v| {
+      v.as_ref().map(from_u32).unwrap_or(null.to_be_bytes())
+    })),
+    (
+      HeapArraySchema::HeapNullableUnsignedLongArray { null, .. },
+      Field::NullableUnsignedLongArray(v),
+    ) => Ok(push_array(heap, v, |v| {
+      v.as_ref().map(from_u64).unwrap_or(null.to_be_bytes())
+    })),
+    (HeapArraySchema::HeapFloatArray(_), Field::FloatArray(v)) => {
+      Ok(push_array(heap, v, |v| v.to_be_bytes()))
+    }
+    (HeapArraySchema::HeapFloatArrayFromFloat(p), Field::FloatArray(v)) => {
+      Ok(push_array(heap, v, |v| {
+        ((v - p.get_offset()) / p.get_scale()).to_be_bytes()
+      }))
+    }
+    (HeapArraySchema::HeapFloatArrayFromByte(p), Field::FloatArray(v)) => {
+      Ok(push_array(heap, v, |v| {
+        [((v - p.get_offset()) / p.get_scale()).round() as u8]
+      }))
+    }
+    (HeapArraySchema::HeapFloatArrayFromShort(p), Field::FloatArray(v)) => {
+      Ok(push_array(heap, v, |v| {
+        (((v - p.get_offset()) / p.get_scale()).round() as i16).to_be_bytes()
+      }))
+    }
+    (HeapArraySchema::HeapDoubleArray(_), Field::DoubleArray(v)) => {
+      Ok(push_array(heap, v, |v| v.to_be_bytes()))
+    }
+    (HeapArraySchema::HeapDoubleArrayFromDouble(p), Field::DoubleArray(v)) => {
+      Ok(push_array(heap, v, |v| {
+        ((v - p.get_offset()) / p.get_scale()).to_be_bytes()
+      }))
+    }
+    (HeapArraySchema::HeapDoubleArrayFromInt(p), Field::DoubleArray(v)) => {
+      Ok(push_array(heap, v, |v| {
+        (((v - p.get_offset()) / p.get_scale()).round() as i32).to_be_bytes()
+      }))
+    }
+    (HeapArraySchema::HeapDoubleArrayFromLong(p), Field::DoubleArray(v)) => {
+      Ok(push_array(heap, v, |v| {
+        (((v - p.get_offset()) / p.get_scale()).round() as i64).to_be_bytes()
+      }))
+    }
+    (HeapArraySchema::HeapComplexFloatArray(_), Field::ComplexFloatArray(v)) => {
+      Ok(push_array(heap, v, from_cf32))
+    }
+    (HeapArraySchema::HeapComplexFloatArrayFromFloat(p), Field::ComplexFloatArray(v)) => {
+      Ok(push_array(heap, v, |v| {
+        from_cf32(&unscale_cf32(v, p.get_scale(), p.get_offset()))
+      }))
+    }
+    (HeapArraySchema::HeapComplexDoubleArray(_), Field::ComplexDoubleArray(v)) => {
+      Ok(push_array(heap, v, from_cf64))
+    }
+    (HeapArraySchema::HeapComplexDoubleArrayFromDouble(p), Field::ComplexDoubleArray(v)) => {
+      Ok(push_array(heap, v, |v| {
+        from_cf64(&unscale_cf64(v, p.get_scale(), p.get_offset()))
+      }))
+    }
+    (HeapArraySchema::HeapAsciiString(_), Field::AsciiString(v)) => {
+      if v.is_ascii() {
+        heap.extend_from_slice(v.as_bytes());
+        Ok(v.len())
+      } else {
+        Err(new_custom(format!("String '{}' is not ASCII.", v)))
+      }
+    }
+    (schema, _) => Err(new_custom(format!(
+      "Field type incompatible with schema '{}'.",
+      schema
+    ))),
+  }
+}
+
+/// Serialize the given values in `dest`, whose size must be exactly `N` times the number of values.
+fn write_array<T, F, const N: usize>(
+  dest: &mut [u8],
+  values: &[T],
+  to_bytes: F,
+) -> Result<(), Error>
+where
+  F: Fn(&T) -> [u8; N],
+{
+  if dest.len() != values.len() * N {
+    return Err(new_custom(format!(
+      "Wrong number of elements. Expected: {}. Actual: {}.",
+      dest.len() / N,
+      values.len()
+    )));
+  }
+  for (chunk, value) in dest.chunks_exact_mut(N).zip(values) {
+    chunk.copy_from_slice(&to_bytes(value));
+  }
+  Ok(())
+}
+
+/// Append the serialized values to the heap, returns the number of values.
+fn push_array<T, F, const N: usize>(heap: &mut Vec<u8>, values: &[T], to_bytes: F) -> usize
+where
+  F: Fn(&T) -> [u8; N],
+{
+  for value in values {
+    heap.extend_from_slice(&to_bytes(value));
+  }
+  values.len()
+}
+
+/// Write the ASCII string, padded with spaces.
+fn write_ascii_string(dest: &mut [u8], value: &str) -> Result<(), Error> {
+  if !value.is_ascii() {
+    Err(new_custom(format!("String '{}' is not ASCII.", value)))
+  } else if value.len() > dest.len() {
+    Err(new_custom(format!(
+      "String '{}' too long. Max number of chars: {}.",
+      value,
+      dest.len()
+    )))
+  } else {
+    let (val, pad) = dest.split_at_mut(value.len());
+    val.copy_from_slice(value.as_bytes());
+    pad.fill(b' ');
+    Ok(())
+  }
+}
+
+// Reverse of the `to_xx` functions in `read::bytes`.
+
+fn from_opt_bool(v: &Option<bool>) -> [u8; 1] {
+  match v {
+    Some(true) => [b'T'],
+    Some(false) => [b'F'],
+    None => [0],
+  }
+}
+fn from_i8(v: &i8) -> [u8; 1] {
+  [(*v as u8) ^ 0x80]
+}
+fn from_u16(v: &u16) -> [u8; 2] {
+  (*v ^ 0x8000).to_be_bytes()
+}
+fn from_u32(v: &u32) -> [u8; 4] {
+  (*v ^ 0x8000_0000).to_be_bytes()
+}
+fn from_u64(v: &u64) -> [u8; 8] {
+  (*v ^ 0x8000_0000_0000_0000).to_be_bytes()
+}
+fn from_cf32(v: &ComplexF32) -> [u8; 8] {
+  let mut bytes = [0_u8; 8];
+  bytes[..4].copy_from_slice(&v.real().to_be_bytes());
+  bytes[4..].copy_from_slice(&v.img().to_be_bytes());
+  bytes
+}
+fn from_cf64(v: &ComplexF64) -> [u8; 16] {
+  let mut bytes = [0_u8; 16];
+  bytes[..8].copy_from_slice(&v.real().to_be_bytes());
+  bytes[8..].copy_from_slice(&v.img().to_be_bytes());
+  bytes
+}
+
+/// Reverse of `offset + scale * v`, the (real) offset applying to the real part only.
+fn unscale_cf32(v: &ComplexF32, scale: f32, offset: f32) -> ComplexF32 {
+  ComplexF32::new((v.real() - offset) / scale, v.img() / scale)
+}
+/// Reverse of `offset + scale * v`, the (real) offset applying to the real part only.
+fn unscale_cf64(v: &ComplexF64, scale: f64, offset: f64) -> ComplexF64 {
+  ComplexF64::new((v.real() - offset) / scale, v.img() / scale)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::Cursor;
+
+  use super::*;
+  use crate::hdu::{
+    header::{HDUHeader, builder::r#impl::bintable::Bintable, raw::RawHeader},
+    xtension::bintable::{
+      read::{deser::sliceheap::DeserializerWithHeap, visitor::field::FieldVisitor},
+      schema::{ArrayParam, HeapArrayParam},
+    },
+  };
+
+  #[test]
+  fn test_write_read_roundtrip() {
+    let row_schema: RowSchema = [
+      Schema::Short,
+      Schema::Double,
+      Schema::NullableInt { null: i32::MIN },
+      Schema::UnsignedShort,
+      Schema::DoubleArray(ArrayParam::new(3)),
+      Schema::AsciiString(ArrayParam::new(5)),
+      Schema::HeapArrayPtr32(HeapArraySchema::HeapIntArray(HeapArrayParam::new(4))),
+    ]
+    .into_iter()
+    .collect();
+    let rows = [
+      (
+        1_i16,
+        0.5_f64,
+        Some(-7_i32),
+        65535_u16,
+        [1.0, 2.0, 3.0],
+        "abcde",
+        vec![1, 2, 3],
+      ),
+      (-2, f64::MAX, None, 0, [-1.5, 0.0, 1e-300], "xy", vec![]),
+      (
+        i16::MAX,
+        -0.25,
+        Some(i32::MAX),
+        32768,
+        [4.0, 5.0, 6.0],
+        "",
+        vec![4, 5, 6, 7],
+      ),
+    ];
+    let mut writer = BinTableWriter::from_row_schema(Cursor::new(Vec::new()), &row_schema).unwrap();
+    for (s, d, n, u, a, t, h) in rows.iter() {
+      writer
+        .write_row(&[
+          Field::Short(*s),
+          Field::Double(*d),
+          Field::NullableInt(*n),
+          Field::UnsignedShort(*u),
+          Field::DoubleArray(a.to_vec()),
+          Field::AsciiString(t.to_string()),
+          Field::IntArray(h.clone()),
+        ])
+        .unwrap();
+    }
+    let bytes = writer.finish().unwrap().into_inner();
+    assert_eq!(bytes.len() % 2880, 0);
+
+    // Read back the header, NAXIS2 and PCOUNT having been updated
+    let (raw_header, data) = RawHeader::<&[u8]>::from_slice(false, &bytes).unwrap();
+    let header = match raw_header.build::<Bintable>(false).unwrap() {
+      HDUHeader::BinTable(header) => header,
+      _ => panic!("BINTABLE header expected"),
+    };
+    assert_eq!(header.n_rows(), rows.len());
+    assert_eq!(header.heap_byte_size(), 7 * 4);
+    assert_eq!(header.build_row_schema(), row_schema);
+
+    // Read back the rows
+    let main_table_byte_size = header.main_table_byte_size();
+    let heap = &data[main_table_byte_size..main_table_byte_size + header.heap_byte_size()];
+    for (row, (s, d, n, u, a, t, h)) in data[..main_table_byte_size]
+      .chunks(header.row_byte_size())
+      .zip(rows.iter())
+    {
+      let mut de = DeserializerWithHeap::new(row, heap);
+      let fields = row_schema
+        .deserialize(&mut de, &mut FieldVisitor {}, FieldVisitor {})
+        .unwrap();
+      let [
+        Field::Short(rs),
+        Field::Double(rd),
+        Field::NullableInt(rn),
+        Field::UnsignedShort(ru),
+        Field::DoubleArray(ra),
+        Field::AsciiString(rt),
+        Field::IntArray(rh),
+      ] = fields.as_slice()
+      else {
+        panic!("Unexpected field types");
+      };
+      assert_eq!(rs, s);
+      assert_eq!(rd, d);
+      assert_eq!(rn, n);
+      assert_eq!(ru, u);
+      assert_eq!(ra, a);
+      assert_eq!(rt.as_str(), *t);
+      assert_eq!(rh, h);
+    }
+  }
+
+  #[test]
+  fn test_write_larger_heap_array_than_max_len() {
+    let row_schema: RowSchema = [
+      Schema::Int,
+      Schema::HeapArrayPtr32(HeapArraySchema::HeapIntArray(HeapArrayParam::new(4))),
+      Schema::HeapArrayPtr64(HeapArraySchema::HeapShortArray(HeapArrayParam::new(8))),
+    ]
+    .into_iter()
+    .collect();
+    let mut writer = BinTableWriter::from_row_schema(Cursor::new(Vec::new()), &row_schema).unwrap();
+    writer
+      .write_row(&[
+        Field::Int(1),
+        Field::IntArray(vec![1, 2, 3, 4, 5]),
+        Field::ShortArray(vec![1, 2]),
+      ])
+      .unwrap();
+    // Wrong field type in the last column: neither the row nor its heap arrays are written
+    assert!(
+      writer
+        .write_row(&[
+          Field::Int(2),
+          Field::IntArray(vec![6, 7, 8, 9, 10, 11]),
+          Field::IntArray(vec![1, 2]),
+        ])
+        .is_err()
+    );
+    assert_eq!(writer.n_rows(), 1);
+    let bytes = writer.finish().unwrap().into_inner();
+
+    let (raw_header, data) = RawHeader::<&[u8]>::from_slice(false, &bytes).unwrap();
+    let header = match raw_header.build::<Bintable>(false).unwrap() {
+      HDUHeader::BinTable(header) => header,
+      _ => panic!("BINTABLE header expected"),
+    };
+    let tforms: Vec<String> = header
+      .cols()
+      .iter()
+      .map(|col| col.tform().unwrap().tform_type().to_string())
+      .collect();
+    assert_eq!(tforms, ["J", "PJ(5)", "QI(8)"]);
+    assert_eq!(header.n_rows(), 1);
+    assert_eq!(header.heap_byte_size(), 5 * 4 + 2 * 2);
+
+    let main_table_byte_size = header.main_table_byte_size();
+    let heap = &data[main_table_byte_size..main_table_byte_size + header.heap_byte_size()];
+    let mut de = DeserializerWithHeap::new(&data[..main_table_byte_size], heap);
+    let fields = header
+      .build_row_schema()
+      .deserialize(&mut de, &mut FieldVisitor {}, FieldVisitor {})
+      .unwrap();
+    let [Field::Int(1), Field::IntArray(ri), Field::ShortArray(rs)] = fields.as_slice() else {
+      panic!("Unexpected fields");
+    };
+    assert_eq!(ri, &[1, 2, 3, 4, 5]);
+    assert_eq!(rs, &[1, 2]);
+  }
+}
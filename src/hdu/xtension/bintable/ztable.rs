@@ -0,0 +1,641 @@
+//! Decompression of tile-compressed tables, i.e. of BINTABLEs following the tiled table
+//! compression convention (see FITS standard v4.0, section 10.3).
+//!
+//! Each row of the compressed table contains a tile, i.e. `ZTILELEN` consecutive rows of the
+//! uncompressed table (the last tile possibly containing less rows).
+//! Each field of a compressed row is a variable length array (`1PB` or `1QB`) pointing to the
+//! heap, and containing the compressed values of the column for all the rows of the tile.
+//!
+//! Supported algorithms (`ZCTYPn`) are `GZIP_1`, `GZIP_2` and `RICE_1`.
+//! Tables containing variable length array columns are not supported so far.
+
+use std::{
+  borrow::Cow,
+  io::{Read, Write},
+  iter,
+};
+
+use flate2::read::GzDecoder;
+
+use crate::{
+  common::{
+    DynValueKwr, KW_RANGE, ValueKwr,
+    header::HeaderWriter,
+    keywords::{
+      checksum::{CheckSum, DataSum},
+      tables::bintable::{
+        tform::TFormValue,
+        theap::THeap,
+        ztable::{ZCTypeValue, ZNAxis1, ZNAxis2, ZPCount, ZTable, ZTileLen},
+      },
+    },
+  },
+  error::{Error, new_custom, new_io_err},
+  hdu::{header::Header, xtension::bintable::header::BinTableHeaderWithColInfo},
+};
+
+/// Rice compression block size used for tables.
+const RICE_BLOCK_SIZE: usize = 32;
+
+/// Information needed to decompress a column.
+struct ZColumn {
+  /// Compression algorithm
+  algo: ZCTypeValue,
+  /// Starting byte of the heap array descriptor in a row of the compressed table
+  ptr_starting_byte: usize,
+  /// `true` for a `Q` descriptor (2 x 64 bits), `false` for a `P` descriptor (2 x 32 bits)
+  ptr_is_64: bool,
+  /// Number of bytes of the column in a row of the uncompressed table
+  row_byte_size: usize,
+  /// Number of bytes of an elementary value (used for shuffling and Rice decompression)
+  elem_byte_size: usize,
+}
+
+impl ZColumn {
+  /// Returns the (length, offset) of the heap array containing the compressed tile.
+  /// 64-bit values not fitting in a `usize` are set to `usize::MAX` (hence out of the heap).
+  fn heap_array(&self, compressed_row: &[u8]) -> (usize, usize) {
+    let from = self.ptr_starting_byte;
+    let to_usize = |v: u64| usize::try_from(v).unwrap_or(usize::MAX);
+    if self.ptr_is_64 {
+      (
+        to_usize(u64::from_be_bytes(
+          compressed_row[from..from + 8].try_into().unwrap(),
+        )),
+        to_usize(u64::from_be_bytes(
+          compressed_row[from + 8..from + 16].try_into().unwrap(),
+        )),
+      )
+    } else {
+      (
+        u32::from_be_bytes(compressed_row[from..from + 4].try_into().unwrap()) as usize,
+        u32::from_be_bytes(compressed_row[from + 4..from + 8].try_into().unwrap()) as usize,
+      )
+    }
+  }
+
+  /// Returns the big-endian values of the column for all rows of a tile.
+  fn decompress(&self, compressed: &[u8], n_bytes: usize) -> Result<Vec<u8>, Error> {
+    if n_bytes == 0 {
+      return Ok(Vec::new());
+    }
+    match self.algo {
+      ZCTypeValue::Gzip1 => gunzip(compressed, n_bytes),
+      ZCTypeValue::Gzip2 => {
+        gunzip(compressed, n_bytes).map(|bytes| unshuffle(bytes, self.elem_byte_size))
+      }
+      ZCTypeValue::Rice1 => rice_decompress(
+        compressed,
+        n_bytes / self.elem_byte_size,
+        self.elem_byte_size,
+      ),
+    }
+  }
+}
+
+/// A tile-compressed table, providing the header of the uncompressed table and methods to
+/// decompress its data.
+pub struct TileCompressedTable {
+  /// Header of the uncompressed table
+  header: BinTableHeaderWithColInfo,
+  /// Number of rows in a tile (except possibly the last one)
+  tile_len: usize,
+  /// Number of tiles, i.e. number of rows in the compressed table
+  n_tiles: usize,
+  /// Byte size of a row of the compressed table
+  compressed_row_byte_size: usize,
+  /// Starting byte of the heap, from the starting byte of the data part
+  heap_starting_byte: usize,
+  cols: Vec<ZColumn>,
+}
+
+impl TileCompressedTable {
+  /// # Params
+  /// * `header`: header of the compressed table, i.e. of a BINTABLE having `ZTABLE = T`
+  pub fn from_header(header: BinTableHeaderWithColInfo) -> Result<Self, Error> {
+    let n_tiles = header.n_rows();
+    let compressed_row_byte_size = header.row_byte_size();
+    let heap_starting_byte = header.main_table_byte_size() + header.gap_byte_size();
+    let tile_len = header
+      .ztable()
+      .tile_len()
+      .ok_or_else(|| new_custom("Keyword ZTILELEN not found in tile-compressed table."))?
+      as usize;
+    let (header, compressed_cols) = header.into_uncompressed()?;
+    let mut ptr_starting_byte = 0;
+    let cols = compressed_cols
+      .into_iter()
+      .zip(header.cols())
+      .enumerate()
+      .map(|(icol, ((tform, algo), col))| {
+        let ptr_is_64 = match tform.tform_type() {
+          TFormValue::P(_) => false,
+          TFormValue::Q(_) => true,
+          _ => {
+            return Err(new_custom(format!(
+              "Column {} of the tile-compressed table is not a variable length array.",
+              icol + 1
+            )));
+          }
+        };
+        // `TFORMn` (from `ZFORMn`) is necessarily set since the uncompressed header has been built
+        let elem_byte_size = match col.tform().map(|tform| tform.tform_type()) {
+          Some(TFormValue::P(_) | TFormValue::Q(_)) | None => {
+            return Err(new_custom(format!(
+              "Tile-compressed variable length array column {} not supported.",
+              icol + 1
+            )));
+          }
+          // Complex values are shuffled as pairs of real values
+          Some(tform @ (TFormValue::C(_) | TFormValue::M(_))) => tform.elem_byte_size() / 2,
+          Some(tform) => tform.elem_byte_size(),
+        };
+        if algo == ZCTypeValue::Rice1
+          && !matches!(
+            col.tform().map(|tform| tform.tform_type()),
+            Some(TFormValue::B(_) | TFormValue::I(_) | TFormValue::J(_))
+          )
+        {
+          return Err(new_custom(format!(
+            "RICE_1 compression of column {} not supported: only for B, I and J columns.",
+            icol + 1
+          )));
+        }
+        let zcol = ZColumn {
+          algo,
+          ptr_starting_byte,
+          ptr_is_64,
+          row_byte_size: col.schema().map(|s| s.stored_byte_len()).unwrap_or(0),
+          elem_byte_size,
+        };
+        ptr_starting_byte += if ptr_is_64 { 16 } else { 8 };
+        Ok(zcol)
+      })
+      .collect::<Result<Vec<ZColumn>, Error>>()?;
+    if ptr_starting_byte != compressed_row_byte_size {
+      return Err(new_custom(format!(
+        "Wrong tile-compressed table row byte size. Expected: {}. Actual: {}.",
+        ptr_starting_byte, compressed_row_byte_size
+      )));
+    }
+    let expected_n_tiles = header.n_rows().div_ceil(tile_len);
+    if n_tiles != expected_n_tiles {
+      return Err(new_custom(format!(
+        "Wrong number of tiles. Expected (from ZNAXIS2 and ZTILELEN): {}. Actual: {}.",
+        expected_n_tiles, n_tiles
+      )));
+    }
+    Ok(Self {
+      header,
+      tile_len,
+      n_tiles,
+      compressed_row_byte_size,
+      heap_starting_byte,
+      cols,
+    })
+  }
+
+  /// Header of the uncompressed table.
+  pub fn header(&self) -> &BinTableHeaderWithColInfo {
+    &self.header
+  }
+
+  /// Returns the header of the uncompressed table.
+  pub fn into_header(self) -> BinTableHeaderWithColInfo {
+    self.header
+  }
+
+  /// Number of rows in a tile (except possibly the last one).
+  pub fn tile_len(&self) -> usize {
+    self.tile_len
+  }
+
+  pub fn n_tiles(&self) -> usize {
+    self.n_tiles
+  }
+
+  /// Returns the rows (of the uncompressed table) of the given tile.
+  /// # Params
+  /// * `data`: the data part of the compressed table (main table, gap and heap)
+  /// * `i_tile`: index of the tile, i.e. of the row in the compressed table
+  pub fn decompress_tile(&self, data: &[u8], i_tile: usize) -> Result<Vec<u8>, Error> {
+    if i_tile >= self.n_tiles {
+      return Err(new_custom(format!(
+        "Out of bound tile index. Expected: max {}. Actual: {}.",
+        self.n_tiles, i_tile
+      )));
+    }
+    let from = i_tile * self.compressed_row_byte_size;
+    let compressed_row = data
+      .get(from..from + self.compressed_row_byte_size)
+      .ok_or_else(|| new_custom("Tile-compressed table data too short."))?;
+    let heap = data
+      .get(self.heap_starting_byte..)
+      .ok_or_else(|| new_custom("Tile-compressed table heap not found."))?;
+    let n_rows = self
+      .tile_len
+      .min(self.header.n_rows() - i_tile * self.tile_len);
+    let row_byte_size = self.header.row_byte_size();
+    let mut rows = vec![0_u8; n_rows * row_byte_size];
+    let mut col_starting_byte = 0;
+    for (icol, col) in self.cols.iter().enumerate() {
+      if col.row_byte_size > 0 {
+        let (len, offset) = col.heap_array(compressed_row);
+        let compressed = offset
+          .checked_add(len)
+          .and_then(|end| heap.get(offset..end))
+          .ok_or_else(|| {
+            new_custom(format!(
+              "Out of heap compressed data for column {} of tile {}.",
+              icol + 1,
+              i_tile
+            ))
+          })?;
+        let values = col
+          .decompress(compressed, n_rows * col.row_byte_size)
+          .map_err(|e| {
+            new_custom(format!(
+              "Error decompressing column {} of tile {}: {}",
+              icol + 1,
+              i_tile,
+              e
+            ))
+          })?;
+        let col_range = col_starting_byte..col_starting_byte + col.row_byte_size;
+        for (row, value) in rows
+          .chunks_exact_mut(row_byte_size)
+          .zip(values.chunks_exact(col.row_byte_size))
+        {
+          row[col_range.clone()].copy_from_slice(value);
+        }
+      }
+      col_starting_byte += col.row_byte_size;
+    }
+    Ok(rows)
+  }
+
+  /// Returns an iterator over the tiles, each tile containing the rows of the uncompressed table.
+  /// # Params
+  /// * `data`: the data part of the compressed table (main table, gap and heap)
+  pub fn tiles<'a>(&'a self, data: &'a [u8]) -> impl Iterator<Item = Result<Vec<u8>, Error>> + 'a {
+    (0..self.n_tiles).map(move |i_tile| self.decompress_tile(data, i_tile))
+  }
+
+  /// Returns the main table of the uncompressed table.
+  /// # Params
+  /// * `data`: the data part of the compressed table (main table, gap and heap)
+  pub fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut main = Vec::with_capacity(self.header.main_table_byte_size());
+    for tile in self.tiles(data) {
+      main.extend_from_slice(&tile?);
+    }
+    Ok(main)
+  }
+}
+
+/// Returns the header and the data part of the uncompressed table if the given table is
+/// tile-compressed, else returns the given header and data.
+/// # Params
+/// * `header`: header of the table
+/// * `data`: the data part of the table (main table, gap and heap)
+pub fn decompress_if_tile_compressed(
+  header: BinTableHeaderWithColInfo,
+  data: &[u8],
+) -> Result<(BinTableHeaderWithColInfo, Cow<'_, [u8]>), Error> {
+  if header.is_tile_compressed() {
+    let table = TileCompressedTable::from_header(header)?;
+    let main = table.decompress(data)?;
+    Ok((table.into_header(), Cow::Owned(main)))
+  } else {
+    Ok((header, Cow::Borrowed(data)))
+  }
+}
+
+/// A BINTABLE, possibly tile-compressed, whose rows are read by chunks.
+/// Contrary to [decompress_if_tile_compressed], a tile-compressed table is decompressed one tile
+/// at a time, so that the memory needed does not depend on the table size.
+pub enum ChunkedTable<'a> {
+  /// A regular BINTABLE.
+  Raw {
+    header: BinTableHeaderWithColInfo,
+    /// The data part of the table (main table, gap and heap)
+    data: &'a [u8],
+  },
+  /// A tile-compressed BINTABLE.
+  TileCompressed {
+    table: TileCompressedTable,
+    /// The data part of the compressed table (main table, gap and heap)
+    data: &'a [u8],
+  },
+}
+
+impl<'a> ChunkedTable<'a> {
+  /// # Params
+  /// * `header`: header of the table
+  /// * `data`: the data part of the table (main table, gap and heap)
+  pub fn new(header: BinTableHeaderWithColInfo, data: &'a [u8]) -> Result<Self, Error> {
+    if header.is_tile_compressed() {
+      TileCompressedTable::from_header(header).map(|table| Self::TileCompressed { table, data })
+    } else {
+      Ok(Self::Raw { header, data })
+    }
+  }
+
+  pub fn is_tile_compressed(&self) -> bool {
+    matches!(self, Self::TileCompressed { .. })
+  }
+
+  /// Header of the (uncompressed) table.
+  pub fn header(&self) -> &BinTableHeaderWithColInfo {
+    match self {
+      Self::Raw { header, .. } => header,
+      Self::TileCompressed { table, .. } => table.header(),
+    }
+  }
+
+  /// Heap of the (uncompressed) table, always empty for a tile-compressed table.
+  pub fn heap(&self) -> &'a [u8] {
+    match self {
+      Self::Raw { header, data } => data
+        .get(header.main_table_byte_size() + header.gap_byte_size()..)
+        .unwrap_or(&[]),
+      Self::TileCompressed { .. } => &[],
+    }
+  }
+
+  /// Returns an iterator over chunks of consecutive rows of the (uncompressed) table:
+  /// chunks of `n_rows` rows (the last one possibly containing less rows) for a regular table,
+  /// tiles for a tile-compressed table.
+  pub fn row_chunks(
+    &self,
+    n_rows: usize,
+  ) -> Box<dyn Iterator<Item = Result<Cow<'a, [u8]>, Error>> + Send + '_> {
+    match self {
+      Self::Raw { header, data } => match data.get(..header.main_table_byte_size()) {
+        Some(main) => Box::new(
+          main
+            .chunks((n_rows * header.row_byte_size()).max(1))
+            .map(|chunk| Ok(Cow::Borrowed(chunk))),
+        ),
+        None => Box::new(iter::once(Err(new_custom(format!(
+          "BINTABLE data too short. Expected: at least {}. Actual: {}.",
+          header.main_table_byte_size(),
+          data.len()
+        ))))),
+      },
+      Self::TileCompressed { table, data } => {
+        Box::new(table.tiles(data).map(|tile| tile.map(Cow::Owned)))
+      }
+    }
+  }
+}
+
+/// Write the header of the uncompressed table, copying the keyword records of the compressed
+/// table header except the compression keywords, `THEAP`, `CHECKSUM` and `DATASUM`;
+/// `TFORMn` values being replaced by `ZFORMn` values.
+/// # Params
+/// * `header`: header of the uncompressed table (e.g. from `TileCompressedTable::header`)
+/// * `kw_records_it`: the keyword records of the compressed table header (e.g. from
+///   `RawHeader::kw_records_iter`)
+/// * `writer`: destination of the header
+pub fn write_uncompressed_header<'a, I, W>(
+  header: &BinTableHeaderWithColInfo,
+  kw_records_it: I,
+  writer: &mut W,
+) -> Result<(), Error>
+where
+  I: Iterator<Item = &'a [u8; 80]>,
+  W: Write,
+{
+  let mut header_writer = HeaderWriter::new(writer);
+  header.write_starting_mandatory_kw_records(&mut header_writer)?;
+  for kwr in kw_records_it.skip(header.n_kw_records()) {
+    let kw = &kwr[KW_RANGE];
+    if kw == ZTable::KEYWORD
+      || kw == ZNAxis1::KEYWORD
+      || kw == ZNAxis2::KEYWORD
+      || kw == ZPCount::KEYWORD
+      || kw == ZTileLen::KEYWORD
+      || kw == THeap::KEYWORD
+      || kw == CheckSum::KEYWORD
+      || kw == DataSum::KEYWORD
+      || matches!(
+        kw,
+        b"ZTHEAP  "
+          | b"ZHECKSUM"
+          | b"ZDATASUM"
+          | [b'Z', b'F', b'O', b'R', b'M', ..]
+          | [b'Z', b'C', b'T', b'Y', b'P', ..]
+      )
+    {
+      continue;
+    }
+    let tform = match kw {
+      [b'T', b'F', b'O', b'R', b'M', nbr @ ..] => str::from_utf8(nbr)
+        .ok()
+        .and_then(|n| n.trim().parse::<usize>().ok())
+        .and_then(|n| header.cols().get(n.wrapping_sub(1)))
+        .and_then(|col| col.tform()),
+      _ => None,
+    };
+    match tform {
+      Some(tform) => tform.write_kw_record(&mut header_writer)?,
+      None => header_writer
+        .next()
+        .unwrap()
+        .map(|dest_kwr| dest_kwr.copy_from_slice(kwr))?,
+    }
+  }
+  header_writer.finalize()
+}
+
+fn gunzip(compressed: &[u8], n_bytes: usize) -> Result<Vec<u8>, Error> {
+  let mut bytes = Vec::with_capacity(n_bytes);
+  GzDecoder::new(compressed)
+    .read_to_end(&mut bytes)
+    .map_err(new_io_err)?;
+  if bytes.len() != n_bytes {
+    Err(new_custom(format!(
+      "Wrong number of decompressed bytes. Expected: {}. Actual: {}.",
+      n_bytes,
+      bytes.len()
+    )))
+  } else {
+    Ok(bytes)
+  }
+}
+
+/// Undo the `GZIP_2` shuffling: the first bytes of all values are stored first, then the second
+/// bytes, and so on.
+fn unshuffle(bytes: Vec<u8>, elem_byte_size: usize) -> Vec<u8> {
+  if elem_byte_size == 1 || bytes.is_empty() {
+    return bytes;
+  }
+  let n_elems = bytes.len() / elem_byte_size;
+  let mut unshuffled = vec![0_u8; bytes.len()];
+  for (j, plane) in bytes.chunks_exact(n_elems).enumerate() {
+    for (i, b) in plane.iter().enumerate() {
+      unshuffled[i * elem_byte_size + j] = *b;
+    }
+  }
+  unshuffled
+}
+
+/// Rice decompression of `n_values` integers of `bytepix` bytes (1, 2 or 4), adapted from
+/// `fits_rdecomp` of CFITSIO. Returns the big-endian values.
+fn rice_decompress(compressed: &[u8], n_values: usize, bytepix: usize) -> Result<Vec<u8>, Error> {
+  // Number of bits coding the split level, max split level and number of bits of a value
+  let (fsbits, fsmax, bbits): (i32, i32, i32) = match bytepix {
+    1 => (3, 6, 8),
+    2 => (4, 14, 16),
+    4 => (5, 25, 32),
+    _ => {
+      return Err(new_custom(format!(
+        "Rice decompression of {}-byte values not supported.",
+        bytepix
+      )));
+    }
+  };
+  let mut bytes = compressed.iter().map(|b| *b as u64);
+  let mut next_byte = || {
+    bytes
+      .next()
+      .ok_or_else(|| new_custom("Rice decompression error: unexpected end of compressed data."))
+  };
+  let mut values = Vec::with_capacity(n_values * bytepix);
+  let mut push_value = |diff: u64, lastpix: &mut u64| {
+    // Undo the mapping of negative differences on odd positive values
+    let diff = if diff & 1 == 0 {
+      diff >> 1
+    } else {
+      !(diff >> 1)
+    };
+    *lastpix = diff.wrapping_add(*lastpix) & (u64::MAX >> (64 - bbits));
+    values.extend_from_slice(&lastpix.to_be_bytes()[8 - bytepix..]);
+  };
+  // The first value is stored without compression
+  let mut lastpix = 0_u64;
+  for _ in 0..bytepix {
+    lastpix = (lastpix << 8) | next_byte()?;
+  }
+  let mut b = next_byte()?;
+  let mut nbits = 8_i32;
+  let mut i = 0;
+  while i < n_values {
+    // Read the split level of the block
+    nbits -= fsbits;
+    while nbits < 0 {
+      b = (b << 8) | next_byte()?;
+      nbits += 8;
+    }
+    let fs = (b >> nbits) as i32 - 1;
+    b &= (1 << nbits) - 1;
+    let imax = (i + RICE_BLOCK_SIZE).min(n_values);
+    if fs < 0 {
+      // Low-entropy block: all differences are 0
+      for _ in i..imax {
+        push_value(0, &mut lastpix);
+      }
+    } else if fs == fsmax {
+      // High-entropy block: differences are stored on bbits
+      for _ in i..imax {
+        let mut k = bbits - nbits;
+        let mut diff = b << k;
+        k -= 8;
+        while k >= 0 {
+          b = next_byte()?;
+          diff |= b << k;
+          k -= 8;
+        }
+        if nbits > 0 {
+          b = next_byte()?;
+          diff |= b >> -k;
+          b &= (1 << nbits) - 1;
+        } else {
+          b = 0;
+        }
+        push_value(diff, &mut lastpix);
+      }
+    } else {
+      // Normal block: count the leading zeros (unary coded upper part) and read the fs lower bits
+      for _ in i..imax {
+        while b == 0 {
+          nbits += 8;
+          b = next_byte()?;
+        }
+        let nzero = nbits - (64 - b.leading_zeros()) as i32;
+        nbits -= nzero + 1;
+        b ^= 1 << nbits;
+        nbits -= fs;
+        while nbits < 0 {
+          b = (b << 8) | next_byte()?;
+          nbits += 8;
+        }
+        let diff = ((nzero as u64) << fs) | (b >> nbits);
+        b &= (1 << nbits) - 1;
+        push_value(diff, &mut lastpix);
+      }
+    }
+    i = imax;
+  }
+  Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_rice_decompress_low_entropy() {
+    // First value (8 bits), then split level code `000` (all differences are 0)
+    let values = rice_decompress(&[0x0A, 0x00], 4, 1).unwrap();
+    assert_eq!(values, vec![10_u8; 4]);
+    // Same with 16-bit values: first value on 16 bits, then split level code `0000`
+    let values = rice_decompress(&[0x01, 0x02, 0x00], 3, 2).unwrap();
+    assert_eq!(values, vec![0x01, 0x02, 0x01, 0x02, 0x01, 0x02]);
+  }
+
+  #[test]
+  fn test_rice_decompress_normal() {
+    // First value 0, split level code `001` (fs = 0), then the unary coded mapped differences
+    // 0, 2, 2, 2 (i.e. `1`, `001`, `001`, `001`), for the differences 0, +1, +1, +1
+    let values = rice_decompress(&[0x00, 0x32, 0x48], 4, 1).unwrap();
+    assert_eq!(values, vec![0_u8, 1, 2, 3]);
+  }
+
+  #[test]
+  fn test_rice_decompress_high_entropy() {
+    // First value 0, split level code `111` (fs = fsmax), then the mapped differences on 8 bits
+    // 0, 200, 199, 200, for the differences 0, +100, -100, +100
+    let values = rice_decompress(&[0x00, 0xE0, 0x19, 0x18, 0xF9, 0x00], 4, 1).unwrap();
+    assert_eq!(values, vec![0_u8, 100, 0, 100]);
+  }
+
+  #[test]
+  fn test_rice_decompress_errors() {
+    assert!(rice_decompress(&[0x00, 0x32], 4, 1).is_err());
+    assert!(rice_decompress(&[0x00, 0x00, 0x00], 1, 8).is_err());
+  }
+
+  #[test]
+  fn test_unshuffle() {
+    assert_eq!(unshuffle(vec![1, 3, 5, 2, 4, 6], 2), vec![1, 2, 3, 4, 5, 6]);
+    assert_eq!(
+      unshuffle(vec![1, 5, 2, 6, 3, 7, 4, 8], 4),
+      vec![1, 2, 3, 4, 5, 6, 7, 8]
+    );
+    assert_eq!(unshuffle(vec![1, 2, 3], 1), vec![1, 2, 3]);
+  }
+
+  #[test]
+  fn test_gunzip() {
+    // Output of `gzip.compress(b"FITS tile data!!", mtime=0)` in Python
+    let compressed = [
+      31, 139, 8, 0, 0, 0, 0, 0, 2, 3, 115, 243, 12, 9, 86, 40, 201, 204, 73, 85, 72, 73, 44, 73,
+      84, 84, 4, 0, 112, 23, 106, 107, 16, 0, 0, 0,
+    ];
+    assert_eq!(gunzip(&compressed, 16).unwrap(), b"FITS tile data!!");
+    assert!(gunzip(&compressed, 15).is_err());
+    assert!(gunzip(&compressed[..20], 16).is_err());
+  }
+}
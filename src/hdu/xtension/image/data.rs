@@ -0,0 +1,308 @@
+//! Read the data part of an image HDU, i.e. of a primary HDU or of an `IMAGE` extension.
+//!
+//! Array values are stored in big-endian, the first axis (`NAXIS1`) varying the most rapidly.
+//! Physical values are computed from array values using:
+//! > `physical_value = BZERO + BSCALE * array_value`
+//!
+//! with integer array values equal to `BLANK` (if any) being undefined, i.e. converted into `NaN`.
+
+use crate::{
+  common::{
+    ValueKwr,
+    keywords::{
+      bitpix::BitPix,
+      bscalebzero::{BScale, BZero, Blank},
+      naxis::NAxisn,
+      tables::tscaltzero::UIF64,
+    },
+    read::{FixedFormatRead, KwrFormatRead, is_value_indicator},
+  },
+  error::{Error, new_custom},
+};
+
+/// Values of the `BSCALE`, `BZERO` and `BLANK` keywords.
+#[derive(Debug, Clone, Copy)]
+pub struct ImageScaling {
+  bscale: f64,
+  bzero: UIF64,
+  blank: Option<i64>,
+}
+
+impl Default for ImageScaling {
+  /// No scaling (`BSCALE = 1`, `BZERO = 0`) and no `BLANK`.
+  fn default() -> Self {
+    Self::new(1.0, UIF64::I64(0), None)
+  }
+}
+
+impl ImageScaling {
+  pub fn new(bscale: f64, bzero: UIF64, blank: Option<i64>) -> Self {
+    Self {
+      bscale,
+      bzero,
+      blank,
+    }
+  }
+
+  /// Look for the `BSCALE`, `BZERO` and `BLANK` keywords in the given keyword records
+  /// (e.g. from `RawHeader::kw_records_iter`), using default values for missing keywords.
+  pub fn from_kw_records<'a, I>(kw_records_it: I) -> Result<Self, Error>
+  where
+    I: Iterator<Item = &'a [u8; 80]>,
+  {
+    let mut scaling = Self::default();
+    for kwr in kw_records_it {
+      let (kw, ind, kw_value_comment) = FixedFormatRead::split_kw_indicator_value(kwr);
+      // Skip keyword if it does not contain a value indicator
+      if !is_value_indicator(ind) {
+        continue;
+      }
+      match kw {
+        BScale::KEYWORD => {
+          scaling.bscale = BScale::from_value_comment(kw_value_comment)
+            .map_err(|e| e.kwr_context(kwr))?
+            .get();
+        }
+        BZero::KEYWORD => {
+          scaling.bzero = BZero::from_value_comment(kw_value_comment)
+            .map_err(|e| e.kwr_context(kwr))?
+            .get();
+        }
+        Blank::KEYWORD => {
+          scaling.blank = Some(
+            Blank::from_value_comment(kw_value_comment)
+              .map_err(|e| e.kwr_context(kwr))?
+              .get(),
+          );
+        }
+        _ => {}
+      }
+    }
+    Ok(scaling)
+  }
+
+  pub fn bscale(&self) -> f64 {
+    self.bscale
+  }
+  pub fn bzero(&self) -> UIF64 {
+    self.bzero
+  }
+  pub fn blank(&self) -> Option<i64> {
+    self.blank
+  }
+
+  /// `true` if `BSCALE = 1` and `BZERO = 0`.
+  pub fn is_identity(&self) -> bool {
+    self.bscale == 1.0 && self.bzero.is_0()
+  }
+
+  /// Physical value of the given array value (`NaN` if the array value equals `BLANK`).
+  fn physical<T: ImagePixel>(&self, v: T) -> f64 {
+    match (v.to_i64(), self.blank) {
+      (Some(v), Some(blank)) if v == blank => f64::NAN,
+      _ => self.bzero.as_f64() + self.bscale * v.to_f64(),
+    }
+  }
+}
+
+/// Types of array values, one per `BITPIX` value.
+pub trait ImagePixel: Copy + 'static {
+  const BITPIX: BitPix;
+  const BYTE_SIZE: usize;
+
+  /// Build the value from its big-endian representation (`BYTE_SIZE` bytes).
+  fn from_be_slice(bytes: &[u8]) -> Self;
+  /// `None` for floating point types.
+  fn to_i64(self) -> Option<i64>;
+  fn to_f64(self) -> f64;
+}
+
+macro_rules! impl_image_pixel {
+  ($t:ty, $bitpix:expr, $to_i64:expr) => {
+    impl ImagePixel for $t {
+      const BITPIX: BitPix = $bitpix;
+      const BYTE_SIZE: usize = size_of::<$t>();
+
+      fn from_be_slice(bytes: &[u8]) -> Self {
+        <$t>::from_be_bytes(bytes.try_into().unwrap())
+      }
+      fn to_i64(self) -> Option<i64> {
+        $to_i64(self)
+      }
+      fn to_f64(self) -> f64 {
+        self as f64
+      }
+    }
+  };
+}
+impl_image_pixel!(u8, BitPix::U8, |v: u8| Some(v as i64));
+impl_image_pixel!(i16, BitPix::I16, |v: i16| Some(v as i64));
+impl_image_pixel!(i32, BitPix::I32, |v: i32| Some(v as i64));
+impl_image_pixel!(i64, BitPix::I64, |v: i64| Some(v));
+impl_image_pixel!(f32, BitPix::F32, |_: f32| None);
+impl_image_pixel!(f64, BitPix::F64, |_: f64| None);
+
+/// View on the data part of an image HDU.
+pub struct ImageData<'a> {
+  bitpix: BitPix,
+  /// Length of each axis, starting by `NAXIS1`.
+  shape: Vec<usize>,
+  scaling: ImageScaling,
+  /// Big-endian array values.
+  data: &'a [u8],
+}
+
+impl<'a> ImageData<'a> {
+  /// # Params
+  /// * `bitpix`: type of the array values
+  /// * `shape`: length of each axis, starting by `NAXIS1`
+  /// * `scaling`: `BSCALE`, `BZERO` and `BLANK` values
+  /// * `data`: the data part of the HDU (possibly followed by padding bytes)
+  pub fn new(
+    bitpix: BitPix,
+    shape: Vec<usize>,
+    scaling: ImageScaling,
+    data: &'a [u8],
+  ) -> Result<Self, Error> {
+    let n_pixels = if shape.is_empty() {
+      0
+    } else {
+      shape.iter().product()
+    };
+    let byte_size = n_pixels * bitpix.byte_size() as usize;
+    if data.len() < byte_size {
+      return Err(new_custom(format!(
+        "Image data too short. Expected: {} bytes. Actual: {} bytes.",
+        byte_size,
+        data.len()
+      )));
+    }
+    Ok(Self {
+      bitpix,
+      shape,
+      scaling,
+      data: &data[..byte_size],
+    })
+  }
+
+  /// Same as `new`, taking the `NAXISn` keywords of a primary or `IMAGE` header.
+  pub fn from_naxisn(
+    bitpix: BitPix,
+    naxisn: &[NAxisn],
+    scaling: ImageScaling,
+    data: &'a [u8],
+  ) -> Result<Self, Error> {
+    let shape = naxisn.iter().map(|n| n.axis_len() as usize).collect();
+    Self::new(bitpix, shape, scaling, data)
+  }
+
+  pub fn bitpix(&self) -> BitPix {
+    self.bitpix
+  }
+
+  /// Length of each axis, starting by `NAXIS1`.
+  pub fn shape(&self) -> &[usize] {
+    &self.shape
+  }
+
+  pub fn scaling(&self) -> &ImageScaling {
+    &self.scaling
+  }
+
+  pub fn n_pixels(&self) -> usize {
+    self.data.len() / self.bitpix.byte_size() as usize
+  }
+
+  /// Big-endian array values.
+  pub fn raw_bytes(&self) -> &'a [u8] {
+    self.data
+  }
+
+  /// Returns the index, in the flat array of values, of the pixel at the given position
+  /// (coordinate along `NAXIS1` first), or `None` if the position is out of the image.
+  pub fn flat_index(&self, pos: &[usize]) -> Option<usize> {
+    if pos.len() != self.shape.len() {
+      return None;
+    }
+    let mut index = 0;
+    let mut stride = 1;
+    for (&p, &len) in pos.iter().zip(&self.shape) {
+      if p >= len {
+        return None;
+      }
+      index += p * stride;
+      stride *= len;
+    }
+    Some(index)
+  }
+
+  /// Returns an iterator over the array values (i.e. without applying `BSCALE`, `BZERO` nor
+  /// `BLANK`), the first axis varying the most rapidly.
+  /// # Errors
+  /// If the type `T` does not match `BITPIX`.
+  pub fn raw_values<T: ImagePixel>(&self) -> Result<impl Iterator<Item = T> + 'a, Error> {
+    self.check_bitpix::<T>().map(|()| self.values_of::<T>())
+  }
+
+  /// Returns the array value of the pixel at the given position (coordinate along `NAXIS1` first),
+  /// or `None` if the position is out of the image.
+  /// # Errors
+  /// If the type `T` does not match `BITPIX`.
+  pub fn raw_value<T: ImagePixel>(&self, pos: &[usize]) -> Result<Option<T>, Error> {
+    self.check_bitpix::<T>().map(|()| self.value_of::<T>(pos))
+  }
+
+  /// Returns an iterator over the physical values, the first axis varying the most rapidly.
+  /// Undefined values (`BLANK` or `NaN`) are provided as `NaN`.
+  pub fn values(&self) -> Box<dyn Iterator<Item = f64> + 'a> {
+    let scaling = self.scaling;
+    match self.bitpix {
+      BitPix::U8 => Box::new(self.values_of::<u8>().map(move |v| scaling.physical(v))),
+      BitPix::I16 => Box::new(self.values_of::<i16>().map(move |v| scaling.physical(v))),
+      BitPix::I32 => Box::new(self.values_of::<i32>().map(move |v| scaling.physical(v))),
+      BitPix::I64 => Box::new(self.values_of::<i64>().map(move |v| scaling.physical(v))),
+      BitPix::F32 => Box::new(self.values_of::<f32>().map(move |v| scaling.physical(v))),
+      BitPix::F64 => Box::new(self.values_of::<f64>().map(move |v| scaling.physical(v))),
+    }
+  }
+
+  /// Returns the physical value of the pixel at the given position (coordinate along `NAXIS1`
+  /// first), or `None` if the position is out of the image.
+  /// An undefined value (`BLANK` or `NaN`) is provided as `NaN`.
+  pub fn value(&self, pos: &[usize]) -> Option<f64> {
+    let scaling = self.scaling;
+    match self.bitpix {
+      BitPix::U8 => self.value_of::<u8>(pos).map(|v| scaling.physical(v)),
+      BitPix::I16 => self.value_of::<i16>(pos).map(|v| scaling.physical(v)),
+      BitPix::I32 => self.value_of::<i32>(pos).map(|v| scaling.physical(v)),
+      BitPix::I64 => self.value_of::<i64>(pos).map(|v| scaling.physical(v)),
+      BitPix::F32 => self.value_of::<f32>(pos).map(|v| scaling.physical(v)),
+      BitPix::F64 => self.value_of::<f64>(pos).map(|v| scaling.physical(v)),
+    }
+  }
+
+  fn check_bitpix<T: ImagePixel>(&self) -> Result<(), Error> {
+    if T::BITPIX == self.bitpix {
+      Ok(())
+    } else {
+      Err(new_custom(format!(
+        "Wrong image value type. BITPIX: {}. Requested type BITPIX: {}.",
+        self.bitpix.i16_value(),
+        T::BITPIX.i16_value()
+      )))
+    }
+  }
+
+  /// Iterator over the array values, the type being assumed to match `BITPIX`.
+  fn values_of<T: ImagePixel>(&self) -> impl Iterator<Item = T> + 'a {
+    self.data.chunks_exact(T::BYTE_SIZE).map(T::from_be_slice)
+  }
+
+  /// Array value at the given position, the type being assumed to match `BITPIX`.
+  fn value_of<T: ImagePixel>(&self, pos: &[usize]) -> Option<T> {
+    self.flat_index(pos).map(|i| {
+      let from = i * T::BYTE_SIZE;
+      T::from_be_slice(&self.data[from..from + T::BYTE_SIZE])
+    })
+  }
+}
@@ -1 +1,2 @@
-pub mod header;
\ No newline at end of file
+pub mod data;
+pub mod header;
@@ -8,3 +8,6 @@ pub mod common;
 pub mod error;
 pub mod hdu;
 pub mod read;
+
+#[cfg(feature = "derive")]
+pub use fitstable_derive::FitsRow;
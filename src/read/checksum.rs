@@ -0,0 +1,381 @@
+//! Module dedicated to the `CHECKSUM` and `DATASUM` keywords, following the FITS checksum
+//! convention (Seaman et al., 2002) also implemented in HEASARC tools (e.g. `fitsverify`).
+//!
+//! The checksum of a set of bytes is the 32-bit 1's complement sum of its big-endian 32-bit words.
+//! * `DATASUM` is the checksum of the data unit (padding included), stored as a string.
+//! * `CHECKSUM` is the ASCII encoding of the 1's complement of the checksum of the full HDU
+//!   (header + data unit) computed with `CHECKSUM = '0000000000000000'`, so that the checksum
+//!   of a valid HDU equals `0xFFFFFFFF` (i.e. `-0` in 1's complement).
+
+use std::{
+  fs::{self, File, OpenOptions},
+  io::{BufWriter, Seek, SeekFrom, Write},
+  path::Path,
+};
+
+use log::debug;
+use memmap2::MmapOptions;
+
+use crate::{
+  common::{
+    KW_RANGE, ValueKwr,
+    header::HeaderWriter,
+    keywords::{
+      checksum::{CheckSum, DataSum},
+      simple::Simple,
+    },
+  },
+  error::{Error, new_custom, new_io_err},
+  hdu::header::{builder::r#impl::bintable::Bintable, raw::RawHeader},
+  read::{
+    decompress::detect_file_compression,
+    slice::{FitsBytes, HDU},
+  },
+};
+
+/// Incremental computation of the 32-bit 1's complement sum of big-endian 32-bit words.
+/// Bytes can be provided in several chunks of any size (e.g. the data and the padding).
+#[derive(Debug, Clone, Default)]
+pub struct OnesComplementSum {
+  /// Sum of the words, the carries being folded from time to time.
+  sum: u64,
+  /// Bytes of an incomplete word.
+  rem: [u8; 4],
+  /// Number of bytes in the incomplete word.
+  n_rem: usize,
+}
+
+impl OnesComplementSum {
+  /// Number of words added before folding the carries (no possible overflow of the `u64` sum).
+  const FOLD_PERIOD: usize = 1 << 20;
+
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn update(&mut self, mut bytes: &[u8]) -> &mut Self {
+    if self.n_rem > 0 {
+      let n = (4 - self.n_rem).min(bytes.len());
+      self.rem[self.n_rem..self.n_rem + n].copy_from_slice(&bytes[..n]);
+      self.n_rem += n;
+      bytes = &bytes[n..];
+      if self.n_rem < 4 {
+        return self;
+      }
+      self.sum += u32::from_be_bytes(self.rem) as u64;
+      self.n_rem = 0;
+    }
+    for chunk in bytes.chunks(4 * Self::FOLD_PERIOD) {
+      let mut words = chunk.chunks_exact(4);
+      for word in &mut words {
+        self.sum += u32::from_be_bytes(word.try_into().unwrap()) as u64;
+      }
+      self.sum = fold(self.sum) as u64;
+      let rem = words.remainder();
+      self.rem[..rem.len()].copy_from_slice(rem);
+      self.n_rem = rem.len();
+    }
+    self
+  }
+
+  /// Returns the 1's complement sum of all bytes provided so far, an incomplete last word being
+  /// completed by zeros.
+  pub fn value(&self) -> u32 {
+    let mut last = [0_u8; 4];
+    last[..self.n_rem].copy_from_slice(&self.rem[..self.n_rem]);
+    fold(self.sum + u32::from_be_bytes(last) as u64)
+  }
+}
+
+fn fold(mut sum: u64) -> u32 {
+  while sum >> 32 != 0 {
+    sum = (sum & 0xFFFF_FFFF) + (sum >> 32);
+  }
+  sum as u32
+}
+
+/// Returns the 1's complement sum of the two given checksums.
+pub fn ones_complement_add(a: u32, b: u32) -> u32 {
+  fold(a as u64 + b as u64)
+}
+
+/// Returns the 16 chars ASCII encoding of the 1's complement of the given HDU checksum,
+/// i.e. the `CHECKSUM` value, the HDU checksum having been computed with
+/// `CHECKSUM = '0000000000000000'`.
+pub fn encode_checksum(hdu_sum: u32) -> [u8; 16] {
+  const EXCLUDE: [u8; 13] = [
+    0x3a, 0x3b, 0x3c, 0x3d, 0x3e, 0x3f, 0x40, 0x5b, 0x5c, 0x5d, 0x5e, 0x5f, 0x60,
+  ];
+  const OFFSET: u8 = 0x30; // ASCII '0'
+  let value = !hdu_sum;
+  let mut asc = [0_u8; 16];
+  for (i, byte) in value.to_be_bytes().into_iter().enumerate() {
+    let quotient = byte / 4 + OFFSET;
+    let mut ch = [quotient; 4];
+    ch[0] += byte % 4;
+    // Avoid ASCII punctuation, keeping the sum of the chars unchanged
+    let mut check = true;
+    while check {
+      check = false;
+      for k in EXCLUDE {
+        for j in [0, 2] {
+          if ch[j] == k || ch[j + 1] == k {
+            ch[j] += 1;
+            ch[j + 1] -= 1;
+            check = true;
+          }
+        }
+      }
+    }
+    for (j, c) in ch.into_iter().enumerate() {
+      asc[4 * j + i] = c;
+    }
+  }
+  // Rotate right by one byte to align the chars on the FITS 32-bit words
+  let mut ascii = [0_u8; 16];
+  for (i, c) in ascii.iter_mut().enumerate() {
+    *c = asc[(i + 15) % 16];
+  }
+  ascii
+}
+
+/// Returns `true` for the `CHECKSUM` and `DATASUM` keyword records, no more valid once the
+/// header or the data of a HDU have been modified (they must then be removed or re-computed).
+pub fn is_checksum_kw_record(kwr: &[u8; 80]) -> bool {
+  let kw = &kwr[KW_RANGE];
+  kw == CheckSum::KEYWORD || kw == DataSum::KEYWORD
+}
+
+/// Copies the given header without its `CHECKSUM` and `DATASUM` keyword records, to be used
+/// when the data of the HDU is modified and the checksums are not re-computed.
+pub fn copy_header_without_checksums<T: AsRef<[u8]>, W: Write>(
+  raw_header: &RawHeader<T>,
+  writer: &mut W,
+) -> Result<(), Error> {
+  let mut header_writer = HeaderWriter::new(writer);
+  for kwr in raw_header
+    .kw_records_iter()
+    .filter(|kwr| !is_checksum_kw_record(kwr))
+  {
+    header_writer
+      .next()
+      .unwrap()
+      .map(|dest_kwr| dest_kwr.copy_from_slice(kwr))?;
+  }
+  header_writer.finalize()
+}
+
+/// Result of the verification of a checksum keyword.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumStatus {
+  /// The keyword is not in the header.
+  Missing,
+  /// The keyword value matches the computed checksum.
+  Valid,
+  /// The keyword value does not match the computed checksum (or cannot be parsed).
+  Invalid,
+}
+
+/// Result of the verification of the `CHECKSUM` and `DATASUM` keywords of a HDU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChecksumVerification {
+  pub checksum: ChecksumStatus,
+  pub datasum: ChecksumStatus,
+}
+
+impl ChecksumVerification {
+  /// Returns `false` if at least one of the two keywords is invalid.
+  pub fn is_valid(&self) -> bool {
+    self.checksum != ChecksumStatus::Invalid && self.datasum != ChecksumStatus::Invalid
+  }
+}
+
+/// Returns a copy of the given HDU bytes (header, possibly followed by other bytes) in which the
+/// `CHECKSUM` and `DATASUM` keyword records have been added, if not already present, with
+/// placeholder values. The header possibly gains one block of 2880 bytes.
+/// # TIP
+/// Made to build a header whose size will not change when calling [write_checksums] on the
+/// file containing it, e.g. if byte offsets in the file are stored elsewhere.
+pub fn reserve_checksum_kw_records(hdu_bytes: &[u8]) -> Result<Vec<u8>, Error> {
+  let is_primary = hdu_bytes.starts_with(Simple::KEYWORD);
+  RawHeader::<&[u8]>::from_slice(is_primary, hdu_bytes).and_then(|(raw_header, remaining)| {
+    let mut bytes = header_with_checksum_kw_records(&raw_header, &CheckSum::ZERO, 0)?;
+    bytes.extend_from_slice(remaining);
+    Ok(bytes)
+  })
+}
+
+/// Computes and writes (or overwrites) the `CHECKSUM` and `DATASUM` keywords of all HDUs of the
+/// given FITS file, returning the number of HDUs.
+/// # Remark
+/// Headers are overwritten in place if their sizes do not change (i.e. if they already contain
+/// both keywords, or if they have enough free keyword records).
+/// Else, the full file is re-written.
+/// # Errors
+/// If the file is compressed (gzip, bzip2 or xz): it can not be modified in place.
+pub fn write_checksums<P: AsRef<Path>>(path: P) -> Result<usize, Error> {
+  let path = path.as_ref();
+  let file = File::open(path).map_err(new_io_err)?;
+  if let Some(compression) = detect_file_compression(&file)? {
+    return Err(new_custom(format!(
+      "Unable to write checksums in a {} compressed file. File: '{}'.",
+      compression,
+      path.display()
+    )));
+  }
+  let mmap = unsafe { MmapOptions::new().map(&file) }.map_err(new_io_err)?;
+  let fits = FitsBytes::from_slice(mmap.as_ref());
+  // Compute all new headers before writing anything
+  let mut headers = Vec::new();
+  let mut same_size = true;
+  for hdu in fits.new_iterator::<Bintable>() {
+    let hdu = hdu?;
+    let header = stamped_header(&hdu)?;
+    same_size &= header.len() == hdu.raw_header.byte_size();
+    headers.push((hdu, header));
+  }
+  let n_hdu = headers.len();
+  if same_size {
+    debug!("Overwrite headers in place...");
+    let mut file = OpenOptions::new()
+      .write(true)
+      .open(path)
+      .map_err(new_io_err)?;
+    for (hdu, header) in headers {
+      file
+        .seek(SeekFrom::Start(hdu.starting_byte as u64))
+        .and_then(|_| file.write_all(&header))
+        .map_err(new_io_err)?;
+    }
+    file.flush().map_err(new_io_err).map(|()| n_hdu)
+  } else {
+    debug!("Header size changed: re-write the full file...");
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".checksum.tmp");
+    let mut writer = File::create(&tmp_path)
+      .map(BufWriter::new)
+      .map_err(new_io_err)?;
+    for (hdu, header) in headers {
+      writer
+        .write_all(&header)
+        .and_then(|()| writer.write_all(hdu.data))
+        .and_then(|()| writer.write_all(hdu.padding))
+        .map_err(new_io_err)?;
+      // Complete a truncated last block, if any
+      let rem2880 = (hdu.data.len() + hdu.padding.len()) % 2880;
+      if rem2880 != 0 {
+        writer
+          .write_all(vec![0_u8; 2880 - rem2880].as_slice())
+          .map_err(new_io_err)?;
+      }
+    }
+    writer.flush().map_err(new_io_err)?;
+    drop(writer);
+    drop(mmap);
+    fs::rename(&tmp_path, path)
+      .map_err(new_io_err)
+      .map(|()| n_hdu)
+  }
+}
+
+/// Returns the header of the given HDU with the `CHECKSUM` and `DATASUM` keyword records set.
+fn stamped_header(hdu: &HDU<Bintable>) -> Result<Vec<u8>, Error> {
+  let datasum = hdu.datasum();
+  let mut header = header_with_checksum_kw_records(&hdu.raw_header, &CheckSum::ZERO, datasum)?;
+  let hdu_sum = ones_complement_add(OnesComplementSum::new().update(&header).value(), datasum);
+  let checksum = CheckSum::new(encode_checksum(hdu_sum));
+  let kwr = header
+    .chunks_exact_mut(80)
+    .find(|kwr| &kwr[KW_RANGE] == CheckSum::KEYWORD)
+    .unwrap(); // Added by `header_with_checksum_kw_records`
+  checksum.write_kw_record(&mut std::iter::once(Ok(kwr.try_into().unwrap())))?;
+  Ok(header)
+}
+
+/// Returns a copy of the given header in which the `CHECKSUM` and `DATASUM` keyword records
+/// are overwritten, or inserted before `END` if not present.
+fn header_with_checksum_kw_records<T: AsRef<[u8]>>(
+  raw_header: &RawHeader<T>,
+  checksum: &CheckSum,
+  datasum: u32,
+) -> Result<Vec<u8>, Error> {
+  let mut checksum_kwr = [b' '; 80];
+  let mut datasum_kwr = [b' '; 80];
+  checksum.write_kw_record(&mut std::iter::once(Ok(&mut checksum_kwr)))?;
+  DataSum::new(datasum).write_kw_record(&mut std::iter::once(Ok(&mut datasum_kwr)))?;
+  let mut has_checksum = false;
+  let mut has_datasum = false;
+  let mut bytes = Vec::with_capacity(raw_header.byte_size() + 2880);
+  for kwr in raw_header.kw_records_iter() {
+    if &kwr[KW_RANGE] == CheckSum::KEYWORD {
+      has_checksum = true;
+      bytes.extend_from_slice(&checksum_kwr);
+    } else if &kwr[KW_RANGE] == DataSum::KEYWORD {
+      has_datasum = true;
+      bytes.extend_from_slice(&datasum_kwr);
+    } else {
+      bytes.extend_from_slice(kwr);
+    }
+  }
+  if !has_checksum {
+    bytes.extend_from_slice(&checksum_kwr);
+  }
+  if !has_datasum {
+    bytes.extend_from_slice(&datasum_kwr);
+  }
+  bytes.extend_from_slice(b"END");
+  bytes.resize(bytes.len().div_ceil(2880) * 2880, b' ');
+  Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_encode_checksum() {
+    // Example of the FITS checksum convention document
+    assert_eq!(&encode_checksum(868229149), b"hcHjjc9ghcEghc9g");
+  }
+
+  #[test]
+  fn test_ones_complement_sum() {
+    // Bytes provided in several chunks, the incomplete last word being completed by zeros
+    let mut sum = OnesComplementSum::new();
+    sum.update(&[0x01, 0x02]).update(&[0x03, 0x04, 0x05]);
+    assert_eq!(sum.value(), 0x06020304);
+    // Carries are added back to the sum
+    let mut sum = OnesComplementSum::new();
+    sum.update(&[0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x02]);
+    assert_eq!(sum.value(), 0x00000002);
+    assert_eq!(ones_complement_add(0xFFFFFFFE, 0x00000003), 0x00000002);
+  }
+
+  #[test]
+  fn test_stamped_header() {
+    let mut bytes = Vec::new();
+    for kwr in [
+      "SIMPLE  =                    T",
+      "BITPIX  =                    8",
+      "NAXIS   =                    0",
+      "END",
+    ] {
+      bytes.extend_from_slice(format!("{:<80}", kwr).as_bytes());
+    }
+    bytes.resize(2880, b' ');
+    let fits = FitsBytes::from_slice(&bytes);
+    let hdu = fits.new_iterator::<Bintable>().next().unwrap().unwrap();
+    let header = stamped_header(&hdu).unwrap();
+    assert_eq!(header.len(), 2880);
+    let kwrs: Vec<&[u8]> = header.chunks_exact(80).collect();
+    assert!(kwrs[3].starts_with(b"CHECKSUM= '"));
+    assert!(kwrs[4].starts_with(b"DATASUM = '0'"));
+    // The checksum of a valid HDU is `-0` in 1's complement
+    assert_eq!(OnesComplementSum::new().update(&header).value(), 0xFFFFFFFF);
+    // Removing the keywords gives back the original header
+    let (raw_header, _) = RawHeader::<&[u8]>::from_slice(true, &header).unwrap();
+    let mut copy = Vec::new();
+    copy_header_without_checksums(&raw_header, &mut copy).unwrap();
+    assert_eq!(copy, bytes);
+  }
+}
@@ -0,0 +1,148 @@
+//! Module dedicated to the transparent reading of compressed FITS files (e.g. `.fits.gz`),
+//! the compression algorithm being detected from the first (magic) bytes of the input:
+//! * `gzip`, always supported
+//! * `bzip2`, requires the `bzip2` feature
+//! * `xz`, requires the `xz` feature
+//!
+//! A memory mapped compressed file is first decompressed in a temporary file.
+
+use std::{
+  fmt::{Display, Formatter},
+  fs::File,
+  io::{self, Cursor, Read, Seek, SeekFrom},
+};
+
+use flate2::read::MultiGzDecoder;
+use log::debug;
+use memmap2::{Mmap, MmapOptions};
+
+use crate::error::{Error, new_custom, new_io_err};
+
+/// Number of bytes needed to detect all supported compression algorithms.
+const MAGIC_BYTES_LEN: usize = 6;
+
+/// Compression algorithms detected from the first bytes of the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+  Gzip,
+  Bzip2,
+  Xz,
+}
+
+impl Compression {
+  const GZIP_MAGIC: &'static [u8] = &[0x1F, 0x8B];
+  const BZIP2_MAGIC: &'static [u8] = b"BZh";
+  const XZ_MAGIC: &'static [u8] = &[0xFD, b'7', b'z', b'X', b'Z', 0x00];
+
+  /// Returns the compression algorithm from the first bytes of the input, or `None` if the
+  /// input is not compressed (or compressed with an unknown algorithm).
+  pub fn from_magic_bytes(bytes: &[u8]) -> Option<Self> {
+    if bytes.starts_with(Self::GZIP_MAGIC) {
+      Some(Self::Gzip)
+    } else if bytes.starts_with(Self::BZIP2_MAGIC) {
+      Some(Self::Bzip2)
+    } else if bytes.starts_with(Self::XZ_MAGIC) {
+      Some(Self::Xz)
+    } else {
+      None
+    }
+  }
+
+  /// Returns a reader decompressing the given (compressed) reader.
+  /// # Errors
+  /// If the library has been compiled without the feature supporting the compression algorithm.
+  pub fn decoder<'a, R: Read + 'a>(&self, reader: R) -> Result<Box<dyn Read + 'a>, Error> {
+    match self {
+      Self::Gzip => Ok(Box::new(MultiGzDecoder::new(reader))),
+      #[cfg(feature = "bzip2")]
+      Self::Bzip2 => Ok(Box::new(bzip2::read::MultiBzDecoder::new(reader))),
+      #[cfg(feature = "xz")]
+      Self::Xz => Ok(Box::new(liblzma::read::XzDecoder::new_multi_decoder(
+        reader,
+      ))),
+      #[allow(unreachable_patterns)]
+      _ => Err(new_custom(format!(
+        "{} compressed input not supported: compile with the '{}' feature.",
+        self,
+        self.feature()
+      ))),
+    }
+  }
+
+  /// Name of the feature required to support the compression algorithm.
+  fn feature(&self) -> &'static str {
+    match self {
+      Self::Gzip => "default",
+      Self::Bzip2 => "bzip2",
+      Self::Xz => "xz",
+    }
+  }
+}
+
+impl Display for Compression {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    f.write_str(match self {
+      Self::Gzip => "gzip",
+      Self::Bzip2 => "bzip2",
+      Self::Xz => "xz",
+    })
+  }
+}
+
+/// Read (at most) the first `MAGIC_BYTES_LEN` bytes of the given reader.
+fn read_magic_bytes<R: Read>(reader: &mut R) -> Result<Vec<u8>, Error> {
+  let mut magic = Vec::with_capacity(MAGIC_BYTES_LEN);
+  reader
+    .take(MAGIC_BYTES_LEN as u64)
+    .read_to_end(&mut magic)
+    .map_err(new_io_err)?;
+  Ok(magic)
+}
+
+/// Detects the compression algorithm of the given file, the file cursor being reset to the
+/// start of the file.
+pub fn detect_file_compression(mut file: &File) -> Result<Option<Compression>, Error> {
+  let magic = file
+    .seek(SeekFrom::Start(0))
+    .map_err(new_io_err)
+    .and_then(|_| read_magic_bytes(&mut file))?;
+  file
+    .seek(SeekFrom::Start(0))
+    .map_err(new_io_err)
+    .map(|_| Compression::from_magic_bytes(&magic))
+}
+
+/// Returns a reader providing the decompressed bytes of the given reader if it is compressed,
+/// else providing the bytes of the given reader.
+/// Made to transparently read compressed FITS files in streaming mode (e.g. from stdin).
+pub fn decompressed_reader<'a, R: Read + 'a>(mut reader: R) -> Result<Box<dyn Read + 'a>, Error> {
+  let magic = read_magic_bytes(&mut reader)?;
+  let compression = Compression::from_magic_bytes(&magic);
+  // Put the magic bytes back in front of the reader
+  let reader = Cursor::new(magic).chain(reader);
+  match compression {
+    Some(compression) => {
+      debug!("{} compressed input detected", compression);
+      compression.decoder(reader)
+    }
+    None => Ok(Box::new(reader)),
+  }
+}
+
+/// Memory maps the given FITS file.
+/// If the file is compressed, it is first decompressed in a temporary file which is then
+/// memory mapped (the temporary file is automatically removed).
+pub fn mmap(file: &File) -> Result<Mmap, Error> {
+  match detect_file_compression(file)? {
+    None => unsafe { MmapOptions::new().map(file) }.map_err(new_io_err),
+    Some(compression) => {
+      debug!(
+        "{} compressed file detected: decompress it in a temporary file",
+        compression
+      );
+      let mut tmp_file = tempfile::tempfile().map_err(new_io_err)?;
+      io::copy(&mut compression.decoder(file)?, &mut tmp_file).map_err(new_io_err)?;
+      unsafe { MmapOptions::new().map(&tmp_file) }.map_err(new_io_err)
+    }
+  }
+}
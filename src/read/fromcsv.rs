@@ -0,0 +1,434 @@
+//! Module dedicated to the conversion of a CSV file into a FITS file containing a single
+//! BINTABLE, the type of each column being inferred from its values.
+
+use std::{
+  collections::HashMap,
+  fs::File,
+  io::{BufRead, BufReader, BufWriter, Write},
+  mem,
+  path::Path,
+};
+
+use log::{debug, warn};
+
+use crate::{
+  error::{Error, new_custom, new_io_err},
+  hdu::{
+    primary::header::PrimaryHeader,
+    xtension::bintable::{
+      field::Field,
+      header::BinTableColumnHeader,
+      schema::{ArrayParam, Schema},
+      write::BinTableWriter,
+    },
+  },
+};
+
+/// Metadata overriding the ones of a CSV column.
+#[derive(Debug, Clone, Default)]
+pub struct ColumnSpec {
+  /// New column name (`TTYPEn`), replacing the one of the CSV header.
+  pub name: Option<String>,
+  /// Column unit (`TUNITn`).
+  pub unit: Option<String>,
+  /// Column UCD (`TUCDn`).
+  pub ucd: Option<String>,
+  /// Column description (`TCOMMn`).
+  pub description: Option<String>,
+}
+
+/// Reads the given CSV file and writes a FITS file made of an empty primary HDU followed by
+/// a BINTABLE containing the CSV rows. Returns the number of rows written.
+///
+/// # Params
+/// * `input`: path of the input CSV file, the first line containing the column names
+/// * `separator`: field separator (e.g. `,`)
+/// * `sample`: number of rows scanned to infer the column types (`None` to scan all rows)
+/// * `specs`: metadata overriding the ones of the columns, keyed by the CSV column names
+/// * `output`: path of the output FITS file
+///
+/// # Remarks
+/// * the input file is read twice: first to infer the columns types, then to convert the rows
+/// * the narrowest type is chosen among: boolean (`T`/`F`/`true`/`false`), short, int, long,
+///   float (if the shortest decimal representation of all values is unchanged in single
+///   precision), double, ASCII char and ASCII string (of the maximum length found), non-ASCII
+///   chars being replaced by `?`
+/// * empty cells are NULL values: `TNULLn` is set for integer columns containing empty cells
+///   (or always when using a sample) and NULL reals are NaN
+/// * when using a sample, an error is returned if a value does not fit the inferred type
+pub fn csv_to_fits<P: AsRef<Path>, Q: AsRef<Path>>(
+  input: P,
+  separator: char,
+  sample: Option<usize>,
+  specs: &HashMap<String, ColumnSpec>,
+  output: Q,
+) -> Result<u64, Error> {
+  debug!("Start CSV to FITS conversion...");
+  // First pass: infer the column types
+  let mut records = CsvRecords::open(&input, separator)?;
+  let col_names = records
+    .next()
+    .ok_or_else(|| new_custom("Empty CSV file!"))??;
+  let mut infos = vec![ColInfo::default(); col_names.len()];
+  for _ in 0..sample.unwrap_or(usize::MAX) {
+    let Some(record) = records.next() else {
+      break;
+    };
+    let record = check_n_fields(record?, col_names.len(), records.line_num)?;
+    for (info, value) in infos.iter_mut().zip(record) {
+      info.update(value.trim());
+    }
+  }
+  let schemas: Vec<Schema> = infos
+    .iter()
+    .map(|info| info.schema(sample.is_some()))
+    .collect();
+
+  // Build the columns headers
+  for name in specs.keys() {
+    if !col_names.contains(name) {
+      warn!("Column '{}' of the columns specifications not found.", name);
+    }
+  }
+  let cols = col_names
+    .iter()
+    .zip(schemas.iter())
+    .enumerate()
+    .map(|(i, (name, schema))| {
+      debug!("Column '{}' schema: {}", name, schema);
+      let icol = i as u16;
+      let mut col = BinTableColumnHeader::from_schema(icol, schema)?;
+      let spec = specs.get(name).cloned().unwrap_or_default();
+      col.set_colname(icol, spec.name.unwrap_or_else(|| name.clone()));
+      if let Some(unit) = spec.unit {
+        col.set_unit(icol, unit);
+      }
+      if let Some(ucd) = spec.ucd {
+        col.set_ucd(icol, ucd);
+      }
+      if let Some(description) = spec.description {
+        col.set_description(icol, description);
+      }
+      Ok(col)
+    })
+    .collect::<Result<Vec<BinTableColumnHeader>, Error>>()?;
+
+  // Write the primary HDU and the BINTABLE
+  let output_file = File::create(output).map_err(new_io_err)?;
+  let mut writer = BufWriter::new(output_file);
+  PrimaryHeader::write_empty_hdu(&mut writer)?;
+  let mut bintable_writer = BinTableWriter::new(writer, cols)?;
+
+  // Second pass: write the rows
+  let mut records = CsvRecords::open(&input, separator)?;
+  records.next().transpose()?; // Skip the header line
+  while let Some(record) = records.next() {
+    let row = check_n_fields(record?, col_names.len(), records.line_num)?
+      .iter()
+      .zip(schemas.iter())
+      .zip(col_names.iter())
+      .map(|((value, schema), name)| {
+        to_field(schema, value.trim()).map_err(|e| {
+          new_custom(format!(
+            "Error at line {}, column '{}': {}",
+            records.line_num, name, e
+          ))
+        })
+      })
+      .collect::<Result<Vec<Field>, Error>>()?;
+    bintable_writer.write_row(&row)?;
+  }
+  let n_rows = bintable_writer.n_rows();
+  bintable_writer
+    .finish()?
+    .flush()
+    .map_err(new_io_err)
+    .map(|()| n_rows)
+}
+
+fn check_n_fields(
+  record: Vec<String>,
+  n_cols: usize,
+  line_num: usize,
+) -> Result<Vec<String>, Error> {
+  if record.len() == n_cols {
+    Ok(record)
+  } else {
+    Err(new_custom(format!(
+      "Wrong number of fields at line {}. Expected: {}. Actual: {}.",
+      line_num,
+      n_cols,
+      record.len()
+    )))
+  }
+}
+
+/// Information gathered on the (non-empty) values of a column to infer its type.
+#[derive(Debug, Clone)]
+struct ColInfo {
+  /// The column contains empty values.
+  has_null: bool,
+  /// Number of non-empty values.
+  n_values: u64,
+  /// All values are booleans.
+  all_bool: bool,
+  /// All values are integers.
+  all_int: bool,
+  /// All values are reals (integers included).
+  all_real: bool,
+  /// All real values are exactly represented in single precision.
+  all_f32: bool,
+  min: i64,
+  max: i64,
+  /// Maximum number of chars.
+  max_len: usize,
+}
+
+impl Default for ColInfo {
+  fn default() -> Self {
+    Self {
+      has_null: false,
+      n_values: 0,
+      all_bool: true,
+      all_int: true,
+      all_real: true,
+      all_f32: true,
+      min: i64::MAX,
+      max: i64::MIN,
+      max_len: 0,
+    }
+  }
+}
+
+impl ColInfo {
+  fn update(&mut self, value: &str) {
+    if value.is_empty() {
+      self.has_null = true;
+      return;
+    }
+    self.n_values += 1;
+    self.max_len = self.max_len.max(value.chars().count());
+    self.all_bool &= parse_bool(value).is_some();
+    if self.all_int {
+      match value.parse::<i64>() {
+        Ok(v) => {
+          self.min = self.min.min(v);
+          self.max = self.max.max(v);
+        }
+        Err(_) => self.all_int = false,
+      }
+    }
+    if self.all_real {
+      match value.parse::<f64>() {
+        Ok(v) => self.all_f32 &= is_f32(v),
+        Err(_) => self.all_real = false,
+      }
+    }
+  }
+
+  /// # Params
+  /// * `nullable`: force integer columns to be nullable (i.e. to have a `TNULLn`)
+  fn schema(&self, nullable: bool) -> Schema {
+    let nullable = nullable || self.has_null;
+    // The smallest value of the type is reserved for NULL
+    let fits =
+      |min: i64, max: i64| (if nullable { min + 1 } else { min }) <= self.min && self.max <= max;
+    if self.n_values == 0 {
+      Schema::AsciiChar
+    } else if self.all_bool {
+      Schema::NullableBoolean
+    } else if self.all_int {
+      match (
+        nullable,
+        fits(i16::MIN as i64, i16::MAX as i64),
+        fits(i32::MIN as i64, i32::MAX as i64),
+      ) {
+        (false, true, _) => Schema::Short,
+        (true, true, _) => Schema::NullableShort { null: i16::MIN },
+        (false, false, true) => Schema::Int,
+        (true, false, true) => Schema::NullableInt { null: i32::MIN },
+        (false, false, false) => Schema::Long,
+        (true, false, false) => Schema::NullableLong { null: i64::MIN },
+      }
+    } else if self.all_real {
+      if self.all_f32 {
+        Schema::Float
+      } else {
+        Schema::Double
+      }
+    } else if self.max_len == 1 {
+      Schema::AsciiChar
+    } else {
+      Schema::AsciiString(ArrayParam::new(self.max_len))
+    }
+  }
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+  match value {
+    "T" | "t" | "true" | "True" | "TRUE" => Some(true),
+    "F" | "f" | "false" | "False" | "FALSE" => Some(false),
+    _ => None,
+  }
+}
+
+/// Returns `true` if the shortest decimal representation of the given value, converted in
+/// single precision, leads back to the same value.
+fn is_f32(v: f64) -> bool {
+  !v.is_finite() || (v as f32).to_string().parse::<f64>() == Ok(v)
+}
+
+/// Convert a CSV value into a BINTABLE field of the given schema.
+fn to_field(schema: &Schema, value: &str) -> Result<Field, Error> {
+  fn parse<T: std::str::FromStr>(value: &str) -> Result<T, Error> {
+    value.parse::<T>().map_err(|_| {
+      new_custom(format!(
+        "Value '{}' does not fit the inferred type (increase the sample size?).",
+        value
+      ))
+    })
+  }
+  fn parse_opt<T: std::str::FromStr>(value: &str) -> Result<Option<T>, Error> {
+    if value.is_empty() {
+      Ok(None)
+    } else {
+      parse(value).map(Some)
+    }
+  }
+  match schema {
+    Schema::NullableBoolean if value.is_empty() => Ok(Field::NullableBoolean(None)),
+    Schema::NullableBoolean => parse_bool(value)
+      .map(|v| Field::NullableBoolean(Some(v)))
+      .ok_or_else(|| {
+        new_custom(format!(
+          "Value '{}' is not a boolean (increase the sample size?).",
+          value
+        ))
+      }),
+    Schema::Short => parse(value).map(Field::Short),
+    Schema::NullableShort { null } => parse_opt(value)
+      .and_then(|v| not_null(v, *null))
+      .map(Field::NullableShort),
+    Schema::Int => parse(value).map(Field::Int),
+    Schema::NullableInt { null } => parse_opt(value)
+      .and_then(|v| not_null(v, *null))
+      .map(Field::NullableInt),
+    Schema::Long => parse(value).map(Field::Long),
+    Schema::NullableLong { null } => parse_opt(value)
+      .and_then(|v| not_null(v, *null))
+      .map(Field::NullableLong),
+    Schema::Float => parse_opt(value).map(|v| Field::Float(v.unwrap_or(f32::NAN))),
+    Schema::Double => parse_opt(value).map(|v| Field::Double(v.unwrap_or(f64::NAN))),
+    Schema::AsciiChar | Schema::AsciiString(_) => {
+      let max_len = match schema {
+        Schema::AsciiString(p) => p.get_len(),
+        _ => 1,
+      };
+      if value.chars().count() > max_len {
+        return Err(new_custom(format!(
+          "Value '{}' longer than the inferred max length {} (increase the sample size?).",
+          value, max_len
+        )));
+      }
+      let value: String = value.chars().map(to_ascii).collect();
+      Ok(match schema {
+        Schema::AsciiString(_) => Field::AsciiString(value),
+        _ => Field::AsciiChar(value.bytes().next().unwrap_or(0)),
+      })
+    }
+    _ => unreachable!(), // Schemas not built by `ColInfo::schema`
+  }
+}
+
+fn to_ascii(c: char) -> char {
+  if c.is_ascii() { c } else { '?' }
+}
+
+/// Returns an error if the given value equals the value coding NULL.
+fn not_null<T: PartialEq + std::fmt::Display>(
+  value: Option<T>,
+  null: T,
+) -> Result<Option<T>, Error> {
+  match value {
+    Some(v) if v == null => Err(new_custom(format!(
+      "Value '{}' is reserved for NULL (increase the sample size?).",
+      v
+    ))),
+    _ => Ok(value),
+  }
+}
+
+/// Iterates over the records of a CSV file, each record being a list of fields.
+/// Fields may be quoted (using `"`, and `""` for a quote inside a quoted field), quoted fields
+/// possibly containing separators and new lines. Empty lines are ignored.
+struct CsvRecords<R: BufRead> {
+  reader: R,
+  separator: char,
+  line: String,
+  /// Number of the last read line, starting at 1.
+  line_num: usize,
+}
+
+impl CsvRecords<BufReader<File>> {
+  fn open<P: AsRef<Path>>(path: P, separator: char) -> Result<Self, Error> {
+    File::open(path).map_err(new_io_err).map(|file| Self {
+      reader: BufReader::new(file),
+      separator,
+      line: String::new(),
+      line_num: 0,
+    })
+  }
+}
+
+impl<R: BufRead> Iterator for CsvRecords<R> {
+  type Item = Result<Vec<String>, Error>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let mut fields: Vec<String> = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    loop {
+      self.line.clear();
+      match self.reader.read_line(&mut self.line) {
+        Ok(0) if in_quotes => {
+          return Some(Err(new_custom(format!(
+            "Unterminated quoted field at line {}.",
+            self.line_num
+          ))));
+        }
+        Ok(0) => return None,
+        Ok(_) => self.line_num += 1,
+        Err(e) => return Some(Err(new_io_err(e))),
+      }
+      let line = self.line.trim_end_matches(['\n', '\r']);
+      if !in_quotes && line.is_empty() {
+        continue;
+      }
+      let mut chars = line.chars().peekable();
+      while let Some(c) = chars.next() {
+        if in_quotes {
+          if c != '"' {
+            field.push(c);
+          } else if chars.peek() == Some(&'"') {
+            chars.next();
+            field.push('"');
+          } else {
+            in_quotes = false;
+          }
+        } else if c == '"' && field.is_empty() {
+          in_quotes = true;
+        } else if c == self.separator {
+          fields.push(mem::take(&mut field));
+        } else {
+          field.push(c);
+        }
+      }
+      if in_quotes {
+        field.push('\n');
+      } else {
+        fields.push(field);
+        return Some(Ok(fields));
+      }
+    }
+  }
+}
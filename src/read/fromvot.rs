@@ -0,0 +1,410 @@
+//! Module dedicated to the conversion of a VOTable (`TABLEDATA`, `BINARY` or `BINARY2`) into
+//! a FITS-plus file, i.e. a primary HDU containing the VOTable metadata followed by one BINTABLE
+//! per VOTable `TABLE`.
+
+use std::{
+  fs::File,
+  io::{BufWriter, Write},
+  path::Path,
+};
+
+use log::debug;
+use votable::{
+  Resource, TableElem, VOTableError, VoidTableDataContent,
+  data::{Data, DataElem},
+  datatype::Datatype as VOTDatatype,
+  field::Field as VOTField,
+  impls::VOTableValue,
+  iter::VOTableIterator,
+  resource::ResourceOrTable,
+};
+
+use crate::{
+  error::{Error, new_custom, new_io_err},
+  hdu::{
+    primary::header::PrimaryHeaderWithVOTable,
+    xtension::bintable::{
+      field::{ComplexF32, ComplexF64, Field},
+      header::BinTableColumnHeader,
+      schema::{HeapArraySchema, Schema},
+      write::BinTableWriter,
+    },
+  },
+};
+
+/// Reads the given VOTable and writes a FITS-plus file made of a primary HDU containing
+/// the VOTable metadata (without the data), followed by one BINTABLE per VOTable `TABLE`.
+/// Returns the number of BINTABLEs written.
+///
+/// # Params
+/// * `input`: path of the input VOTable, containing `TABLEDATA`, `BINARY` or `BINARY2` data
+/// * `output`: path of the output FITS file
+///
+/// # Remarks
+/// * the input file is read twice: first to get the metadata, then to convert the rows
+/// * integer columns without `null` value in VOTable get a `TNULLn` (the smallest value of the
+///   type, or 255 for unsigned bytes) since `TABLEDATA` and `BINARY2` may contain NULL values
+/// * `unicodeChar` columns are converted into ASCII columns, non-ASCII chars being replaced by `?`
+/// * bit arrays (fixed or variable length) are not supported, only single bits
+/// * a `TABLE` without `DATA` element is converted into a BINTABLE without rows
+pub fn vot_to_fits_plus<P: AsRef<Path>, Q: AsRef<Path>>(input: P, output: Q) -> Result<u16, Error> {
+  debug!("Start VOTable to FITS-plus conversion...");
+  // First pass: read the metadata and remove the (empty) DATA elements
+  let mut votable = VOTableIterator::from_file(&input)
+    .and_then(|vot_it| vot_it.read_all_skipping_data())
+    .map_err(new_vot_err)?;
+  let mut tables_meta: Vec<TableMeta> = Vec::new();
+  for resource in votable.resources.iter_mut() {
+    remove_data(resource, &mut tables_meta)?;
+  }
+  let n_table = u16::try_from(tables_meta.len())
+    .map_err(|_| new_custom(format!("Too many tables: {}.", tables_meta.len())))?;
+  let xml = votable.wrap().to_ivoa_xml_bytes().map_err(new_vot_err)?;
+
+  // Write the primary HDU
+  let output_file = File::create(output).map_err(new_io_err)?;
+  let mut writer = BufWriter::new(output_file);
+  PrimaryHeaderWithVOTable::write_fits_plus_hdu(&mut writer, n_table, &xml)?;
+  debug!(
+    "Primary HDU written, containing {} tables metadata",
+    n_table
+  );
+
+  // Second pass: write one BINTABLE per table
+  // (the row iterator skips the tables without data, which have no rows)
+  let mut vot_it = VOTableIterator::from_file(&input).map_err(new_vot_err)?;
+  for TableMeta { fields, has_data } in tables_meta {
+    let cols = fields
+      .iter()
+      .enumerate()
+      .map(|(i, field)| to_col_header(i as u16, field))
+      .collect::<Result<Vec<BinTableColumnHeader>, Error>>()?;
+    let mut bintable_writer = BinTableWriter::new(writer, cols)?;
+    if has_data {
+      let row_it = vot_it
+        .next_table_row_value_iter()
+        .map_err(new_vot_err)?
+        .ok_or_else(|| new_custom("Less tables than in the VOTable metadata!"))?;
+      let schemas: Vec<Schema> = bintable_writer
+        .row_schema()
+        .fields_schemas()
+        .iter()
+        .map(|field_schema| field_schema.schema.clone())
+        .collect();
+      for row in row_it {
+        let row = row
+          .map_err(new_vot_err)?
+          .into_iter()
+          .zip(schemas.iter())
+          .map(|(value, schema)| to_field(schema, value))
+          .collect::<Result<Vec<Field>, Error>>()?;
+        bintable_writer.write_row(&row)?;
+      }
+    }
+    writer = bintable_writer.finish()?;
+  }
+  if vot_it
+    .next_table_row_value_iter()
+    .map_err(new_vot_err)?
+    .is_some()
+  {
+    return Err(new_custom("More tables than in the VOTable metadata!"));
+  }
+  writer.flush().map_err(new_io_err).map(|()| n_table)
+}
+
+fn new_vot_err(e: VOTableError) -> Error {
+  new_custom(format!("VOTable error: {}", e))
+}
+
+/// Metadata of a VOTable `TABLE`, gathered in the first pass.
+struct TableMeta {
+  fields: Vec<VOTField>,
+  /// `false` if the table has no `DATA` element, i.e. no rows.
+  has_data: bool,
+}
+
+/// Remove the `DATA` element of the tables of the given resource (and of its sub-resources),
+/// pushing the metadata of each table in `tables_meta`.
+fn remove_data(
+  resource: &mut Resource<VoidTableDataContent>,
+  tables_meta: &mut Vec<TableMeta>,
+) -> Result<(), Error> {
+  for sub_elem in resource.sub_elems.iter_mut() {
+    match &mut sub_elem.resource_or_table {
+      ResourceOrTable::Resource(r) => remove_data(r, tables_meta)?,
+      ResourceOrTable::Table(table) => {
+        let has_data = match table.data.take() {
+          Some(Data {
+            data: DataElem::TableData(_) | DataElem::Binary(_) | DataElem::Binary2(_),
+            ..
+          }) => true,
+          None => false,
+          Some(_) => {
+            return Err(new_custom(format!(
+              "TABLE '{}' contains no TABLEDATA, BINARY or BINARY2 data.",
+              table.name.as_deref().unwrap_or("")
+            )));
+          }
+        };
+        let fields = table
+          .elems
+          .iter()
+          .filter_map(|elem| match elem {
+            TableElem::Field(field) => Some(field.clone()),
+            _ => None,
+          })
+          .collect();
+        tables_meta.push(TableMeta { fields, has_data });
+      }
+    }
+  }
+  Ok(())
+}
+
+/// Build the header of the BINTABLE column corresponding to the given VOTable field.
+fn to_col_header(icol: u16, field: &VOTField) -> Result<BinTableColumnHeader, Error> {
+  let mut col_header = BinTableColumnHeader::default();
+  match (&field.datatype, &field.arraysize) {
+    (VOTDatatype::Bit, Some(arraysize)) if arraysize.is_variable() => {
+      return Err(new_custom(format!(
+        "Variable length bit arrays not supported. Column: '{}'.",
+        field.name
+      )));
+    }
+    (VOTDatatype::CharUnicode, _) => {
+      let mut field = field.clone();
+      field.datatype = VOTDatatype::CharASCII;
+      col_header.merge(icol, &field, true);
+    }
+    _ => col_header.merge(icol, field, true),
+  }
+  let null = match col_header.schema() {
+    // Only single bits are converted (into `1X` columns)
+    Some(Schema::Bits { n_bits }) if n_bits > 1 => {
+      return Err(new_custom(format!(
+        "Bit arrays not supported. Column: '{}'.",
+        field.name
+      )));
+    }
+    Some(Schema::UnsignedByte) => Some(u8::MAX as i64),
+    Some(Schema::Short) => Some(i16::MIN as i64),
+    Some(Schema::Int) => Some(i32::MIN as i64),
+    Some(Schema::Long) => Some(i64::MIN),
+    Some(_) => None,
+    None => {
+      return Err(new_custom(format!(
+        "Unable to build the schema of column '{}'.",
+        field.name
+      )));
+    }
+  };
+  if let Some(null) = null {
+    col_header.set_null(icol, null);
+  }
+  Ok(col_header)
+}
+
+/// Convert a VOTable value into a BINTABLE field of the given schema.
+fn to_field(schema: &Schema, value: VOTableValue) -> Result<Field, Error> {
+  use VOTableValue as V;
+  match (schema, value) {
+    (Schema::Empty(_), _) => Ok(Field::Empty),
+    // Booleans and bits
+    (Schema::NullableBoolean, V::Bool(v)) => Ok(Field::NullableBoolean(Some(v))),
+    (Schema::NullableBoolean, V::Null) => Ok(Field::NullableBoolean(None)),
+    (Schema::Bits { n_bits: 1 }, V::Bool(v)) => Ok(Field::BitArray(vec![(v as u8) << 7])),
+    (Schema::Bits { n_bits: 1 }, V::Null) => Ok(Field::BitArray(vec![0])),
+    // Integers
+    (Schema::UnsignedByte, V::Byte(v)) => Ok(Field::UnsignedByte(v)),
+    (Schema::NullableUnsignedByte { .. }, V::Byte(v)) => Ok(Field::NullableUnsignedByte(Some(v))),
+    (Schema::NullableUnsignedByte { .. }, V::Null) => Ok(Field::NullableUnsignedByte(None)),
+    (Schema::Short, V::Short(v)) => Ok(Field::Short(v)),
+    (Schema::NullableShort { .. }, V::Short(v)) => Ok(Field::NullableShort(Some(v))),
+    (Schema::NullableShort { .. }, V::Null) => Ok(Field::NullableShort(None)),
+    (Schema::Int, V::Int(v)) => Ok(Field::Int(v)),
+    (Schema::NullableInt { .. }, V::Int(v)) => Ok(Field::NullableInt(Some(v))),
+    (Schema::NullableInt { .. }, V::Null) => Ok(Field::NullableInt(None)),
+    (Schema::Long, V::Long(v)) => Ok(Field::Long(v)),
+    (Schema::NullableLong { .. }, V::Long(v)) => Ok(Field::NullableLong(Some(v))),
+    (Schema::NullableLong { .. }, V::Null) => Ok(Field::NullableLong(None)),
+    // Reals
+    (Schema::Float, V::Float(v)) => Ok(Field::Float(v)),
+    (Schema::Float, V::Double(v)) => Ok(Field::Float(v as f32)),
+    (Schema::Float, V::Null) => Ok(Field::Float(f32::NAN)),
+    (Schema::Double, V::Double(v)) => Ok(Field::Double(v)),
+    (Schema::Double, V::Float(v)) => Ok(Field::Double(v as f64)),
+    (Schema::Double, V::Null) => Ok(Field::Double(f64::NAN)),
+    // Complex
+    (Schema::ComplexFloat, V::ComplexFloat((r, i))) => {
+      Ok(Field::ComplexFloat(ComplexF32::new(r, i)))
+    }
+    (Schema::ComplexFloat, V::Null) => Ok(Field::ComplexFloat(ComplexF32::new(f32::NAN, f32::NAN))),
+    (Schema::ComplexDouble, V::ComplexDouble((r, i))) => {
+      Ok(Field::ComplexDouble(ComplexF64::new(r, i)))
+    }
+    (Schema::ComplexDouble, V::Null) => {
+      Ok(Field::ComplexDouble(ComplexF64::new(f64::NAN, f64::NAN)))
+    }
+    // Chars and strings
+    (Schema::AsciiChar, V::CharASCII(c) | V::CharUnicode(c)) => Ok(Field::AsciiChar(to_ascii(c))),
+    (Schema::AsciiChar, V::String(s)) => Ok(Field::AsciiChar(
+      s.chars().next().map(to_ascii).unwrap_or(0),
+    )),
+    (Schema::AsciiChar, V::Null) => Ok(Field::AsciiChar(0)),
+    (Schema::AsciiString(_), V::String(s)) => Ok(Field::AsciiString(to_ascii_string(&s))),
+    (Schema::AsciiString(_), V::CharASCII(c) | V::CharUnicode(c)) => {
+      Ok(Field::AsciiString((to_ascii(c) as char).to_string()))
+    }
+    (Schema::AsciiString(_), V::Null) => Ok(Field::AsciiString(String::new())),
+    // Fixed length arrays
+    (Schema::NullableBooleanArray(p), V::BooleanArray(v)) => {
+      Ok(Field::NullableBooleanArray(resize(v, p.get_len(), None)))
+    }
+    (Schema::NullableBooleanArray(p), V::Null) => {
+      Ok(Field::NullableBooleanArray(vec![None; p.get_len()]))
+    }
+    (Schema::UnsignedByteArray(p), V::ByteArray(v)) => {
+      Ok(Field::UnsignedByteArray(resize(v, p.get_len(), 0)))
+    }
+    (Schema::NullableUnsignedByteArray { p, .. }, V::ByteArray(v)) => Ok(
+      Field::NullableUnsignedByteArray(resize(some(v), p.get_len(), None)),
+    ),
+    (Schema::NullableUnsignedByteArray { p, .. }, V::Null) => {
+      Ok(Field::NullableUnsignedByteArray(vec![None; p.get_len()]))
+    }
+    (Schema::ShortArray(p), V::ShortArray(v)) => Ok(Field::ShortArray(resize(v, p.get_len(), 0))),
+    (Schema::NullableShortArray { p, .. }, V::ShortArray(v)) => Ok(Field::NullableShortArray(
+      resize(some(v), p.get_len(), None),
+    )),
+    (Schema::NullableShortArray { p, .. }, V::Null) => {
+      Ok(Field::NullableShortArray(vec![None; p.get_len()]))
+    }
+    (Schema::IntArray(p), V::IntArray(v)) => Ok(Field::IntArray(resize(v, p.get_len(), 0))),
+    (Schema::NullableIntArray { p, .. }, V::IntArray(v)) => {
+      Ok(Field::NullableIntArray(resize(some(v), p.get_len(), None)))
+    }
+    (Schema::NullableIntArray { p, .. }, V::Null) => {
+      Ok(Field::NullableIntArray(vec![None; p.get_len()]))
+    }
+    (Schema::LongArray(p), V::LongArray(v)) => Ok(Field::LongArray(resize(v, p.get_len(), 0))),
+    (Schema::NullableLongArray { p, .. }, V::LongArray(v)) => {
+      Ok(Field::NullableLongArray(resize(some(v), p.get_len(), None)))
+    }
+    (Schema::NullableLongArray { p, .. }, V::Null) => {
+      Ok(Field::NullableLongArray(vec![None; p.get_len()]))
+    }
+    (Schema::FloatArray(p), V::FloatArray(v)) => {
+      Ok(Field::FloatArray(resize(v, p.get_len(), f32::NAN)))
+    }
+    (Schema::FloatArray(p), V::Null) => Ok(Field::FloatArray(vec![f32::NAN; p.get_len()])),
+    (Schema::DoubleArray(p), V::DoubleArray(v)) => {
+      Ok(Field::DoubleArray(resize(v, p.get_len(), f64::NAN)))
+    }
+    (Schema::DoubleArray(p), V::Null) => Ok(Field::DoubleArray(vec![f64::NAN; p.get_len()])),
+    (Schema::ComplexFloatArray(p), V::ComplexFloatArray(v)) => {
+      Ok(Field::ComplexFloatArray(resize(
+        v.into_iter().map(|(r, i)| ComplexF32::new(r, i)).collect(),
+        p.get_len(),
+        ComplexF32::new(f32::NAN, f32::NAN),
+      )))
+    }
+    (Schema::ComplexFloatArray(p), V::Null) => Ok(Field::ComplexFloatArray(vec![
+      ComplexF32::new(
+        f32::NAN,
+        f32::NAN
+      );
+      p.get_len()
+    ])),
+    (Schema::ComplexDoubleArray(p), V::ComplexDoubleArray(v)) => {
+      Ok(Field::ComplexDoubleArray(resize(
+        v.into_iter().map(|(r, i)| ComplexF64::new(r, i)).collect(),
+        p.get_len(),
+        ComplexF64::new(f64::NAN, f64::NAN),
+      )))
+    }
+    (Schema::ComplexDoubleArray(p), V::Null) => Ok(Field::ComplexDoubleArray(vec![
+      ComplexF64::new(
+        f64::NAN,
+        f64::NAN
+      );
+      p.get_len()
+    ])),
+    // Variable length arrays
+    (Schema::HeapArrayPtr32(has) | Schema::HeapArrayPtr64(has), value) => to_heap_field(has, value),
+    (schema, value) => Err(new_custom(format!(
+      "Unable to convert VOTable value '{:?}' into a FITS field of schema '{}'.",
+      value, schema
+    ))),
+  }
+}
+
+/// Convert a VOTable value into a BINTABLE variable length array field.
+fn to_heap_field(schema: &HeapArraySchema, value: VOTableValue) -> Result<Field, Error> {
+  use HeapArraySchema as H;
+  use VOTableValue as V;
+  match (schema, value) {
+    (H::HeapNullableBooleanArray(_), V::BooleanArray(v)) => Ok(Field::NullableBooleanArray(v)),
+    (H::HeapUnsignedByteArray(_), V::ByteArray(v)) => Ok(Field::UnsignedByteArray(v)),
+    (H::HeapNullableUnsignedByteArray { .. }, V::ByteArray(v)) => {
+      Ok(Field::NullableUnsignedByteArray(some(v)))
+    }
+    (H::HeapShortArray(_), V::ShortArray(v)) => Ok(Field::ShortArray(v)),
+    (H::HeapNullableShortArray { .. }, V::ShortArray(v)) => Ok(Field::NullableShortArray(some(v))),
+    (H::HeapIntArray(_), V::IntArray(v)) => Ok(Field::IntArray(v)),
+    (H::HeapNullableIntArray { .. }, V::IntArray(v)) => Ok(Field::NullableIntArray(some(v))),
+    (H::HeapLongArray(_), V::LongArray(v)) => Ok(Field::LongArray(v)),
+    (H::HeapNullableLongArray { .. }, V::LongArray(v)) => Ok(Field::NullableLongArray(some(v))),
+    (H::HeapFloatArray(_), V::FloatArray(v)) => Ok(Field::FloatArray(v)),
+    (H::HeapDoubleArray(_), V::DoubleArray(v)) => Ok(Field::DoubleArray(v)),
+    (H::HeapComplexFloatArray(_), V::ComplexFloatArray(v)) => Ok(Field::ComplexFloatArray(
+      v.into_iter().map(|(r, i)| ComplexF32::new(r, i)).collect(),
+    )),
+    (H::HeapComplexDoubleArray(_), V::ComplexDoubleArray(v)) => Ok(Field::ComplexDoubleArray(
+      v.into_iter().map(|(r, i)| ComplexF64::new(r, i)).collect(),
+    )),
+    (H::HeapAsciiString(_), V::String(s)) => Ok(Field::AsciiString(to_ascii_string(&s))),
+    (H::HeapAsciiString(_), V::CharASCII(c) | V::CharUnicode(c)) => {
+      Ok(Field::AsciiString((to_ascii(c) as char).to_string()))
+    }
+    // Null values are empty arrays
+    (H::HeapNullableBooleanArray(_), V::Null) => Ok(Field::NullableBooleanArray(vec![])),
+    (H::HeapUnsignedByteArray(_), V::Null) => Ok(Field::UnsignedByteArray(vec![])),
+    (H::HeapNullableUnsignedByteArray { .. }, V::Null) => {
+      Ok(Field::NullableUnsignedByteArray(vec![]))
+    }
+    (H::HeapShortArray(_), V::Null) => Ok(Field::ShortArray(vec![])),
+    (H::HeapNullableShortArray { .. }, V::Null) => Ok(Field::NullableShortArray(vec![])),
+    (H::HeapIntArray(_), V::Null) => Ok(Field::IntArray(vec![])),
+    (H::HeapNullableIntArray { .. }, V::Null) => Ok(Field::NullableIntArray(vec![])),
+    (H::HeapLongArray(_), V::Null) => Ok(Field::LongArray(vec![])),
+    (H::HeapNullableLongArray { .. }, V::Null) => Ok(Field::NullableLongArray(vec![])),
+    (H::HeapFloatArray(_), V::Null) => Ok(Field::FloatArray(vec![])),
+    (H::HeapDoubleArray(_), V::Null) => Ok(Field::DoubleArray(vec![])),
+    (H::HeapComplexFloatArray(_), V::Null) => Ok(Field::ComplexFloatArray(vec![])),
+    (H::HeapComplexDoubleArray(_), V::Null) => Ok(Field::ComplexDoubleArray(vec![])),
+    (H::HeapAsciiString(_), V::Null) => Ok(Field::AsciiString(String::new())),
+    (_, value) => Err(new_custom(format!(
+      "Unable to convert VOTable value '{:?}' into a FITS variable length array.",
+      value
+    ))),
+  }
+}
+
+/// Truncate or pad the given vector so that its length equals the given one.
+fn resize<T: Clone>(mut v: Vec<T>, len: usize, pad: T) -> Vec<T> {
+  v.resize(len, pad);
+  v
+}
+
+fn some<T>(v: Vec<T>) -> Vec<Option<T>> {
+  v.into_iter().map(Some).collect()
+}
+
+fn to_ascii(c: char) -> u8 {
+  if c.is_ascii() { c as u8 } else { b'?' }
+}
+
+fn to_ascii_string(s: &str) -> String {
+  s.chars().map(|c| to_ascii(c) as char).collect()
+}
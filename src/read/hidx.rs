@@ -9,7 +9,6 @@ use std::{
 use log::{debug, trace, warn};
 #[cfg(not(windows))]
 use memmap2::Advice;
-use memmap2::MmapOptions;
 
 use cdshealpix::nested::{
   get, n_hash,
@@ -26,24 +25,30 @@ use crate::{
     header::{builder::r#impl::bintable::Bintable, HDUHeader},
     xtension::bintable::schema::{RowSchema, Schema},
   },
-  read::slice::FitsBytes,
+  read::{
+    checksum::copy_header_without_checksums, decompress, slice::FitsBytes, table::HDUSelector,
+  },
 };
 
 // ADD https://github.com/cds-astro/cds-bstree-file-readonly-rust INDEX!
 
 /// Create an HEALPix Cumulative Index.
+/// If `hdu_selector` is `None`, one index is created per BINTABLE (the HDU index being added in
+/// the output file extension from the second BINTABLE), else only the selected BINTABLE is indexed.
+/// Returns the paths of the written index files.
 pub fn hcidx(
   input: PathBuf,
+  hdu_selector: Option<HDUSelector>,
   output: PathBuf,
   i_lon: usize,
   i_lat: usize,
   depth: u8,
   in_mem_explicit: bool,
   in_file_implicit_over_explicit_ratio: Option<f64>,
-) -> Result<(), Error> {
+) -> Result<Vec<PathBuf>, Error> {
   // Prepare reading, creating a memory map
   let file = File::open(&input).map_err(new_io_err)?;
-  let mmap = unsafe { MmapOptions::new().map(&file) }.map_err(new_io_err)?;
+  let mmap = decompress::mmap(&file)?;
   #[cfg(not(windows))]
   if let Err(e) = mmap.advise(Advice::Sequential) {
     warn!(
@@ -56,9 +61,21 @@ pub fn hcidx(
   let bytes = mmap.as_ref();
   let fits = FitsBytes::from_slice(bytes);
   let mut is_first_bintable = true;
+  let mut index_paths = Vec::new();
   // Iterate on all HDUs
   for (i, hdu) in fits.new_iterator::<Bintable>().enumerate() {
+    // Only the first matching HDU is selected
+    if hdu_selector.is_some() && !is_first_bintable {
+      break;
+    }
     let hdu = hdu?;
+    if let Some(hdu_selector) = &hdu_selector {
+      if !hdu_selector.matches(i, &hdu.parsed_header) {
+        continue;
+      } else if !hdu.is_bintable_hdu() {
+        return Err(new_custom(format!("HDU {} is not a BINTABLE.", i)));
+      }
+    }
     if hdu.is_bintable_hdu() {
       let index_path = if is_first_bintable {
         is_first_bintable = false;
@@ -201,9 +218,15 @@ pub fn hcidx(
           implicit_index,
         )?;
       }
+      index_paths.push(index_path);
+    }
+  }
+  match hdu_selector {
+    Some(hdu_selector) if is_first_bintable => {
+      Err(new_custom(format!("{} not found.", hdu_selector)))
     }
+    _ => Ok(index_paths),
   }
-  Ok(())
 }
 
 fn write_index<H: HCIndex>(
@@ -370,7 +393,7 @@ where
     // Load FITS file data
     let file = File::open(file_name).map_err(new_io_err)?;
     // Prepare reading, creating a memory map
-    let mmap = unsafe { MmapOptions::new().map(&file) }.map_err(new_io_err)?;
+    let mmap = decompress::mmap(&file)?;
     #[cfg(not(windows))]
     if let Err(e) = mmap.advise(Advice::Sequential) {
       warn!(
@@ -430,7 +453,8 @@ where
 
         let mut limit = self.limit.unwrap_or(bintable_header.n_rows());
 
-        hdu.copy_header(&mut self.write)?;
+        // CHECKSUM and DATASUM are not copied since the data is modified
+        copy_header_without_checksums(&hdu.raw_header, &mut self.write)?;
         let mut n_data_bytes_written = 0_usize;
         for (range, flag) in region.sorted_hpx_ranges(hci.depth()) {
           trace!(
@@ -2,7 +2,6 @@
 //! a pair of coordinates.
 
 use std::{
-  convert::Infallible,
   convert::TryInto,
   error::Error,
   fs::{read_dir, File},
@@ -13,16 +12,24 @@ use std::{
 use log::{debug, warn};
 #[cfg(not(windows))]
 use memmap2::Advice;
-use memmap2::{Mmap, MmapOptions};
+use memmap2::Mmap;
 
 use crate::{
   common::{keywords::naxis::NAxis2, ValueKwr},
   error::new_custom,
   hdu::{
     header::{builder::r#impl::bintable::Bintable, HDUHeader},
-    xtension::bintable::schema::{RowSchema, Schema},
+    xtension::bintable::{
+      schema::{RowSchema, Schema},
+      ztable::{write_uncompressed_header, ChunkedTable},
+    },
+  },
+  read::{
+    checksum::copy_header_without_checksums,
+    decompress,
+    slice::{FitsBytes, HDU},
+    table::HDUSelector,
   },
-  read::slice::FitsBytes,
 };
 
 use crate::error::new_io_err;
@@ -35,13 +42,16 @@ use cdshealpix::nested::{
   },
 };
 
-/// Creates a single FITS file containing an HEALPix sorted BINTABLE in the first extension.
+/// Creates a single FITS file containing an HEALPix sorted BINTABLE.
 /// In input, takes either the path of a FITS file or a directory containing FITS files
 /// having the exact same structure (the file contents are concatenated).
+/// * `hdu` selects the BINTABLE to be sorted in each file (e.g. the first one)
 /// * a single FITS file may contain additional HDUs (that are copied without modification)
+/// * `CHECKSUM` and `DATASUM` are removed from the header of the sorted BINTABLE
 /// * each FITS file of a directory **must** have a single extension (a BINTABLE)
 pub fn hsort(
   input: PathBuf,
+  hdu: HDUSelector,
   i_ra: usize,
   i_dec: usize,
   output: PathBuf,
@@ -54,6 +64,7 @@ pub fn hsort(
     File::open(input).map_err(|e| e.into()).and_then(|file| {
       hsort_file(
         file,
+        hdu,
         i_ra,
         i_dec,
         output,
@@ -66,6 +77,7 @@ pub fn hsort(
   } else {
     hsort_files(
       input,
+      hdu,
       i_ra,
       i_dec,
       output,
@@ -77,10 +89,11 @@ pub fn hsort(
   }
 }
 
-/// Copies the input file, sorting the first extension (a BINTABLE) according to HEALPix indices at order 29.
+/// Copies the input file, sorting the selected BINTABLE according to HEALPix indices at order 29.
 ///
 /// # Params
-/// * `file`: the file to be copied, sorting the BINTABLE in the first extension
+/// * `file`: the file to be copied, sorting the selected BINTABLE
+/// * `hdu`: selects the BINTABLE to be sorted
 /// * `i_ra`: index of the column containing the Right Ascension (in degrees)
 /// * `i_dec`: index of the column containing the Declination (in degrees)
 /// * `output`: path of the output file, containing the sorted version of the input file
@@ -90,9 +103,10 @@ pub fn hsort(
 /// * `parallel`: number of threads to be used (all available thread if `None`)
 /// # Warning
 /// * RA and Dec columns units are assumed to be degrees (without any checks)
-/// * the first extension **must be** a BINTABLE, other BINTABLEs are copied without being sorted
+/// * the selected HDU **must be** a BINTABLE, other HDUs are copied without being sorted
 pub fn hsort_file(
   file: File,
+  hdu: HDUSelector,
   i_ra: usize,
   i_dec: usize,
   output: PathBuf,
@@ -107,7 +121,7 @@ pub fn hsort_file(
   let mut writer = BufWriter::new(output_file);
 
   // Prepare reading, creating a memory map
-  let mmap = unsafe { MmapOptions::new().map(&file)? };
+  let mmap = decompress::mmap(&file)?;
   #[cfg(not(windows))]
   if let Err(e) = mmap.advise(Advice::Sequential) {
     warn!(
@@ -119,14 +133,14 @@ pub fn hsort_file(
   // Read as a FITS file, prepare iteration on HDUs
   let bytes = mmap.as_ref();
   let fits = FitsBytes::from_slice(bytes);
-  let mut hdu_it = fits.new_iterator::<Bintable>();
+  let mut hdu_it = fits.new_iterator::<Bintable>().enumerate();
 
   // Read/write Primary HDU
   // * read
   let primary_hdu = hdu_it
     .next()
     .ok_or_else(|| new_custom("No HDU found!"))
-    .and_then(|r| r)?;
+    .and_then(|(_, r)| r)?;
   if !primary_hdu.is_primary_hdu() {
     return Err(String::from("First HDU is not a primary HDU!").into());
   }
@@ -134,20 +148,35 @@ pub fn hsort_file(
   primary_hdu.copy_hdu(&mut writer)?;
   debug!("Primary HDU copied");
 
-  // Read/write BINTABLE in the first Extension HDU
-  let bintable_hdu = hdu_it
-    .next()
-    .ok_or_else(|| new_custom("No second HDU found!"))
-    .and_then(|r| r)?;
-  if !primary_hdu.is_primary_hdu() {
-    return Err(String::from("First HDU is not a primary HDU!").into());
+  // Copy the HDUs preceding the selected BINTABLE (if any)
+  let bintable_hdu = loop {
+    let (i, other_hdu) = hdu_it
+      .next()
+      .ok_or_else(|| new_custom(format!("{} not found!", hdu)))?;
+    let other_hdu = other_hdu?;
+    if hdu.matches(i, &other_hdu.parsed_header) {
+      break other_hdu;
+    }
+    other_hdu.copy_hdu(&mut writer)?;
+  };
+  if !bintable_hdu.is_bintable_hdu() {
+    return Err(String::from("Selected HDU is not a BINTABLE HDU!").into());
   }
 
-  // * read bintable metadata
-  let bintable_header = match &bintable_hdu.parsed_header {
+  // * read bintable metadata (a tile-compressed table being decompressed tile by tile)
+  let HDU {
+    starting_byte: _,
+    raw_header,
+    parsed_header,
+    data,
+    padding: _,
+  } = bintable_hdu;
+  let bintable_header = match parsed_header {
     HDUHeader::BinTable(h) => h,
     _ => unreachable!(), // since we already tested with 'is_bintable_hdu'
   };
+  let table = ChunkedTable::new(bintable_header, data)?;
+  let bintable_header = table.header();
   let row_byte_size = bintable_header.row_byte_size();
   let n_rows = bintable_header.n_rows();
   let main_table_byte_size = bintable_header.main_table_byte_size();
@@ -176,7 +205,12 @@ pub fn hsort_file(
   //     + 1 stating that the file is HPX sorted
   //     + 1 one providing the index of the RA  column used in the HPX sort
   //     + 1 one providing the index of the Dec column used in the HPX sort
-  bintable_hdu.copy_header(&mut writer)?;
+  // (CHECKSUM and DATASUM are removed since the data is modified)
+  if table.is_tile_compressed() {
+    write_uncompressed_header(bintable_header, raw_header.kw_records_iter(), &mut writer)?;
+  } else {
+    copy_header_without_checksums(&raw_header, &mut writer)?;
+  }
   debug!("BINTABLE header copied");
 
   // * sort main table rows
@@ -199,6 +233,8 @@ pub fn hsort_file(
       layer29.hash(lon.to_radians(), lat.to_radians())
     }
   };
+  // Number of rows per chunk when reading a regular table (a tile-compressed table is read by tiles)
+  let chunk_n_rows = (internal_threshold / row_byte_size).max(1);
   //   + performs either an internal or an external sort
   if main_table_byte_size <= internal_threshold {
     debug!("Retrieve (and copy) BINTABLE rows...");
@@ -206,10 +242,10 @@ pub fn hsort_file(
     /*let mut rows: Vec<&[u8]> = (&bintable_hdu.data()[..main_table_byte_size])
     .chunks(row_byte_size)
     .collect();*/
-    let mut rows: Vec<Vec<u8>> = (&bintable_hdu.data()[..main_table_byte_size])
-      .chunks(row_byte_size)
-      .map(|slice| slice.to_vec()) // make a copy in memory
-      .collect();
+    let mut rows: Vec<Vec<u8>> = Vec::with_capacity(n_rows);
+    for chunk in table.row_chunks(chunk_n_rows) {
+      rows.extend(chunk?.chunks(row_byte_size).map(|slice| slice.to_vec())); // make a copy in memory
+    }
     debug!("Start internal sort...");
     hpx_internal_sort(rows.as_mut_slice(), hpx29v, parallel);
     //debug!("Start mem copy of sorted rows...");
@@ -250,12 +286,22 @@ pub fn hsort_file(
     // - read once to get healpix distribution
     let twice_dd = (29 - depth) << 1;
     debug!("Compute count map...");
+    let mut chunk_err = None;
     let count_map = ImplicitCountMapU32::from_hash_values(
       depth,
-      (&bintable_hdu.data()[..main_table_byte_size])
-        .chunks(row_byte_size)
-        .map(|row| (hpx29(&row) >> twice_dd) as u32),
+      table
+        .row_chunks(chunk_n_rows)
+        .map_while(|chunk| chunk.map_err(|e| chunk_err = Some(e)).ok())
+        .flat_map(|chunk| {
+          chunk
+            .chunks(row_byte_size)
+            .map(|row| (hpx29(&row) >> twice_dd) as u32)
+            .collect::<Vec<u32>>()
+        }),
     );
+    if let Some(e) = chunk_err {
+      return Err(e.into());
+    }
 
     let mut sort_params: SimpleExtSortParams = Default::default();
     sort_params = sort_params.set_n_elems_per_chunk((internal_threshold / row_byte_size) as u32);
@@ -271,9 +317,15 @@ pub fn hsort_file(
     debug!("Start external sort...");
     // - sort
     let sorted_row_it = hpx_external_sort_with_knowledge(
-      (&bintable_hdu.data()[..main_table_byte_size])
-        .chunks(row_byte_size)
-        .map(|slice| Ok::<_, Infallible>(slice.to_vec())), // Copy here because of serde!!
+      table
+        .row_chunks(chunk_n_rows)
+        .flat_map(|chunk| match chunk {
+          Ok(chunk) => chunk
+            .chunks(row_byte_size)
+            .map(|slice| Ok(slice.to_vec())) // Copy here because of serde!!
+            .collect::<Vec<_>>(),
+          Err(e) => vec![Err(e)],
+        }),
       &count_map,
       hpx29v,
       Some(sort_params),
@@ -284,14 +336,23 @@ pub fn hsort_file(
     }
   }
   // * copy heap part if any (sizes have not changed, so no need to ckeck for 2880 byte blocks)
+  //   (the uncompressed version of a tile-compressed table has no heap)
   debug!("Copy BINTABLE heap and padding bytes...");
-  writer
-    .write_all(&bintable_hdu.data()[main_table_byte_size..])
-    .map_err(new_io_err)
-    .and_then(|()| bintable_hdu.copy_blanks(&mut writer))?;
+  let heap: &[u8] = if table.is_tile_compressed() {
+    &[]
+  } else {
+    &data[main_table_byte_size..]
+  };
+  writer.write_all(heap).map_err(new_io_err)?;
+  let rem2880 = (main_table_byte_size + heap.len()) % 2880;
+  if rem2880 != 0 {
+    writer
+      .write_all(vec![0_u8; 2880 - rem2880].as_slice())
+      .map_err(new_io_err)?;
+  }
   // Copy other HDUs (if any)
   debug!("Copy other HDUs (if any)...");
-  for other_hdu in hdu_it {
+  for (_, other_hdu) in hdu_it {
     other_hdu.and_then(|hdu| hdu.copy_hdu(&mut writer))?;
   }
   debug!("Done!");
@@ -299,10 +360,11 @@ pub fn hsort_file(
 }
 
 /// # Warning
-/// * stops after the first encountered BINTABLE in each file
+/// * only the selected BINTABLE of each file is read
 /// * all BINTABLEs must have the exact same structure
 pub fn hsort_files(
   dir: PathBuf,
+  hdu: HDUSelector,
   i_ra: usize,
   i_dec: usize,
   output: PathBuf,
@@ -334,7 +396,7 @@ pub fn hsort_files(
 
   // Prepare reading, creating a memory map
   let first_file = File::open(&first_file)?;
-  let mmap = unsafe { MmapOptions::new().map(&first_file)? };
+  let mmap = decompress::mmap(&first_file)?;
   #[cfg(not(windows))]
   if let Err(e) = mmap.advise(Advice::Sequential) {
     warn!(
@@ -360,25 +422,24 @@ pub fn hsort_files(
   // * write
   primary_hdu.copy_hdu(&mut writer)?;
 
-  // Read/write BINTABLE in the first Extension HDU
-  let bintable_hdu = hdu_it
-    .next()
-    .ok_or_else(|| new_custom("No second HDU found!"))
-    .and_then(|r| r)?;
-  if !bintable_hdu.is_bintable_hdu() {
-    return Err(String::from("Second HDU not a BINTABLE HDU!").into());
-  }
+  // Read/write the selected BINTABLE
+  let (_, bintable_hdu) = hdu.select(bytes)?;
 
   // * read bintable metadata
   let bintable_header = match &bintable_hdu.parsed_header {
     HDUHeader::BinTable(h) => h,
-    _ => unreachable!(), // since we already tested with 'is_bintable_hdu'
+    _ => unreachable!(), // since 'select' only returns BINTABLE HDUs
   };
   let row_byte_size = bintable_header.row_byte_size();
   let n_rows = bintable_header.n_rows();
   let main_table_byte_size = bintable_header.main_table_byte_size();
   debug_assert_eq!(main_table_byte_size, n_rows * row_byte_size);
 
+  if bintable_header.is_tile_compressed() {
+    return Err(
+      String::from("Tile-compressed BINTABLEs are not supported, decompress them first!").into(),
+    );
+  }
   if bintable_header.heap_byte_size() != 0 {
     return Err(String::from("BINTABLEs having a HEAP are not supported!").into());
   }
@@ -406,7 +467,8 @@ pub fn hsort_files(
   //     + 1 stating that the file is HPX sorted
   //     + 1 one providing the index of the RA  column used in the HPX sort
   //     + 1 one providing the index of the Dec column used in the HPX sort
-  bintable_hdu.copy_header(&mut writer)?;
+  // (CHECKSUM and DATASUM are removed since the data is modified)
+  copy_header_without_checksums(&bintable_hdu.raw_header, &mut writer)?;
 
   // * sort maintable rows
   let layer29 = get(29);
@@ -433,7 +495,7 @@ pub fn hsort_files(
   let from = bintable_hdu.data_starting_byte();
   let first_file_rows_it = RowFileIt::new(mmap, from, from + main_table_byte_size, row_byte_size);
 
-  let rows_it = FirstBintableRowsIt::new(fits_files_it, row_schema, first_file_rows_it);
+  let rows_it = FirstBintableRowsIt::new(fits_files_it, hdu, row_schema, first_file_rows_it);
 
   let mut sort_params: SimpleExtSortParams = Default::default();
   sort_params = sort_params.set_n_elems_per_chunk((internal_threshold / row_byte_size) as u32);
@@ -480,6 +542,7 @@ where
   I: Iterator<Item = PathBuf>,
 {
   file_it: I,
+  hdu: HDUSelector,
   row_schema: RowSchema,
   curr_row_it: RowFileIt,
 }
@@ -487,9 +550,15 @@ impl<I> FirstBintableRowsIt<I>
 where
   I: Iterator<Item = PathBuf>,
 {
-  pub fn new(file_it: I, row_schema: RowSchema, first_file_row_it: RowFileIt) -> Self {
+  pub fn new(
+    file_it: I,
+    hdu: HDUSelector,
+    row_schema: RowSchema,
+    first_file_row_it: RowFileIt,
+  ) -> Self {
     Self {
       file_it,
+      hdu,
       row_schema,
       curr_row_it: first_file_row_it,
     }
@@ -514,7 +583,7 @@ where
             Ok(file) => file,
             Err(e) => return Some(Err(e)),
           };
-          let mmap = match unsafe { MmapOptions::new().map(&file) }.map_err(|e| {
+          let mmap = match decompress::mmap(&file).map_err(|e| {
             new_custom(format!(
               "Error creating the mmap on file '{:?}': {}",
               file, e
@@ -531,51 +600,21 @@ where
             );
           }
 
-          // Read as a FITS file, prepare iteration on HDUs
-          let bytes = mmap.as_ref();
-          let fits = FitsBytes::from_slice(bytes);
-          let mut hdu_it = fits.new_iterator::<Bintable>();
-
-          // Read Primary HDU
-          if let Err(primary_hdu_err) = hdu_it
-            .next()
-            .ok_or_else(|| new_custom(format!("No HDU found in file '{:?}'!", file)))
-            .and_then(|r| r)
-            .map_err(|e| {
-              new_custom(format!(
-                "Error reading primary HDU in file {:?}: {:?}",
-                file, e
-              ))
-            })
-          {
-            return Some(Err(primary_hdu_err));
-          }
-
-          // Read BINTABLE in the first Extension HDU
-          let bintable_hdu = match hdu_it
-            .next()
-            .ok_or_else(|| new_custom(format!("No secondary HDU found in file '{:?}'!", file)))
-            .and_then(|r| r)
-            .map_err(|e| {
-              new_custom(format!(
-                "Error reading BINTABLE HDU in file {:?}: {:?}",
-                file, e
-              ))
-            }) {
-            Ok(hdu) => hdu,
+          // Read the selected BINTABLE
+          let bintable_hdu = match self.hdu.select(mmap.as_ref()).map_err(|e| {
+            new_custom(format!(
+              "Error reading BINTABLE HDU in file {:?}: {:?}",
+              file, e
+            ))
+          }) {
+            Ok((_, hdu)) => hdu,
             Err(e) => return Some(Err(e)),
           };
-          if !bintable_hdu.is_bintable_hdu() {
-            return Some(Err(new_custom(format!(
-              "2nd HDU in file '{:?}' is not a BINTABLE.",
-              file
-            ))));
-          }
 
           // * read bintable metadata
           let bintable_header = match &bintable_hdu.parsed_header {
             HDUHeader::BinTable(h) => h,
-            _ => unreachable!(), // since we already tested with 'is_bintable_hdu'
+            _ => unreachable!(), // since 'select' only returns BINTABLE HDUs
           };
           let row_byte_size = bintable_header.row_byte_size();
           let n_rows = bintable_header.n_rows();
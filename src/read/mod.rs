@@ -1,6 +1,17 @@
 #[cfg(feature = "hpx")]
 pub mod hsort;
+pub mod checksum;
+pub mod decompress;
+pub mod fromcsv;
+#[cfg(feature = "vot")]
+pub mod fromvot;
 #[cfg(feature = "hpx")]
 pub mod hidx;
 pub mod reader;
+#[cfg(feature = "expreval")]
+pub mod select;
 pub mod slice;
+pub mod table;
+#[cfg(feature = "vot")]
+pub mod tovot;
+pub mod verify;
@@ -1,3 +1,530 @@
 //! Read a FITS from a reader, i.e. in streaming mode.
-//! This mode e.g. **does not supprt** BINTABLE columns having data stored in the HEAP (or, at least,
-//! data on the HEAP is available once all rows have been read).
+//! Since data on the HEAP is available only once all rows have been read, BINTABLE columns having
+//! data stored in the HEAP are supported only by first spooling the main table
+//! (see [StreamHDU::bintable_rows_with_heap]).
+use std::{
+  io::{self, BufReader, ErrorKind, Read, Seek, SeekFrom, Write},
+  marker::PhantomData,
+  mem,
+};
+
+use crate::{
+  error::{Error, new_custom, new_io_err},
+  hdu::{
+    header::{
+      HDUHeader, Header,
+      builder::{HeaderBuilder, r#impl::bintable::Bintable},
+      raw::RawHeader,
+    },
+    xtension::bintable::{
+      header::BinTableHeaderWithColInfo,
+      read::{
+        deser::sliceheap::DeserializerWithHeap,
+        visitor::{FieldVisitorProvider, RowVisitor},
+      },
+      schema::RowSchema,
+    },
+  },
+};
+
+/// Iterates over the HDUs of a FITS file provided by a reader (stdin, pipe, socket, ...).
+/// Since the HDU data part is read from the same reader, this is not an `Iterator`:
+/// an HDU must be dropped before calling `next_hdu` again.
+/// The data part not consumed by the user is automatically skipped when reading the next HDU.
+pub struct HDUReader<R: Read, B: HeaderBuilder> {
+  reader: R,
+  /// Starting byte of the next HDU.
+  next_starting_byte: u64,
+  /// Number of bytes of the data part of the current HDU not yet read.
+  remaining_data: u64,
+  /// Number of padding bytes following the data part of the current HDU.
+  remaining_blanks: u64,
+  _header_builder_type: PhantomData<B>,
+}
+
+impl<R: Read, B: HeaderBuilder> HDUReader<R, B> {
+  /// # Params
+  /// * `reader`: reader positioned on the first byte of the FITS file
+  pub fn from_reader(reader: R) -> Self {
+    Self {
+      reader,
+      next_starting_byte: 0,
+      remaining_data: 0,
+      remaining_blanks: 0,
+      _header_builder_type: PhantomData,
+    }
+  }
+
+  pub fn into_inner(self) -> R {
+    self.reader
+  }
+
+  /// Returns the next HDU, or `None` if the end of the stream has been reached.
+  pub fn next_hdu(&mut self) -> Option<Result<StreamHDU<'_, R, B>, Error>> {
+    // Skip the data part (and the blanks) of the previous HDU
+    let n_skip = self.remaining_data + self.remaining_blanks;
+    if n_skip > 0 {
+      if let Err(e) = skip(&mut self.reader, n_skip) {
+        return Some(Err(e));
+      }
+      self.remaining_data = 0;
+      self.remaining_blanks = 0;
+    }
+    match read_first_block(&mut self.reader) {
+      Ok(None) => None,
+      Ok(Some(first_block)) => {
+        let is_primary = self.next_starting_byte == 0;
+        let mut reader = (&first_block[..]).chain(&mut self.reader);
+        Some(
+          RawHeader::<[u8; 2880]>::from_reader(is_primary, &mut reader).and_then(|raw_header| {
+            raw_header.build(is_primary).map(|parsed_header| {
+              let starting_byte = self.next_starting_byte;
+              let data_byte_size = parsed_header.data_byte_size();
+              let rem2880 = data_byte_size % 2880;
+              self.remaining_data = data_byte_size;
+              self.remaining_blanks = if rem2880 != 0 { 2880 - rem2880 } else { 0 };
+              self.next_starting_byte +=
+                raw_header.byte_size() as u64 + self.remaining_data + self.remaining_blanks;
+              StreamHDU {
+                starting_byte,
+                raw_header,
+                parsed_header,
+                data: DataReader {
+                  reader: &mut self.reader,
+                  remaining: &mut self.remaining_data,
+                },
+              }
+            })
+          }),
+        )
+      }
+      Err(e) => Some(Err(e)),
+    }
+  }
+}
+
+/// Read the first block of a header, returning `None` if the end of the stream has been reached.
+fn read_first_block<R: Read>(reader: &mut R) -> Result<Option<[u8; 2880]>, Error> {
+  let mut block = [0_u8; 2880];
+  let mut n_read = 0;
+  while n_read < block.len() {
+    match reader.read(&mut block[n_read..]) {
+      Ok(0) => break,
+      Ok(n) => n_read += n,
+      Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+      Err(e) => return Err(new_io_err(e)),
+    }
+  }
+  match n_read {
+    0 => Ok(None),
+    2880 => Ok(Some(block)),
+    _ => Err(new_custom(format!(
+      "Truncated FITS stream: last block contains {} bytes instead of 2880.",
+      n_read
+    ))),
+  }
+}
+
+/// Consume exactly `n_bytes` from the given reader.
+fn skip<R: Read>(reader: &mut R, n_bytes: u64) -> Result<(), Error> {
+  io::copy(&mut reader.take(n_bytes), &mut io::sink())
+    .map_err(new_io_err)
+    .and_then(|n_read| {
+      if n_read == n_bytes {
+        Ok(())
+      } else {
+        Err(new_io_err(ErrorKind::UnexpectedEof.into()))
+      }
+    })
+}
+
+/// Reader on the data part of an HDU: it reads at most the number of bytes of the data part.
+pub struct DataReader<'r, R: Read> {
+  reader: &'r mut R,
+  remaining: &'r mut u64,
+}
+impl<'r, R: Read> DataReader<'r, R> {
+  /// Number of bytes of the data part not yet read.
+  pub fn remaining(&self) -> u64 {
+    *self.remaining
+  }
+}
+impl<'r, R: Read> Read for DataReader<'r, R> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    let max = buf
+      .len()
+      .min(usize::try_from(*self.remaining).unwrap_or(usize::MAX));
+    let n = self.reader.read(&mut buf[..max])?;
+    *self.remaining -= n as u64;
+    Ok(n)
+  }
+}
+
+/// An HDU read from a stream: the header is fully parsed, the data part is read on demand.
+/// # Lifetime
+/// `'r`: "r" for "reader", the lifetime of the mutable borrow of the `HDUReader`
+pub struct StreamHDU<'r, R: Read, B: HeaderBuilder> {
+  pub starting_byte: u64,
+  pub raw_header: RawHeader<[u8; 2880]>,
+  pub parsed_header: HDUHeader<B>,
+  data: DataReader<'r, R>,
+}
+impl<'r, R: Read, B: HeaderBuilder> StreamHDU<'r, R, B> {
+  pub fn is_primary_hdu(&self) -> bool {
+    matches!(&self.parsed_header, HDUHeader::Primary(_))
+  }
+  pub fn is_bintable_hdu(&self) -> bool {
+    matches!(&self.parsed_header, HDUHeader::BinTable(_))
+  }
+
+  pub fn starting_byte(&self) -> u64 {
+    self.starting_byte
+  }
+
+  pub fn data_starting_byte(&self) -> u64 {
+    self.starting_byte + self.raw_header.byte_size() as u64
+  }
+
+  pub fn raw_header(&self) -> &RawHeader<[u8; 2880]> {
+    &self.raw_header
+  }
+
+  pub fn parsed_header(&self) -> &HDUHeader<B> {
+    &self.parsed_header
+  }
+
+  /// Returns a reader on the (remaining) data part of the HDU.
+  pub fn data_reader(&mut self) -> &mut DataReader<'r, R> {
+    &mut self.data
+  }
+
+  /// Read the full (remaining) data part of the HDU in memory.
+  pub fn read_data(&mut self) -> Result<Vec<u8>, Error> {
+    let mut data = Vec::with_capacity(self.data.remaining() as usize);
+    self
+      .data
+      .read_to_end(&mut data)
+      .map_err(new_io_err)
+      .and_then(|_| self.check_data_fully_read())
+      .map(|()| data)
+  }
+
+  /// Skip the (remaining) data part of the HDU.
+  pub fn skip_data(&mut self) -> Result<(), Error> {
+    let n_bytes = self.data.remaining();
+    skip(&mut self.data, n_bytes)
+  }
+
+  /// Copy the header and the data part of the HDU, including the blanks (zero padding).
+  /// # Warning
+  /// The data part must not have been read (even partially) before calling this method.
+  pub fn copy_hdu<W: Write>(mut self, w: &mut W) -> Result<(), Error> {
+    let data_byte_size = self.parsed_header.data_byte_size();
+    if self.data.remaining() != data_byte_size {
+      return Err(new_custom(
+        "Unable to copy the HDU: data part already (partially) read.",
+      ));
+    }
+    self.raw_header.copy(w).and_then(|()| {
+      io::copy(&mut self.data, w)
+        .map_err(new_io_err)
+        .and_then(|_| self.check_data_fully_read())
+        .and_then(|()| {
+          let rem2880 = data_byte_size % 2880;
+          if rem2880 != 0 {
+            w.write_all(vec![0_u8; (2880 - rem2880) as usize].as_slice())
+              .map_err(new_io_err)
+          } else {
+            Ok(())
+          }
+        })
+    })
+  }
+
+  fn check_data_fully_read(&self) -> Result<(), Error> {
+    if self.data.remaining() == 0 {
+      Ok(())
+    } else {
+      Err(new_io_err(ErrorKind::UnexpectedEof.into()))
+    }
+  }
+}
+
+impl<'r, R: Read> StreamHDU<'r, R, Bintable> {
+  /// Returns an object allowing to read the BINTABLE rows sequentially.
+  /// # Warning
+  /// Fails if the HDU is not a BINTABLE, if the rows have already been (partially) read
+  /// or if the table contains columns whose data is stored in the HEAP
+  /// (use [Self::bintable_rows_with_heap] in this case).
+  pub fn bintable_rows(&mut self) -> Result<BinTableRowReader<'_, 'r, R>, Error> {
+    match &self.parsed_header {
+      HDUHeader::BinTable(header) => {
+        if self.data.remaining() != header.data_byte_size() {
+          return Err(new_custom(
+            "Unable to read rows: data part already (partially) read.",
+          ));
+        }
+        let row_schema = build_row_schema(header)?;
+        if row_schema.has_heap_array() {
+          Err(new_custom(
+            "Columns with data stored in the HEAP not supported in streaming mode.",
+          ))
+        } else {
+          Ok(BinTableRowReader {
+            data: &mut self.data,
+            row_schema,
+            row: vec![0_u8; header.row_byte_size()],
+            n_rows: header.n_rows(),
+            n_read: 0,
+          })
+        }
+      }
+      _ => Err(new_custom("HDU is not a BINTABLE.")),
+    }
+  }
+
+  /// Returns an object allowing to read the BINTABLE rows sequentially, including columns whose
+  /// data is stored in the HEAP.
+  /// Since the HEAP follows the main table, the main table is first copied into the given `spool`
+  /// (e.g. a temporary file, or a `Cursor<Vec<u8>>` to keep it in memory), then the HEAP is
+  /// loaded in memory and the rows are finally replayed from the spool.
+  /// # Warning
+  /// Fails if the HDU is not a BINTABLE or if the rows have already been (partially) read.
+  pub fn bintable_rows_with_heap<S: Read + Write + Seek>(
+    &mut self,
+    mut spool: S,
+  ) -> Result<SpooledBinTableRowReader<S>, Error> {
+    match &self.parsed_header {
+      HDUHeader::BinTable(header) => {
+        if self.data.remaining() != header.data_byte_size() {
+          return Err(new_custom(
+            "Unable to read rows: data part already (partially) read.",
+          ));
+        }
+        let row_schema = build_row_schema(header)?;
+        let row_byte_size = header.row_byte_size();
+        let n_rows = header.n_rows();
+        let main_table_byte_size = header.main_table_byte_size() as u64;
+        let gap_byte_size = header.gap_byte_size() as u64;
+        // Spool the main table
+        let spool_start = spool.stream_position().map_err(new_io_err)?;
+        let n_copied = io::copy(&mut (&mut self.data).take(main_table_byte_size), &mut spool)
+          .map_err(new_io_err)?;
+        if n_copied != main_table_byte_size {
+          return Err(new_io_err(ErrorKind::UnexpectedEof.into()));
+        }
+        // Load the heap (skipping the gap, if any)
+        skip(&mut self.data, gap_byte_size)?;
+        let mut heap = Vec::with_capacity(self.data.remaining() as usize);
+        self
+          .data
+          .read_to_end(&mut heap)
+          .map_err(new_io_err)
+          .and_then(|_| self.check_data_fully_read())?;
+        // Rewind the spool to replay the rows
+        spool
+          .flush()
+          .and_then(|()| spool.seek(SeekFrom::Start(spool_start)))
+          .map_err(new_io_err)
+          .map(|_| SpooledBinTableRowReader {
+            spool: BufReader::new(spool),
+            heap,
+            row_schema,
+            row: vec![0_u8; row_byte_size],
+            n_rows,
+            n_read: 0,
+          })
+      }
+      _ => Err(new_custom("HDU is not a BINTABLE.")),
+    }
+  }
+}
+
+/// Build the row schema, returning an error instead of panicking if a TFORM is missing.
+fn build_row_schema(header: &BinTableHeaderWithColInfo) -> Result<RowSchema, Error> {
+  header
+    .cols()
+    .iter()
+    .enumerate()
+    .map(|(i, col_header)| {
+      col_header.schema().ok_or_else(|| {
+        new_custom(format!(
+          "Unable to create schema for column {}: TFORM probably missing!",
+          i + 1
+        ))
+      })
+    })
+    .collect()
+}
+
+/// Sequentially read the (fixed length) rows of a BINTABLE from a stream.
+pub struct BinTableRowReader<'a, 'r, R: Read> {
+  data: &'a mut DataReader<'r, R>,
+  row_schema: RowSchema,
+  /// Buffer containing the last row read.
+  row: Vec<u8>,
+  n_rows: usize,
+  n_read: usize,
+}
+impl<'a, 'r, R: Read> BinTableRowReader<'a, 'r, R> {
+  pub fn row_schema(&self) -> &RowSchema {
+    &self.row_schema
+  }
+
+  /// Deserialize only the columns of the given indices (in the given order).
+  pub fn project(&mut self, icols: &[usize]) -> Result<(), Error> {
+    self
+      .row_schema
+      .project(icols)
+      .map(|row_schema| self.row_schema = row_schema)
+  }
+
+  pub fn n_rows(&self) -> usize {
+    self.n_rows
+  }
+
+  /// Number of rows already read.
+  pub fn n_rows_read(&self) -> usize {
+    self.n_read
+  }
+
+  /// Returns the raw bytes of the next row, or `None` if all rows have been read.
+  pub fn next_raw_row(&mut self) -> Option<Result<&[u8], Error>> {
+    if self.n_read < self.n_rows {
+      self.n_read += 1;
+      Some(
+        self
+          .data
+          .read_exact(&mut self.row)
+          .map(|()| self.row.as_slice())
+          .map_err(new_io_err),
+      )
+    } else {
+      None
+    }
+  }
+
+  /// Read and deserialize the next row, or returns `None` if all rows have been read.
+  pub fn deserialize_next_row<F, V>(
+    &mut self,
+    field_visitor_provider: &mut F,
+    row_visitor: V,
+  ) -> Option<Result<V::Value, Error>>
+  where
+    F: FieldVisitorProvider,
+    V: RowVisitor<FieldValue = F::FieldValue>,
+  {
+    if self.n_read < self.n_rows {
+      self.n_read += 1;
+      Some(
+        self
+          .data
+          .read_exact(&mut self.row)
+          .map_err(new_io_err)
+          .and_then(|()| {
+            let mut de = DeserializerWithHeap::new(&self.row, &[]);
+            self
+              .row_schema
+              .deserialize(&mut de, field_visitor_provider, row_visitor)
+          }),
+      )
+    } else {
+      None
+    }
+  }
+}
+
+/// Sequentially read the rows of a BINTABLE from a spool containing the main table,
+/// the HEAP being stored in memory.
+pub struct SpooledBinTableRowReader<S: Read + Seek> {
+  spool: BufReader<S>,
+  heap: Vec<u8>,
+  row_schema: RowSchema,
+  /// Buffer containing the last row read.
+  row: Vec<u8>,
+  n_rows: usize,
+  n_read: usize,
+}
+impl<S: Read + Seek> SpooledBinTableRowReader<S> {
+  pub fn row_schema(&self) -> &RowSchema {
+    &self.row_schema
+  }
+
+  /// Deserialize only the columns of the given indices (in the given order).
+  pub fn project(&mut self, icols: &[usize]) -> Result<(), Error> {
+    self
+      .row_schema
+      .project(icols)
+      .map(|row_schema| self.row_schema = row_schema)
+  }
+
+  pub fn n_rows(&self) -> usize {
+    self.n_rows
+  }
+
+  /// Number of rows already read.
+  pub fn n_rows_read(&self) -> usize {
+    self.n_read
+  }
+
+  pub fn heap(&self) -> &[u8] {
+    self.heap.as_slice()
+  }
+
+  /// Takes the heap out of the reader, e.g. to keep borrowing it while reading the rows with
+  /// [Self::next_raw_row]. The heap of the reader is then empty.
+  pub fn take_heap(&mut self) -> Vec<u8> {
+    mem::take(&mut self.heap)
+  }
+
+  /// Returns the spool, e.g. to remove a temporary file.
+  pub fn into_inner(self) -> S {
+    self.spool.into_inner()
+  }
+
+  /// Returns the raw bytes of the next row, or `None` if all rows have been read.
+  /// Use [Self::heap] to access the data of the variable length arrays.
+  pub fn next_raw_row(&mut self) -> Option<Result<&[u8], Error>> {
+    if self.n_read < self.n_rows {
+      self.n_read += 1;
+      Some(
+        self
+          .spool
+          .read_exact(&mut self.row)
+          .map(|()| self.row.as_slice())
+          .map_err(new_io_err),
+      )
+    } else {
+      None
+    }
+  }
+
+  /// Read and deserialize the next row, or returns `None` if all rows have been read.
+  pub fn deserialize_next_row<F, V>(
+    &mut self,
+    field_visitor_provider: &mut F,
+    row_visitor: V,
+  ) -> Option<Result<V::Value, Error>>
+  where
+    F: FieldVisitorProvider,
+    V: RowVisitor<FieldValue = F::FieldValue>,
+  {
+    if self.n_read < self.n_rows {
+      self.n_read += 1;
+      Some(
+        self
+          .spool
+          .read_exact(&mut self.row)
+          .map_err(new_io_err)
+          .and_then(|()| {
+            let mut de = DeserializerWithHeap::new(&self.row, &self.heap);
+            self
+              .row_schema
+              .deserialize(&mut de, field_visitor_provider, row_visitor)
+          }),
+      )
+    } else {
+      None
+    }
+  }
+}
@@ -0,0 +1,436 @@
+//! Module dedicated to the selection of the rows of a BINTABLE satisfying a boolean expression,
+//! the selected rows being written in a new FITS file.
+
+use std::{
+  fs::File,
+  io::{BufWriter, Seek, Write},
+  path::Path,
+};
+#[cfg(feature = "vot")]
+use std::{io::SeekFrom, mem};
+
+use log::{debug, warn};
+#[cfg(not(windows))]
+use memmap2::Advice;
+#[cfg(feature = "vot")]
+use votable::{Resource, VOTable, VoidTableDataContent, resource::ResourceOrTable};
+
+#[cfg(not(feature = "vot"))]
+use crate::common::{FixedFormat, KwrFormatRead};
+#[cfg(feature = "vot")]
+use crate::{
+  common::keywords::{bitpix::BitPix, tables::bintable::vot::ntable::NTable},
+  hdu::header::Header,
+  read::tovot::for_each_table,
+};
+use crate::{
+  common::{KW_RANGE, ValueKwr, header::HeaderWriter, keywords::tables::bintable::theap::THeap},
+  error::{Error, new_custom, new_io_err},
+  hdu::{
+    header::{HDUHeader, builder::r#impl::bintable::Bintable},
+    primary::header::PrimaryHeader,
+    xtension::bintable::{
+      read::expreval::{ExprEvalRow, TableSchema},
+      schema::{FieldSchema, RowSchema, Schema},
+      write::write_heap_and_update_header,
+      ztable::{ChunkedTable, write_uncompressed_header},
+    },
+  },
+  read::{
+    checksum::is_checksum_kw_record,
+    decompress,
+    slice::{FitsBytes, HDU},
+  },
+};
+
+/// Copies the Primary HDU of the input file, followed by a BINTABLE containing only the rows of
+/// the first BINTABLE of the input file satisfying the given boolean expression.
+/// Returns the number of selected rows.
+///
+/// # Params
+/// * `input`: path of the input FITS file
+/// * `expr`: boolean expression on the columns of the first BINTABLE (e.g. "parallax > 10")
+/// * `output`: path of the output FITS file
+///
+/// # Remarks
+/// * other HDUs are not copied
+/// * in a FITS-plus file, the VOTable of the primary HDU is reduced to the `TABLE` describing
+///   the first BINTABLE, its `nrows` being set to the number of selected rows, and `NTABLE = 1`;
+///   without the `vot` feature, an empty primary HDU is written instead
+/// * the BINTABLE header is copied, except `THEAP`, and `NAXIS2` and `PCOUNT` are updated
+/// * `CHECKSUM` and `DATASUM` are not copied, since headers and data are modified
+/// * the heap (if any) is rewritten, containing only the variable length arrays of the selected rows
+pub fn select<P: AsRef<Path>, Q: AsRef<Path>>(
+  input: P,
+  expr: String,
+  output: Q,
+) -> Result<u64, Error> {
+  debug!("Start select procedure...");
+  // Prepare writer
+  let output_file = File::create(output).map_err(new_io_err)?;
+  let mut writer = BufWriter::new(output_file);
+
+  // Prepare reading, creating a memory map
+  let file = File::open(input).map_err(new_io_err)?;
+  let mmap = decompress::mmap(&file)?;
+  #[cfg(not(windows))]
+  if let Err(e) = mmap.advise(Advice::Sequential) {
+    warn!(
+      "Error advising for sequential read on file '{:?}': {}",
+      file, e
+    );
+  }
+
+  // Read as a FITS file, prepare iteration on HDUs
+  let bytes = mmap.as_ref();
+  let fits = FitsBytes::from_slice(bytes);
+  let mut hdu_it = fits.new_iterator::<Bintable>();
+
+  // Read Primary HDU
+  let primary_hdu = hdu_it
+    .next()
+    .ok_or_else(|| new_custom("No HDU found!"))
+    .and_then(|r| r)?;
+  if !primary_hdu.is_primary_hdu() {
+    return Err(new_custom("First HDU is not a primary HDU!"));
+  }
+
+  // Look for the first BINTABLE
+  let bintable_hdu = loop {
+    match hdu_it.next() {
+      Some(hdu) => {
+        let hdu = hdu?;
+        if hdu.is_bintable_hdu() {
+          break hdu;
+        }
+      }
+      None => return Err(new_custom("No BINTABLE HDU found!")),
+    }
+  };
+  let raw_header = &bintable_hdu.raw_header;
+  let bintable_header = match bintable_hdu.parsed_header {
+    HDUHeader::BinTable(h) => h,
+    _ => unreachable!(), // since we already tested with 'is_bintable_hdu'
+  };
+  // A tile-compressed table is decompressed tile by tile
+  let table = ChunkedTable::new(bintable_header, bintable_hdu.data)?;
+  let bintable_header = table.header();
+  let row_byte_size = bintable_header.row_byte_size();
+  let col_names = bintable_header.build_col_names();
+  let row_schema: RowSchema = bintable_header.build_row_schema();
+
+  // Write Primary HDU
+  #[cfg(feature = "vot")]
+  let fits_plus = write_primary_hdu(&primary_hdu, bintable_header.n_rows() as u64, &mut writer)?;
+  #[cfg(not(feature = "vot"))]
+  write_primary_hdu(&primary_hdu, &mut writer)?;
+  debug!("Primary HDU written");
+
+  // Compile the expression
+  let table_schema = TableSchema::new(&col_names, row_schema.fields_schemas());
+  let keep = table_schema
+    .compile_bool_expr(expr)
+    .map_err(|e| new_custom(format!("Error compiling expression: {}", e)))?;
+
+  // Copy the BINTABLE header, except THEAP since the new heap directly follows the main table
+  // (CHECKSUM and DATASUM are removed by `write_uncompressed_header`)
+  let header_starting_byte = writer.stream_position().map_err(new_io_err)?;
+  if table.is_tile_compressed() {
+    write_uncompressed_header(bintable_header, raw_header.kw_records_iter(), &mut writer)?;
+  } else {
+    let mut header_writer = HeaderWriter::new(&mut writer);
+    for kwr in raw_header
+      .kw_records_iter()
+      .filter(|kwr| &kwr[KW_RANGE] != THeap::KEYWORD && !is_checksum_kw_record(kwr))
+    {
+      copy_kw_record(kwr, &mut header_writer)?;
+    }
+    header_writer.finalize()?;
+  }
+  debug!("BINTABLE header copied");
+
+  // Write the selected rows, copying their variable length arrays (if any) in the new heap
+  let heap = table.heap();
+  let heap_fields_schemas: Vec<&FieldSchema> = row_schema
+    .fields_schemas()
+    .iter()
+    .filter(|field_schema| {
+      matches!(
+        field_schema.schema,
+        Schema::HeapArrayPtr32(_) | Schema::HeapArrayPtr64(_)
+      )
+    })
+    .collect();
+  let mut new_heap = Vec::<u8>::new();
+  let mut row = vec![0_u8; row_byte_size];
+  let mut n_rows = 0_u64;
+  // Rows are copied since the chunks (decompressed tiles) do not live as long as the expression
+  let mut eval_row = ExprEvalRow::new_owned(row_schema.fields_schemas(), heap);
+  if row_byte_size > 0 {
+    for chunk in table.row_chunks(bintable_header.n_rows()) {
+      for raw_row in chunk?.chunks(row_byte_size) {
+        eval_row.set_row(raw_row);
+        if keep(&eval_row) {
+          if heap_fields_schemas.is_empty() {
+            writer.write_all(raw_row).map_err(new_io_err)?;
+          } else {
+            row.copy_from_slice(raw_row);
+            for field_schema in &heap_fields_schemas {
+              copy_heap_array(field_schema, &mut row, heap, &mut new_heap)?;
+            }
+            writer.write_all(&row).map_err(new_io_err)?;
+          }
+          n_rows += 1;
+        }
+      }
+    }
+  }
+  debug!("{} rows selected", n_rows);
+
+  // Write the heap and the padding bytes, and update NAXIS2 and PCOUNT
+  write_heap_and_update_header(
+    &mut writer,
+    header_starting_byte,
+    n_rows,
+    row_byte_size,
+    &new_heap,
+  )?;
+  // Update the FITS-plus VOTable `nrows`
+  #[cfg(feature = "vot")]
+  if let Some(fits_plus) = fits_plus {
+    fits_plus.update_nrows(n_rows, &mut writer)?;
+  }
+  writer.flush().map_err(new_io_err).map(|()| n_rows)
+}
+
+/// Copies the given primary HDU, except `CHECKSUM` and `DATASUM`.
+fn copy_primary_hdu<W: Write>(primary_hdu: &HDU<Bintable>, writer: &mut W) -> Result<(), Error> {
+  let mut header_writer = HeaderWriter::new(writer);
+  for kwr in primary_hdu
+    .raw_header()
+    .kw_records_iter()
+    .filter(|kwr| !is_checksum_kw_record(kwr))
+  {
+    copy_kw_record(kwr, &mut header_writer)?;
+  }
+  header_writer.finalize()?;
+  primary_hdu
+    .copy_data(writer)
+    .and_then(|()| primary_hdu.copy_blanks(writer))
+}
+
+/// Copies the given primary HDU, except `CHECKSUM` and `DATASUM`, or writes an empty primary HDU
+/// if it is the one of a FITS-plus file (`VOTMETA = T`), since its VOTable can not be updated
+/// without the `vot` feature.
+#[cfg(not(feature = "vot"))]
+fn write_primary_hdu<W: Write>(primary_hdu: &HDU<Bintable>, writer: &mut W) -> Result<(), Error> {
+  let is_fits_plus = primary_hdu.raw_header().kw_records_iter().any(|kwr| {
+    &kwr[KW_RANGE] == b"VOTMETA "
+      && FixedFormat::parse_logical_value(kwr[10..].try_into().unwrap()).is_ok_and(|(val, _)| val)
+  });
+  if is_fits_plus {
+    warn!("FITS-plus VOTable not copied ('vot' feature not activated): empty primary HDU written");
+    PrimaryHeader::write_empty_hdu(writer)
+  } else {
+    copy_primary_hdu(primary_hdu, writer)
+  }
+}
+
+/// Copies the given primary HDU, except `CHECKSUM` and `DATASUM`.
+/// In a FITS-plus file, the VOTable is reduced to the `TABLE` describing the first BINTABLE,
+/// its `nrows` being set to the given number of rows (to be updated once the rows are selected,
+/// see [FitsPlusVOTable::update_nrows]), and `NTABLE` is set to 1.
+#[cfg(feature = "vot")]
+fn write_primary_hdu<W: Write + Seek>(
+  primary_hdu: &HDU<Bintable>,
+  n_rows: u64,
+  writer: &mut W,
+) -> Result<Option<FitsPlusVOTable>, Error> {
+  let n_mandatory_kw_records = match &primary_hdu.parsed_header {
+    HDUHeader::Primary(h) => h.primary().n_kw_records(),
+    _ => unreachable!(), // since we already tested with 'is_primary_hdu'
+  };
+  let mut votable = match primary_hdu.parse_votable_if_any() {
+    None => return copy_primary_hdu(primary_hdu, writer).map(|()| None),
+    Some(Err(e)) => {
+      warn!(
+        "Error parsing the FITS-plus VOTable, empty primary HDU written: {}",
+        e
+      );
+      return PrimaryHeader::write_empty_hdu(writer).map(|()| None);
+    }
+    Some(Ok(votable)) => votable,
+  };
+  retain_first_table(&mut votable.resources, &mut false);
+  let xml = to_xml(&mut votable, n_rows)?;
+  // Copy the header, updating NAXIS1 and NTABLE
+  let mut header_writer = HeaderWriter::new(writer);
+  PrimaryHeader::new(true, BitPix::U8, vec![xml.len()])
+    .write_starting_mandatory_kw_records(&mut header_writer)?;
+  for kwr in primary_hdu
+    .raw_header()
+    .kw_records_iter()
+    .skip(n_mandatory_kw_records)
+    .filter(|kwr| !is_checksum_kw_record(kwr))
+  {
+    if &kwr[KW_RANGE] == NTable::KEYWORD {
+      NTable::new(1).write_kw_record(&mut header_writer)?;
+    } else {
+      copy_kw_record(kwr, &mut header_writer)?;
+    }
+  }
+  header_writer.finalize()?;
+  // Write the VOTable
+  let data_starting_byte = writer.stream_position().map_err(new_io_err)?;
+  let rem2880 = xml.len() % 2880;
+  writer
+    .write_all(&xml)
+    .and_then(|()| {
+      if rem2880 != 0 {
+        writer.write_all(vec![0_u8; 2880 - rem2880].as_slice())
+      } else {
+        Ok(())
+      }
+    })
+    .map_err(new_io_err)?;
+  Ok(Some(FitsPlusVOTable {
+    votable,
+    data_starting_byte,
+    data_byte_size: xml.len(),
+  }))
+}
+
+/// The VOTable written in the primary HDU of a FITS-plus output file.
+#[cfg(feature = "vot")]
+struct FitsPlusVOTable {
+  votable: VOTable<VoidTableDataContent>,
+  /// Position, in the output, of the first byte of the VOTable
+  data_starting_byte: u64,
+  /// Size of the VOTable, in bytes (i.e. `NAXIS1`)
+  data_byte_size: usize,
+}
+
+#[cfg(feature = "vot")]
+impl FitsPlusVOTable {
+  /// Overwrites the VOTable, setting `nrows` to the given number of rows.
+  /// Since the number of selected rows has at most as many digits as the number of rows of the
+  /// input table, the new VOTable is not larger and is padded with blanks.
+  fn update_nrows<W: Write + Seek>(mut self, n_rows: u64, writer: &mut W) -> Result<(), Error> {
+    let mut xml = to_xml(&mut self.votable, n_rows)?;
+    if xml.len() > self.data_byte_size {
+      return Err(new_custom(format!(
+        "FITS-plus VOTable larger than the reserved space. Expected: at most {}. Actual: {}.",
+        self.data_byte_size,
+        xml.len()
+      )));
+    }
+    xml.resize(self.data_byte_size, b' ');
+    let end = writer.stream_position().map_err(new_io_err)?;
+    writer
+      .seek(SeekFrom::Start(self.data_starting_byte))
+      .and_then(|_| writer.write_all(&xml))
+      .and_then(|()| writer.seek(SeekFrom::Start(end)))
+      .map_err(new_io_err)
+      .map(|_| ())
+  }
+}
+
+/// Removes all the tables (in document order) but the first one.
+#[cfg(feature = "vot")]
+fn retain_first_table(resources: &mut [Resource<VoidTableDataContent>], found: &mut bool) {
+  for resource in resources {
+    resource
+      .sub_elems
+      .retain_mut(|sub_elem| match &mut sub_elem.resource_or_table {
+        ResourceOrTable::Resource(r) => {
+          retain_first_table(std::slice::from_mut(r), found);
+          true
+        }
+        ResourceOrTable::Table(_) => !mem::replace(found, true),
+      });
+  }
+}
+
+/// Serializes the given VOTable, after having set the `nrows` of its tables.
+#[cfg(feature = "vot")]
+fn to_xml(votable: &mut VOTable<VoidTableDataContent>, n_rows: u64) -> Result<Vec<u8>, Error> {
+  for_each_table(&mut votable.resources, &mut |table| {
+    table.nrows = Some(n_rows)
+  });
+  votable
+    .clone()
+    .wrap()
+    .to_ivoa_xml_bytes()
+    .map_err(|e| new_custom(format!("VOTable error: {}", e)))
+}
+
+fn copy_kw_record<'a, I>(kwr: &[u8; 80], dest_kwr_it: &mut I) -> Result<(), Error>
+where
+  I: Iterator<Item = Result<&'a mut [u8; 80], Error>>,
+{
+  dest_kwr_it
+    .next()
+    .unwrap()
+    .map(|dest_kwr| dest_kwr.copy_from_slice(kwr))
+}
+
+/// Append the variable length array of the given field to `new_heap`, and update the array
+/// descriptor (i.e. the heap offset) in the given row.
+fn copy_heap_array(
+  field_schema: &FieldSchema,
+  row: &mut [u8],
+  heap: &[u8],
+  new_heap: &mut Vec<u8>,
+) -> Result<(), Error> {
+  let from = field_schema.starting_byte;
+  let new_offset = new_heap.len();
+  let (elem_byte_len, len, offset) = match &field_schema.schema {
+    Schema::HeapArrayPtr32(has) => (
+      has.stored_elem_byte_len(),
+      read_i32(row, from) as i64,
+      read_i32(row, from + 4) as i64,
+    ),
+    Schema::HeapArrayPtr64(has) => (
+      has.stored_elem_byte_len(),
+      read_i64(row, from),
+      read_i64(row, from + 8),
+    ),
+    _ => return Ok(()),
+  };
+  let array = usize::try_from(len)
+    .ok()
+    .zip(usize::try_from(offset).ok())
+    .and_then(|(len, offset)| {
+      len
+        .checked_mul(elem_byte_len)
+        .and_then(|byte_len| offset.checked_add(byte_len))
+        .and_then(|end| heap.get(offset..end))
+    })
+    .ok_or_else(|| {
+      new_custom(format!(
+        "Variable length array out of the heap. Expected: offset + len * {} <= {}. Actual: offset = {}; len = {}.",
+        elem_byte_len,
+        heap.len(),
+        offset,
+        len
+      ))
+    })?;
+  match &field_schema.schema {
+    Schema::HeapArrayPtr32(_) => {
+      let new_offset = i32::try_from(new_offset)
+        .map_err(|_| new_custom("Heap too large for 32 bit array descriptors."))?;
+      row[from + 4..from + 8].copy_from_slice(&new_offset.to_be_bytes());
+    }
+    _ => row[from + 8..from + 16].copy_from_slice(&(new_offset as i64).to_be_bytes()),
+  }
+  new_heap.extend_from_slice(array);
+  Ok(())
+}
+
+fn read_i32(row: &[u8], from: usize) -> i32 {
+  i32::from_be_bytes(row[from..from + 4].try_into().unwrap())
+}
+
+fn read_i64(row: &[u8], from: usize) -> i64 {
+  i64::from_be_bytes(row[from..from + 8].try_into().unwrap())
+}
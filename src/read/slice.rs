@@ -3,14 +3,27 @@
 use std::{io::Write, marker::PhantomData};
 
 use crate::{
-  error::{Error, new_io_err},
-  hdu::header::{
-    HDUHeader,
-    builder::{HeaderBuilder, r#impl::bintable::Bintable},
-    raw::RawHeader,
+  common::{
+    KW_RANGE, ValueKwr,
+    keywords::checksum::{CheckSum, DataSum},
   },
+  error::{Error, new_custom, new_io_err},
+  hdu::{
+    header::{
+      HDUHeader,
+      builder::{HeaderBuilder, r#impl::bintable::Bintable},
+      raw::RawHeader,
+    },
+    xtension::{
+      bintable::read::{column::ColumnIter, deser::row::FromField},
+      image::data::{ImageData, ImageScaling},
+    },
+  },
+  read::checksum::{ChecksumStatus, ChecksumVerification, OnesComplementSum, ones_complement_add},
 };
 
+#[cfg(feature = "arrow")]
+use crate::hdu::xtension::bintable::read::visitor::arrow::RecordBatchIter;
 #[cfg(feature = "vot")]
 use votable::{VOTable, VOTableError, impls::mem::VoidTableDataContent, votable::VOTableWrapper};
 
@@ -40,6 +53,9 @@ pub struct HDU<'u, B: HeaderBuilder> {
   pub raw_header: RawHeader<&'u [u8; 2880]>,
   pub parsed_header: HDUHeader<B>,
   pub data: &'u [u8],
+  /// Bytes following the data up to the end of the last 2880 bytes block (may be truncated if
+  /// the file is truncated).
+  pub padding: &'u [u8],
 }
 impl<'u, B: HeaderBuilder> HDU<'u, B> {
   pub fn is_primary_hdu(&self) -> bool {
@@ -69,6 +85,45 @@ impl<'u, B: HeaderBuilder> HDU<'u, B> {
     self.data
   }
 
+  /// Returns the 1's complement checksum of the data unit, padding included, i.e. the expected
+  /// value of the `DATASUM` keyword.
+  pub fn datasum(&self) -> u32 {
+    OnesComplementSum::new()
+      .update(self.data)
+      .update(self.padding)
+      .value()
+  }
+
+  /// Verifies the `CHECKSUM` and `DATASUM` keywords, if present in the header.
+  pub fn verify_checksums(&self) -> ChecksumVerification {
+    let mut checksum = ChecksumStatus::Missing;
+    let mut datasum = ChecksumStatus::Missing;
+    let mut header_sum = OnesComplementSum::new();
+    for block in self.raw_header.blocks_iter() {
+      header_sum.update(block);
+    }
+    let data_sum = self.datasum();
+    let valid_or_invalid = |valid: bool| {
+      if valid {
+        ChecksumStatus::Valid
+      } else {
+        ChecksumStatus::Invalid
+      }
+    };
+    for kwr in self.raw_header.kw_records_iter() {
+      if &kwr[KW_RANGE] == CheckSum::KEYWORD {
+        checksum = valid_or_invalid(
+          ones_complement_add(header_sum.value(), data_sum) == u32::MAX,
+        );
+      } else if &kwr[KW_RANGE] == DataSum::KEYWORD {
+        datasum = valid_or_invalid(
+          DataSum::from_keyword_record(kwr).is_ok_and(|found| found.get() == data_sum),
+        );
+      }
+    }
+    ChecksumVerification { checksum, datasum }
+  }
+
   pub fn copy_hdu<W: Write>(&self, w: &mut W) -> Result<(), Error> {
     self
       .copy_header(w)
@@ -121,6 +176,58 @@ impl<'u> HDU<'u, Bintable> {
       _ => None,
     }
   }
+
+  /// Returns a view on the pixels of the primary HDU or of the `IMAGE` extension, taking into
+  /// account the `BSCALE`, `BZERO` and `BLANK` keywords.
+  /// Returns `None` if the HDU is neither a primary HDU nor an `IMAGE` extension, or if it has
+  /// no data (`NAXIS = 0`).
+  pub fn image_data(&self) -> Option<Result<ImageData<'u>, Error>> {
+    let (bitpix, naxisn) = match &self.parsed_header {
+      #[cfg(not(feature = "vot"))]
+      HDUHeader::Primary(h) => (h.bitpix, &h.naxisn),
+      #[cfg(feature = "vot")]
+      HDUHeader::Primary(h) => (h.primary().bitpix, &h.primary().naxisn),
+      HDUHeader::Image(h) => (h.bitpix, &h.naxisn),
+      _ => return None,
+    };
+    if naxisn.is_empty() {
+      return None;
+    }
+    Some(
+      ImageScaling::from_kw_records(self.raw_header.kw_records_iter())
+        .and_then(|scaling| ImageData::from_naxisn(bitpix, naxisn, scaling, self.data)),
+    )
+  }
+
+  /// Returns an iterator over the values of the BINTABLE column of given name, reading only
+  /// the bytes of the column in each row.
+  /// Use [ColumnIter::chunks] to process the values in parallel.
+  /// # Warning
+  /// Fails if the HDU is not a BINTABLE, if the column is not found or if its values can't be
+  /// deserialized into `T`.
+  pub fn column<T: FromField>(&self, name: &str) -> Result<ColumnIter<'u, T>, Error> {
+    match &self.parsed_header {
+      HDUHeader::BinTable(h) => ColumnIter::new(h, self.data, name),
+      _ => Err(new_custom("HDU is not a BINTABLE.")),
+    }
+  }
+
+  /// Returns an iterator over the rows of the BINTABLE, by chunks of (at most) `n_rows` rows
+  /// converted into Arrow `RecordBatch`es.
+  /// The values of the `TUNITn`, `TUCDn` and `TCOMMn` keywords are stored in the fields metadata.
+  /// # Warning
+  /// Fails if the HDU is not a BINTABLE, or if the BINTABLE is tile-compressed (use
+  /// [Table::arrow_record_batches](crate::read::table::Table::arrow_record_batches) instead).
+  #[cfg(feature = "arrow")]
+  pub fn arrow_record_batches(&self, n_rows: usize) -> Result<RecordBatchIter<'u>, Error> {
+    match &self.parsed_header {
+      HDUHeader::BinTable(h) if h.is_tile_compressed() => Err(new_custom(
+        "Tile-compressed BINTABLE: decompress it first (see the ztable module).",
+      )),
+      HDUHeader::BinTable(h) => Ok(RecordBatchIter::new(h, self.data, n_rows)),
+      _ => Err(new_custom("HDU is not a BINTABLE.")),
+    }
+  }
 }
 
 pub struct HDUIterator<'a, B: HeaderBuilder> {
@@ -170,11 +277,16 @@ impl<'a, B: HeaderBuilder> Iterator for HDUIterator<'a, B> {
               if self.ptr % 2880 != 0 {
                 self.ptr = (1 + self.ptr / 2880) * 2880;
               }
+              // get padding part (up to the end of the block, if not truncated)
+              let padding_end = (self.ptr - starting_byte - raw_header.byte_size())
+                .min(remaining_bytes.len());
+              let padding = &remaining_bytes[data_byte_size..padding_end];
               HDU {
                 starting_byte,
                 raw_header,
                 parsed_header,
                 data,
+                padding,
               }
             })
           },
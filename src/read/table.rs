@@ -0,0 +1,433 @@
+//! High-level access to a single BINTABLE of a FITS file.
+//!
+//! [Table] hides the memory mapping, the HDU iteration, the split of the data part into the main
+//! table, the gap and the heap, and the decompression of compressed files and tile-compressed
+//! tables:
+//! ```ignore
+//! let table = Table::open("cat.fits.gz", HDUSelector::First)?;
+//! println!("{}", table.col_names().join(","));
+//! for row in table.rows() {
+//!   let fields: Vec<Field> = row?;
+//! }
+//! ```
+
+use std::{
+  borrow::Cow,
+  fmt::{Display, Formatter},
+  fs::File,
+  ops::Range,
+  path::Path,
+  str::FromStr,
+  thread,
+};
+
+use memmap2::Mmap;
+
+use crate::{
+  error::{Error, new_custom, new_io_err},
+  hdu::{
+    header::{HDUHeader, builder::r#impl::bintable::Bintable, raw::RawHeader},
+    xtension::bintable::{
+      field::Field,
+      header::{BinTableColumnHeader, BinTableHeaderWithColInfo},
+      read::{
+        column::ColumnIter,
+        deser::{
+          row::{FitsRow, FromField, RowBinding},
+          sliceheap::DeserializerWithHeap,
+        },
+        visitor::{FieldVisitorProvider, RowVisitor, field::FieldVisitor},
+      },
+      schema::RowSchema,
+      ztable::decompress_if_tile_compressed,
+    },
+  },
+  read::{
+    decompress,
+    slice::{HDU, HDUIterator},
+  },
+};
+
+#[cfg(feature = "arrow")]
+use crate::hdu::xtension::bintable::read::visitor::arrow::RecordBatchIter;
+
+/// Selects the BINTABLE HDU to be read in a FITS file.
+///
+/// Parsed from a string (e.g. a command line argument) being either:
+/// * an HDU index, e.g. `2`
+/// * an extension name, possibly followed by a version, e.g. `EVENTS` or `EVENTS,2`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum HDUSelector {
+  /// The first BINTABLE HDU of the file.
+  #[default]
+  First,
+  /// The HDU of given index, `0` being the primary HDU.
+  Index(usize),
+  /// The first BINTABLE HDU having the given `EXTNAME` (or `HDUNAME`), case insensitive, and the
+  /// given `EXTVER` if provided.
+  Name { name: String, version: Option<i64> },
+}
+
+impl HDUSelector {
+  /// Tells whether the HDU of given index and header matches the selector.
+  /// For [HDUSelector::Index], the HDU is not required to be a BINTABLE.
+  /// # Warning
+  /// Several HDUs may match [HDUSelector::First] or [HDUSelector::Name]: when iterating over the
+  /// HDUs, only the first matching one is the selected HDU (see [HDUSelector::select]).
+  pub fn matches(&self, index: usize, parsed_header: &HDUHeader<Bintable>) -> bool {
+    match (self, parsed_header) {
+      (Self::First, HDUHeader::BinTable(_)) => true,
+      (Self::Index(i), _) => *i == index,
+      (Self::Name { name, version }, HDUHeader::BinTable(h)) => h.extid().matches(name, *version),
+      _ => false,
+    }
+  }
+
+  /// Returns the index of the selected HDU, together with the HDU.
+  /// # Params
+  /// * `bytes`: the full content, all bytes, of a FITS file
+  /// # Errors
+  /// If the HDU is not found, or if it is not a BINTABLE.
+  pub fn select<'a>(&self, bytes: &'a [u8]) -> Result<(usize, HDU<'a, Bintable>), Error> {
+    for (i, hdu) in HDUIterator::<Bintable>::from_slice(bytes).enumerate() {
+      let hdu = hdu?;
+      if self.matches(i, &hdu.parsed_header) {
+        return if hdu.is_bintable_hdu() {
+          Ok((i, hdu))
+        } else {
+          Err(new_custom(format!("HDU {} is not a BINTABLE.", i)))
+        };
+      }
+    }
+    Err(new_custom(format!("{} not found.", self)))
+  }
+}
+
+impl FromStr for HDUSelector {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let s = s.trim();
+    if let Ok(index) = s.parse::<usize>() {
+      return Ok(Self::Index(index));
+    }
+    let (name, version) = match s.rsplit_once(',') {
+      Some((name, version)) => version
+        .trim()
+        .parse::<i64>()
+        .map(|v| (name.trim(), Some(v)))
+        .map_err(|e| format!("Wrong EXTVER value in HDU selector '{}': {}", s, e))?,
+      None => (s, None),
+    };
+    if name.is_empty() {
+      Err(String::from("Empty HDU selector."))
+    } else {
+      Ok(Self::Name {
+        name: name.to_string(),
+        version,
+      })
+    }
+  }
+}
+
+impl Display for HDUSelector {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::First => f.write_str("BINTABLE HDU"),
+      Self::Index(i) => write!(f, "HDU {}", i),
+      Self::Name {
+        name,
+        version: None,
+      } => write!(f, "BINTABLE HDU '{}'", name),
+      Self::Name {
+        name,
+        version: Some(v),
+      } => write!(f, "BINTABLE HDU '{}' version {}", name, v),
+    }
+  }
+}
+
+/// A single BINTABLE of a (possibly compressed) FITS file.
+pub struct Table {
+  /// Memory map of the (decompressed) FITS file.
+  mmap: Mmap,
+  /// Index of the BINTABLE HDU in the file.
+  hdu_index: usize,
+  /// Range of bytes, in the file, of the primary HDU (header, data and padding).
+  primary_hdu_range: Range<usize>,
+  /// Range of bytes, in the file, of the BINTABLE header.
+  header_range: Range<usize>,
+  /// Range of bytes, in the file, of the table data part.
+  data_range: Range<usize>,
+  /// Data part of a tile-compressed table, once decompressed.
+  decompressed_data: Option<Vec<u8>>,
+  /// Header of the (decompressed) table.
+  header: BinTableHeaderWithColInfo,
+  row_schema: RowSchema,
+  col_names: Vec<String>,
+}
+
+impl Table {
+  /// Opens the given FITS file (possibly `gzip`, `bzip2` or `xz` compressed) and selects one of
+  /// its BINTABLE. A tile-compressed table is decompressed in memory.
+  pub fn open<P: AsRef<Path>>(path: P, selector: HDUSelector) -> Result<Self, Error> {
+    let file = File::open(path).map_err(new_io_err)?;
+    decompress::mmap(&file).and_then(|mmap| Self::from_mmap(mmap, selector))
+  }
+
+  /// Same as [Table::open], but from the memory map of an (uncompressed) FITS file, e.g. to
+  /// select the HDU after having inspected the file content.
+  pub fn from_mmap(mmap: Mmap, selector: HDUSelector) -> Result<Self, Error> {
+    let primary_hdu_range = HDUIterator::<Bintable>::from_slice(&mmap)
+      .next()
+      .ok_or_else(|| new_custom("No HDU found."))?
+      .map(|hdu| {
+        hdu.starting_byte..hdu.data_starting_byte() + hdu.data.len() + hdu.padding.len()
+      })?;
+    let (hdu_index, hdu) = selector.select(&mmap)?;
+    let header_range = hdu.starting_byte..hdu.data_starting_byte();
+    let data = hdu.data;
+    let data_range = hdu.data_starting_byte()..hdu.data_starting_byte() + data.len();
+    let header = match hdu.parsed_header {
+      HDUHeader::BinTable(header) => header,
+      _ => unreachable!("HDU selector only returns BINTABLE HDUs"),
+    };
+    let (header, decompressed_data) = match decompress_if_tile_compressed(header, data)? {
+      (header, Cow::Owned(data)) => (header, Some(data)),
+      (header, Cow::Borrowed(_)) => (header, None),
+    };
+    let row_schema = header.build_row_schema();
+    let col_names = header.build_col_names();
+    Ok(Self {
+      mmap,
+      hdu_index,
+      primary_hdu_range,
+      header_range,
+      data_range,
+      decompressed_data,
+      header,
+      row_schema,
+      col_names,
+    })
+  }
+
+  /// Memory map of the FITS file (of its decompressed version if the file is compressed).
+  pub fn mmap(&self) -> &Mmap {
+    &self.mmap
+  }
+
+  /// Index of the table HDU in the file, `0` being the primary HDU.
+  pub fn hdu_index(&self) -> usize {
+    self.hdu_index
+  }
+
+  /// Raw bytes of the primary HDU (header, data and padding), e.g. to be copied in a new file.
+  pub fn raw_primary_hdu(&self) -> &[u8] {
+    &self.mmap[self.primary_hdu_range.clone()]
+  }
+
+  /// Raw header of the table HDU, as in the file (i.e. the header of the tile-compressed table
+  /// if the table is tile-compressed).
+  pub fn raw_header(&self) -> RawHeader<&[u8; 2880]> {
+    RawHeader::<&[u8; 2880]>::from_slice(false, &self.mmap[self.header_range.clone()])
+      .map(|(raw_header, _)| raw_header)
+      .expect("Header already parsed when opening the table")
+  }
+
+  /// Header of the table (of the uncompressed table if it is tile-compressed).
+  pub fn header(&self) -> &BinTableHeaderWithColInfo {
+    &self.header
+  }
+
+  /// Tells whether the table is tile-compressed in the file (and thus decompressed in memory).
+  pub fn is_tile_compressed(&self) -> bool {
+    self.decompressed_data.is_some()
+  }
+
+  pub fn n_rows(&self) -> usize {
+    self.header.n_rows()
+  }
+
+  pub fn n_cols(&self) -> usize {
+    self.header.n_cols()
+  }
+
+  /// Metadata (name, format, unit, UCD, ...) of all columns.
+  pub fn columns(&self) -> &[BinTableColumnHeader] {
+    self.header.cols()
+  }
+
+  /// Names of all columns, `col_{i}` being used for columns having no name.
+  pub fn col_names(&self) -> &[String] {
+    &self.col_names
+  }
+
+  /// Index of the column of given name, if any.
+  pub fn col_index(&self, col_name: &str) -> Option<usize> {
+    self.col_names.iter().position(|name| name == col_name)
+  }
+
+  pub fn row_schema(&self) -> &RowSchema {
+    &self.row_schema
+  }
+
+  /// Starting byte, in the (decompressed) file, of the table data part.
+  /// Meaningless for a tile-compressed table, its data being decompressed in memory.
+  pub fn data_starting_byte(&self) -> usize {
+    self.data_range.start
+  }
+
+  /// Whole data part of the table (main table, gap and heap).
+  pub fn data(&self) -> &[u8] {
+    match &self.decompressed_data {
+      Some(data) => data,
+      None => &self.mmap[self.data_range.clone()],
+    }
+  }
+
+  /// Main table, i.e. the fixed length rows.
+  pub fn main_table(&self) -> &[u8] {
+    &self.data()[..self.header.main_table_byte_size()]
+  }
+
+  /// Heap, i.e. the variable length arrays (possibly empty).
+  pub fn heap(&self) -> &[u8] {
+    let from = self.header.main_table_byte_size() + self.header.gap_byte_size();
+    self.data().get(from..).unwrap_or(&[])
+  }
+
+  /// Bytes of the row of given index (starting at 0).
+  /// # Panics
+  /// If the row index is out of bounds.
+  pub fn row_bytes(&self, irow: usize) -> &[u8] {
+    let row_byte_size = self.header.row_byte_size();
+    let from = irow * row_byte_size;
+    &self.main_table()[from..from + row_byte_size]
+  }
+
+  /// Deserialize the row of given index (starting at 0) using the given visitors.
+  /// # Errors
+  /// If the row index is out of bounds, or if a visitor fails.
+  pub fn visit_row<F, R>(
+    &self,
+    irow: usize,
+    field_visitor_provider: &mut F,
+    row_visitor: R,
+  ) -> Result<R::Value, Error>
+  where
+    F: FieldVisitorProvider,
+    R: RowVisitor<FieldValue = F::FieldValue>,
+  {
+    if irow >= self.n_rows() {
+      return Err(new_custom(format!(
+        "Row index {} out of bounds: {} rows.",
+        irow,
+        self.n_rows()
+      )));
+    }
+    let mut de = DeserializerWithHeap::new(self.row_bytes(irow), self.heap());
+    self
+      .row_schema
+      .deserialize(&mut de, field_visitor_provider, row_visitor)
+  }
+
+  /// Returns the fields of the row of given index (starting at 0).
+  pub fn row(&self, irow: usize) -> Result<Vec<Field>, Error> {
+    self.visit_row(irow, &mut FieldVisitor {}, FieldVisitor {})
+  }
+
+  /// Returns an iterator over the fields of all rows.
+  pub fn rows(&self) -> Rows<'_> {
+    self.rows_range(0..self.n_rows())
+  }
+
+  /// Returns an iterator over the fields of the rows in the given range of row indices.
+  pub fn rows_range(&self, irows: Range<usize>) -> Rows<'_> {
+    Rows {
+      table: self,
+      irows: irows.start..irows.end.min(self.n_rows()),
+    }
+  }
+
+  /// Splits the rows in `n_threads` contiguous chunks, each chunk being processed by `f` in a
+  /// separate thread. The results are returned in the rows order.
+  /// # Example
+  /// ```ignore
+  /// let n_valid_rows: usize = table
+  ///   .par_rows(4, |rows| rows.filter(Result::is_ok).count())
+  ///   .into_iter()
+  ///   .sum();
+  /// ```
+  pub fn par_rows<F, R>(&self, n_threads: usize, f: F) -> Vec<R>
+  where
+    F: Fn(Rows<'_>) -> R + Sync,
+    R: Send,
+  {
+    let n_rows_per_chunk = self.n_rows().div_ceil(n_threads.max(1)).max(1);
+    let f = &f;
+    thread::scope(|s| {
+      (0..self.n_rows())
+        .step_by(n_rows_per_chunk)
+        .map(|from| {
+          let rows = self.rows_range(from..from + n_rows_per_chunk);
+          s.spawn(move || f(rows))
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+        .map(|handle| handle.join().expect("Rows processing thread panicked"))
+        .collect()
+    })
+  }
+
+  /// Returns a typed iterator over the values of the column of given name.
+  pub fn column<T: FromField>(&self, col_name: &str) -> Result<ColumnIter<'_, T>, Error> {
+    ColumnIter::new(&self.header, self.data(), col_name)
+  }
+
+  /// Returns an iterator over the rows deserialized into `T`.
+  /// # Errors
+  /// If the columns do not match the fields of `T`, or if the table data is too short.
+  pub fn rows_as<'a, T: FitsRow + 'a>(
+    &'a self,
+  ) -> Result<impl Iterator<Item = Result<T, Error>> + 'a, Error> {
+    let binding = RowBinding::<T>::new(&self.col_names, &self.row_schema)?;
+    let main_table_byte_size = self.header.main_table_byte_size();
+    if self.data().len() < main_table_byte_size {
+      return Err(new_custom(format!(
+        "BINTABLE data too short. Expected: at least {}. Actual: {}.",
+        main_table_byte_size,
+        self.data().len()
+      )));
+    }
+    let heap = self.heap();
+    // Rows are not iterated with `chunks_exact` which panics on a zero row size (NAXIS1 = 0)
+    Ok((0..self.n_rows()).map(move |irow| binding.deserialize_row(self.row_bytes(irow), heap)))
+  }
+
+  /// Returns an iterator over the rows, by chunks of (at most) `n_rows` rows converted into
+  /// Arrow `RecordBatch`es (see [HDU::arrow_record_batches]).
+  #[cfg(feature = "arrow")]
+  pub fn arrow_record_batches(&self, n_rows: usize) -> RecordBatchIter<'_> {
+    RecordBatchIter::new(&self.header, self.data(), n_rows)
+  }
+}
+
+/// Iterates over the fields of a range of rows of a [Table].
+pub struct Rows<'t> {
+  table: &'t Table,
+  irows: Range<usize>,
+}
+
+impl<'t> Iterator for Rows<'t> {
+  type Item = Result<Vec<Field>, Error>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    self.irows.next().map(|irow| self.table.row(irow))
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    self.irows.size_hint()
+  }
+}
+
+impl<'t> ExactSizeIterator for Rows<'t> {}
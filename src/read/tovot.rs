@@ -0,0 +1,415 @@
+//! Module dedicated to the conversion of the BINTABLEs of a FITS file into a VOTable, the data
+//! being serialized in `TABLEDATA`, `BINARY` or `BINARY2`.
+
+use std::{
+  fs::File,
+  io::{BufWriter, Write},
+  mem,
+  path::Path,
+  str::FromStr,
+};
+
+use log::{debug, warn};
+#[cfg(not(windows))]
+use memmap2::Advice;
+use votable::{
+  Resource, Table, TableElem, VOTable, VOTableError, VoidTableDataContent,
+  field::Field as VOTField,
+  impls::{TableSchema, VOTableValue, mem::InMemTableDataRows},
+  resource::ResourceOrTable,
+  votable::Version,
+};
+
+use crate::{
+  error::{Error, new_custom, new_io_err},
+  hdu::{
+    header::{HDUHeader, builder::r#impl::bintable::Bintable},
+    xtension::bintable::{
+      header::BinTableHeaderWithColInfo,
+      read::{
+        deser::sliceheap::DeserializerWithHeap,
+        visitor::vot::{VOTableRowVisitor, VOTableVisitor},
+      },
+      schema::RowSchema,
+      ztable::ChunkedTable,
+    },
+  },
+  read::{decompress, slice::FitsBytes},
+};
+
+/// Number of rows read at once from a regular (not tile-compressed) BINTABLE.
+const N_ROWS_PER_CHUNK: usize = 4096;
+
+/// Serialization of the VOTable data.
+#[derive(Debug, Clone, Copy)]
+pub enum VOTDataFormat {
+  TableData,
+  Binary,
+  Binary2,
+}
+impl FromStr for VOTDataFormat {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "tabledata" | "TableData" | "TABLEDATA" => Ok(Self::TableData),
+      "binary" | "Binary" | "BINARY" => Ok(Self::Binary),
+      "binary2" | "Binary2" | "BINARY2" => Ok(Self::Binary2),
+      _ => Err(format!(
+        "Unrecognized VOTable data format '{}'. Expected: 'tabledata', 'binary' or 'binary2'",
+        s
+      )),
+    }
+  }
+}
+
+/// Writes all the BINTABLEs of the given FITS file in a VOTable, one `TABLE` per BINTABLE.
+/// Returns the number of tables written.
+///
+/// # Params
+/// * `input`: path of the input FITS file
+/// * `format`: serialization of the VOTable data
+/// * `writer`: destination of the VOTable
+///
+/// # Remarks
+/// * in a FITS-plus file, the VOTable of the primary HDU is used as a template (providing e.g.
+///   `PARAM`s, `GROUP`s, `COOSYS`s, ...), if consistent with the BINTABLEs
+/// * else, the `FIELD`s are built from the BINTABLE headers, variable length arrays
+///   having `arraysize="*"`
+/// * tile-compressed BINTABLEs are decompressed one tile at a time
+/// * bit array columns (`X`) are not supported
+pub fn fits_to_vot<P: AsRef<Path>, W: Write>(
+  input: P,
+  format: VOTDataFormat,
+  writer: W,
+) -> Result<u16, Error> {
+  debug!("Start FITS to VOTable conversion...");
+  let mut writer = BufWriter::new(writer);
+
+  // Prepare reading, creating a memory map
+  let file = File::open(input).map_err(new_io_err)?;
+  let mmap = decompress::mmap(&file)?;
+  #[cfg(not(windows))]
+  if let Err(e) = mmap.advise(Advice::Sequential) {
+    warn!(
+      "Error advising for sequential read on file '{:?}': {}",
+      file, e
+    );
+  }
+
+  // Read the HDUs headers
+  let fits = FitsBytes::from_slice(mmap.as_ref());
+  let mut template: Option<VOTable<VoidTableDataContent>> = None;
+  let mut tables: Vec<ChunkedTable> = Vec::new();
+  for hdu in fits.new_iterator::<Bintable>() {
+    let hdu = hdu?;
+    if hdu.is_primary_hdu() {
+      template = match hdu.parse_votable_if_any() {
+        Some(Ok(votable)) => Some(votable),
+        Some(Err(e)) => {
+          warn!(
+            "Error parsing the FITS-plus VOTable, template ignored: {}",
+            e
+          );
+          None
+        }
+        None => None,
+      };
+    } else if let HDUHeader::BinTable(header) = hdu.parsed_header {
+      tables.push(ChunkedTable::new(header, hdu.data)?);
+    }
+  }
+  if tables.is_empty() {
+    return Err(new_custom("No BINTABLE HDU found!"));
+  }
+  let n_table = u16::try_from(tables.len())
+    .map_err(|_| new_custom(format!("Too many tables: {}.", tables.len())))?;
+  let headers: Vec<&BinTableHeaderWithColInfo> =
+    tables.iter().map(|table| table.header()).collect();
+
+  // Build the VOTable metadata
+  let mut votable = match template {
+    Some(mut votable) => {
+      if prepare_template(&mut votable, &headers) {
+        debug!("FITS-plus VOTable used as template");
+        votable
+      } else {
+        warn!("FITS-plus VOTable not consistent with the BINTABLEs, template ignored");
+        build_votable(&headers)?
+      }
+    }
+    None => build_votable(&headers)?,
+  };
+  let mut tables_schemas: Vec<TableSchema> = Vec::with_capacity(headers.len());
+  for_each_table(&mut votable.resources, &mut |table| {
+    tables_schemas.push(TableSchema::from(table.elems.as_slice()))
+  });
+  let xml = votable.wrap().to_ivoa_xml_bytes().map_err(new_vot_err)?;
+
+  // Write the VOTable, inserting the data of each table before its `</TABLE>` end tag
+  let (mut from, mut search_from) = (0, 0);
+  for (table, table_schema) in tables.iter().zip(tables_schemas) {
+    let (line_start, tag_start) = find_table_end(&xml, search_from)?;
+    writer
+      .write_all(&xml[from..line_start])
+      .map_err(new_io_err)?;
+    let indent = " ".repeat(tag_start - line_start + 4);
+    write_data(&mut writer, table, table_schema, format, &indent)?;
+    from = line_start;
+    search_from = tag_start + 1;
+  }
+  writer
+    .write_all(&xml[from..])
+    .and_then(|()| writer.flush())
+    .map_err(new_io_err)
+    .map(|()| n_table)
+}
+
+fn new_vot_err(e: VOTableError) -> Error {
+  new_custom(format!("VOTable error: {}", e))
+}
+
+/// Build a VOTable containing a single `RESOURCE` with one `TABLE` per BINTABLE.
+fn build_votable(
+  headers: &[&BinTableHeaderWithColInfo],
+) -> Result<VOTable<VoidTableDataContent>, Error> {
+  let mut resource = Resource::new();
+  for header in headers {
+    let mut table = Table::new();
+    let row_schema: RowSchema = header.build_row_schema();
+    for (i, (col, field_schema)) in header
+      .cols()
+      .iter()
+      .zip(row_schema.fields_schemas())
+      .enumerate()
+    {
+      table.push_field_by_ref(col.to_vot_field_with_schema(i as u16, &field_schema.schema)?);
+    }
+    resource.push_table_by_ref(table);
+  }
+  Ok(VOTable::new(Version::V1_5, resource))
+}
+
+/// Returns `false` if the template is not consistent with the BINTABLEs (different number of
+/// tables or of columns, or `FIELD`s having other datatypes or arraysizes than the ones built from
+/// the BINTABLE headers), else removes the `DATA` part of the template tables, sets their `nrows`
+/// and returns `true`.
+fn prepare_template(
+  votable: &mut VOTable<VoidTableDataContent>,
+  headers: &[&BinTableHeaderWithColInfo],
+) -> bool {
+  let mut n_tables = 0;
+  let mut is_consistent = true;
+  for_each_table(&mut votable.resources, &mut |table| {
+    match headers.get(n_tables) {
+      Some(header) => {
+        is_consistent &= are_fields_consistent(
+          table.elems.iter().filter_map(|elem| match elem {
+            TableElem::Field(field) => Some(field),
+            _ => None,
+          }),
+          header,
+        );
+        table.nrows = Some(header.n_rows() as u64);
+      }
+      None => is_consistent = false,
+    }
+    n_tables += 1;
+    table.data = None;
+    // INFOs following the DATA are moved before the FIELDs since the data are written at the end
+    let mut post_infos = mem::take(&mut table.post_infos);
+    table.infos.append(&mut post_infos);
+  });
+  is_consistent && n_tables == headers.len()
+}
+
+/// Tells whether the given template `FIELD`s have the datatypes and arraysizes of the `FIELD`s
+/// built from the BINTABLE columns (the data being serialized according to the latter).
+fn are_fields_consistent<'a, I>(fields: I, header: &BinTableHeaderWithColInfo) -> bool
+where
+  I: Iterator<Item = &'a VOTField>,
+{
+  let fields: Vec<&VOTField> = fields.collect();
+  let row_schema: RowSchema = header.build_row_schema();
+  fields.len() == header.cols().len()
+    && header
+      .cols()
+      .iter()
+      .zip(row_schema.fields_schemas())
+      .zip(fields)
+      .enumerate()
+      .all(|(i, ((col, field_schema), field))| {
+        col
+          .to_vot_field_with_schema(i as u16, &field_schema.schema)
+          .is_ok_and(|expected| {
+            expected.datatype == field.datatype && expected.arraysize == field.arraysize
+          })
+      })
+}
+
+/// Apply the given function on each table, in document order.
+pub(crate) fn for_each_table<F>(resources: &mut [Resource<VoidTableDataContent>], f: &mut F)
+where
+  F: FnMut(&mut Table<VoidTableDataContent>),
+{
+  for resource in resources {
+    for sub_elem in resource.sub_elems.iter_mut() {
+      match &mut sub_elem.resource_or_table {
+        ResourceOrTable::Resource(r) => for_each_table(std::slice::from_mut(r), f),
+        ResourceOrTable::Table(table) => f(table),
+      }
+    }
+  }
+}
+
+/// Returns the index of the first byte of the line containing the first `</TABLE>` end tag
+/// found from the given index, together with the index of the tag itself.
+fn find_table_end(xml: &[u8], from: usize) -> Result<(usize, usize), Error> {
+  const END_TAG: &[u8] = b"</TABLE>";
+  let pos = xml[from..]
+    .windows(END_TAG.len())
+    .position(|w| w == END_TAG)
+    .map(|pos| from + pos)
+    .ok_or_else(|| new_custom("TABLE end tag not found in the VOTable metadata."))?;
+  let line_start = xml[from..pos]
+    .iter()
+    .rposition(|b| *b == b'\n')
+    .map(|i| from + i + 1)
+    .unwrap_or(pos);
+  Ok((line_start, pos))
+}
+
+/// Write the `DATA` element of a table.
+fn write_data<W: Write>(
+  writer: &mut W,
+  table: &ChunkedTable,
+  table_schema: TableSchema,
+  format: VOTDataFormat,
+  indent: &str,
+) -> Result<(), Error> {
+  let header = table.header();
+  let row_byte_size = header.row_byte_size();
+  let heap = table.heap();
+  let row_schema: RowSchema = header.build_row_schema();
+  let mut visitor = VOTableVisitor::new(table_schema.clone().unwrap());
+  let mut error: Option<Error> = None;
+  let rows = table
+    .row_chunks(N_ROWS_PER_CHUNK)
+    .take(if row_byte_size > 0 { usize::MAX } else { 0 })
+    .map_while(|chunk| {
+      let rows = chunk.and_then(|chunk| {
+        chunk
+          .chunks(row_byte_size)
+          .map(|raw_row| {
+            let mut de = DeserializerWithHeap::new(raw_row, heap);
+            row_schema.deserialize(&mut de, &mut visitor, VOTableRowVisitor)
+          })
+          .collect::<Result<Vec<_>, Error>>()
+      });
+      match rows {
+        Ok(rows) => Some(rows),
+        Err(e) => {
+          error = Some(e);
+          None
+        }
+      }
+    })
+    .flatten();
+  match format {
+    VOTDataFormat::TableData => {
+      write!(writer, "{}<DATA>\n{}  <TABLEDATA>\n", indent, indent).map_err(new_io_err)?;
+      for row in rows {
+        write_tabledata_row(writer, &row, indent).map_err(new_io_err)?;
+      }
+      write!(writer, "{}  </TABLEDATA>\n{}</DATA>\n", indent, indent).map_err(new_io_err)
+    }
+    VOTDataFormat::Binary | VOTDataFormat::Binary2 => {
+      let tag = if matches!(format, VOTDataFormat::Binary) {
+        "BINARY"
+      } else {
+        "BINARY2"
+      };
+      write!(
+        writer,
+        "{}<DATA>\n{}  <{}>\n{}    <STREAM encoding=\"base64\">\n",
+        indent, indent, tag, indent
+      )
+      .map_err(new_io_err)?;
+      if matches!(format, VOTDataFormat::Binary) {
+        InMemTableDataRows::write_binary_rows(&mut *writer, rows, table_schema)
+      } else {
+        InMemTableDataRows::write_binary2_rows(&mut *writer, rows, table_schema)
+      }
+      .map_err(new_vot_err)?;
+      write!(
+        writer,
+        "\n{}    </STREAM>\n{}  </{}>\n{}</DATA>\n",
+        indent, indent, tag, indent
+      )
+      .map_err(new_io_err)
+    }
+  }?;
+  match error {
+    Some(e) => Err(e),
+    None => Ok(()),
+  }
+}
+
+fn write_tabledata_row<W: Write>(
+  writer: &mut W,
+  row: &[VOTableValue],
+  indent: &str,
+) -> std::io::Result<()> {
+  write!(writer, "{}    <TR>", indent)?;
+  for value in row {
+    write!(writer, "<TD>{}</TD>", tabledata_value(value))?;
+  }
+  writer.write_all(b"</TR>\n")
+}
+
+/// Returns the `TABLEDATA` (XML escaped) representation of the given value.
+fn tabledata_value(value: &VOTableValue) -> String {
+  fn join<T, F: Fn(&T) -> String>(elems: &[T], f: F) -> String {
+    elems.iter().map(f).collect::<Vec<String>>().join(" ")
+  }
+  fn real<T: Into<f64> + ToString + Copy>(v: &T) -> String {
+    let f: f64 = (*v).into();
+    if f.is_infinite() {
+      String::from(if f > 0.0 { "+Inf" } else { "-Inf" })
+    } else {
+      v.to_string()
+    }
+  }
+  fn bool(v: &Option<bool>) -> String {
+    String::from(match v {
+      Some(true) => "T",
+      Some(false) => "F",
+      None => "?",
+    })
+  }
+  fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+      .replace('<', "&lt;")
+      .replace('>', "&gt;")
+  }
+  match value {
+    VOTableValue::Null => String::new(),
+    VOTableValue::Bool(v) => bool(&Some(*v)),
+    VOTableValue::Float(v) => real(v),
+    VOTableValue::Double(v) => real(v),
+    VOTableValue::ComplexFloat((r, i)) => format!("{} {}", real(r), real(i)),
+    VOTableValue::ComplexDouble((r, i)) => format!("{} {}", real(r), real(i)),
+    VOTableValue::CharASCII(c) | VOTableValue::CharUnicode(c) => escape(c.to_string().as_str()),
+    VOTableValue::String(s) => escape(s.trim_end_matches('\0')),
+    VOTableValue::BooleanArray(v) => join(v, bool),
+    VOTableValue::ByteArray(v) => join(v, |e| e.to_string()),
+    VOTableValue::ShortArray(v) => join(v, |e| e.to_string()),
+    VOTableValue::IntArray(v) => join(v, |e| e.to_string()),
+    VOTableValue::LongArray(v) => join(v, |e| e.to_string()),
+    VOTableValue::FloatArray(v) => join(v, real),
+    VOTableValue::DoubleArray(v) => join(v, real),
+    VOTableValue::ComplexFloatArray(v) => join(v, |(r, i)| format!("{} {}", real(r), real(i))),
+    VOTableValue::ComplexDoubleArray(v) => join(v, |(r, i)| format!("{} {}", real(r), real(i))),
+    _ => escape(value.to_string().as_str()),
+  }
+}
@@ -0,0 +1,762 @@
+//! Module dedicated to the verification of the conformance of a FITS file to the FITS standard,
+//! in the spirit of HEASARC's `fitsverify`.
+//! Contrary to the regular readers, failing at the first error, all detected issues are reported.
+
+use std::{
+  collections::HashMap,
+  fmt::{self, Display, Formatter},
+  fs::File,
+  path::Path,
+};
+
+use crate::{
+  common::{
+    DynValueKwr, KW_RANGE, ValueKwr,
+    keywords::{
+      naxis::NAxis,
+      simple::Simple,
+      tables::{
+        bintable::{
+          tdim::TDim,
+          tdisp::TDispn,
+          tform::{TFormValue, TFormn, VariableLenghtArrayDataType},
+          theap::THeap,
+        },
+        tcomm::TComm,
+        tdminmax::{TDMax, TDMin},
+        tnull::TNull,
+        tscaltzero::{TScal, TZero},
+        ttype::TType,
+        tucd::TUCD,
+        tunit::TUnit,
+      },
+      xtension::Xtension,
+    },
+    read::{FixedFormatRead, KwrFormatRead, is_value_indicator},
+  },
+  error::{Error, new_io_err},
+  hdu::{
+    header::{HDUHeader, builder::r#impl::minimal::Minimalist, raw::RawHeader},
+    xtension::bintable::header::BinTableHeader,
+  },
+  read::{checksum::ChecksumStatus, decompress, slice::HDU},
+};
+
+/// Severity of an issue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+  /// The file does not follow a recommendation, or may be misinterpreted.
+  Warning,
+  /// The file does not conform to the FITS standard.
+  Error,
+}
+
+impl Display for Severity {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Warning => f.write_str("warning"),
+      Self::Error => f.write_str("error"),
+    }
+  }
+}
+
+/// An issue detected while verifying a FITS file.
+#[derive(Debug, Clone)]
+pub struct Issue {
+  pub severity: Severity,
+  /// Index of the HDU, starting at 0 for the primary HDU.
+  pub hdu: usize,
+  /// Number of the keyword record in the HDU header, starting at 1, if the issue is related
+  /// to a given keyword record.
+  pub kw_record: Option<usize>,
+  /// Keyword the issue is related to, if any.
+  pub keyword: Option<String>,
+  pub message: String,
+}
+
+/// Verifies the given FITS file, see [verify].
+pub fn verify_file<P: AsRef<Path>>(path: P) -> Result<Vec<Issue>, Error> {
+  let file = File::open(path).map_err(new_io_err)?;
+  let mmap = decompress::mmap(&file)?;
+  Ok(verify(mmap.as_ref()))
+}
+
+/// Verifies the given FITS file bytes, returning the list of warnings and errors found.
+///
+/// # Checks
+/// * headers: illegal characters, keyword names, duplicated keywords, mandatory keywords order
+///   and values, `END` and header fill area
+/// * data: truncated data unit, fill area (zeros, or blanks for ASCII tables)
+/// * BINTABLEs: columns keywords values (`TTYPEn`, `TFORMn`, `TNULLn`, `TSCALn`, ...),
+///   missing `TFORMn`, `NAXIS1` consistency with `TFORMn`, `TNULLn` on non-integer columns,
+///   `TSCALn`/`TZEROn` on `A`, `L` or `X` columns, `TDIMn` consistency, `THEAP` validity,
+///   `PCOUNT` without variable length arrays and out of range heap descriptors
+/// * `CHECKSUM` and `DATASUM`, if present (warnings)
+///
+/// # Remark
+/// The verification stops at the first HDU whose size cannot be determined.
+pub fn verify(bytes: &[u8]) -> Vec<Issue> {
+  let mut report = Report::default();
+  if bytes.is_empty() {
+    report.error(None, None, "Empty file.");
+  }
+  let mut starting_byte = 0;
+  while starting_byte < bytes.len() {
+    match verify_hdu(bytes, starting_byte, &mut report) {
+      Some(hdu_byte_size) => starting_byte += hdu_byte_size,
+      None => break,
+    }
+    report.hdu += 1;
+  }
+  report.issues
+}
+
+#[derive(Default)]
+struct Report {
+  /// Index of the current HDU.
+  hdu: usize,
+  issues: Vec<Issue>,
+}
+
+impl Report {
+  fn add<S: Into<String>>(
+    &mut self,
+    severity: Severity,
+    kw_record: Option<usize>,
+    keyword: Option<&str>,
+    message: S,
+  ) {
+    self.issues.push(Issue {
+      severity,
+      hdu: self.hdu,
+      kw_record,
+      keyword: keyword.map(String::from),
+      // Error messages may contain contexts on several lines
+      message: message.into().replace('\n', " "),
+    });
+  }
+
+  fn warning<S: Into<String>>(&mut self, kw_record: Option<usize>, keyword: Option<&str>, msg: S) {
+    self.add(Severity::Warning, kw_record, keyword, msg);
+  }
+
+  fn error<S: Into<String>>(&mut self, kw_record: Option<usize>, keyword: Option<&str>, msg: S) {
+    self.add(Severity::Error, kw_record, keyword, msg);
+  }
+}
+
+/// Verifies the HDU starting at the given byte, and returns its size (including padding) or
+/// `None` if the size can not be determined.
+fn verify_hdu(bytes: &[u8], starting_byte: usize, report: &mut Report) -> Option<usize> {
+  let is_primary = starting_byte == 0;
+  let hdu_bytes = &bytes[starting_byte..];
+
+  // Check the first keyword and the presence of END (else `RawHeader` panics).
+  let first_kw = if is_primary {
+    Simple::KEYWORD
+  } else {
+    Xtension::KEYWORD
+  };
+  if !hdu_bytes.starts_with(first_kw) {
+    if is_primary {
+      report.error(Some(1), None, "The file does not start by 'SIMPLE'.");
+    } else {
+      report.error(
+        None,
+        None,
+        format!(
+          "{} extra bytes after the last HDU (not starting by 'XTENSION').",
+          hdu_bytes.len()
+        ),
+      );
+    }
+    return None;
+  }
+  let end_block = hdu_bytes.chunks_exact(2880).position(|block| {
+    block
+      .chunks_exact(80)
+      .any(|kwr| kwr.starts_with(b"END     "))
+  });
+  if end_block.is_none() {
+    report.error(
+      None,
+      Some("END"),
+      "Keyword 'END' not found (truncated header?).",
+    );
+    return None;
+  }
+  let (raw_header, _) = RawHeader::<&[u8]>::from_slice(is_primary, hdu_bytes).ok()?;
+
+  check_kw_records(&raw_header, report);
+  check_header_fill_area(&raw_header, report);
+  check_mandatory_kw_records_order(&raw_header, is_primary, report);
+
+  // Parse the mandatory keywords to get the data size
+  let parsed_header = match raw_header.build::<Minimalist>(is_primary) {
+    Ok(parsed_header) => parsed_header,
+    Err(e) => {
+      report.error(None, None, format!("Invalid mandatory keywords: {}", e));
+      return None;
+    }
+  };
+
+  // Check data size and fill area
+  let header_byte_size = raw_header.byte_size();
+  let data_byte_size = parsed_header.data_byte_size() as usize;
+  let padded_data_byte_size = data_byte_size.div_ceil(2880) * 2880;
+  let remaining = &hdu_bytes[header_byte_size..];
+  if remaining.len() < data_byte_size {
+    report.error(
+      None,
+      None,
+      format!(
+        "Truncated data unit. Expected: {} bytes. Actual: {} bytes.",
+        data_byte_size,
+        remaining.len()
+      ),
+    );
+    return None;
+  }
+  let data = &remaining[..data_byte_size];
+  let padding = &remaining[data_byte_size..padded_data_byte_size.min(remaining.len())];
+  if remaining.len() < padded_data_byte_size {
+    report.error(
+      None,
+      None,
+      format!(
+        "Truncated data fill area. Expected: {} bytes. Actual: {} bytes.",
+        padded_data_byte_size - data_byte_size,
+        padding.len()
+      ),
+    );
+  }
+  let fill_byte = if matches!(parsed_header, HDUHeader::AsciiTable(_)) {
+    b' '
+  } else {
+    0
+  };
+  let n_invalid = padding.iter().filter(|b| **b != fill_byte).count();
+  if n_invalid > 0 {
+    report.error(
+      None,
+      None,
+      format!(
+        "Data fill area contains {} bytes different from {}.",
+        n_invalid,
+        if fill_byte == 0 { "zero" } else { "blank" }
+      ),
+    );
+  }
+
+  if let HDUHeader::BinTable(h) = &parsed_header {
+    check_bintable(&raw_header, h, data, report);
+  }
+
+  // Check checksums, if any
+  let hdu = HDU::<Minimalist> {
+    starting_byte,
+    raw_header,
+    parsed_header,
+    data,
+    padding,
+  };
+  let checksums = hdu.verify_checksums();
+  if checksums.checksum == ChecksumStatus::Invalid {
+    report.warning(
+      None,
+      Some("CHECKSUM"),
+      "The HDU checksum does not match the 'CHECKSUM' value.",
+    );
+  }
+  if checksums.datasum == ChecksumStatus::Invalid {
+    report.warning(
+      None,
+      Some("DATASUM"),
+      format!(
+        "The data unit checksum does not match the 'DATASUM' value. Actual: {}.",
+        hdu.datasum()
+      ),
+    );
+  }
+
+  Some(header_byte_size + padded_data_byte_size)
+}
+
+/// Returns the keyword of the given keyword record, without trailing spaces.
+fn keyword(kwr: &[u8; 80]) -> String {
+  String::from_utf8_lossy(&kwr[KW_RANGE])
+    .trim_end()
+    .to_string()
+}
+
+/// Checks the characters of each keyword record and looks for duplicated keywords.
+fn check_kw_records<T: AsRef<[u8]>>(raw_header: &RawHeader<T>, report: &mut Report) {
+  let mut first_occurrences: HashMap<&[u8], usize> = HashMap::new();
+  for (i, kwr) in raw_header.kw_records_iter().enumerate() {
+    let pos = Some(i + 1);
+    let kw_string = keyword(kwr);
+    let kw = Some(kw_string.as_str());
+    let name = &kwr[KW_RANGE];
+    let name_len = name.iter().position(|c| *c == b' ').unwrap_or(8);
+    if !name[..name_len]
+      .iter()
+      .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || *c == b'-' || *c == b'_')
+      || name[name_len..].iter().any(|c| *c != b' ')
+    {
+      report.error(
+        pos,
+        kw,
+        "Illegal keyword name (only left justified upper case letters, digits, '-' and '_' allowed).",
+      );
+    }
+    if let Some(j) = kwr.iter().position(|c| !(0x20..=0x7E).contains(c)) {
+      report.error(
+        pos,
+        kw,
+        format!("Illegal character 0x{:02X} in column {}.", kwr[j], j + 1),
+      );
+    }
+    let (_, ind, _) = FixedFormatRead::split_kw_indicator_value(kwr);
+    if name_len > 0 && is_value_indicator(ind) && name != b"CONTINUE" {
+      if let Some(first) = first_occurrences.get(name) {
+        report.warning(
+          pos,
+          kw,
+          format!("Duplicated keyword (first occurrence at record {}).", first),
+        );
+      } else {
+        first_occurrences.insert(name, i + 1);
+      }
+    }
+  }
+}
+
+/// Checks that the `END` keyword record and the remaining of the last header block are blank.
+fn check_header_fill_area<T: AsRef<[u8]>>(raw_header: &RawHeader<T>, report: &mut Report) {
+  let n_kwr = raw_header.n_kw_records();
+  let fill = raw_header
+    .blocks_iter()
+    .flat_map(|block| block.chunks_exact(80))
+    .enumerate()
+    .skip(n_kwr);
+  for (i, kwr) in fill {
+    let expected_start: &[u8] = if i == n_kwr { b"END" } else { b"" };
+    if kwr[expected_start.len()..].iter().any(|c| *c != b' ') {
+      report.error(
+        Some(i + 1),
+        Some("END"),
+        if i == n_kwr {
+          "The 'END' keyword record is not filled with blanks."
+        } else {
+          "The header fill area (after 'END') is not filled with blanks."
+        },
+      );
+      break;
+    }
+  }
+}
+
+/// Checks the names and the order of the leading mandatory keyword records.
+fn check_mandatory_kw_records_order<T: AsRef<[u8]>>(
+  raw_header: &RawHeader<T>,
+  is_primary: bool,
+  report: &mut Report,
+) {
+  let kwrs: Vec<&[u8; 80]> = raw_header.kw_records_iter().collect();
+  let mut expected: Vec<String> = vec![
+    String::from(if is_primary { "SIMPLE" } else { "XTENSION" }),
+    String::from("BITPIX"),
+    String::from("NAXIS"),
+  ];
+  let naxis = kwrs
+    .get(2)
+    .and_then(|kwr| NAxis::from_keyword_record(kwr).ok())
+    .map(|naxis| naxis.get())
+    .unwrap_or(0);
+  expected.extend((1..=naxis).map(|n| format!("NAXIS{}", n)));
+  if !is_primary {
+    expected.push(String::from("PCOUNT"));
+    expected.push(String::from("GCOUNT"));
+    if matches!(
+      Xtension::from_keyword_record(kwrs[0]),
+      Ok(Xtension::BinTable | Xtension::AsciiTable)
+    ) {
+      expected.push(String::from("TFIELDS"));
+    }
+  }
+  for (i, expected) in expected.iter().enumerate() {
+    let found = kwrs.get(i).map(|kwr| keyword(kwr)).unwrap_or_default();
+    if &found != expected {
+      report.error(
+        Some(i + 1),
+        Some(&found),
+        format!(
+          "Mandatory keyword '{}' expected at record {}. Found: '{}'.",
+          expected,
+          i + 1,
+          found
+        ),
+      );
+      break;
+    }
+  }
+}
+
+/// Information gathered on a column keywords.
+#[derive(Default)]
+struct ColKwrs {
+  tform: Option<(usize, TFormn)>,
+  tnull: Option<(usize, TNull)>,
+  tscal: Option<usize>,
+  tzero: Option<usize>,
+  tdim: Option<(usize, TDim)>,
+}
+
+/// Checks the columns keywords, `THEAP`, `PCOUNT` and the heap descriptors of a BINTABLE.
+fn check_bintable<T: AsRef<[u8]>>(
+  raw_header: &RawHeader<T>,
+  header: &BinTableHeader,
+  data: &[u8],
+  report: &mut Report,
+) {
+  fn split_indexed_kw(kw: &str) -> Option<(&str, u16)> {
+    let i = kw.find(|c: char| c.is_ascii_digit())?;
+    let (prefix, n) = kw.split_at(i);
+    n.parse::<u16>().ok().map(|n| (prefix, n))
+  }
+
+  // Parse columns keywords and THEAP
+  let n_cols = header.n_cols();
+  let mut cols: Vec<ColKwrs> = (0..n_cols).map(|_| ColKwrs::default()).collect();
+  let mut theap: Option<(usize, THeap)> = None;
+  for (i, kwr) in raw_header.kw_records_iter().enumerate() {
+    let pos = i + 1;
+    let (kw_bytes, ind, vc) = FixedFormatRead::split_kw_indicator_value(kwr);
+    if !is_value_indicator(ind) {
+      continue;
+    }
+    let kw = keyword(kwr);
+    if kw_bytes == THeap::KEYWORD {
+      match THeap::from_value_comment(vc) {
+        Ok(v) => theap = Some((pos, v)),
+        Err(e) => report.error(Some(pos), Some(&kw), format!("Invalid value: {}", e)),
+      }
+      continue;
+    }
+    let Some((prefix, n)) = split_indexed_kw(&kw) else {
+      continue;
+    };
+    if !matches!(
+      prefix,
+      "TTYPE"
+        | "TFORM"
+        | "TDISP"
+        | "TUNIT"
+        | "TUCD"
+        | "TCOMM"
+        | "TNULL"
+        | "TSCAL"
+        | "TZERO"
+        | "TDIM"
+        | "TDMIN"
+        | "TDMAX"
+    ) {
+      continue;
+    }
+    if n == 0 || n as usize > n_cols {
+      report.error(
+        Some(pos),
+        Some(&kw),
+        format!("Column number out of range [1, TFIELDS = {}].", n_cols),
+      );
+      continue;
+    }
+    let col = &mut cols[n as usize - 1];
+    let res = match prefix {
+      "TTYPE" => TType::from_value_comment(n, vc).map(|_| ()),
+      "TFORM" => TFormn::from_value_comment(n, vc).map(|v| col.tform = Some((pos, v))),
+      "TDISP" => TDispn::from_value_comment(n, vc).map(|_| ()),
+      "TUNIT" => TUnit::from_value_comment(n, vc).map(|_| ()),
+      "TUCD" => TUCD::from_value_comment(n, vc).map(|_| ()),
+      "TCOMM" => TComm::from_value_comment(n, vc).map(|_| ()),
+      "TNULL" => TNull::from_value_comment(n, vc).map(|v| col.tnull = Some((pos, v))),
+      "TSCAL" => TScal::from_value_comment(n, vc).map(|_| col.tscal = Some(pos)),
+      "TZERO" => TZero::from_value_comment(n, vc).map(|_| col.tzero = Some(pos)),
+      "TDIM" => TDim::from_value_comment(n, vc).map(|v| col.tdim = Some((pos, v))),
+      "TDMIN" => TDMin::from_value_comment(n, vc).map(|_| ()),
+      "TDMAX" => TDMax::from_value_comment(n, vc).map(|_| ()),
+      _ => unreachable!(),
+    };
+    if let Err(e) = res {
+      report.error(Some(pos), Some(&kw), format!("Invalid value: {}", e));
+    }
+  }
+
+  // Check columns keywords consistency
+  let mut row_byte_size = 0;
+  let mut all_tforms = true;
+  let mut heap_cols: Vec<HeapCol> = Vec::new();
+  for (i, col) in cols.iter().enumerate() {
+    let n = i + 1;
+    let Some((tform_pos, tform)) = &col.tform else {
+      report.error(
+        None,
+        Some(format!("TFORM{}", n).as_str()),
+        "Missing mandatory keyword.",
+      );
+      all_tforms = false;
+      continue;
+    };
+    let tform = tform.tform_type();
+    if let Some((pos, tnull)) = &col.tnull {
+      match int_range(tform) {
+        None => report.error(
+          Some(*pos),
+          Some(format!("TNULL{}", n).as_str()),
+          format!(
+            "TNULLn not allowed for non-integer columns (TFORM{} = '{}').",
+            n, tform
+          ),
+        ),
+        Some((min, max)) if tnull.col_null_value() < min || tnull.col_null_value() > max => report
+          .warning(
+            Some(*pos),
+            Some(format!("TNULL{}", n).as_str()),
+            format!("TNULLn value out of the range of TFORM{} = '{}'.", n, tform),
+          ),
+        _ => {}
+      }
+    }
+    if !allows_scale_offset(tform) {
+      for (pos, kw) in [(col.tscal, "TSCAL"), (col.tzero, "TZERO")] {
+        if let Some(pos) = pos {
+          report.error(
+            Some(pos),
+            Some(format!("{}{}", kw, n).as_str()),
+            format!("{}n not allowed with TFORM{} = '{}'.", kw, n, tform),
+          );
+        }
+      }
+    }
+    if let (Some((pos, tdim)), Some(repeat_count)) = (&col.tdim, repeat_count(tform)) {
+      let n_elems = tdim
+        .dimensions()
+        .iter()
+        .map(|d| *d as usize)
+        .product::<usize>();
+      if n_elems > repeat_count {
+        report.error(
+          Some(*pos),
+          Some(format!("TDIM{}", n).as_str()),
+          format!(
+            "TDIMn number of elements ({}) larger than the TFORM{} = '{}' repeat count.",
+            n_elems, n, tform
+          ),
+        );
+      }
+    }
+    match tform {
+      TFormValue::P(info) | TFormValue::Q(info) if info.is_repeat_count_eq_1() => {
+        heap_cols.push(HeapCol {
+          n,
+          tform_pos: *tform_pos,
+          starting_byte: row_byte_size,
+          is_64: matches!(tform, TFormValue::Q(_)),
+          elem_byte_size: info.data_type().byte_size() as u64,
+          max_len: info.max_len() as u64,
+        });
+      }
+      _ => {}
+    }
+    row_byte_size += tform.byte_size();
+  }
+  let consistent_row_byte_size = !all_tforms || row_byte_size == header.row_byte_size();
+  if !consistent_row_byte_size {
+    report.error(
+      Some(4),
+      Some("NAXIS1"),
+      format!(
+        "NAXIS1 = {} differs from the sum of the TFORMn byte sizes: {}.",
+        header.row_byte_size(),
+        row_byte_size
+      ),
+    );
+  }
+
+  // Check THEAP and PCOUNT
+  let main_table_byte_size = header.main_table_byte_size();
+  let pcount = header.heap_byte_size();
+  let mut heap_starting_byte = main_table_byte_size;
+  if let Some((pos, theap)) = theap {
+    let byte_offset = theap.byte_offset();
+    if pcount == 0 {
+      report.warning(Some(pos), Some("THEAP"), "THEAP while PCOUNT = 0.");
+    } else if byte_offset < main_table_byte_size {
+      report.error(
+        Some(pos),
+        Some("THEAP"),
+        format!(
+          "THEAP = {} lower than the main table size NAXIS1 x NAXIS2 = {}.",
+          byte_offset, main_table_byte_size
+        ),
+      );
+    } else if byte_offset > main_table_byte_size + pcount {
+      report.error(
+        Some(pos),
+        Some("THEAP"),
+        format!(
+          "THEAP = {} larger than the data size NAXIS1 x NAXIS2 + PCOUNT = {}.",
+          byte_offset,
+          main_table_byte_size + pcount
+        ),
+      );
+    } else {
+      heap_starting_byte = byte_offset;
+    }
+  }
+  if pcount > 0 && heap_cols.is_empty() && all_tforms {
+    report.warning(
+      Some(6),
+      Some("PCOUNT"),
+      format!("PCOUNT = {} but no variable length array column.", pcount),
+    );
+  }
+
+  // Check heap descriptors
+  if consistent_row_byte_size && header.row_byte_size() > 0 {
+    let heap_byte_size = (main_table_byte_size + pcount - heap_starting_byte) as u64;
+    let rows = data[..main_table_byte_size].chunks_exact(header.row_byte_size());
+    for col in heap_cols {
+      col.check_descriptors(rows.clone(), heap_byte_size, report);
+    }
+  }
+}
+
+/// Variable length array column information.
+struct HeapCol {
+  /// Column number, starting at 1.
+  n: usize,
+  /// Number of the `TFORMn` keyword record.
+  tform_pos: usize,
+  /// Starting byte of the array descriptor in a row.
+  starting_byte: usize,
+  /// 64-bit (`Q`) or 32-bit (`P`) array descriptor.
+  is_64: bool,
+  elem_byte_size: u64,
+  /// Maximum array length in `TFORMn`, if any (0 else).
+  max_len: u64,
+}
+
+impl HeapCol {
+  fn check_descriptors<'a, I: Iterator<Item = &'a [u8]>>(
+    &self,
+    rows: I,
+    heap_byte_size: u64,
+    report: &mut Report,
+  ) {
+    let kw = format!("TFORM{}", self.n);
+    // (number, first (row, len, offset))
+    let mut out_of_range: (usize, (usize, u64, u64)) = (0, (0, 0, 0));
+    let mut too_long: (usize, (usize, u64, u64)) = (0, (0, 0, 0));
+    for (irow, row) in rows.enumerate() {
+      let bytes = &row[self.starting_byte..];
+      let (len, offset) = if self.is_64 {
+        (
+          u64::from_be_bytes(bytes[0..8].try_into().unwrap()),
+          u64::from_be_bytes(bytes[8..16].try_into().unwrap()),
+        )
+      } else {
+        (
+          u32::from_be_bytes(bytes[0..4].try_into().unwrap()) as u64,
+          u32::from_be_bytes(bytes[4..8].try_into().unwrap()) as u64,
+        )
+      };
+      let end = len
+        .checked_mul(self.elem_byte_size)
+        .and_then(|size| size.checked_add(offset));
+      if len > 0 && end.is_none_or(|end| end > heap_byte_size) {
+        if out_of_range.0 == 0 {
+          out_of_range.1 = (irow + 1, len, offset);
+        }
+        out_of_range.0 += 1;
+      }
+      if self.max_len > 0 && len > self.max_len {
+        if too_long.0 == 0 {
+          too_long.1 = (irow + 1, len, offset);
+        }
+        too_long.0 += 1;
+      }
+    }
+    if out_of_range.0 > 0 {
+      let (irow, len, offset) = out_of_range.1;
+      report.error(
+        Some(self.tform_pos),
+        Some(&kw),
+        format!(
+          "{} out of range heap array descriptor(s) (heap size: {} bytes). First one at row {}: length {}, offset {}.",
+          out_of_range.0, heap_byte_size, irow, len, offset
+        ),
+      );
+    }
+    if too_long.0 > 0 {
+      let (irow, len, _) = too_long.1;
+      report.warning(
+        Some(self.tform_pos),
+        Some(&kw),
+        format!(
+          "{} array length(s) larger than the TFORMn maximum length {}. First one at row {}: length {}.",
+          too_long.0, self.max_len, irow, len
+        ),
+      );
+    }
+  }
+}
+
+/// Returns the range of the stored values of an integer column, `None` for other columns.
+fn int_range(tform: &TFormValue) -> Option<(i64, i64)> {
+  let int_range_vla = |vdt: VariableLenghtArrayDataType| match vdt {
+    VariableLenghtArrayDataType::B => Some((0, u8::MAX as i64)),
+    VariableLenghtArrayDataType::I => Some((i16::MIN as i64, i16::MAX as i64)),
+    VariableLenghtArrayDataType::J => Some((i32::MIN as i64, i32::MAX as i64)),
+    VariableLenghtArrayDataType::K => Some((i64::MIN, i64::MAX)),
+    _ => None,
+  };
+  match tform {
+    TFormValue::B(_) => int_range_vla(VariableLenghtArrayDataType::B),
+    TFormValue::I(_) => int_range_vla(VariableLenghtArrayDataType::I),
+    TFormValue::J(_) => int_range_vla(VariableLenghtArrayDataType::J),
+    TFormValue::K(_) => int_range_vla(VariableLenghtArrayDataType::K),
+    TFormValue::P(info) | TFormValue::Q(info) => int_range_vla(info.data_type()),
+    _ => None,
+  }
+}
+
+/// `TSCALn` and `TZEROn` are not allowed for `A`, `L` and `X` columns.
+fn allows_scale_offset(tform: &TFormValue) -> bool {
+  match tform {
+    TFormValue::L(_) | TFormValue::X(_) | TFormValue::A(_) => false,
+    TFormValue::P(info) | TFormValue::Q(info) => !matches!(
+      info.data_type(),
+      VariableLenghtArrayDataType::L | VariableLenghtArrayDataType::A
+    ),
+    _ => true,
+  }
+}
+
+/// Repeat count of a fixed length column.
+fn repeat_count(tform: &TFormValue) -> Option<usize> {
+  match tform {
+    TFormValue::L(rc)
+    | TFormValue::X(rc)
+    | TFormValue::B(rc)
+    | TFormValue::I(rc)
+    | TFormValue::J(rc)
+    | TFormValue::K(rc)
+    | TFormValue::A(rc)
+    | TFormValue::E(rc)
+    | TFormValue::D(rc)
+    | TFormValue::C(rc)
+    | TFormValue::M(rc) => Some(rc.repeat_count() as usize),
+    TFormValue::P(_) | TFormValue::Q(_) => None,
+  }
+}